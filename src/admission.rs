@@ -0,0 +1,469 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+};
+
+use async_trait::async_trait;
+use datafusion::{
+    arrow::{json::ReaderBuilder, record_batch::RecordBatch},
+    catalog::{
+        CatalogProvider, CatalogProviderList, MemoryCatalogProvider, MemoryCatalogProviderList,
+        SchemaProvider, TableProvider,
+    },
+    common::Result as DataFusionResult,
+    datasource::MemTable,
+    execution::{SessionStateBuilder, context::SessionContext},
+};
+use http::{Method, Request, StatusCode};
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::body::{Bytes, Incoming};
+use serde_json::{Value, json};
+use tokio_rustls::TlsAcceptor;
+
+use crate::{
+    check::{self, Expect, Outcome, Policies, Policy, Severity},
+    dynamic::DynamicObject,
+    http::{response, serve_tls},
+    params::Param,
+    schema::infer_schema,
+    views::Views,
+    warnings::Warnings,
+};
+
+/// Table of the object admitted, empty for deletions
+pub const OBJECT_TABLE: &str = "object";
+
+/// Table of the existing object, empty for creations
+pub const OLD_OBJECT_TABLE: &str = "old_object";
+
+/// Maximum size of AdmissionReview bodies
+const MAX_BODY_SIZE: usize = 3 * 1024 * 1024;
+
+/// Validating admission webhook, allowing an object unless a policy of `severity: error`
+/// fails against it. Failed policies of `severity: warning` are returned as warnings.
+///
+/// The policies query the object as the `object` table and the existing object as the
+/// `old_object` table, along with the tables of the cluster. A policy failing to run
+/// denies the object. Each review has its own session, so reviews run concurrently.
+pub struct AdmissionWebhook {
+    ctx: SessionContext,
+    views: Views,
+    /// Warnings of the queries, printed after each review
+    warnings: Warnings,
+    policies: Vec<Policy>,
+    params: Vec<Param>,
+}
+
+impl AdmissionWebhook {
+    pub fn new(ctx: SessionContext, views: Views, warnings: Warnings, policies: Policies) -> Self {
+        Self {
+            ctx,
+            views,
+            warnings,
+            policies: policies.policies,
+            params: Vec::new(),
+        }
+    }
+
+    /// Values of the placeholders of the policies
+    pub fn with_params(mut self, params: Vec<Param>) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Serve AdmissionReviews posted to any path on the address until interrupted,
+    /// over TLS with the acceptor if given.
+    pub async fn serve(self, addr: SocketAddr, tls: Option<TlsAcceptor>) -> anyhow::Result<()> {
+        let webhook = Arc::new(self);
+        serve_tls(addr, tls, move |request, _| {
+            let webhook = Arc::clone(&webhook);
+            async move { webhook.handle(request).await }
+        })
+        .await
+    }
+
+    async fn handle(&self, request: Request<Incoming>) -> http::Response<Full<Bytes>> {
+        if request.method() != Method::POST {
+            return error(StatusCode::METHOD_NOT_ALLOWED, "use POST");
+        }
+        let body = match Limited::new(request.into_body(), MAX_BODY_SIZE)
+            .collect()
+            .await
+        {
+            Ok(body) => body.to_bytes(),
+            Err(e) => {
+                return error(
+                    StatusCode::BAD_REQUEST,
+                    &format!("failed to read the body: {}", e),
+                );
+            }
+        };
+        let review: Value = match serde_json::from_slice(&body) {
+            Ok(review) => review,
+            Err(e) => return error(StatusCode::BAD_REQUEST, &format!("invalid review: {}", e)),
+        };
+        let Some(request) = review.get("request") else {
+            return error(StatusCode::BAD_REQUEST, "no request in the review");
+        };
+        let review = json!({
+            "apiVersion": review
+                .get("apiVersion")
+                .cloned()
+                .unwrap_or("admission.k8s.io/v1".into()),
+            "kind": "AdmissionReview",
+            "response": self.review(request).await,
+        });
+        response(StatusCode::OK, "application/json", review.to_string())
+    }
+
+    /// Response to the request of an AdmissionReview
+    pub async fn review(&self, request: &Value) -> Value {
+        let field = |pointer: &str| request.pointer(pointer).and_then(Value::as_str);
+        let uid = field("/uid").unwrap_or_default();
+        let kind = field("/kind/kind").unwrap_or_default();
+
+        let mut denials = Vec::new();
+        let mut warnings = Vec::new();
+        let policies = self
+            .policies
+            .iter()
+            .filter(|policy| {
+                policy.kinds.is_empty()
+                    || policy
+                        .kinds
+                        .iter()
+                        .any(|policy_kind| policy_kind.eq_ignore_ascii_case(kind))
+            })
+            .collect::<Vec<_>>();
+        if !policies.is_empty() {
+            match self.session(request) {
+                Ok(ctx) => {
+                    for policy in policies {
+                        let outcome = check::check(&ctx, &self.views, policy, &self.params).await;
+                        let message = match outcome {
+                            Outcome::Passed => continue,
+                            Outcome::Failed(batches) => failure(policy, &batches),
+                            Outcome::Error(e) => {
+                                denials.push(format!("{}: failed to evaluate: {}", policy.name, e));
+                                continue;
+                            }
+                        };
+                        match policy.severity {
+                            Severity::Error => denials.push(message),
+                            Severity::Warning => warnings.push(message),
+                        }
+                    }
+                }
+                Err(e) => denials.push(format!("failed to read the object: {}", e)),
+            }
+            self.warnings.print();
+        }
+
+        eprintln!(
+            "{} {} {} {}/{} by {}: {}",
+            uid,
+            field("/operation").unwrap_or("-"),
+            kind,
+            field("/namespace").unwrap_or("-"),
+            field("/name").unwrap_or("-"),
+            field("/userInfo/username").unwrap_or("-"),
+            if denials.is_empty() {
+                "allowed"
+            } else {
+                "denied"
+            },
+        );
+        let mut response = json!({ "uid": uid, "allowed": denials.is_empty() });
+        if !denials.is_empty() {
+            response["status"] = json!({
+                "code": StatusCode::FORBIDDEN.as_u16(),
+                "reason": "Forbidden",
+                "message": denials.join("; "),
+            });
+        }
+        if !warnings.is_empty() {
+            response["warnings"] = json!(warnings);
+        }
+        response
+    }
+
+    /// Session of the review: the shared session with the object and the existing object
+    /// of the request as tables, either empty with the schema of the other if not given.
+    fn session(&self, request: &Value) -> anyhow::Result<SessionContext> {
+        let object = parse_object(request.get("object"))?;
+        let old_object = parse_object(request.get("oldObject"))?;
+        let Some(either) = object.as_ref().or(old_object.as_ref()) else {
+            anyhow::bail!("no object in the request");
+        };
+        let mut tables = HashMap::new();
+        for (name, object) in [(OBJECT_TABLE, &object), (OLD_OBJECT_TABLE, &old_object)] {
+            let schema = infer_schema(std::slice::from_ref(object.as_ref().unwrap_or(either)))?;
+            let mut decoder = ReaderBuilder::new(Arc::clone(&schema))
+                .with_coerce_primitive(true)
+                .build_decoder()?;
+            decoder.serialize(object.as_slice())?;
+            let batch = decoder
+                .flush()?
+                .unwrap_or_else(|| RecordBatch::new_empty(Arc::clone(&schema)));
+            let table = MemTable::try_new(schema, vec![vec![batch]])?;
+            tables.insert(name.to_string(), Arc::new(table) as Arc<dyn TableProvider>);
+        }
+
+        // The catalogs are shared with the session, but for the default schema
+        // overlaid with the tables of the review.
+        let state = self.ctx.state();
+        let options = &state.config().options().catalog;
+        let shared = state.catalog_list();
+        let catalogs = MemoryCatalogProviderList::new();
+        for name in shared.catalog_names() {
+            let Some(catalog) = shared.catalog(&name) else {
+                continue;
+            };
+            if name != options.default_catalog {
+                catalogs.register_catalog(name, catalog);
+                continue;
+            }
+            let overlaid = MemoryCatalogProvider::new();
+            for schema_name in catalog.schema_names() {
+                let Some(mut schema) = catalog.schema(&schema_name) else {
+                    continue;
+                };
+                if schema_name == options.default_schema {
+                    schema = Arc::new(OverlaySchema {
+                        tables: RwLock::new(tables.clone()),
+                        inner: schema,
+                    });
+                }
+                overlaid.register_schema(&schema_name, schema)?;
+            }
+            catalogs.register_catalog(name, Arc::new(overlaid));
+        }
+        let state = SessionStateBuilder::new_from_existing(state)
+            .with_catalog_list(Arc::new(catalogs))
+            .build();
+        Ok(SessionContext::new_with_state(state))
+    }
+}
+
+/// Schema of the tables of a review in front of the shared schema.
+/// Views created by the policies are of the review too, as they may read its tables.
+#[derive(Debug)]
+struct OverlaySchema {
+    tables: RwLock<HashMap<String, Arc<dyn TableProvider>>>,
+    inner: Arc<dyn SchemaProvider>,
+}
+
+#[async_trait]
+impl SchemaProvider for OverlaySchema {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn table_names(&self) -> Vec<String> {
+        let mut names = self.inner.table_names();
+        names.extend(self.tables.read().unwrap().keys().cloned());
+        names
+    }
+
+    async fn table(&self, name: &str) -> DataFusionResult<Option<Arc<dyn TableProvider>>> {
+        let table = self.tables.read().unwrap().get(name).cloned();
+        match table {
+            Some(table) => Ok(Some(table)),
+            None => self.inner.table(name).await,
+        }
+    }
+
+    fn register_table(
+        &self,
+        name: String,
+        table: Arc<dyn TableProvider>,
+    ) -> DataFusionResult<Option<Arc<dyn TableProvider>>> {
+        Ok(self.tables.write().unwrap().insert(name, table))
+    }
+
+    fn deregister_table(&self, name: &str) -> DataFusionResult<Option<Arc<dyn TableProvider>>> {
+        Ok(self.tables.write().unwrap().remove(name))
+    }
+
+    fn table_exist(&self, name: &str) -> bool {
+        self.tables.read().unwrap().contains_key(name) || self.inner.table_exist(name)
+    }
+}
+
+/// Object of the field of a request, `None` if null or missing
+fn parse_object(value: Option<&Value>) -> anyhow::Result<Option<DynamicObject>> {
+    match value {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => Ok(Some(serde_json::from_value(value.clone())?)),
+    }
+}
+
+/// Message of a failed policy: its description, or the number of violations
+fn failure(policy: &Policy, batches: &[RecordBatch]) -> String {
+    let reason = match (&policy.description, policy.expect) {
+        (Some(description), _) => description.trim().to_string(),
+        (None, Expect::Empty) => format!(
+            "{} violation(s)",
+            batches.iter().map(|batch| batch.num_rows()).sum::<usize>()
+        ),
+        (None, Expect::Nonempty) => "no rows".to_string(),
+    };
+    format!("{}: {}", policy.name, reason)
+}
+
+fn error(status: StatusCode, message: &str) -> http::Response<Full<Bytes>> {
+    response(
+        status,
+        "application/json",
+        json!({ "error": message }).to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cluster::Cluster, lister::fake::FakeLister, provider::KubernetesTableProviderFactory,
+        session_context, url::Namespaces,
+    };
+
+    fn webhook() -> AdmissionWebhook {
+        let lister = Arc::new(FakeLister::new(vec![
+            json!({"apiVersion": "v1", "kind": "Pod",
+            "metadata": {"name": "web-1", "namespace": "default"},
+            "spec": {"containers": [{"name": "web", "image": "nginx:1.25"}]},
+            "status": {"phase": "Running"}}),
+        ]));
+        let cluster = Cluster::from_lister(lister, Namespaces::One("default".to_string()));
+        let factory = KubernetesTableProviderFactory::new("fake".to_string(), cluster);
+        let ctx = session_context(Arc::new(factory)).unwrap();
+        let policies: Policies = serde_yaml::from_str(
+            r#"
+policies:
+  - name: team-label
+    description: pods need a team label
+    kinds: [Pod]
+    query: SELECT 1 FROM object WHERE label(metadata.labels, 'team') IS NULL
+  - name: known-images
+    kinds: [Pod]
+    query: SELECT 1 FROM pod_containers WHERE image = 'absent'
+  - name: replicas
+    severity: warning
+    kinds: [deployment]
+    query: SELECT 1 FROM object WHERE spec.replicas < 2
+  - name: protected
+    kinds: [ConfigMap]
+    query: |
+      SELECT 1 FROM old_object
+      WHERE label(metadata.labels, 'protected') = 'true' AND (SELECT count(*) FROM object) = 0
+"#,
+        )
+        .unwrap();
+        AdmissionWebhook::new(ctx, Views::builtin(), Warnings::default(), policies)
+    }
+
+    fn request(kind: &str, object: Value, old_object: Value) -> Value {
+        json!({"uid": "1", "kind": {"kind": kind}, "object": object, "oldObject": old_object})
+    }
+
+    #[tokio::test]
+    async fn review() {
+        let webhook = webhook();
+        let pod = |labels: Value| {
+            json!({"apiVersion": "v1", "kind": "Pod",
+                "metadata": {"name": "new", "namespace": "default", "labels": labels}})
+        };
+
+        let response = webhook
+            .review(&request("Pod", pod(json!({"team": "a"})), Value::Null))
+            .await;
+        assert_eq!(response, json!({"uid": "1", "allowed": true}));
+
+        let response = webhook
+            .review(&request("Pod", pod(json!({"app": "a"})), Value::Null))
+            .await;
+        assert_eq!(response["allowed"], json!(false));
+        assert_eq!(
+            response["status"]["message"],
+            json!("team-label: pods need a team label")
+        );
+
+        let deployment = |replicas: i64| {
+            json!({"apiVersion": "apps/v1", "kind": "Deployment",
+                "metadata": {"name": "web", "namespace": "default"},
+                "spec": {"replicas": replicas}})
+        };
+        let response = webhook
+            .review(&request("Deployment", deployment(1), deployment(3)))
+            .await;
+        assert_eq!(response["allowed"], json!(true));
+        assert_eq!(response["warnings"], json!(["replicas: 1 violation(s)"]));
+
+        // Deletions only have the existing object.
+        let config_map = |protected: &str| {
+            json!({"apiVersion": "v1", "kind": "ConfigMap",
+                "metadata": {"name": "c", "namespace": "default",
+                    "labels": {"protected": protected}}})
+        };
+        let response = webhook
+            .review(&request("ConfigMap", Value::Null, config_map("true")))
+            .await;
+        assert_eq!(
+            response["status"]["message"],
+            json!("protected: 1 violation(s)")
+        );
+        let response = webhook
+            .review(&request("ConfigMap", Value::Null, config_map("false")))
+            .await;
+        assert_eq!(response["allowed"], json!(true));
+
+        // No policy applies to other kinds.
+        let response = webhook
+            .review(&request("Secret", Value::Null, Value::Null))
+            .await;
+        assert_eq!(response["allowed"], json!(true));
+
+        let response = webhook
+            .review(&request("Pod", Value::Null, Value::Null))
+            .await;
+        assert_eq!(
+            response["status"]["message"],
+            json!("failed to read the object: no object in the request")
+        );
+
+        // The tables and views of the reviews are not left in the shared session.
+        for table in [OBJECT_TABLE, OLD_OBJECT_TABLE, "pod_containers"] {
+            assert!(!webhook.ctx.table_exist(table).unwrap(), "{}", table);
+        }
+    }
+
+    #[tokio::test]
+    async fn concurrent_reviews() {
+        let webhook = webhook();
+        let pod = |name: &str, labels: Value| {
+            json!({"apiVersion": "v1", "kind": "Pod",
+                "metadata": {"name": name, "namespace": "default", "labels": labels}})
+        };
+        let allowed = request("Pod", pod("a", json!({"team": "a"})), Value::Null);
+        let denied = request("Pod", pod("b", json!({})), Value::Null);
+        let (allowed, denied) = tokio::join!(webhook.review(&allowed), webhook.review(&denied));
+        assert_eq!(allowed["allowed"], json!(true));
+        assert_eq!(denied["allowed"], json!(false));
+    }
+}
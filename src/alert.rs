@@ -0,0 +1,93 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use datafusion::arrow::{
+    json::ArrayWriter, record_batch::RecordBatch, util::pretty::pretty_format_batches,
+};
+use serde_json::{Value, json};
+
+use crate::http::post_json;
+
+/// Maximum number of rows in a Slack message, to keep it readable
+const SLACK_MAX_ROWS: usize = 20;
+
+/// Payload of the notifications posted to a webhook.
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum WebhookFormat {
+    /// `{"context", "query", "count", "rows"}`
+    #[default]
+    Json,
+    /// Slack incoming webhook message with the rows as a table
+    Slack,
+}
+
+/// Webhook notified of the rows returned by queries
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    url: String,
+    format: WebhookFormat,
+}
+
+impl Webhook {
+    pub fn new(url: String, format: WebhookFormat) -> Self {
+        Self { url, format }
+    }
+
+    /// POST the rows returned by the query in the context
+    pub async fn notify(
+        &self,
+        context: &str,
+        query: &str,
+        batches: &[RecordBatch],
+    ) -> anyhow::Result<()> {
+        let count: usize = batches.iter().map(|batch| batch.num_rows()).sum();
+        let payload = match self.format {
+            WebhookFormat::Json => json!({
+                "context": context,
+                "query": query,
+                "count": count,
+                "rows": json_rows(batches)?,
+            }),
+            WebhookFormat::Slack => {
+                let mut shown = Vec::new();
+                let mut remaining = SLACK_MAX_ROWS;
+                for batch in batches {
+                    let rows = batch.num_rows().min(remaining);
+                    shown.push(batch.slice(0, rows));
+                    remaining -= rows;
+                }
+                let mut text = format!(
+                    "*kuqu*: {} row(s) in `{}`\n```\n{}\n{}\n```",
+                    count,
+                    context,
+                    query.trim(),
+                    pretty_format_batches(&shown)?
+                );
+                if count > SLACK_MAX_ROWS {
+                    text.push_str(&format!("\n(first {} rows shown)", SLACK_MAX_ROWS));
+                }
+                json!({ "text": text })
+            }
+        };
+        post_json(&self.url, &payload).await
+    }
+}
+
+/// Rows of the batches as JSON objects
+pub fn json_rows(batches: &[RecordBatch]) -> anyhow::Result<Vec<Value>> {
+    let mut writer = ArrayWriter::new(Vec::new());
+    writer.write_batches(&batches.iter().collect::<Vec<_>>())?;
+    writer.finish()?;
+    Ok(serde_json::from_slice(&writer.into_inner())?)
+}
@@ -0,0 +1,159 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{fs, path::Path, sync::Arc};
+
+use datafusion::{
+    arrow::json::ReaderBuilder, datasource::MemTable, execution::context::SessionContext,
+};
+use serde_json::{Deserializer, Value};
+
+use crate::schema::{conform, infer_json_schema};
+
+/// Table of the audit events read
+pub const AUDIT_EVENTS_TABLE: &str = "audit_events";
+
+/// Maximum number of events per batch
+const BATCH_SIZE: usize = 1024;
+
+/// Read the audit events of the files, written by the log backend of the API server
+/// (an event per line) or posted to the webhook backend (`EventList` documents).
+pub fn read(paths: &[impl AsRef<Path>]) -> anyhow::Result<Vec<Value>> {
+    let mut events = Vec::new();
+    for path in paths {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("failed to read {}: {}", path.display(), e))?;
+        for document in Deserializer::from_str(&content).into_iter::<Value>() {
+            let document = document
+                .map_err(|e| anyhow::anyhow!("invalid audit log {}: {}", path.display(), e))?;
+            match document.get("kind").and_then(Value::as_str) {
+                Some("EventList") => {
+                    if let Some(Value::Array(items)) = document.get("items") {
+                        events.extend(items.iter().cloned());
+                    }
+                }
+                Some("Event") => events.push(document),
+                kind => anyhow::bail!(
+                    "invalid audit log {}: expected audit events, got kind {}",
+                    path.display(),
+                    kind.unwrap_or("none")
+                ),
+            }
+        }
+    }
+    Ok(events)
+}
+
+/// Register the audit events as the `audit_events` table, with the schema inferred from them.
+///
+/// Fields of different types across events (e.g. `responseObject.status`, a string in
+/// `Status` responses and an object in others) are widened as for resources.
+pub fn register(ctx: &SessionContext, events: &[Value]) -> anyhow::Result<()> {
+    if events.is_empty() {
+        anyhow::bail!("no audit events to query");
+    }
+    let schema = infer_json_schema(events.iter().cloned())?;
+    let events: Vec<Value> = events
+        .iter()
+        .cloned()
+        .map(|mut event| {
+            conform(&mut event, schema.fields());
+            event
+        })
+        .collect();
+    let mut decoder = ReaderBuilder::new(Arc::clone(&schema))
+        .with_batch_size(BATCH_SIZE)
+        .with_coerce_primitive(true)
+        .build_decoder()?;
+    let mut batches = Vec::new();
+    for events in events.chunks(BATCH_SIZE) {
+        decoder.serialize(events)?;
+        batches.extend(decoder.flush()?);
+    }
+    let table = MemTable::try_new(schema, vec![batches])?;
+    ctx.register_table(AUDIT_EVENTS_TABLE, Arc::new(table))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::{array::Array, util::display::array_value_to_string};
+    use serde_json::json;
+
+    use super::*;
+
+    fn event(verb: &str, status: Value) -> Value {
+        json!({"kind": "Event", "apiVersion": "audit.k8s.io/v1", "verb": verb,
+            "responseObject": {"status": status}})
+    }
+
+    fn write(name: &str, content: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("kuqu-audit-{}-{}", std::process::id(), name));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn read_log_and_webhook_documents() {
+        let log = write(
+            "log",
+            &format!(
+                "{}\n{}\n",
+                event("get", json!("Success")),
+                event("list", json!("Success"))
+            ),
+        );
+        let webhook = write(
+            "webhook",
+            &json!({"kind": "EventList", "items": [event("delete", json!("Success"))]}).to_string(),
+        );
+        let events = read(&[&log, &webhook]).unwrap();
+        let verbs: Vec<_> = events.iter().map(|event| event["verb"].clone()).collect();
+        assert_eq!(verbs, [json!("get"), json!("list"), json!("delete")]);
+
+        let pods = write("pods", &json!({"kind": "PodList", "items": []}).to_string());
+        let error = read(&[&pods]).unwrap_err().to_string();
+        assert!(error.contains("got kind PodList"), "{}", error);
+        for path in [log, webhook, pods] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn register_mixed_events() {
+        let ctx = SessionContext::new();
+        assert!(register(&ctx, &[]).is_err());
+        register(
+            &ctx,
+            &[
+                event("delete", json!("Success")),
+                event("get", json!({"phase": "Running"})),
+            ],
+        )
+        .unwrap();
+        let batches = ctx
+            .sql("SELECT verb, \"responseObject\".status FROM audit_events ORDER BY verb")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let column = batches[0].column(1);
+        let statuses: Vec<_> = (0..column.len())
+            .map(|i| array_value_to_string(column, i).unwrap())
+            .collect();
+        assert_eq!(statuses, ["Success", r#"{"phase":"Running"}"#]);
+    }
+}
@@ -0,0 +1,673 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{fs, io::Write, path::Path};
+
+use datafusion::{arrow::record_batch::RecordBatch, execution::context::SessionContext};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::{
+    alert::json_rows,
+    output::{self, OutputFormat},
+    params::{self, Param},
+    provider::KubernetesTableProviderFactory,
+    views::Views,
+};
+
+/// Format of the results of the policies
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum CheckFormat {
+    /// The outcome of each policy with the violations in the output format, and a summary
+    #[default]
+    Text,
+    /// JUnit XML, a test case per policy
+    Junit,
+    /// SARIF 2.1.0, a result per violation
+    Sarif,
+}
+
+/// Policies read from a YAML file, checked in order
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policies {
+    pub policies: Vec<Policy>,
+}
+
+impl Policies {
+    /// Read the policies from the YAML file
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("failed to read {}: {}", path.display(), e))?;
+        let policies: Self = serde_yaml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("invalid policies in {}: {}", path.display(), e))?;
+        if policies.policies.is_empty() {
+            anyhow::bail!("no policies in {}", path.display());
+        }
+        Ok(policies)
+    }
+}
+
+/// Query with the expectation of its rows, e.g. none for a query of violations
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub query: String,
+    #[serde(default)]
+    pub expect: Expect,
+    #[serde(default)]
+    pub severity: Severity,
+    /// Kinds of the objects the policy applies to at admission (e.g. `Pod`), any if empty
+    #[serde(default)]
+    pub kinds: Vec<String>,
+}
+
+/// Expectation of the rows of a policy query
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Expect {
+    /// No rows, each row being a violation
+    #[default]
+    Empty,
+    /// At least one row
+    Nonempty,
+}
+
+/// Whether a failing policy fails the check
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    #[default]
+    Error,
+    /// Reported without failing the check
+    Warning,
+}
+
+/// Outcome of checking a policy
+#[derive(Debug)]
+pub enum Outcome {
+    Passed,
+    /// Not as expected, with the rows returned (violations for policies expecting none)
+    Failed(Vec<RecordBatch>),
+    /// The query failed
+    Error(String),
+}
+
+/// Policy checked with its outcome
+#[derive(Debug)]
+pub struct CheckResult {
+    pub policy: Policy,
+    pub outcome: Outcome,
+}
+
+impl CheckResult {
+    /// Whether the result fails the check: an error, or a failed policy of the error severity
+    pub fn fails(&self) -> bool {
+        match self.outcome {
+            Outcome::Passed => false,
+            Outcome::Failed(_) => self.policy.severity == Severity::Error,
+            Outcome::Error(_) => true,
+        }
+    }
+
+    /// Number of rows returned by a failed policy
+    pub fn violations(&self) -> usize {
+        match &self.outcome {
+            Outcome::Failed(batches) => batches.iter().map(|batch| batch.num_rows()).sum(),
+            _ => 0,
+        }
+    }
+}
+
+/// Check the policies in order
+pub async fn run(
+    ctx: &SessionContext,
+    factory: &KubernetesTableProviderFactory,
+    views: &Views,
+    policies: Policies,
+    params: &[Param],
+) -> Vec<CheckResult> {
+    let mut results = Vec::new();
+    for policy in policies.policies {
+        let outcome = check(ctx, views, &policy, params).await;
+        factory.warnings().print();
+        results.push(CheckResult { policy, outcome });
+    }
+    results
+}
+
+/// Check a policy
+pub async fn check(
+    ctx: &SessionContext,
+    views: &Views,
+    policy: &Policy,
+    params: &[Param],
+) -> Outcome {
+    match evaluate(ctx, views, &policy.query, params).await {
+        Ok(batches) => {
+            let empty = batches.iter().all(|batch| batch.num_rows() == 0);
+            match (policy.expect, empty) {
+                (Expect::Empty, true) | (Expect::Nonempty, false) => Outcome::Passed,
+                _ => Outcome::Failed(batches),
+            }
+        }
+        Err(e) => Outcome::Error(e.to_string()),
+    }
+}
+
+async fn evaluate(
+    ctx: &SessionContext,
+    views: &Views,
+    query: &str,
+    params: &[Param],
+) -> anyhow::Result<Vec<RecordBatch>> {
+    views.register(ctx, query).await?;
+    let df = params::bind(ctx.sql(query).await?, params)?;
+    Ok(df.collect().await?)
+}
+
+/// Write the outcome of each policy, with the rows of those failed in the format,
+/// followed by a summary.
+pub fn write_text<W: Write>(
+    mut w: W,
+    results: &[CheckResult],
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    for result in results {
+        let policy = &result.policy;
+        match &result.outcome {
+            Outcome::Passed => writeln!(w, "PASS {}", policy.name)?,
+            Outcome::Failed(batches) => {
+                let label = match policy.severity {
+                    Severity::Error => "FAIL",
+                    Severity::Warning => "WARN",
+                };
+                match policy.expect {
+                    Expect::Empty => writeln!(
+                        w,
+                        "{} {}: {} violation(s)",
+                        label,
+                        policy.name,
+                        result.violations()
+                    )?,
+                    Expect::Nonempty => writeln!(w, "{} {}: no rows", label, policy.name)?,
+                }
+                if let Some(description) = &policy.description {
+                    writeln!(w, "  {}", description.trim())?;
+                }
+                if !batches.is_empty() && result.violations() > 0 {
+                    output::write_batches(&mut w, batches, format)?;
+                }
+            }
+            Outcome::Error(e) => writeln!(w, "ERROR {}: {}", policy.name, e)?,
+        }
+    }
+    let passed = results
+        .iter()
+        .filter(|result| matches!(result.outcome, Outcome::Passed))
+        .count();
+    let failed = results.iter().filter(|result| result.fails()).count();
+    writeln!(
+        w,
+        "\n{} policies: {} passed, {} failed, {} warned",
+        results.len(),
+        passed,
+        failed,
+        results.len() - passed - failed
+    )?;
+    Ok(())
+}
+
+/// Write the results as JUnit XML, a test suite named after the policies file
+/// with a test case per policy. Policies of the warning severity pass with the violations as output.
+pub fn write_junit<W: Write>(mut w: W, suite: &str, results: &[CheckResult]) -> anyhow::Result<()> {
+    let failures = results
+        .iter()
+        .filter(|result| matches!(result.outcome, Outcome::Failed(_)) && result.fails())
+        .count();
+    let errors = results
+        .iter()
+        .filter(|result| matches!(result.outcome, Outcome::Error(_)))
+        .count();
+    writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        w,
+        r#"<testsuites name="kuqu" tests="{}" failures="{}" errors="{}">"#,
+        results.len(),
+        failures,
+        errors
+    )?;
+    writeln!(
+        w,
+        r#"  <testsuite name="{}" tests="{}" failures="{}" errors="{}" skipped="0">"#,
+        escape_xml(suite),
+        results.len(),
+        failures,
+        errors
+    )?;
+    for result in results {
+        let policy = &result.policy;
+        write!(
+            w,
+            r#"    <testcase name="{}" classname="{}""#,
+            escape_xml(&policy.name),
+            escape_xml(suite)
+        )?;
+        match &result.outcome {
+            Outcome::Passed => writeln!(w, "/>")?,
+            Outcome::Failed(batches) => {
+                writeln!(w, ">")?;
+                let message = failure_message(result);
+                let rows = serde_json::to_string_pretty(&json_rows(batches)?)?;
+                let details = match &policy.description {
+                    Some(description) => format!("{}\n{}", description.trim(), rows),
+                    None => rows,
+                };
+                match policy.severity {
+                    Severity::Error => writeln!(
+                        w,
+                        r#"      <failure message="{}" type="violation">{}</failure>"#,
+                        escape_xml(&message),
+                        escape_xml(&details)
+                    )?,
+                    Severity::Warning => writeln!(
+                        w,
+                        "      <system-out>warning: {}\n{}</system-out>",
+                        escape_xml(&message),
+                        escape_xml(&details)
+                    )?,
+                }
+                writeln!(w, "    </testcase>")?;
+            }
+            Outcome::Error(e) => {
+                writeln!(w, ">")?;
+                writeln!(
+                    w,
+                    r#"      <error message="{}" type="error">{}</error>"#,
+                    escape_xml(e),
+                    escape_xml(&policy.query)
+                )?;
+                writeln!(w, "    </testcase>")?;
+            }
+        }
+    }
+    writeln!(w, "  </testsuite>")?;
+    writeln!(w, "</testsuites>")?;
+    Ok(())
+}
+
+/// Write the results as SARIF 2.1.0, a rule per policy and a result per violation,
+/// located at the policy in the policies file. Failed queries are reported as
+/// notifications of an unsuccessful execution.
+pub fn write_sarif<W: Write>(w: W, path: &Path, results: &[CheckResult]) -> anyhow::Result<()> {
+    let source = fs::read_to_string(path).unwrap_or_default();
+    let uri = path.to_string_lossy().replace('\\', "/");
+    let rules: Vec<Value> = results
+        .iter()
+        .map(|result| {
+            let policy = &result.policy;
+            json!({
+                "id": policy.name,
+                "shortDescription": { "text": policy.description.as_deref().unwrap_or(&policy.name).trim() },
+                "defaultConfiguration": { "level": level(policy.severity) },
+            })
+        })
+        .collect();
+
+    let mut sarif_results = Vec::new();
+    let mut notifications = Vec::new();
+    for result in results {
+        let policy = &result.policy;
+        let mut location = json!({ "artifactLocation": { "uri": uri } });
+        if let Some(line) = policy_line(&source, &policy.name) {
+            location["region"] = json!({ "startLine": line });
+        }
+        let locations = json!([{ "physicalLocation": location }]);
+        match &result.outcome {
+            Outcome::Passed => {}
+            Outcome::Failed(batches) => {
+                let rows = json_rows(batches)?;
+                let messages: Vec<String> = match policy.expect {
+                    Expect::Empty => rows
+                        .iter()
+                        .map(|row| format!("{}: {}", policy.name, row))
+                        .collect(),
+                    Expect::Nonempty => vec![failure_message(result)],
+                };
+                for message in messages {
+                    sarif_results.push(json!({
+                        "ruleId": policy.name,
+                        "level": level(policy.severity),
+                        "message": { "text": message },
+                        "locations": locations,
+                    }));
+                }
+            }
+            Outcome::Error(e) => notifications.push(json!({
+                "level": "error",
+                "message": { "text": format!("{}: {}", policy.name, e) },
+                "descriptor": { "id": policy.name },
+            })),
+        }
+    }
+
+    let sarif = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "kuqu",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": "https://github.com/ynqa/kuqu",
+                    "rules": rules,
+                },
+            },
+            "invocations": [{
+                "executionSuccessful": notifications.is_empty(),
+                "toolExecutionNotifications": notifications,
+            }],
+            "results": sarif_results,
+        }],
+    });
+    serde_json::to_writer_pretty(w, &sarif)?;
+    Ok(())
+}
+
+fn failure_message(result: &CheckResult) -> String {
+    match result.policy.expect {
+        Expect::Empty => format!("{} violation(s)", result.violations()),
+        Expect::Nonempty => "no rows".to_string(),
+    }
+}
+
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+    }
+}
+
+/// Line of the policy of the name in the policies file, 1-based
+fn policy_line(source: &str, name: &str) -> Option<usize> {
+    source
+        .lines()
+        .position(|line| {
+            let line = line.trim_start().trim_start_matches("- ").trim();
+            line.strip_prefix("name:")
+                .map(|value| value.trim().trim_matches(['"', '\'']) == name)
+                .unwrap_or(false)
+        })
+        .map(|index| index + 1)
+}
+
+/// Escape the string as XML text or attribute value, replacing characters not allowed
+/// in XML 1.0 (e.g. control characters in log lines) with U+FFFD.
+fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            '\u{0}'..='\u{1f}' | '\u{fffe}' | '\u{ffff}' => {
+                escaped.push(char::REPLACEMENT_CHARACTER)
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use datafusion::arrow::array::{ArrayRef, StringArray};
+
+    use super::*;
+    use crate::{cluster::Cluster, lister::fake::FakeLister, session_context, url::Namespaces};
+
+    const POLICIES: &str = r#"policies:
+  - name: ok
+    query: SELECT 1 WHERE false
+  - name: pinned
+    description: Images must be <pinned>
+    query: SELECT 'web' AS name
+  - name: "replicas"
+    severity: warning
+    expect: nonempty
+    query: SELECT 1 WHERE false
+  - name: broken
+    query: SELECT * FROM missing
+"#;
+
+    fn results() -> Vec<CheckResult> {
+        let policies: Policies = serde_yaml::from_str(POLICIES).unwrap();
+        let batch = RecordBatch::try_from_iter([(
+            "name",
+            Arc::new(StringArray::from(vec!["web\u{1b}[0m"])) as ArrayRef,
+        )])
+        .unwrap();
+        let outcomes = [
+            Outcome::Passed,
+            Outcome::Failed(vec![batch]),
+            Outcome::Failed(Vec::new()),
+            Outcome::Error("table 'missing' not found\u{7}".to_string()),
+        ];
+        policies
+            .policies
+            .into_iter()
+            .zip(outcomes)
+            .map(|(policy, outcome)| CheckResult { policy, outcome })
+            .collect()
+    }
+
+    #[test]
+    fn escape() {
+        assert_eq!(
+            escape_xml("<a href=\"x\">'&'</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&apos;&amp;&apos;&lt;/a&gt;"
+        );
+        assert_eq!(
+            escape_xml("a\tb\r\nc\u{0}\u{1b}d"),
+            "a\tb\r\nc\u{fffd}\u{fffd}d"
+        );
+    }
+
+    #[test]
+    fn lines_of_policies() {
+        assert_eq!(policy_line(POLICIES, "ok"), Some(2));
+        assert_eq!(policy_line(POLICIES, "pinned"), Some(4));
+        assert_eq!(policy_line(POLICIES, "replicas"), Some(7));
+        assert_eq!(policy_line(POLICIES, "absent"), None);
+    }
+
+    #[test]
+    fn junit() {
+        let mut out = Vec::new();
+        write_junit(&mut out, "policies", &results()).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="kuqu" tests="4" failures="1" errors="1">
+  <testsuite name="policies" tests="4" failures="1" errors="1" skipped="0">
+    <testcase name="ok" classname="policies"/>
+    <testcase name="pinned" classname="policies">
+      <failure message="1 violation(s)" type="violation">Images must be &lt;pinned&gt;
+[
+  {
+    &quot;name&quot;: &quot;web\u001b[0m&quot;
+  }
+]</failure>
+    </testcase>
+    <testcase name="replicas" classname="policies">
+      <system-out>warning: no rows
+[]</system-out>
+    </testcase>
+    <testcase name="broken" classname="policies">
+      <error message="table &apos;missing&apos; not found�" type="error">SELECT * FROM missing</error>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+        );
+    }
+
+    #[test]
+    fn sarif() {
+        let path = std::env::temp_dir().join(format!("kuqu-policies-{}.yaml", std::process::id()));
+        fs::write(&path, POLICIES).unwrap();
+        let mut out = Vec::new();
+        write_sarif(&mut out, &path, &results()).unwrap();
+        fs::remove_file(&path).unwrap();
+        let sarif: Value = serde_json::from_slice(&out).unwrap();
+        let run = &sarif["runs"][0];
+        let uri = path.to_string_lossy();
+        let location = |line: usize| {
+            json!([{ "physicalLocation": {
+                "artifactLocation": { "uri": uri },
+                "region": { "startLine": line },
+            }}])
+        };
+        assert_eq!(
+            run["results"],
+            json!([
+                {
+                    "ruleId": "pinned",
+                    "level": "error",
+                    "message": { "text": r#"pinned: {"name":"web\u001b[0m"}"# },
+                    "locations": location(4),
+                },
+                {
+                    "ruleId": "replicas",
+                    "level": "warning",
+                    "message": { "text": "no rows" },
+                    "locations": location(7),
+                },
+            ])
+        );
+        assert_eq!(
+            run["invocations"],
+            json!([{
+                "executionSuccessful": false,
+                "toolExecutionNotifications": [{
+                    "level": "error",
+                    "message": { "text": "broken: table 'missing' not found\u{7}" },
+                    "descriptor": { "id": "broken" },
+                }],
+            }])
+        );
+        let rules: Vec<_> = run["tool"]["driver"]["rules"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|rule| {
+                (
+                    rule["id"].as_str().unwrap(),
+                    rule["shortDescription"]["text"].as_str().unwrap(),
+                    rule["defaultConfiguration"]["level"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rules,
+            [
+                ("ok", "ok", "error"),
+                ("pinned", "Images must be <pinned>", "error"),
+                ("replicas", "replicas", "warning"),
+                ("broken", "broken", "error"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn expect_and_severity() {
+        let lister = Arc::new(FakeLister::new(vec![
+            json!({"apiVersion": "v1", "kind": "Pod",
+                "metadata": {"name": "web-1", "namespace": "default"},
+                "spec": {"nodeName": "n1"}}),
+            json!({"apiVersion": "v1", "kind": "Pod",
+                "metadata": {"name": "web-2", "namespace": "default"},
+                "spec": {"nodeName": "n1"}}),
+        ]));
+        let cluster = Cluster::from_lister(lister, Namespaces::One("default".to_string()));
+        let factory = Arc::new(KubernetesTableProviderFactory::new(
+            "fake".to_string(),
+            cluster,
+        ));
+        let ctx = session_context(Arc::clone(&factory)).unwrap();
+        let policies: Policies = serde_yaml::from_str(
+            r#"
+policies:
+  - name: none-unscheduled
+    query: SELECT metadata.name AS name FROM pods WHERE spec.nodeName IS NULL
+  - name: none-on-n1
+    description: Pods must not run on n1
+    query: SELECT metadata.name AS name FROM pods WHERE spec.nodeName = 'n1' ORDER BY metadata.name
+  - name: none-on-n1-warning
+    severity: warning
+    query: SELECT metadata.name AS name FROM pods WHERE spec.nodeName = 'n1' LIMIT 1
+  - name: some-pods
+    expect: nonempty
+    query: SELECT 1 FROM pods
+  - name: some-on-n2
+    expect: nonempty
+    query: SELECT 1 FROM pods WHERE spec.nodeName = 'n2'
+  - name: some-on-n2-warning
+    expect: nonempty
+    severity: warning
+    query: SELECT 1 FROM pods WHERE spec.nodeName = 'n2'
+  - name: broken
+    query: SELECT FROM
+"#,
+        )
+        .unwrap();
+        let results = run(&ctx, &factory, &Views::builtin(), policies, &[]).await;
+        let fails: Vec<_> = results.iter().map(CheckResult::fails).collect();
+        assert_eq!(fails, [false, true, false, false, true, false, true]);
+        let violations: Vec<_> = results.iter().map(CheckResult::violations).collect();
+        assert_eq!(violations, [0, 2, 1, 0, 0, 0, 0]);
+
+        let mut out = Vec::new();
+        write_text(&mut out, &results, OutputFormat::Ndjson).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let (out, error) = out.split_once("ERROR broken: ").unwrap();
+        assert_eq!(
+            out,
+            r#"PASS none-unscheduled
+FAIL none-on-n1: 2 violation(s)
+  Pods must not run on n1
+{"name":"web-1"}
+{"name":"web-2"}
+WARN none-on-n1-warning: 1 violation(s)
+{"name":"web-1"}
+PASS some-pods
+FAIL some-on-n2: no rows
+WARN some-on-n2-warning: no rows
+"#
+        );
+        assert!(
+            error.ends_with("\n\n7 policies: 2 passed, 3 failed, 2 warned\n"),
+            "{}",
+            error
+        );
+    }
+}
@@ -0,0 +1,387 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::APIResource;
+use kube::{
+    Api, Client, Config,
+    api::ListParams,
+    client::ClientBuilder,
+    config::{
+        AuthInfo, Context, KubeConfigOptions, Kubeconfig, NamedAuthInfo, NamedCluster, NamedContext,
+    },
+};
+use tokio::sync::OnceCell;
+
+use crate::{
+    discover::{DiscoveryCache, normalize},
+    dynamic::DynamicObject,
+    lister::{DiscoveryScope, KubeLister, ResourceLister},
+    ratelimit::RateLimitLayer,
+    retry::Retry,
+    url::{Namespaces, find_resource},
+};
+
+/// Read the kubeconfig from the path if given,
+/// or from `KUBECONFIG` or `~/.kube/config` otherwise.
+pub fn read_kubeconfig(path: Option<&Path>) -> anyhow::Result<Kubeconfig> {
+    Ok(match path {
+        Some(path) => Kubeconfig::read_from(path)?,
+        None => Kubeconfig::read()?,
+    })
+}
+
+/// Kubeconfig with a single context for the API server given explicitly,
+/// named after the server.
+fn server_kubeconfig(server: &str, options: &ConnectOptions) -> Kubeconfig {
+    let cluster = kube::config::Cluster {
+        server: Some(server.to_string()),
+        ..Default::default()
+    };
+    let auth_info = AuthInfo {
+        token: options.token.clone().map(Into::into),
+        ..Default::default()
+    };
+    Kubeconfig {
+        clusters: vec![NamedCluster {
+            name: server.to_string(),
+            cluster: Some(cluster),
+        }],
+        auth_infos: vec![NamedAuthInfo {
+            name: server.to_string(),
+            auth_info: Some(auth_info),
+        }],
+        contexts: vec![NamedContext {
+            name: server.to_string(),
+            context: Some(Context {
+                cluster: server.to_string(),
+                user: Some(server.to_string()),
+                ..Default::default()
+            }),
+        }],
+        current_context: Some(server.to_string()),
+        ..Default::default()
+    }
+}
+
+/// Apply the TLS options to the cluster and user of the context,
+/// taking precedence over the kubeconfig as kubectl flags do.
+fn apply_tls_options(kubeconfig: &mut Kubeconfig, context: &str, options: &ConnectOptions) {
+    let Some(context) = kubeconfig
+        .contexts
+        .iter()
+        .find(|named| named.name == context)
+        .and_then(|named| named.context.clone())
+    else {
+        return;
+    };
+
+    if let Some(cluster) = kubeconfig
+        .clusters
+        .iter_mut()
+        .find(|named| named.name == context.cluster)
+        .and_then(|named| named.cluster.as_mut())
+    {
+        if options.insecure_skip_tls_verify {
+            cluster.insecure_skip_tls_verify = Some(true);
+            cluster.certificate_authority = None;
+            cluster.certificate_authority_data = None;
+        } else if let Some(path) = &options.certificate_authority {
+            cluster.certificate_authority = Some(path.to_string_lossy().into_owned());
+            cluster.certificate_authority_data = None;
+        }
+    }
+
+    if let Some(auth_info) = kubeconfig
+        .auth_infos
+        .iter_mut()
+        .find(|named| Some(&named.name) == context.user.as_ref())
+        .and_then(|named| named.auth_info.as_mut())
+    {
+        if let Some(path) = &options.client_certificate {
+            auth_info.client_certificate = Some(path.to_string_lossy().into_owned());
+            auth_info.client_certificate_data = None;
+        }
+        if let Some(path) = &options.client_key {
+            auth_info.client_key = Some(path.to_string_lossy().into_owned());
+            auth_info.client_key_data = None;
+        }
+    }
+}
+
+/// Options for connecting to clusters
+#[derive(Debug, Clone, Default)]
+pub struct ConnectOptions {
+    /// Path of the kubeconfig to use instead of the default one
+    pub kubeconfig: Option<PathBuf>,
+    /// API server to connect to directly instead of through kubeconfig,
+    /// available as the context named after it
+    pub server: Option<String>,
+    /// Bearer token to authenticate to `server` with
+    pub token: Option<String>,
+    /// Path of the CA certificate bundle to verify the API server with
+    pub certificate_authority: Option<PathBuf>,
+    /// Skip verifying the certificate of the API server
+    pub insecure_skip_tls_verify: bool,
+    /// Path of the client certificate for TLS client authentication
+    pub client_certificate: Option<PathBuf>,
+    /// Path of the key of `client_certificate`
+    pub client_key: Option<PathBuf>,
+    /// Timeout for connecting to and each read from or write to the API server
+    pub request_timeout: Option<Duration>,
+    /// Rate limit of the requests to each API server
+    pub rate_limit: Option<RateLimitLayer>,
+    /// Retry policy of list, get and discovery requests
+    pub retry: Retry,
+    /// Discover the resources again instead of using the discovery cached on disk
+    pub refresh_discovery: bool,
+    /// Do not request gzip compressed responses
+    pub disable_compression: bool,
+    /// Namespace of tables without a namespace, instead of that of the context
+    pub namespace: Option<String>,
+    /// List tables without a namespace from all namespaces
+    pub all_namespaces: bool,
+}
+
+/// Connection to a Kubernetes cluster along with its resources, discovered lazily.
+pub struct Cluster {
+    /// Source of the objects and resources of the cluster
+    pub lister: Arc<dyn ResourceLister>,
+    /// Client of the API server, if any, to watch the objects with
+    client: Option<Client>,
+    /// Namespace of the context (or given by `ConnectOptions::namespace`,
+    /// or all namespaces with `ConnectOptions::all_namespaces`),
+    /// used for tables without a namespace
+    pub default_namespace: Namespaces,
+    /// Namespace of the context (or given by `ConnectOptions::namespace`) even with all namespaces,
+    /// to list in when neither a resource nor the namespaces can be listed across namespaces
+    pub context_namespace: Option<String>,
+    /// Resources of all groups
+    api_resources: OnceCell<Vec<APIResource>>,
+    /// Resources of the core group
+    core_api_resources: OnceCell<Vec<APIResource>>,
+    /// Cache to store the resources of all groups to once discovered
+    cache: Option<DiscoveryCache>,
+}
+
+impl Cluster {
+    /// Connect to the cluster of the kubeconfig context.
+    /// The resources are taken from the discovery cached on disk if fresh,
+    /// and discovered on demand otherwise.
+    pub async fn connect(context: &str, options: &ConnectOptions) -> anyhow::Result<Self> {
+        let mut kubeconfig = match &options.server {
+            Some(server) => server_kubeconfig(server, options),
+            None => read_kubeconfig(options.kubeconfig.as_deref())?,
+        };
+        apply_tls_options(&mut kubeconfig, context, options);
+        let kubeconfig_options = KubeConfigOptions {
+            context: Some(context.to_string()),
+            ..Default::default()
+        };
+        let mut config = Config::from_custom_kubeconfig(kubeconfig, &kubeconfig_options).await?;
+        // Responses are gzip compressed unless disabled here or in the kubeconfig.
+        config.disable_compression |= options.disable_compression;
+        if let Some(namespace) = &options.namespace {
+            config.default_namespace = namespace.clone();
+        }
+        if let Some(timeout) = options.request_timeout {
+            config.connect_timeout = Some(timeout);
+            config.read_timeout = Some(timeout);
+            config.write_timeout = Some(timeout);
+        }
+        let client = match &options.rate_limit {
+            Some(rate_limit) => ClientBuilder::try_from(config.clone())?
+                .with_layer(rate_limit)
+                .build(),
+            None => Client::try_from(config.clone())?,
+        };
+
+        let cache = DiscoveryCache::new(context, &config.cluster_url.to_string());
+        let cached = match &cache {
+            Some(cache) if !options.refresh_discovery => cache.load(),
+            _ => None,
+        };
+
+        Ok(Self {
+            default_namespace: if options.all_namespaces {
+                Namespaces::All
+            } else {
+                Namespaces::One(config.default_namespace.clone())
+            },
+            context_namespace: Some(config.default_namespace.clone()),
+            lister: Arc::new(KubeLister::new(client.clone()).with_retry(options.retry)),
+            client: Some(client),
+            api_resources: OnceCell::new_with(cached),
+            core_api_resources: OnceCell::new(),
+            cache,
+        })
+    }
+
+    /// Query the cluster of the client, in its default namespace,
+    /// discovering the resources on demand without caching them on disk.
+    pub fn from_client(client: Client) -> Self {
+        let mut cluster = Self::from_lister(
+            Arc::new(KubeLister::new(client.clone())),
+            Namespaces::One(client.default_namespace().to_string()),
+        );
+        cluster.client = Some(client);
+        cluster
+    }
+
+    /// Query the objects of the lister, e.g. an in-memory fake,
+    /// which cannot be watched.
+    pub fn from_lister(lister: Arc<dyn ResourceLister>, default_namespace: Namespaces) -> Self {
+        Self {
+            lister,
+            client: None,
+            context_namespace: match &default_namespace {
+                Namespaces::One(namespace) => Some(namespace.clone()),
+                Namespaces::All | Namespaces::Glob(_) => None,
+            },
+            default_namespace,
+            api_resources: OnceCell::new(),
+            core_api_resources: OnceCell::new(),
+            cache: None,
+        }
+    }
+
+    /// Client of the API server, `None` for objects of a lister only
+    pub fn client(&self) -> Option<&Client> {
+        self.client.as_ref()
+    }
+
+    /// Returns the resources of all groups, discovering them on first use.
+    pub async fn api_resources(&self) -> anyhow::Result<&[APIResource]> {
+        let api_resources = self
+            .api_resources
+            .get_or_try_init(|| async {
+                let discovery = self.lister.discover(DiscoveryScope::All).await?;
+                discovery.warn_failures();
+                // Do not cache incomplete discovery so that skipped groups are retried next time.
+                if let Some(cache) = self
+                    .cache
+                    .as_ref()
+                    .filter(|_| discovery.failures.is_empty())
+                {
+                    cache.store(&discovery.api_resources);
+                }
+                Ok::<_, anyhow::Error>(discovery.api_resources)
+            })
+            .await?;
+        Ok(api_resources)
+    }
+
+    /// Returns the resources to find the resource name in.
+    ///
+    /// Unless all resources are already known, only the groups needed are discovered
+    /// so that e.g. `pods` does not wait on every aggregated API and CRD group:
+    /// the core group first, then the groups the name is qualified with, and all groups last.
+    pub async fn api_resources_for(&self, resource: &str) -> anyhow::Result<Vec<APIResource>> {
+        if let Some(api_resources) = self.api_resources.get() {
+            return Ok(api_resources.clone());
+        }
+
+        let core_api_resources = self
+            .core_api_resources
+            .get_or_try_init(|| async {
+                let discovery = self.lister.discover(DiscoveryScope::Core).await?;
+                Ok::<_, anyhow::Error>(normalize(discovery.api_resources))
+            })
+            .await?;
+        if find_resource(resource, core_api_resources).is_some() {
+            return Ok(core_api_resources.clone());
+        }
+
+        if resource.contains('.') {
+            let discovery = self
+                .lister
+                .discover(DiscoveryScope::Qualified(resource))
+                .await?;
+            if find_resource(resource, &discovery.api_resources).is_some() {
+                return Ok(discovery.api_resources);
+            }
+        }
+
+        Ok(self.api_resources().await?.to_vec())
+    }
+
+    /// Returns the namespaces of a resource type to list,
+    /// `None` standing for all namespaces (or none, for cluster-scoped resources).
+    /// A namespace pattern is matched against the namespaces listed once from the cluster.
+    pub async fn namespaces(
+        &self,
+        api_resource: &APIResource,
+        namespaces: &Namespaces,
+    ) -> anyhow::Result<Vec<Option<String>>> {
+        if !api_resource.namespaced {
+            return Ok(vec![None]);
+        }
+        match namespaces {
+            Namespaces::One(namespace) => Ok(vec![Some(namespace.clone())]),
+            Namespaces::All => Ok(vec![None]),
+            Namespaces::Glob(pattern) => {
+                let namespaces = self
+                    .lister
+                    .list(&namespace_resource(), None, &ListParams::default(), true)
+                    .await?;
+                Ok(namespaces
+                    .items
+                    .into_iter()
+                    .filter_map(|namespace| namespace.metadata.name)
+                    .filter(|name| pattern.matches(name))
+                    .map(Some)
+                    .collect())
+            }
+        }
+    }
+
+    /// Create Apis covering the given namespaces of a resource type,
+    /// one per namespace matching a pattern, to watch the objects with.
+    pub async fn apis(
+        &self,
+        api_resource: &APIResource,
+        namespaces: &Namespaces,
+    ) -> anyhow::Result<Vec<Api<DynamicObject>>> {
+        let client = self
+            .client
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("only the objects of an API server can be watched"))?;
+        let lister = KubeLister::new(client.clone());
+        Ok(self
+            .namespaces(api_resource, namespaces)
+            .await?
+            .iter()
+            .map(|namespace| lister.api(api_resource, namespace.as_deref()))
+            .collect())
+    }
+}
+
+/// Resource of the Namespaces, to match namespace patterns against
+fn namespace_resource() -> APIResource {
+    APIResource {
+        name: "namespaces".to_string(),
+        singular_name: "namespace".to_string(),
+        group: Some("core".to_string()),
+        version: Some("v1".to_string()),
+        kind: "Namespace".to_string(),
+        namespaced: false,
+        verbs: vec!["list".to_string()],
+        ..Default::default()
+    }
+}
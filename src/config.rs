@@ -0,0 +1,157 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use clap::{ArgAction, Command};
+use toml::{Table, Value};
+
+/// Returns the directory of the configuration of kuqu,
+/// `$XDG_CONFIG_HOME/kuqu` (or `~/.config/kuqu`).
+pub fn config_dir() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join("kuqu"))
+}
+
+/// Returns the path of the configuration file, `<config dir>/config.toml`.
+pub fn config_path() -> Option<PathBuf> {
+    Some(config_dir()?.join("config.toml"))
+}
+
+/// Sets the defaults of the flags of the command to the values of the configuration file,
+/// keyed by the long names of the flags (e.g. `output = "json"`),
+/// so that the flags given explicitly override them. A missing file sets no defaults.
+pub fn with_defaults(mut command: Command, path: &Path) -> anyhow::Result<Command> {
+    let config = match fs::read_to_string(path) {
+        Ok(config) => config,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(command),
+        Err(e) => anyhow::bail!("failed to read {}: {}", path.display(), e),
+    };
+    let config: Table = toml::from_str(&config)
+        .map_err(|e| anyhow::anyhow!("failed to parse {}: {}", path.display(), e))?;
+    for (key, value) in config {
+        let arg = command
+            .get_arguments()
+            .find(|arg| {
+                arg.get_long() == Some(key.as_str()) && !matches!(key.as_str(), "help" | "version")
+            })
+            .ok_or_else(|| anyhow::anyhow!("unknown key '{}' in {}", key, path.display()))?;
+        let values = default_values(&value)
+            .ok_or_else(|| anyhow::anyhow!("invalid value of '{}' in {}", key, path.display()))?;
+        let multiple =
+            matches!(arg.get_action(), ArgAction::Append) || arg.get_value_delimiter().is_some();
+        if values.len() > 1 && !multiple {
+            anyhow::bail!("'{}' in {} takes a single value", key, path.display());
+        }
+        let id = arg.get_id().clone();
+        command = command.mut_arg(id, |arg| arg.default_values(values));
+    }
+    Ok(command)
+}
+
+/// Returns the values as given on the command line, an item of an array each.
+fn default_values(value: &Value) -> Option<Vec<String>> {
+    match value {
+        Value::Array(values) => values.iter().map(default_value).collect(),
+        value => Some(vec![default_value(value)?]),
+    }
+}
+
+/// Returns the scalar value as given on the command line.
+fn default_value(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Integer(i) => Some(i.to_string()),
+        Value::Float(f) => Some(f.to_string()),
+        Value::Boolean(b) => Some(b.to_string()),
+        Value::Array(_) | Value::Datetime(_) | Value::Table(_) => None,
+    }
+}
+
+/// Returns the path of the saved query of the name, `<config dir>/queries/<name>.sql`.
+pub fn saved_query_path(name: &str) -> Option<PathBuf> {
+    Some(config_dir()?.join("queries").join(format!("{}.sql", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{Arg, ArgMatches};
+
+    use super::*;
+
+    fn parse(name: &str, config: &str, args: &[&str]) -> anyhow::Result<ArgMatches> {
+        let path = std::env::temp_dir().join(format!("kuqu-{}-{}.toml", name, std::process::id()));
+        fs::write(&path, config)?;
+        let command = Command::new("kuqu")
+            .arg(Arg::new("output").long("output"))
+            .arg(Arg::new("param").long("param").action(ArgAction::Append))
+            .arg(Arg::new("resources").long("resources").value_delimiter(','));
+        let command = with_defaults(command, &path);
+        fs::remove_file(&path)?;
+        Ok(command?.try_get_matches_from(args)?)
+    }
+
+    fn values<'a>(matches: &'a ArgMatches, id: &str) -> Vec<&'a str> {
+        matches
+            .get_many::<String>(id)
+            .unwrap_or_default()
+            .map(String::as_str)
+            .collect()
+    }
+
+    #[test]
+    fn defaults_of_arrays() {
+        let config = r#"
+output = "json"
+param = ["a=1", "b=2"]
+resources = ["pods", "services,nodes"]
+"#;
+        let matches = parse("arrays", config, &["kuqu"]).unwrap();
+        assert_eq!(values(&matches, "output"), ["json"]);
+        assert_eq!(values(&matches, "param"), ["a=1", "b=2"]);
+        assert_eq!(values(&matches, "resources"), ["pods", "services", "nodes"]);
+
+        let matches = parse("arrays", config, &["kuqu", "--param", "c=3"]).unwrap();
+        assert_eq!(values(&matches, "param"), ["c=3"]);
+    }
+
+    #[test]
+    fn invalid_values() {
+        let error = |config: &str| {
+            parse("invalid", config, &["kuqu"])
+                .unwrap_err()
+                .to_string()
+                .replace(&std::env::temp_dir().display().to_string(), "")
+        };
+        let path = format!("/kuqu-invalid-{}.toml", std::process::id());
+        assert_eq!(
+            error(r#"output = ["json", "yaml"]"#),
+            format!("'output' in {} takes a single value", path)
+        );
+        assert_eq!(
+            error("param = [[\"a=1\"]]"),
+            format!("invalid value of 'param' in {}", path)
+        );
+        assert_eq!(
+            error("unknown = 1"),
+            format!("unknown key 'unknown' in {}", path)
+        );
+    }
+}
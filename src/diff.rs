@@ -0,0 +1,293 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use datafusion::arrow::{
+    datatypes::Schema,
+    json::{ArrayWriter, ReaderBuilder},
+    record_batch::RecordBatch,
+};
+use serde_json::{Map, Value};
+
+use crate::schema::{conform, infer_json_schema};
+
+/// Column telling how the row differs: `only in left`, `only in right` or `changed`
+pub const DIFF_COLUMN: &str = "_diff";
+
+/// Column holding the context the row came from
+pub const CONTEXT_COLUMN: &str = "_context";
+
+type Row = Map<String, Value>;
+
+/// Results of a query in a context
+pub struct Side<'a> {
+    pub context: &'a str,
+    pub batches: &'a [RecordBatch],
+}
+
+/// Compare the results of a query in two contexts, keyed by the namespace and name columns
+/// (e.g. `namespace` or `deployments.metadata[name]`), or by the whole row without a name column.
+///
+/// Returns the rows only in either side, and both rows of each key whose rows differ,
+/// with the `_diff` and `_context` columns first. `None` if the results are the same.
+pub fn diff(left: Side, right: Side) -> anyhow::Result<Option<RecordBatch>> {
+    let mut columns: Vec<String> = Vec::new();
+    for batch in left.batches.iter().chain(right.batches) {
+        for field in batch.schema().fields() {
+            if !columns.contains(field.name()) {
+                columns.push(field.name().clone());
+            }
+        }
+    }
+    let key = key_columns(&columns);
+
+    let mut pairs: BTreeMap<String, (Vec<Row>, Vec<Row>)> = BTreeMap::new();
+    for row in rows(left.batches)? {
+        pairs.entry(row_key(&row, &key)).or_default().0.push(row);
+    }
+    for row in rows(right.batches)? {
+        pairs.entry(row_key(&row, &key)).or_default().1.push(row);
+    }
+
+    let mut diffs = Vec::new();
+    let tagged = |diff: &str, context: &str, row: Row| {
+        let mut tagged = Map::new();
+        tagged.insert(DIFF_COLUMN.to_string(), diff.into());
+        tagged.insert(CONTEXT_COLUMN.to_string(), context.into());
+        tagged.extend(row);
+        tagged
+    };
+    for (left_rows, right_rows) in pairs.into_values() {
+        let mut right_rows = right_rows.into_iter();
+        for left_row in left_rows {
+            match right_rows.next() {
+                Some(right_row) if right_row == left_row => {}
+                Some(right_row) => {
+                    diffs.push(tagged("changed", left.context, left_row));
+                    diffs.push(tagged("changed", right.context, right_row));
+                }
+                None => diffs.push(tagged("only in left", left.context, left_row)),
+            }
+        }
+        diffs.extend(right_rows.map(|row| tagged("only in right", right.context, row)));
+    }
+    if diffs.is_empty() {
+        return Ok(None);
+    }
+
+    // Infer the schema from the rows, since the sides may type a column differently,
+    // keeping the columns in the order of the query.
+    let mut diffs: Vec<Value> = diffs.into_iter().map(Value::Object).collect();
+    let inferred = infer_json_schema(diffs.iter().cloned())?;
+    let fields = [DIFF_COLUMN, CONTEXT_COLUMN]
+        .into_iter()
+        .chain(columns.iter().map(String::as_str))
+        .filter_map(|name| inferred.field_with_name(name).ok().cloned())
+        .collect::<Vec<_>>();
+    let schema = Arc::new(Schema::new(fields));
+    for diff in &mut diffs {
+        conform(diff, schema.fields());
+    }
+    // Numbers of columns widened to strings are decoded as strings.
+    let mut decoder = ReaderBuilder::new(schema.clone())
+        .with_coerce_primitive(true)
+        .build_decoder()?;
+    decoder.serialize(&diffs)?;
+    Ok(Some(
+        decoder
+            .flush()?
+            .unwrap_or_else(|| RecordBatch::new_empty(schema)),
+    ))
+}
+
+/// Columns identifying an object in the results: those of its namespace (if any) and name.
+/// Empty without a name column.
+fn key_columns(columns: &[String]) -> Vec<String> {
+    let find = |key: &str| {
+        columns
+            .iter()
+            .find(|column| {
+                column.as_str() == key
+                    || column.ends_with(&format!("[{}]", key))
+                    || column.ends_with(&format!(".{}", key))
+            })
+            .cloned()
+    };
+    match find("name") {
+        Some(name) => find("namespace").into_iter().chain([name]).collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Key of the row: the values of the key columns, or the whole row without key columns
+fn row_key(row: &Row, key: &[String]) -> String {
+    if key.is_empty() {
+        return Value::Object(row.clone()).to_string();
+    }
+    let values: Vec<&Value> = key
+        .iter()
+        .map(|column| row.get(column).unwrap_or(&Value::Null))
+        .collect();
+    serde_json::to_string(&values).unwrap_or_default()
+}
+
+/// Rows of the batches as JSON objects
+fn rows(batches: &[RecordBatch]) -> anyhow::Result<Vec<Row>> {
+    let mut writer = ArrayWriter::new(Vec::new());
+    writer.write_batches(&batches.iter().collect::<Vec<_>>())?;
+    writer.finish()?;
+    Ok(serde_json::from_slice(&writer.into_inner())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::{
+        array::{ArrayRef, Int64Array, StringArray},
+        util::pretty::pretty_format_batches,
+    };
+
+    use super::*;
+
+    fn batch(columns: Vec<(&str, ArrayRef)>) -> RecordBatch {
+        RecordBatch::try_from_iter(columns).unwrap()
+    }
+
+    fn strings(values: &[&str]) -> ArrayRef {
+        Arc::new(StringArray::from(values.to_vec()))
+    }
+
+    fn deployments(names: &[&str], replicas: ArrayRef) -> RecordBatch {
+        batch(vec![
+            (
+                "deployments.metadata[namespace]",
+                strings(&vec!["default"; names.len()]),
+            ),
+            ("deployments.metadata[name]", strings(names)),
+            ("replicas", replicas),
+        ])
+    }
+
+    #[test]
+    fn keys() {
+        let columns = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            key_columns(&columns(&[
+                "replicas",
+                "metadata.name",
+                "metadata.namespace"
+            ])),
+            ["metadata.namespace", "metadata.name"]
+        );
+        assert_eq!(
+            key_columns(&columns(&["pods.metadata[name]", "phase"])),
+            ["pods.metadata[name]"]
+        );
+        assert_eq!(key_columns(&columns(&["name"])), ["name"]);
+        assert!(key_columns(&columns(&["namespace", "count"])).is_empty());
+    }
+
+    #[test]
+    fn diff_by_key() {
+        let left = [deployments(
+            &["web", "api", "old"],
+            Arc::new(Int64Array::from(vec![1, 2, 1])),
+        )];
+        let right = [deployments(
+            &["web", "api", "new"],
+            Arc::new(Int64Array::from(vec![3, 2, 1])),
+        )];
+        let diff = diff(
+            Side {
+                context: "a",
+                batches: &left,
+            },
+            Side {
+                context: "b",
+                batches: &right,
+            },
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            pretty_format_batches(&[diff]).unwrap().to_string(),
+            "\
++---------------+----------+---------------------------------+----------------------------+----------+
+| _diff         | _context | deployments.metadata[namespace] | deployments.metadata[name] | replicas |
++---------------+----------+---------------------------------+----------------------------+----------+
+| only in right | b        | default                         | new                        | 1        |
+| only in left  | a        | default                         | old                        | 1        |
+| changed       | a        | default                         | web                        | 1        |
+| changed       | b        | default                         | web                        | 3        |
++---------------+----------+---------------------------------+----------------------------+----------+"
+        );
+    }
+
+    #[test]
+    fn same_results() {
+        let left = [deployments(&["web"], Arc::new(Int64Array::from(vec![1])))];
+        let right = left.clone();
+        let diff = diff(
+            Side {
+                context: "a",
+                batches: &left,
+            },
+            Side {
+                context: "b",
+                batches: &right,
+            },
+        )
+        .unwrap();
+        assert!(diff.is_none());
+    }
+
+    /// Rows without a name column are keyed (and ordered) by their JSON text.
+    #[test]
+    fn diff_whole_rows_of_different_types() {
+        let left = [batch(vec![(
+            "count",
+            Arc::new(Int64Array::from(vec![1, 2])) as ArrayRef,
+        )])];
+        let right = [batch(vec![("count", strings(&["2", "x"]))])];
+        let diff = diff(
+            Side {
+                context: "a",
+                batches: &left,
+            },
+            Side {
+                context: "b",
+                batches: &right,
+            },
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            pretty_format_batches(&[diff]).unwrap().to_string(),
+            "\
++---------------+----------+-------+
+| _diff         | _context | count |
++---------------+----------+-------+
+| only in right | b        | 2     |
+| only in right | b        | x     |
+| only in left  | a        | 1     |
+| only in left  | a        | 2     |
++---------------+----------+-------+"
+        );
+    }
+}
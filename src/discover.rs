@@ -12,64 +12,226 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::{
+    collections::HashSet,
+    fs,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
 use futures::{
     future::try_join_all,
     stream::{self, StreamExt},
 };
-use k8s_openapi::apimachinery::pkg::apis::meta::v1::APIResource;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{APIGroup, APIResource};
 use kube::Client;
+use sha2::{Digest, Sha256};
+
+use crate::retry::Retry;
+
+/// How long discovered resources cached on disk are used before discovering again.
+const DISCOVERY_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Resources discovered from a context, cached on disk
+/// (`$XDG_CACHE_HOME/kuqu/discovery/<context>-<hash>.json`, or under `~/.cache`)
+/// because discovery requests every API group and takes seconds on clusters with many CRDs.
+/// The hash is of the context and the URL of its API server, since kubeconfigs
+/// commonly reuse context names (e.g. `default`, `kind-kind`) for different clusters.
+///
+/// The cache is best-effort: failures to read or write it fall back to discovery.
+pub struct DiscoveryCache {
+    path: PathBuf,
+}
+
+impl DiscoveryCache {
+    /// Returns `None` if there is no cache directory to use.
+    pub fn new(context: &str, server: &str) -> Option<Self> {
+        let cache_dir = std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+        // Context names may contain path separators (e.g. EKS ARNs).
+        let file_name: String = context
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+                _ => '_',
+            })
+            .collect();
+        let mut hasher = Sha256::new();
+        hasher.update(server);
+        hasher.update([0]);
+        hasher.update(context);
+        let hash = format!("{:x}", hasher.finalize());
+        Some(Self {
+            path: cache_dir.join("kuqu").join("discovery").join(format!(
+                "{}-{}.json",
+                file_name,
+                &hash[..16]
+            )),
+        })
+    }
+
+    /// Load the cached resources, unless missing, expired or unreadable.
+    pub fn load(&self) -> Option<Vec<APIResource>> {
+        let modified = fs::metadata(&self.path).ok()?.modified().ok()?;
+        if SystemTime::now().duration_since(modified).ok()? > DISCOVERY_CACHE_TTL {
+            return None;
+        }
+        serde_json::from_slice(&fs::read(&self.path).ok()?).ok()
+    }
+
+    /// Store the resources, replacing the cached ones atomically.
+    pub fn store(&self, api_resources: &[APIResource]) {
+        let _ = self.try_store(api_resources);
+    }
+
+    fn try_store(&self, api_resources: &[APIResource]) -> anyhow::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = self
+            .path
+            .with_extension(format!("json.{}", std::process::id()));
+        fs::write(&tmp, serde_json::to_vec(api_resources)?)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Resources discovered from the group versions that responded,
+/// along with the errors of those that did not
+/// (commonly an unavailable aggregated API such as `metrics.k8s.io`).
+#[derive(Default)]
+pub struct Discovery {
+    pub api_resources: Vec<APIResource>,
+    pub failures: Vec<(String, anyhow::Error)>,
+}
+
+impl Discovery {
+    /// Print a warning for each group version that could not be discovered.
+    pub fn warn_failures(&self) {
+        for (group_version, e) in &self.failures {
+            eprintln!(
+                "Warning: skipped API group version '{}' during discovery: {}",
+                group_version, e
+            );
+        }
+    }
+}
 
 pub struct DiscoverClient {
     client: Client,
+    retry: Retry,
 }
 
 impl DiscoverClient {
     pub fn new(client: Client) -> Self {
-        Self { client }
+        Self {
+            client,
+            retry: Retry::default(),
+        }
+    }
+
+    /// Set the retry policy of the discovery requests
+    pub fn with_retry(mut self, retry: Retry) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// List the resources of all groups, the core group first.
+    /// The versions of each group are listed with the preferred version first,
+    /// so that `find_resource` picks it for names served in several versions.
+    pub async fn list_api_resources(&self) -> anyhow::Result<Discovery> {
+        let core_api_resources = self.list_core_api_resources().await?;
+        let discovery = self.list_api_groups_resources().await?;
+        Ok(Discovery {
+            api_resources: normalize(
+                core_api_resources
+                    .into_iter()
+                    .chain(discovery.api_resources),
+            ),
+            failures: discovery.failures,
+        })
     }
 
-    pub async fn list_api_resources(&self) -> anyhow::Result<Vec<APIResource>> {
-        Ok(self
-            .list_api_groups_resources()
+    /// List the resources of the groups the resource name is qualified with,
+    /// e.g. `apps` for `deployments.apps` or `deployments.v1.apps`.
+    pub async fn list_qualified_api_resources(&self, resource: &str) -> anyhow::Result<Discovery> {
+        let groups = self
+            .retry
+            .run(|| self.client.list_api_groups())
             .await?
+            .groups
             .into_iter()
-            .chain(self.list_core_api_resources().await?)
-            // Filter out subresources.
-            .filter(|resource| !resource.name.contains("/"))
-            .collect())
+            .filter(|group| resource.ends_with(&format!(".{}", group.name)))
+            .collect();
+        Ok(self.list_groups_resources(groups).await)
     }
 
-    pub async fn list_api_groups_resources(&self) -> anyhow::Result<Vec<APIResource>> {
-        let groups = self.client.list_api_groups().await?.groups;
-        let resources = stream::iter(groups)
-            .flat_map(|group| stream::iter(group.versions))
-            .then(|version| async move {
-                let mut resources = self
-                    .client
-                    .list_api_group_resources(&version.group_version)
-                    .await?;
-                // NOTE: For some reason, `version` and `group` are None, so we need to set them manually.
-                for resource in &mut resources.resources {
-                    if let Some((group, version)) = version.group_version.split_once('/') {
-                        resource.group = Some(group.to_string());
-                        resource.version = Some(version.to_string());
-                    }
-                }
-                Ok::<_, anyhow::Error>(resources)
+    pub async fn list_api_groups_resources(&self) -> anyhow::Result<Discovery> {
+        let groups = self
+            .retry
+            .run(|| self.client.list_api_groups())
+            .await?
+            .groups;
+        Ok(self.list_groups_resources(groups).await)
+    }
+
+    /// List the resources of the groups,
+    /// skipping the group versions that fail instead of failing entirely.
+    async fn list_groups_resources(&self, groups: Vec<APIGroup>) -> Discovery {
+        let results = stream::iter(groups)
+            .flat_map(|group| {
+                let preferred = group
+                    .preferred_version
+                    .map(|preferred| preferred.group_version);
+                let mut versions = group.versions;
+                // Stable sort keeps the server's order of the other versions.
+                versions.sort_by_key(|version| Some(&version.group_version) != preferred.as_ref());
+                stream::iter(versions)
             })
-            .flat_map(|api_resource_list| {
-                stream::iter(api_resource_list.unwrap_or_default().resources)
+            .then(|version| async move {
+                let result = self
+                    .retry
+                    .run(|| self.client.list_api_group_resources(&version.group_version))
+                    .await;
+                (version.group_version, result)
             })
             .collect::<Vec<_>>()
             .await;
-        Ok(resources)
+
+        let mut discovery = Discovery::default();
+        for (group_version, result) in results {
+            match result {
+                Ok(mut resources) => {
+                    // NOTE: For some reason, `version` and `group` are None, so we need to set them manually.
+                    for resource in &mut resources.resources {
+                        if let Some((group, version)) = group_version.split_once('/') {
+                            resource.group = Some(group.to_string());
+                            resource.version = Some(version.to_string());
+                        }
+                    }
+                    discovery.api_resources.extend(resources.resources);
+                }
+                Err(e) => discovery.failures.push((group_version, e.into())),
+            }
+        }
+        discovery.api_resources = normalize(discovery.api_resources);
+        discovery
     }
 
     pub async fn list_core_api_resources(&self) -> anyhow::Result<Vec<APIResource>> {
-        let versions = self.client.list_core_api_versions().await?.versions;
+        let versions = self
+            .retry
+            .run(|| self.client.list_core_api_versions())
+            .await?
+            .versions;
 
         Ok(try_join_all(versions.into_iter().map(|version| async move {
-            let mut resources = self.client.list_core_api_resources(&version).await?;
+            let mut resources = self
+                .retry
+                .run(|| self.client.list_core_api_resources(&version))
+                .await?;
             // NOTE: For some reason, `version` is None, so we need to set them manually.
             for resource in &mut resources.resources {
                 resource.group = Some("core".to_string());
@@ -83,3 +245,43 @@ impl DiscoverClient {
         .collect())
     }
 }
+
+/// Filter out subresources and duplicates.
+pub fn normalize(resources: impl IntoIterator<Item = APIResource>) -> Vec<APIResource> {
+    let mut seen = HashSet::new();
+    resources
+        .into_iter()
+        .filter(|resource| !resource.name.contains("/"))
+        .filter(|resource| {
+            seen.insert((
+                resource.group.clone(),
+                resource.version.clone(),
+                resource.name.clone(),
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_path_per_server_and_context() {
+        let path = |context: &str, server: &str| {
+            DiscoveryCache::new(context, server)
+                .map(|cache| cache.path)
+                .unwrap_or_default()
+        };
+        let kind = path("kind-kind", "https://127.0.0.1:6443/");
+        assert!(
+            kind.file_name()
+                .unwrap()
+                .to_string_lossy()
+                .starts_with("kind-kind-")
+        );
+        assert_eq!(kind, path("kind-kind", "https://127.0.0.1:6443/"));
+        assert_ne!(kind, path("kind-kind", "https://127.0.0.1:7443/"));
+        assert_ne!(path("a:b", ""), path("a/b", ""));
+    }
+}
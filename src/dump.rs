@@ -0,0 +1,514 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use flate2::read::GzDecoder;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::APIResource;
+use kube::{
+    api::{ListParams, ObjectList, TypeMeta},
+    core::{ApiResource, GroupVersionKind, ListMeta},
+};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    discover::Discovery,
+    dynamic::DynamicObject,
+    lister::{DiscoveryScope, ResourceLister},
+};
+
+/// Magic bytes of gzip
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Objects of a resource by namespace and name
+type Objects = BTreeMap<(String, String), DynamicObject>;
+
+/// Objects read from files written by `kubectl get -o json` (or `-o yaml`)
+/// or `kubectl cluster-info dump`, to query without access to the cluster.
+///
+/// The resources are those of the objects found,
+/// namespaced if any of their objects has a namespace.
+pub struct DumpLister {
+    /// Objects of each resource
+    resources: Vec<(APIResource, Objects)>,
+}
+
+impl DumpLister {
+    /// Read the objects of the file, or of the JSON and YAML files under the directory.
+    ///
+    /// Files of a directory that are not valid JSON or YAML (e.g. Helm templates)
+    /// are skipped with a warning.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let mut documents = Vec::new();
+        if path.is_dir() {
+            let mut files = Vec::new();
+            find_files(path, &mut files)?;
+            for file in files {
+                match read_documents(&file) {
+                    Ok(values) => documents.extend(values),
+                    Err(e) => eprintln!("Warning: skipped {}", e),
+                }
+            }
+        } else {
+            documents = read_documents(path)?;
+        }
+        Ok(Self::from_documents(documents))
+    }
+
+    /// Read the objects of manifests of multiple YAML documents,
+    /// e.g. rendered by `helm template` or `kustomize build`, gzipped or not.
+    pub fn from_manifests(manifests: &[u8]) -> anyhow::Result<Self> {
+        let manifests = text(manifests, false)
+            .map_err(|e| anyhow::anyhow!("failed to read the manifests: {}", e))?;
+        let documents = parse_yaml(&manifests)
+            .map_err(|e| anyhow::anyhow!("invalid YAML in the manifests: {}", e))?;
+        Ok(Self::from_documents(documents))
+    }
+
+    pub(crate) fn from_documents(documents: Vec<Value>) -> Self {
+        let mut objects_by_type: BTreeMap<(String, String), Vec<DynamicObject>> = BTreeMap::new();
+        for value in documents {
+            for object in flatten_list(value) {
+                let Some(types) = object.types.clone() else {
+                    continue;
+                };
+                objects_by_type
+                    .entry((types.api_version, types.kind))
+                    .or_default()
+                    .push(object);
+            }
+        }
+
+        Self::from_resources(
+            objects_by_type
+                .into_iter()
+                .map(|((api_version, kind), objects)| {
+                    (api_resource(&api_version, &kind, &objects), objects)
+                })
+                .collect(),
+        )
+    }
+
+    /// Serve the objects of each resource, e.g. read from a snapshot.
+    pub fn from_resources(resources: Vec<(APIResource, Vec<DynamicObject>)>) -> Self {
+        let resources = resources
+            .into_iter()
+            .map(|(api_resource, objects)| {
+                // Later objects override earlier ones, e.g. of an older dump.
+                let objects = objects
+                    .into_iter()
+                    .map(|object| {
+                        let key = (
+                            object.metadata.namespace.clone().unwrap_or_default(),
+                            object.metadata.name.clone().unwrap_or_default(),
+                        );
+                        (key, object)
+                    })
+                    .collect();
+                (api_resource, objects)
+            })
+            .collect();
+        Self { resources }
+    }
+
+    /// Returns the objects of the resource
+    fn objects(&self, api_resource: &APIResource) -> Option<&Objects> {
+        self.resources
+            .iter()
+            .find(|(resource, _)| {
+                resource.group == api_resource.group
+                    && resource.version == api_resource.version
+                    && resource.name == api_resource.name
+            })
+            .map(|(_, objects)| objects)
+    }
+}
+
+/// Extension of the file, that of the decompressed file for a gzipped one
+/// (e.g. `json` for `pods.json.gz`), and whether it is gzipped.
+fn extension(path: &Path) -> (Option<&str>, bool) {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("gz") => (
+            path.file_stem()
+                .map(Path::new)
+                .and_then(|stem| stem.extension())
+                .and_then(|extension| extension.to_str()),
+            true,
+        ),
+        extension => (extension, false),
+    }
+}
+
+/// Text of the content, decompressed if gzipped as told or by its magic bytes.
+fn text(content: &[u8], gzipped: bool) -> std::io::Result<String> {
+    let mut text = String::new();
+    if gzipped || content.starts_with(&GZIP_MAGIC) {
+        GzDecoder::new(content).read_to_string(&mut text)?;
+    } else {
+        text = String::from_utf8(content.to_vec())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    }
+    Ok(text)
+}
+
+/// Collect the JSON and YAML files under the directory, in the order of their paths.
+fn find_files(dir: &Path, files: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    let mut entries = fs::read_dir(dir)
+        .map_err(|e| anyhow::anyhow!("failed to read {}: {}", dir.display(), e))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            find_files(&path, files)?;
+        } else if matches!(extension(&path).0, Some("json" | "yaml" | "yml")) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Read the documents of the file: JSON values one after another,
+/// or YAML documents separated by `---`.
+/// Gzipped files (`.gz` or by their magic bytes) are decompressed first.
+fn read_documents(path: &Path) -> anyhow::Result<Vec<Value>> {
+    let (extension, gzipped) = extension(path);
+    let content = fs::read(path)
+        .and_then(|content| text(&content, gzipped))
+        .map_err(|e| anyhow::anyhow!("failed to read {}: {}", path.display(), e))?;
+    let documents = if extension == Some("json") {
+        serde_json::Deserializer::from_str(&content)
+            .into_iter::<Value>()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow::anyhow!("invalid JSON in {}: {}", path.display(), e))?
+    } else {
+        parse_yaml(&content)
+            .map_err(|e| anyhow::anyhow!("invalid YAML in {}: {}", path.display(), e))?
+    };
+    Ok(documents
+        .into_iter()
+        .filter(|value| !value.is_null())
+        .collect())
+}
+
+/// Parse the YAML documents separated by `---`.
+fn parse_yaml(content: &str) -> Result<Vec<Value>, serde_yaml::Error> {
+    serde_yaml::Deserializer::from_str(content)
+        .map(Value::deserialize)
+        .collect()
+}
+
+/// Returns the objects of a list (e.g. `kind: List` or `PodList`), or the object itself.
+/// Items of typed lists, which omit their type, are typed after the list.
+fn flatten_list(value: Value) -> Vec<DynamicObject> {
+    let Some(items) = value.get("items").and_then(Value::as_array) else {
+        return serde_json::from_value(value).into_iter().collect();
+    };
+    let item_types = match (
+        value.get("apiVersion").and_then(Value::as_str),
+        value
+            .get("kind")
+            .and_then(Value::as_str)
+            .and_then(|kind| kind.strip_suffix("List"))
+            .filter(|kind| !kind.is_empty()),
+    ) {
+        (Some(api_version), Some(kind)) => Some(TypeMeta {
+            api_version: api_version.to_string(),
+            kind: kind.to_string(),
+        }),
+        _ => None,
+    };
+    items
+        .iter()
+        .filter_map(|item| serde_json::from_value::<DynamicObject>(item.clone()).ok())
+        .map(|mut object| {
+            if object.types.is_none() {
+                object.types = item_types.clone();
+            }
+            object
+        })
+        .collect()
+}
+
+/// Resource of the objects of the type, named as discovery would (e.g. `networkpolicies`)
+fn api_resource(api_version: &str, kind: &str, objects: &[DynamicObject]) -> APIResource {
+    let (group, version) = match api_version.split_once('/') {
+        Some((group, version)) => (group, version),
+        None => ("", api_version),
+    };
+    let plural = ApiResource::from_gvk(&GroupVersionKind::gvk(group, version, kind)).plural;
+    APIResource {
+        name: plural,
+        singular_name: kind.to_lowercase(),
+        group: Some(if group.is_empty() { "core" } else { group }.to_string()),
+        version: Some(version.to_string()),
+        kind: kind.to_string(),
+        namespaced: objects
+            .iter()
+            .any(|object| object.metadata.namespace.is_some()),
+        verbs: vec!["get".to_string(), "list".to_string()],
+        ..Default::default()
+    }
+}
+
+/// Check the labels against a label selector, e.g. `app=web,tier notin (db),!canary`.
+fn matches_label_selector(selector: &str, labels: &BTreeMap<String, String>) -> bool {
+    split_requirements(selector).iter().all(|requirement| {
+        let requirement = requirement.trim();
+        if let Some(key) = requirement.strip_prefix('!') {
+            return !labels.contains_key(key.trim());
+        }
+        for (operator, negated) in [(" notin ", true), (" in ", false)] {
+            if let Some((key, values)) = requirement.split_once(operator) {
+                let values = values.trim().trim_start_matches('(').trim_end_matches(')');
+                let contained = labels
+                    .get(key.trim())
+                    .is_some_and(|value| values.split(',').any(|v| v.trim() == value));
+                // `notin` also matches objects without the label.
+                return contained != negated;
+            }
+        }
+        match parse_equality(requirement) {
+            Some((key, value, true)) => labels.get(key) == Some(&value.to_string()),
+            Some((key, value, false)) => labels.get(key) != Some(&value.to_string()),
+            None => labels.contains_key(requirement),
+        }
+    })
+}
+
+/// Check the object against a field selector, e.g. `status.phase!=Running`.
+fn matches_field_selector(selector: &str, object: &Value) -> bool {
+    split_requirements(selector).iter().all(|requirement| {
+        let Some((path, value, equal)) = parse_equality(requirement.trim()) else {
+            return false;
+        };
+        let field = path
+            .split('.')
+            .try_fold(object, |value, key| value.get(key))
+            .map(|field| match field {
+                Value::String(field) => field.clone(),
+                field => field.to_string(),
+            })
+            .unwrap_or_default();
+        (field == value) == equal
+    })
+}
+
+/// Split a selector into its requirements, keeping the values of `in (a,b)` together.
+fn split_requirements(selector: &str) -> Vec<&str> {
+    let mut requirements = Vec::new();
+    let (mut start, mut depth) = (0, 0);
+    for (i, c) in selector.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                requirements.push(&selector[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    requirements.push(&selector[start..]);
+    requirements
+        .into_iter()
+        .filter(|requirement| !requirement.trim().is_empty())
+        .collect()
+}
+
+/// Parse `key=value`, `key==value` or `key!=value` into the key, the value
+/// and whether they must be equal.
+fn parse_equality(requirement: &str) -> Option<(&str, &str, bool)> {
+    if let Some((key, value)) = requirement.split_once("!=") {
+        return Some((key.trim(), value.trim(), false));
+    }
+    let (key, value) = requirement.split_once('=')?;
+    Some((key.trim(), value.trim_start_matches('=').trim(), true))
+}
+
+/// Whether the object is of the namespace (`None` for all) and matches the list parameters
+fn matches(object: &DynamicObject, namespace: Option<&str>, lp: &ListParams) -> bool {
+    if namespace.is_some_and(|namespace| object.metadata.namespace.as_deref() != Some(namespace)) {
+        return false;
+    }
+    if let Some(selector) = &lp.label_selector {
+        let labels = object.metadata.labels.clone().unwrap_or_default();
+        if !matches_label_selector(selector, &labels) {
+            return false;
+        }
+    }
+    if let Some(selector) = &lp.field_selector {
+        let value = serde_json::to_value(object).unwrap_or_default();
+        if !matches_field_selector(selector, &value) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Keep only the metadata of the object, as a metadata-only response
+fn metadata_object(object: &DynamicObject) -> DynamicObject {
+    DynamicObject {
+        types: object.types.clone(),
+        metadata: object.metadata.clone(),
+        data: Value::Object(Default::default()),
+    }
+}
+
+#[async_trait]
+impl ResourceLister for DumpLister {
+    /// List the objects, a page of `limit` objects at a time
+    /// with the offset of the next page as the continue token.
+    async fn list(
+        &self,
+        api_resource: &APIResource,
+        namespace: Option<&str>,
+        lp: &ListParams,
+        metadata_only: bool,
+    ) -> kube::Result<ObjectList<DynamicObject>> {
+        let namespace = namespace.filter(|_| api_resource.namespaced);
+        let objects: Vec<&DynamicObject> = self
+            .objects(api_resource)
+            .into_iter()
+            .flat_map(|objects| objects.values())
+            .filter(|object| matches(object, namespace, lp))
+            .collect();
+        let start = lp
+            .continue_token
+            .as_deref()
+            .and_then(|token| token.parse().ok())
+            .unwrap_or(0)
+            .min(objects.len());
+        let end = match lp.limit {
+            Some(limit) => objects.len().min(start + limit as usize),
+            None => objects.len(),
+        };
+        let items = objects[start..end]
+            .iter()
+            .map(|object| match metadata_only {
+                true => metadata_object(object),
+                false => (*object).clone(),
+            })
+            .collect();
+        Ok(ObjectList {
+            types: Default::default(),
+            metadata: ListMeta {
+                continue_: (end < objects.len()).then(|| end.to_string()),
+                ..Default::default()
+            },
+            items,
+        })
+    }
+
+    async fn get(
+        &self,
+        api_resource: &APIResource,
+        namespace: Option<&str>,
+        name: &str,
+        metadata_only: bool,
+    ) -> kube::Result<Option<DynamicObject>> {
+        let namespace = namespace.filter(|_| api_resource.namespaced);
+        Ok(self
+            .objects(api_resource)
+            .into_iter()
+            .flat_map(|objects| objects.values())
+            .find(|object| {
+                object.metadata.name.as_deref() == Some(name)
+                    && namespace.is_none_or(|namespace| {
+                        object.metadata.namespace.as_deref() == Some(namespace)
+                    })
+            })
+            .map(|object| match metadata_only {
+                true => metadata_object(object),
+                false => object.clone(),
+            }))
+    }
+
+    async fn discover(&self, scope: DiscoveryScope<'_>) -> anyhow::Result<Discovery> {
+        let api_resources = self
+            .resources
+            .iter()
+            .map(|(api_resource, _)| api_resource.clone())
+            .filter(|api_resource| match scope {
+                DiscoveryScope::Core => api_resource.group.as_deref() == Some("core"),
+                DiscoveryScope::Qualified(_) | DiscoveryScope::All => true,
+            })
+            .collect();
+        Ok(Discovery {
+            api_resources,
+            failures: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{Compression, write::GzEncoder};
+
+    use super::*;
+
+    fn names(lister: &DumpLister, kind: &str) -> Vec<String> {
+        lister
+            .resources
+            .iter()
+            .filter(|(resource, _)| resource.kind == kind)
+            .flat_map(|(_, objects)| objects.values())
+            .filter_map(|object| object.metadata.name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn load_gzipped_dump() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/pods.json.gz");
+        let lister = DumpLister::load(&path).unwrap();
+        assert_eq!(names(&lister, "Pod"), ["web-1", "web-2"]);
+    }
+
+    #[test]
+    fn find_gzipped_files() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let lister = DumpLister::load(&dir).unwrap();
+        assert_eq!(names(&lister, "Pod"), ["web-1", "web-2"]);
+    }
+
+    #[test]
+    fn gzipped_manifests() {
+        let manifests = "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: a\n---\n\
+                         apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: b\n";
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(manifests.as_bytes()).unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        let lister = DumpLister::from_manifests(&gzipped).unwrap();
+        assert_eq!(names(&lister, "ConfigMap"), ["a", "b"]);
+        let lister = DumpLister::from_manifests(manifests.as_bytes()).unwrap();
+        assert_eq!(names(&lister, "ConfigMap"), ["a", "b"]);
+    }
+
+    #[test]
+    fn extension_of_gzipped_file() {
+        assert_eq!(extension(Path::new("pods.json.gz")), (Some("json"), true));
+        assert_eq!(extension(Path::new("pods.yaml")), (Some("yaml"), false));
+        assert_eq!(extension(Path::new("pods.gz")), (None, true));
+    }
+}
@@ -0,0 +1,348 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{fmt::Write, fs, path::Path, sync::Arc};
+
+use datafusion::{
+    arrow::{
+        array::{Array, AsArray},
+        compute::cast,
+        datatypes::{DataType, Float64Type},
+        record_batch::RecordBatch,
+    },
+    execution::context::SessionContext,
+};
+use http::{Method, StatusCode};
+use serde::Deserialize;
+
+use crate::{
+    http::{response, serve},
+    views::Views,
+    warnings::Warnings,
+};
+
+/// Content type of the Prometheus text exposition format
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Column of the value of a gauge, unless configured
+const DEFAULT_VALUE_COLUMN: &str = "value";
+
+/// Gauges to export, read from `metrics.toml`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub metrics: Vec<Metric>,
+}
+
+impl Config {
+    /// Read the metrics from the TOML file
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("failed to read {}: {}", path.display(), e))?;
+        let config: Self = toml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("invalid metrics in {}: {}", path.display(), e))?;
+        if config.metrics.is_empty() {
+            anyhow::bail!("no metrics in {}", path.display());
+        }
+        Ok(config)
+    }
+}
+
+/// Gauge of a query, a sample per row
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Metric {
+    /// Name of the gauge, e.g. `kube_pods_not_running`
+    pub name: String,
+    #[serde(default)]
+    pub help: Option<String>,
+    pub query: String,
+    /// Columns of the labels, all columns except the value column by default
+    #[serde(default)]
+    pub labels: Option<Vec<String>>,
+    /// Column of the value, `value` by default
+    #[serde(default)]
+    pub value: Option<String>,
+}
+
+/// Serves the gauges of the queries at `/metrics`, running the queries on each scrape.
+pub struct Exporter {
+    ctx: SessionContext,
+    views: Views,
+    /// Warnings of the queries, printed after each scrape
+    warnings: Warnings,
+    metrics: Vec<Metric>,
+}
+
+impl Exporter {
+    pub fn new(ctx: SessionContext, views: Views, warnings: Warnings, config: Config) -> Self {
+        Self {
+            ctx,
+            views,
+            warnings,
+            metrics: config.metrics,
+        }
+    }
+
+    /// Serve on the address until interrupted
+    pub async fn serve(self, addr: std::net::SocketAddr) -> anyhow::Result<()> {
+        let exporter = Arc::new(self);
+        serve(addr, move |request, _| {
+            let exporter = Arc::clone(&exporter);
+            async move {
+                if request.method() != Method::GET || request.uri().path() != "/metrics" {
+                    return response(StatusCode::NOT_FOUND, "text/plain", "not found\n");
+                }
+                response(StatusCode::OK, CONTENT_TYPE, exporter.scrape().await)
+            }
+        })
+        .await
+    }
+
+    /// Run the queries and format the gauges in the text exposition format.
+    ///
+    /// Gauges of failing queries are left out, with the errors reported to stderr.
+    pub async fn scrape(&self) -> String {
+        let mut text = String::new();
+        for metric in &self.metrics {
+            match self.samples(metric).await {
+                Ok(samples) => {
+                    if let Some(help) = &metric.help {
+                        let _ = writeln!(text, "# HELP {} {}", metric.name, escape_help(help));
+                    }
+                    let _ = writeln!(text, "# TYPE {} gauge", metric.name);
+                    text.push_str(&samples);
+                }
+                Err(e) => eprintln!("Warning: failed to evaluate {}: {}", metric.name, e),
+            }
+        }
+        self.warnings.print();
+        text
+    }
+
+    /// Samples of the gauge, a line per row
+    async fn samples(&self, metric: &Metric) -> anyhow::Result<String> {
+        self.views.register(&self.ctx, &metric.query).await?;
+        let batches = self.ctx.sql(&metric.query).await?.collect().await?;
+        let mut samples = String::new();
+        for batch in &batches {
+            write_samples(&mut samples, metric, batch)?;
+        }
+        Ok(samples)
+    }
+}
+
+/// Format the rows of the batch as samples of the gauge.
+/// Rows without a value are skipped, and so are labels without one.
+/// Fails if label columns have the same label name (e.g. `pods.metadata[name]`
+/// and `nodes.metadata[name]`), as labels of a sample must be unique.
+fn write_samples(samples: &mut String, metric: &Metric, batch: &RecordBatch) -> anyhow::Result<()> {
+    let schema = batch.schema();
+    let value_column = metric.value.as_deref().unwrap_or(DEFAULT_VALUE_COLUMN);
+    let value_index = schema
+        .index_of(value_column)
+        .map_err(|_| anyhow::anyhow!("no value column '{}' in the result", value_column))?;
+    let label_columns = match &metric.labels {
+        Some(labels) => labels.clone(),
+        None => schema
+            .fields()
+            .iter()
+            .filter(|field| field.name() != value_column)
+            .map(|field| field.name().clone())
+            .collect(),
+    };
+    let mut labels: Vec<(String, _)> = Vec::new();
+    for (i, column_name) in label_columns.iter().enumerate() {
+        let column = batch
+            .column_by_name(column_name)
+            .ok_or_else(|| anyhow::anyhow!("no label column '{}' in the result", column_name))?;
+        let name = label_name(column_name);
+        if let Some(other) = label_columns[..i]
+            .iter()
+            .find(|other| label_name(other) == name)
+        {
+            anyhow::bail!(
+                "columns '{}' and '{}' are both labeled '{}', alias one of them",
+                other,
+                column_name,
+                name
+            );
+        }
+        labels.push((name, cast(column, &DataType::Utf8)?));
+    }
+    let values = cast(batch.column(value_index), &DataType::Float64)?;
+    let values = values.as_primitive::<Float64Type>();
+
+    for row in 0..batch.num_rows() {
+        if values.is_null(row) {
+            continue;
+        }
+        samples.push_str(&metric.name);
+        let labels = labels
+            .iter()
+            .filter(|(_, column)| column.is_valid(row))
+            .map(|(name, column)| {
+                format!(
+                    "{}=\"{}\"",
+                    name,
+                    escape_label_value(column.as_string::<i32>().value(row))
+                )
+            })
+            .collect::<Vec<_>>();
+        if !labels.is_empty() {
+            let _ = write!(samples, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(samples, " {}", format_value(values.value(row)));
+    }
+    Ok(())
+}
+
+/// Label name of the column, the last field of an unaliased column (e.g. `name` of `pods.metadata[name]`),
+/// with characters not allowed replaced with `_`
+fn label_name(column: &str) -> String {
+    let column = column.trim_end_matches(']');
+    let field = column.rsplit(['[', '.']).next().unwrap_or(column);
+    let name: String = field
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    match name.chars().next() {
+        Some(c) if c.is_ascii_digit() => format!("_{}", name),
+        _ => name,
+    }
+}
+
+fn escape_help(help: &str) -> String {
+    help.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::array::{ArrayRef, Float64Array, Int64Array, StringArray};
+
+    use super::*;
+
+    fn metric(labels: Option<&[&str]>) -> Metric {
+        Metric {
+            name: "kube_pods".to_string(),
+            help: None,
+            query: String::new(),
+            labels: labels.map(|labels| labels.iter().map(|label| label.to_string()).collect()),
+            value: None,
+        }
+    }
+
+    #[test]
+    fn label_names() {
+        assert_eq!(label_name("pods.metadata[name]"), "name");
+        assert_eq!(label_name("spec.nodeName"), "nodeName");
+        assert_eq!(label_name("node"), "node");
+        assert_eq!(label_name("app.kubernetes.io/name"), "io_name");
+        assert_eq!(label_name("1st"), "_1st");
+    }
+
+    #[test]
+    fn samples() {
+        let batch = RecordBatch::try_from_iter([
+            (
+                "pods.metadata[namespace]",
+                Arc::new(StringArray::from(vec![
+                    Some("default"),
+                    None,
+                    Some("a\"b\\c\nd"),
+                ])) as ArrayRef,
+            ),
+            (
+                "value",
+                Arc::new(Float64Array::from(vec![
+                    Some(2.0),
+                    Some(f64::INFINITY),
+                    Some(0.5),
+                ])),
+            ),
+            ("count", Arc::new(Int64Array::from(vec![1, 2, 3]))),
+        ])
+        .unwrap();
+        let mut samples = String::new();
+        write_samples(&mut samples, &metric(None), &batch).unwrap();
+        assert_eq!(
+            samples,
+            "kube_pods{namespace=\"default\",count=\"1\"} 2\n\
+             kube_pods{count=\"2\"} +Inf\n\
+             kube_pods{namespace=\"a\\\"b\\\\c\\nd\",count=\"3\"} 0.5\n"
+        );
+
+        let mut samples = String::new();
+        write_samples(&mut samples, &metric(Some(&[])), &batch).unwrap();
+        assert_eq!(samples, "kube_pods 2\nkube_pods +Inf\nkube_pods 0.5\n");
+    }
+
+    #[test]
+    fn skip_null_values() {
+        let batch = RecordBatch::try_from_iter([
+            (
+                "node",
+                Arc::new(StringArray::from(vec!["n1", "n2"])) as ArrayRef,
+            ),
+            ("value", Arc::new(Int64Array::from(vec![None, Some(3)]))),
+        ])
+        .unwrap();
+        let mut samples = String::new();
+        write_samples(&mut samples, &metric(None), &batch).unwrap();
+        assert_eq!(samples, "kube_pods{node=\"n2\"} 3\n");
+    }
+
+    #[test]
+    fn duplicate_labels() {
+        let names = Arc::new(StringArray::from(vec!["a"])) as ArrayRef;
+        let batch = RecordBatch::try_from_iter([
+            ("pods.metadata[name]", Arc::clone(&names)),
+            ("nodes.metadata[name]", names),
+            ("value", Arc::new(Int64Array::from(vec![1]))),
+        ])
+        .unwrap();
+        let error = write_samples(&mut String::new(), &metric(None), &batch).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "columns 'pods.metadata[name]' and 'nodes.metadata[name]' are both labeled 'name', \
+             alias one of them"
+        );
+        let mut samples = String::new();
+        write_samples(
+            &mut samples,
+            &metric(Some(&["pods.metadata[name]"])),
+            &batch,
+        )
+        .unwrap();
+        assert_eq!(samples, "kube_pods{name=\"a\"} 1\n");
+    }
+}
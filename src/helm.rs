@@ -0,0 +1,144 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::{Read, Write};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use serde_json::{Map, Value};
+
+/// Type of the Secrets Helm v3 stores releases in
+pub const RELEASE_SECRET_TYPE: &str = "helm.sh/release.v1";
+
+/// Magic bytes of gzip, which Helm compresses releases with
+const GZIP_MAGIC: [u8; 3] = [0x1f, 0x8b, 0x08];
+
+/// Decode a Helm v3 release stored in `data.release` of a Secret
+/// (base64 of the base64 of the gzipped JSON) or of a ConfigMap (base64 of the gzipped JSON).
+pub fn decode_release(release: &str) -> Option<Value> {
+    let mut bytes = release.as_bytes().to_vec();
+    // Decode base64 until the gzipped (or, from older versions, plain) JSON.
+    for _ in 0..2 {
+        if bytes.starts_with(&GZIP_MAGIC) || bytes.starts_with(b"{") {
+            break;
+        }
+        bytes = STANDARD.decode(&bytes).ok()?;
+    }
+    if bytes.starts_with(&GZIP_MAGIC) {
+        let mut json = Vec::new();
+        GzDecoder::new(bytes.as_slice())
+            .read_to_end(&mut json)
+            .ok()?;
+        bytes = json;
+    }
+    serde_json::from_slice(&bytes).ok()
+}
+
+/// Encode the release as `data.release` of a Secret.
+fn encode_release(release: &Value) -> Option<String> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&serde_json::to_vec(release).ok()?).ok()?;
+    let gzipped = encoder.finish().ok()?;
+    Some(STANDARD.encode(STANDARD.encode(gzipped)))
+}
+
+/// Strip the release of `data.release` of a Secret down to what is not sensitive:
+/// its name, namespace, revision, status and dates, and the metadata of its chart.
+/// The values, manifests, hooks and notes, which may hold secrets, are removed.
+pub fn strip_release(release: &str) -> Option<String> {
+    let release = decode_release(release)?;
+    let mut stripped = Map::new();
+    for key in ["name", "namespace", "version"] {
+        if let Some(value) = release.get(key) {
+            stripped.insert(key.to_string(), value.clone());
+        }
+    }
+    if let Some(info) = release.get("info").and_then(Value::as_object) {
+        let info: Map<String, Value> = info
+            .iter()
+            .filter(|(key, _)| key.as_str() != "notes")
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        stripped.insert("info".to_string(), Value::Object(info));
+    }
+    if let Some(metadata) = release.pointer("/chart/metadata") {
+        stripped.insert(
+            "chart".to_string(),
+            serde_json::json!({ "metadata": metadata }),
+        );
+    }
+    encode_release(&Value::Object(stripped))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn release() -> Value {
+        json!({
+            "name": "web",
+            "namespace": "default",
+            "version": 3,
+            "info": {"status": "deployed", "last_deployed": "2025-01-01T00:00:00Z",
+                "notes": "password: hunter2"},
+            "chart": {"metadata": {"name": "nginx", "version": "1.2.3"},
+                "values": {"password": "hunter2"}, "templates": [{"name": "secret.yaml"}]},
+            "config": {"password": "hunter2"},
+            "manifest": "kind: Secret\ndata:\n  password: aHVudGVyMg==",
+            "hooks": [{"manifest": "kind: Job"}]
+        })
+    }
+
+    fn gzip(release: &Value) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(&serde_json::to_vec(release).unwrap())
+            .unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn decode_encodings() {
+        let release = release();
+        let secret = encode_release(&release).unwrap();
+        assert_eq!(decode_release(&secret), Some(release.clone()));
+        let config_map = STANDARD.encode(gzip(&release));
+        assert_eq!(decode_release(&config_map), Some(release.clone()));
+        assert_eq!(
+            decode_release(&STANDARD.encode(release.to_string())),
+            Some(release)
+        );
+        assert_eq!(decode_release("not a release"), None);
+    }
+
+    #[test]
+    fn strip_sensitive_fields() {
+        let stripped = strip_release(&encode_release(&release()).unwrap()).unwrap();
+        let stripped = decode_release(&stripped).unwrap();
+        assert!(!stripped.to_string().contains("hunter2"));
+        assert_eq!(
+            stripped,
+            json!({
+                "name": "web",
+                "namespace": "default",
+                "version": 3,
+                "info": {"status": "deployed", "last_deployed": "2025-01-01T00:00:00Z"},
+                "chart": {"metadata": {"name": "nginx", "version": "1.2.3"}}
+            })
+        );
+        assert_eq!(strip_release("not a release"), None);
+    }
+}
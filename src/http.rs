@@ -0,0 +1,201 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    convert::Infallible, fs, future::Future, io::BufReader, net::SocketAddr, path::Path, sync::Arc,
+};
+
+use http::{Request, Response, StatusCode, header::CONTENT_TYPE};
+use http_body_util::{BodyExt, Full};
+use hyper::{
+    body::{Bytes, Incoming},
+    server::conn::http1,
+    service::service_fn,
+};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use hyper_util::{
+    client::legacy::{Client, connect::HttpConnector},
+    rt::{TokioExecutor, TokioIo},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpListener,
+};
+use tokio_rustls::{
+    TlsAcceptor,
+    rustls::{ServerConfig, crypto::aws_lc_rs},
+};
+
+/// Parse the address to listen on, e.g. `:9123` (on all interfaces) or `127.0.0.1:9123`.
+pub fn parse_listen(listen: &str) -> Result<SocketAddr, String> {
+    let listen = match listen.strip_prefix(':') {
+        Some(port) => format!("0.0.0.0:{}", port),
+        None => listen.to_string(),
+    };
+    listen
+        .parse()
+        .map_err(|e| format!("expected [host]:port, got '{}': {}", listen, e))
+}
+
+/// Response of the status with the body
+pub fn response(
+    status: StatusCode,
+    content_type: &str,
+    body: impl Into<Bytes>,
+) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(body.into()));
+    *response.status_mut() = status;
+    if let Ok(content_type) = content_type.parse() {
+        response.headers_mut().insert(CONTENT_TYPE, content_type);
+    }
+    response
+}
+
+/// Client of `http` and `https` URLs, verifying servers with the native roots
+fn client() -> anyhow::Result<Client<HttpsConnector<HttpConnector>, Full<Bytes>>> {
+    let connector = HttpsConnectorBuilder::new()
+        .with_native_roots()?
+        .https_or_http()
+        .enable_http1()
+        .build();
+    Ok(Client::builder(TokioExecutor::new()).build(connector))
+}
+
+/// GET the JSON of the URL (`http` or `https`), failing unless answered with a success status.
+pub async fn get_json(url: &str) -> anyhow::Result<serde_json::Value> {
+    let request = Request::get(url).body(Full::new(Bytes::new()))?;
+    let response = client()?
+        .request(request)
+        .await
+        .map_err(|e| anyhow::anyhow!("failed to GET {}: {}", url, e))?;
+    let status = response.status();
+    let body = response.into_body().collect().await?.to_bytes();
+    if !status.is_success() {
+        anyhow::bail!(
+            "GET {} failed with {}: {}",
+            url,
+            status,
+            String::from_utf8_lossy(&body).trim()
+        );
+    }
+    serde_json::from_slice(&body).map_err(|e| anyhow::anyhow!("invalid JSON from {}: {}", url, e))
+}
+
+/// POST the JSON to the URL (`http` or `https`), failing unless answered with a success status.
+pub async fn post_json(url: &str, body: &serde_json::Value) -> anyhow::Result<()> {
+    let client = client()?;
+    let request = Request::post(url)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(body.to_string())))?;
+    let response = client
+        .request(request)
+        .await
+        .map_err(|e| anyhow::anyhow!("failed to POST to {}: {}", url, e))?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.into_body().collect().await?.to_bytes();
+        anyhow::bail!(
+            "POST to {} failed with {}: {}",
+            url,
+            status,
+            String::from_utf8_lossy(&body).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Acceptor of TLS connections with the certificate chain and the private key of the PEM files
+pub fn tls_acceptor(cert: &Path, key: &Path) -> anyhow::Result<TlsAcceptor> {
+    let read = |path: &Path| {
+        fs::File::open(path)
+            .map(BufReader::new)
+            .map_err(|e| anyhow::anyhow!("failed to read {}: {}", path.display(), e))
+    };
+    let certs = rustls_pemfile::certs(&mut read(cert)?)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| anyhow::anyhow!("invalid certificate in {}: {}", cert.display(), e))?;
+    if certs.is_empty() {
+        anyhow::bail!("no certificate in {}", cert.display());
+    }
+    let key = rustls_pemfile::private_key(&mut read(key)?)
+        .map_err(|e| anyhow::anyhow!("invalid private key in {}: {}", key.display(), e))?
+        .ok_or_else(|| anyhow::anyhow!("no private key in {}", key.display()))?;
+    let config = ServerConfig::builder_with_provider(Arc::new(aws_lc_rs::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Serve HTTP/1 on the address until interrupted, responding to each request with the handler,
+/// given the address of the client.
+pub async fn serve<F, Fut>(addr: SocketAddr, handler: F) -> anyhow::Result<()>
+where
+    F: Fn(Request<Incoming>, SocketAddr) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = Response<Full<Bytes>>> + Send + 'static,
+{
+    serve_tls(addr, None, handler).await
+}
+
+/// Serve HTTP/1 on the address like [`serve`], over TLS with the acceptor if given.
+pub async fn serve_tls<F, Fut>(
+    addr: SocketAddr,
+    tls: Option<TlsAcceptor>,
+    handler: F,
+) -> anyhow::Result<()>
+where
+    F: Fn(Request<Incoming>, SocketAddr) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = Response<Full<Bytes>>> + Send + 'static,
+{
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(|e| anyhow::anyhow!("failed to listen on {}: {}", addr, e))?;
+    loop {
+        let (stream, remote) = listener.accept().await?;
+        let handler = handler.clone();
+        let tls = tls.clone();
+        tokio::spawn(async move {
+            let result = match tls {
+                Some(tls) => match tls.accept(stream).await {
+                    Ok(stream) => serve_connection(stream, remote, handler).await,
+                    Err(e) => Err(anyhow::anyhow!("TLS handshake failed: {}", e)),
+                },
+                None => serve_connection(stream, remote, handler).await,
+            };
+            if let Err(e) = result {
+                eprintln!("Warning: failed to serve a connection: {}", e);
+            }
+        });
+    }
+}
+
+async fn serve_connection<S, F, Fut>(
+    stream: S,
+    remote: SocketAddr,
+    handler: F,
+) -> anyhow::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    F: Fn(Request<Incoming>, SocketAddr) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Response<Full<Bytes>>> + Send + 'static,
+{
+    let service = service_fn(move |request| {
+        let response = handler(request, remote);
+        async move { Ok::<_, Infallible>(response.await) }
+    });
+    http1::Builder::new()
+        .serve_connection(TokioIo::new(stream), service)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,276 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! SQL for Kubernetes resources.
+//!
+//! [`KuquContext`] builds a DataFusion [`SessionContext`] in which the resources
+//! of Kubernetes clusters are queryable as tables (e.g. `SELECT * FROM 'pods/*'`).
+
+use std::{collections::HashMap, sync::Arc};
+
+use datafusion::{
+    catalog::{DynamicFileCatalog, UrlTableFactory},
+    common::{Result as DataFusionResult, TableReference},
+    execution::context::SessionContext,
+    prelude::SessionConfig,
+    sql::{parser::DFParserBuilder, sqlparser::dialect::dialect_from_str},
+};
+use kube::Client;
+
+use crate::url::Namespaces;
+
+pub mod cluster;
+pub mod discover;
+pub mod dynamic;
+pub mod lister;
+pub mod views;
+
+mod helm;
+mod openapi;
+mod owners;
+mod schema;
+mod selector;
+mod session;
+mod stats;
+mod udf;
+mod udtf;
+mod warnings;
+
+// Modules of the commands of the kuqu binary, not a part of the library API.
+#[doc(hidden)]
+pub mod admission;
+#[doc(hidden)]
+pub mod alert;
+#[doc(hidden)]
+pub mod audit;
+#[doc(hidden)]
+pub mod check;
+#[doc(hidden)]
+pub mod config;
+#[doc(hidden)]
+pub mod diff;
+#[doc(hidden)]
+pub mod dump;
+#[doc(hidden)]
+pub mod exporter;
+#[doc(hidden)]
+pub mod http;
+#[doc(hidden)]
+pub mod mcp;
+#[doc(hidden)]
+pub mod output;
+#[doc(hidden)]
+pub mod params;
+#[doc(hidden)]
+pub mod postgres;
+#[doc(hidden)]
+pub mod provider;
+#[doc(hidden)]
+pub mod ratelimit;
+#[doc(hidden)]
+pub mod retry;
+#[doc(hidden)]
+pub mod schedule;
+#[doc(hidden)]
+pub mod server;
+#[doc(hidden)]
+pub mod snapshot;
+#[doc(hidden)]
+pub mod url;
+#[doc(hidden)]
+pub mod watch;
+
+pub use crate::{
+    cluster::{Cluster, ConnectOptions},
+    lister::{KubeLister, ResourceLister},
+    params::Param,
+    provider::KubernetesTableProviderFactory,
+    ratelimit::RateLimitLayer,
+    retry::Retry,
+    session::{KuquSession, PreparedQuery},
+    views::Views,
+};
+
+/// Builder of a session querying the resources of Kubernetes clusters as tables.
+///
+/// ```no_run
+/// # async fn example() -> anyhow::Result<()> {
+/// let ctx = kuqu::KuquContext::connect("kind-kind", Default::default())
+///     .await?
+///     .build()?;
+/// ctx.sql("SELECT metadata.name FROM 'pods/*'").await?.show().await?;
+///
+/// let session = kuqu::KuquContext::connect("kind-kind", Default::default())
+///     .await?
+///     .session()?;
+/// let pods = session
+///     .prepare("SELECT metadata.name FROM 'pods/*' WHERE status.phase = $phase")
+///     .await?;
+/// for phase in ["Running", "Pending"] {
+///     let batches = pods.execute(&[("phase".into(), phase.into())]).await?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct KuquContext {
+    factory: KubernetesTableProviderFactory,
+    views: Views,
+}
+
+impl KuquContext {
+    /// Connect to the cluster of the kubeconfig context.
+    pub async fn connect(context: &str, options: ConnectOptions) -> anyhow::Result<Self> {
+        let cluster = Cluster::connect(context, &options).await?;
+        Ok(Self {
+            factory: KubernetesTableProviderFactory::new(context.to_string(), cluster)
+                .with_connect_options(options),
+            views: Views::builtin(),
+        })
+    }
+
+    /// Query the cluster of the client (e.g. that of a controller),
+    /// available as the context of the name.
+    pub fn from_client(context: &str, client: Client) -> Self {
+        Self {
+            factory: KubernetesTableProviderFactory::new(
+                context.to_string(),
+                Cluster::from_client(client),
+            ),
+            views: Views::builtin(),
+        }
+    }
+
+    /// Query the objects of the lister (e.g. an in-memory fake),
+    /// available as the context of the name, with the default namespace for tables without one.
+    pub fn from_lister(
+        context: &str,
+        lister: Arc<dyn ResourceLister>,
+        default_namespace: &str,
+    ) -> Self {
+        Self {
+            factory: KubernetesTableProviderFactory::new(
+                context.to_string(),
+                Cluster::from_lister(lister, Namespaces::One(default_namespace.to_string())),
+            ),
+            views: Views::builtin(),
+        }
+    }
+
+    /// Set the contexts of kubeconfig to list each table from
+    pub fn with_contexts(mut self, contexts: Vec<String>) -> Self {
+        self.factory = self.factory.with_contexts(contexts);
+        self
+    }
+
+    /// Set the number of objects used to infer the schema
+    pub fn with_infer_rows(mut self, infer_rows: u32) -> Self {
+        self.factory = self.factory.with_infer_rows(infer_rows);
+        self
+    }
+
+    /// Set the timeout of each list request, in seconds
+    pub fn with_list_timeout(mut self, list_timeout: Option<u32>) -> Self {
+        self.factory = self.factory.with_list_timeout(list_timeout);
+        self
+    }
+
+    /// Set whether to show the values of Secrets instead of redacting them
+    pub fn with_show_secrets(mut self, show_secrets: bool) -> Self {
+        self.factory = self.factory.with_show_secrets(show_secrets);
+        self
+    }
+
+    /// Set the views queryable by name, the built-in views by default
+    /// (e.g. with those of a file by [`Views::with_file`])
+    pub fn with_views(mut self, views: Views) -> Self {
+        self.views = views;
+        self
+    }
+
+    /// Returns the views queryable by name
+    pub fn views(&self) -> &Views {
+        &self.views
+    }
+
+    /// Create the session.
+    ///
+    /// Views are created as queries reference them, which [`KuquSession`] does;
+    /// queries of this session read them once created by [`Views::register`].
+    pub fn build(self) -> DataFusionResult<SessionContext> {
+        session_context(Arc::new(self.factory))
+    }
+
+    /// Create the session, to execute many (prepared) queries against
+    pub fn session(self) -> DataFusionResult<KuquSession> {
+        let views = self.views.clone();
+        Ok(KuquSession::from(self.build()?).with_views(views))
+    }
+}
+
+/// Creates a session querying the resources of the clusters of the factory as tables,
+/// with the functions of kuqu registered.
+pub fn session_context(
+    factory: Arc<KubernetesTableProviderFactory>,
+) -> DataFusionResult<SessionContext> {
+    let ctx = SessionContext::new();
+    let catalog_list = Arc::new(DynamicFileCatalog::new(
+        Arc::clone(ctx.state().catalog_list()),
+        Arc::clone(&factory) as Arc<dyn UrlTableFactory>,
+    ));
+    let ctx: SessionContext = ctx
+        .into_state_builder()
+        .with_config(SessionConfig::from_string_hash_map(&HashMap::from([(
+            // To avoid e.g. spec.nodeName => spec.nodename normalization in DataFusion SQL parser
+            "datafusion.sql_parser.enable_ident_normalization".to_owned(),
+            "false".to_owned(),
+        )]))?)
+        .with_catalog_list(catalog_list)
+        .build()
+        .into();
+    udf::register(&ctx);
+    udtf::register(&ctx, &factory);
+    ctx.register_table(
+        owners::OWNERS_TABLE,
+        Arc::new(owners::OwnersTable::new(Arc::clone(&factory))),
+    )?;
+    ctx.register_table(
+        stats::NODE_STATS_TABLE,
+        Arc::new(stats::NodeStatsTable::new(factory)),
+    )?;
+    Ok(ctx)
+}
+
+/// Returns the tables referenced in the query.
+pub fn table_references(ctx: &SessionContext, query: &str) -> anyhow::Result<Vec<TableReference>> {
+    let state = ctx.state();
+    let statement = state.sql_to_statement(query, &state.config().options().sql_parser.dialect)?;
+    Ok(state.resolve_table_references(&statement)?)
+}
+
+/// Splits the semicolon-separated statements of the SQL.
+/// A single statement is returned as is.
+pub fn split_statements(ctx: &SessionContext, sql: &str) -> anyhow::Result<Vec<String>> {
+    let state = ctx.state();
+    let dialect_name = &state.config().options().sql_parser.dialect;
+    let dialect = dialect_from_str(dialect_name)
+        .ok_or_else(|| anyhow::anyhow!("unsupported SQL dialect: {}", dialect_name))?;
+    let statements = DFParserBuilder::new(sql)
+        .with_dialect(dialect.as_ref())
+        .build()?
+        .parse_statements()?;
+    if statements.len() == 1 {
+        return Ok(vec![sql.to_string()]);
+    }
+    Ok(statements.iter().map(ToString::to_string).collect())
+}
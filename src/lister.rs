@@ -0,0 +1,331 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use k8s_openapi::{
+    api::authorization::v1::{
+        ResourceAttributes, SelfSubjectAccessReview, SelfSubjectAccessReviewSpec,
+    },
+    apimachinery::pkg::apis::meta::v1::APIResource,
+};
+use kube::{
+    Api, Client, Resource,
+    api::{ListParams, ObjectList, PartialObjectMeta, PostParams, TypeMeta},
+};
+use serde_json::Value;
+
+use crate::{
+    discover::{DiscoverClient, Discovery},
+    dynamic::DynamicObject,
+    openapi::OpenApiClient,
+    retry::Retry,
+};
+
+/// Groups of the resources to discover
+#[derive(Debug, Clone, Copy)]
+pub enum DiscoveryScope<'a> {
+    /// The core group
+    Core,
+    /// The groups the resource name is qualified with, e.g. `apps` for `deployments.apps`
+    Qualified(&'a str),
+    /// All groups, the core group first
+    All,
+}
+
+/// Source of the objects of a cluster, which the tables are listed from.
+///
+/// Implemented by [`KubeLister`] for API servers,
+/// and by anything else holding objects, e.g. an in-memory fake in tests.
+#[async_trait]
+pub trait ResourceLister: Send + Sync {
+    /// List the objects of the resource in the namespace (`None` for all namespaces),
+    /// only their metadata if `metadata_only`.
+    async fn list(
+        &self,
+        api_resource: &APIResource,
+        namespace: Option<&str>,
+        lp: &ListParams,
+        metadata_only: bool,
+    ) -> kube::Result<ObjectList<DynamicObject>>;
+
+    /// Get the named object, `None` if missing.
+    async fn get(
+        &self,
+        api_resource: &APIResource,
+        namespace: Option<&str>,
+        name: &str,
+        metadata_only: bool,
+    ) -> kube::Result<Option<DynamicObject>>;
+
+    /// Discover the resources of the groups of the scope.
+    async fn discover(&self, scope: DiscoveryScope<'_>) -> anyhow::Result<Discovery>;
+
+    /// Whether the user may perform the verb on the resource type in the namespace,
+    /// or on the named object. `None` if unknown, in which case access is not checked.
+    async fn can_i(
+        &self,
+        _verb: &str,
+        _api_resource: &APIResource,
+        _namespace: Option<&str>,
+        _name: Option<&str>,
+    ) -> Option<bool> {
+        None
+    }
+
+    /// OpenAPI v3 document of the group version the resource belongs to,
+    /// to complete the schema inferred from the objects with.
+    async fn openapi_document(&self, api_resource: &APIResource) -> anyhow::Result<Arc<Value>> {
+        anyhow::bail!("no OpenAPI document of {}", api_resource.name)
+    }
+}
+
+/// Lister of the objects of an API server, retrying transient failures.
+pub struct KubeLister {
+    client: Client,
+    retry: Retry,
+    openapi: OpenApiClient,
+}
+
+impl KubeLister {
+    pub fn new(client: Client) -> Self {
+        Self {
+            openapi: OpenApiClient::new(client.clone()),
+            client,
+            retry: Retry::default(),
+        }
+    }
+
+    /// Set the retry policy of the requests
+    pub fn with_retry(mut self, retry: Retry) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Create an Api for a given resource type and namespace (`None` for all namespaces)
+    pub fn api(&self, api_resource: &APIResource, namespace: Option<&str>) -> Api<DynamicObject> {
+        match namespace {
+            Some(namespace) if api_resource.namespaced => {
+                Api::namespaced_with(self.client.clone(), namespace, api_resource)
+            }
+            _ => Api::all_with(self.client.clone(), api_resource),
+        }
+    }
+}
+
+/// Object holding only the metadata of a metadata-only response,
+/// typed as the objects of the resource rather than as `PartialObjectMetadata`.
+fn metadata_object(
+    api_resource: &APIResource,
+    object: PartialObjectMeta<DynamicObject>,
+) -> DynamicObject {
+    DynamicObject {
+        types: Some(TypeMeta {
+            api_version: DynamicObject::api_version(api_resource).into_owned(),
+            kind: api_resource.kind.clone(),
+        }),
+        metadata: object.metadata,
+        data: Value::Object(Default::default()),
+    }
+}
+
+#[async_trait]
+impl ResourceLister for KubeLister {
+    async fn list(
+        &self,
+        api_resource: &APIResource,
+        namespace: Option<&str>,
+        lp: &ListParams,
+        metadata_only: bool,
+    ) -> kube::Result<ObjectList<DynamicObject>> {
+        let api = self.api(api_resource, namespace);
+        if !metadata_only {
+            return self.retry.run(|| api.list(lp)).await;
+        }
+        let list = self
+            .retry
+            .run(|| async { api.list_metadata(lp).await })
+            .await?;
+        Ok(ObjectList {
+            types: list.types,
+            metadata: list.metadata,
+            items: list
+                .items
+                .into_iter()
+                .map(|object| metadata_object(api_resource, object))
+                .collect(),
+        })
+    }
+
+    async fn get(
+        &self,
+        api_resource: &APIResource,
+        namespace: Option<&str>,
+        name: &str,
+        metadata_only: bool,
+    ) -> kube::Result<Option<DynamicObject>> {
+        let api = self.api(api_resource, namespace);
+        if !metadata_only {
+            return self.retry.run(|| api.get_opt(name)).await;
+        }
+        let object = self
+            .retry
+            .run(|| async { api.get_metadata_opt(name).await })
+            .await?;
+        Ok(object.map(|object| metadata_object(api_resource, object)))
+    }
+
+    async fn discover(&self, scope: DiscoveryScope<'_>) -> anyhow::Result<Discovery> {
+        let discover_client = DiscoverClient::new(self.client.clone()).with_retry(self.retry);
+        match scope {
+            DiscoveryScope::Core => Ok(Discovery {
+                api_resources: discover_client.list_core_api_resources().await?,
+                failures: Vec::new(),
+            }),
+            DiscoveryScope::Qualified(resource) => {
+                discover_client.list_qualified_api_resources(resource).await
+            }
+            DiscoveryScope::All => discover_client.list_api_resources().await,
+        }
+    }
+
+    /// Ask the API server with a SelfSubjectAccessReview, as `kubectl auth can-i`.
+    /// `None` if the review itself fails, e.g. when it is not permitted either.
+    async fn can_i(
+        &self,
+        verb: &str,
+        api_resource: &APIResource,
+        namespace: Option<&str>,
+        name: Option<&str>,
+    ) -> Option<bool> {
+        let review = SelfSubjectAccessReview {
+            spec: SelfSubjectAccessReviewSpec {
+                resource_attributes: Some(ResourceAttributes {
+                    verb: Some(verb.to_string()),
+                    group: Some(DynamicObject::group(api_resource).into_owned()),
+                    version: api_resource.version.clone(),
+                    resource: Some(api_resource.name.clone()),
+                    namespace: namespace.map(str::to_string),
+                    name: name.map(str::to_string),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let api = Api::<SelfSubjectAccessReview>::all(self.client.clone());
+        let pp = PostParams::default();
+        let review = self.retry.run(|| api.create(&pp, &review)).await.ok()?;
+        Some(review.status?.allowed)
+    }
+
+    async fn openapi_document(&self, api_resource: &APIResource) -> anyhow::Result<Arc<Value>> {
+        self.openapi.document(api_resource).await
+    }
+}
+
+/// In-memory lister for tests, recording the requests made to it.
+#[cfg(test)]
+pub(crate) mod fake {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::dump::DumpLister;
+
+    /// List request made to a [`FakeLister`]
+    #[derive(Debug, Clone)]
+    pub struct Request {
+        pub resource: String,
+        pub namespace: Option<String>,
+        pub lp: ListParams,
+        pub metadata_only: bool,
+    }
+
+    /// Lister of the objects given, as if on an API server
+    pub struct FakeLister {
+        objects: DumpLister,
+        requests: Mutex<Vec<Request>>,
+        /// Resources and namespaces (`None` for all) listing which is forbidden
+        forbidden: Vec<(String, Option<String>)>,
+    }
+
+    impl FakeLister {
+        pub fn new(objects: Vec<Value>) -> Self {
+            Self {
+                objects: DumpLister::from_documents(objects),
+                requests: Mutex::new(Vec::new()),
+                forbidden: Vec::new(),
+            }
+        }
+
+        /// Forbid listing the resource in the namespace, across namespaces if `None`
+        pub fn with_forbidden(mut self, resource: &str, namespace: Option<&str>) -> Self {
+            self.forbidden
+                .push((resource.to_string(), namespace.map(str::to_string)));
+            self
+        }
+
+        /// List requests made so far
+        pub fn requests(&self) -> Vec<Request> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl ResourceLister for FakeLister {
+        async fn list(
+            &self,
+            api_resource: &APIResource,
+            namespace: Option<&str>,
+            lp: &ListParams,
+            metadata_only: bool,
+        ) -> kube::Result<ObjectList<DynamicObject>> {
+            self.requests.lock().unwrap().push(Request {
+                resource: api_resource.name.clone(),
+                namespace: namespace.map(str::to_string),
+                lp: lp.clone(),
+                metadata_only,
+            });
+            let forbidden = (api_resource.name.clone(), namespace.map(str::to_string));
+            if self.forbidden.contains(&forbidden) {
+                return Err(kube::Error::Api(kube::core::ErrorResponse {
+                    status: "Failure".to_string(),
+                    message: format!("{} is forbidden", api_resource.name),
+                    reason: "Forbidden".to_string(),
+                    code: 403,
+                }));
+            }
+            self.objects
+                .list(api_resource, namespace, lp, metadata_only)
+                .await
+        }
+
+        async fn get(
+            &self,
+            api_resource: &APIResource,
+            namespace: Option<&str>,
+            name: &str,
+            metadata_only: bool,
+        ) -> kube::Result<Option<DynamicObject>> {
+            self.objects
+                .get(api_resource, namespace, name, metadata_only)
+                .await
+        }
+
+        async fn discover(&self, scope: DiscoveryScope<'_>) -> anyhow::Result<Discovery> {
+            self.objects.discover(scope).await
+        }
+    }
+}
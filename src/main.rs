@@ -12,53 +12,531 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    fs,
+    io::{self, Read, Write},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
-use clap::Parser;
+use chrono::{DateTime, Utc};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
 use datafusion::{
-    catalog::{DynamicFileCatalog, UrlTableFactory},
-    execution::context::SessionContext,
-    prelude::SessionConfig,
+    arrow::record_batch::RecordBatch, dataframe::DataFrame, execution::context::SessionContext,
+    physical_plan::displayable,
 };
-use kube::{
-    Client, Config,
-    config::{KubeConfigOptions, Kubeconfig},
+use kuqu::{
+    admission::AdmissionWebhook,
+    alert::{Webhook, WebhookFormat},
+    audit,
+    check::{self, CheckFormat, Policies},
+    cluster::{Cluster, ConnectOptions, read_kubeconfig},
+    config,
+    diff::Side,
+    dump::DumpLister,
+    exporter::{self, Exporter},
+    http,
+    mcp::{self, McpServer},
+    output::{self, OutputFormat},
+    params::{self, Param},
+    postgres::PostgresServer,
+    provider::{self, DEFAULT_INFER_ROWS, KubernetesTableProviderFactory},
+    ratelimit::{self, RateLimitLayer},
+    retry::{DEFAULT_MAX_RETRIES, Retry},
+    schedule::{self, Sink},
+    server::{self, Server},
+    session_context, snapshot, split_statements,
+    url::{Namespaces, Resolution, SessionTable},
+    views::{self, ReadTable, Views},
+    watch,
 };
+use tokio::sync::mpsc;
 
-mod discover;
-use discover::DiscoverClient;
-mod dynamic;
-mod provider;
-mod url;
+mod repl;
 
-use crate::provider::KubernetesTableProviderFactory;
+/// Time to wait after a change for further changes before re-evaluating the query,
+/// so that a burst of changes (e.g. a rollout) results in a single re-evaluation.
+const FOLLOW_DEBOUNCE: Duration = Duration::from_millis(500);
 
 /// Query Kubernetes resources using SQL-like syntax.
-#[derive(Parser)]
+#[derive(Parser, Clone)]
 #[command(name = "kuqu", version)]
 pub struct Args {
+    #[arg(
+        long = "kubeconfig",
+        help = "Path to the kubeconfig file (defaults to KUBECONFIG or ~/.kube/config)."
+    )]
+    pub kubeconfig: Option<PathBuf>,
+
+    #[arg(
+        long = "server",
+        conflicts_with_all = ["kubeconfig", "context", "contexts", "all_contexts"],
+        help = "Address of the Kubernetes API server to connect to without kubeconfig."
+    )]
+    pub server: Option<String>,
+
+    #[arg(
+        long = "token",
+        requires = "server",
+        help = "Bearer token for authentication to the API server."
+    )]
+    pub token: Option<String>,
+
+    #[arg(
+        long = "certificate-authority",
+        help = "Path to a CA certificate bundle to verify the API server with."
+    )]
+    pub certificate_authority: Option<PathBuf>,
+
+    #[arg(
+        long = "insecure-skip-tls-verify",
+        conflicts_with = "certificate_authority",
+        help = "Skip verifying the certificate of the API server (insecure)."
+    )]
+    pub insecure_skip_tls_verify: bool,
+
+    #[arg(
+        long = "client-certificate",
+        requires = "client_key",
+        help = "Path to a client certificate file for TLS client authentication."
+    )]
+    pub client_certificate: Option<PathBuf>,
+
+    #[arg(
+        long = "client-key",
+        requires = "client_certificate",
+        help = "Path to the key file of the client certificate."
+    )]
+    pub client_key: Option<PathBuf>,
+
+    #[arg(
+        long = "request-timeout",
+        value_parser = humantime::parse_duration,
+        help = "Timeout for connecting to and each read from or write to the API server (e.g. 30s)."
+    )]
+    pub request_timeout: Option<Duration>,
+
+    #[arg(
+        long = "list-timeout",
+        value_parser = humantime::parse_duration,
+        help = "Timeout of each list request (e.g. 2m), rounded up to seconds."
+    )]
+    pub list_timeout: Option<Duration>,
+
+    #[arg(
+        long = "qps",
+        value_parser = ratelimit::parse_qps,
+        help = "Maximum number of requests per second to each API server (unlimited by default)."
+    )]
+    pub qps: Option<f64>,
+
+    #[arg(
+        long = "burst",
+        requires = "qps",
+        help = "Maximum number of requests sent at once before --qps applies (defaults to --qps)."
+    )]
+    pub burst: Option<u32>,
+
+    #[arg(
+        long = "max-retries",
+        default_value_t = DEFAULT_MAX_RETRIES,
+        help = "Maximum number of retries of a request failing with 429 or 5xx, with exponential backoff."
+    )]
+    pub max_retries: u32,
+
+    #[arg(
+        long = "disable-compression",
+        help = "Do not request gzip compressed responses from the API server."
+    )]
+    pub disable_compression: bool,
+
+    #[arg(
+        long = "from-file",
+        conflicts_with_all = ["server", "kubeconfig", "context", "contexts", "all_contexts", "follow"],
+        help = "Query the objects in the file or directory of `kubectl get -o json|yaml` outputs instead of a cluster."
+    )]
+    pub from_file: Option<PathBuf>,
+
+    #[arg(
+        long = "from-stdin-manifests",
+        conflicts_with_all = ["from_file", "server", "kubeconfig", "context", "contexts", "all_contexts", "follow"],
+        help = "Query the manifests read from stdin (e.g. of `helm template`) instead of a cluster."
+    )]
+    pub from_stdin_manifests: bool,
+
+    #[arg(
+        long = "snapshots",
+        conflicts_with_all = ["from_file", "from_stdin_manifests", "server", "kubeconfig", "context", "contexts", "all_contexts", "follow"],
+        help = "Query a snapshot written by `kuqu snapshot` instead of a cluster: the directory of the snapshot, or of snapshots to choose from with --as-of (the latest by default)."
+    )]
+    pub snapshots: Option<PathBuf>,
+
+    #[arg(
+        long = "as-of",
+        requires = "snapshots",
+        value_parser = parse_as_of,
+        help = "Query the latest of the --snapshots taken at or before the time, in RFC 3339 (e.g. 2025-01-01T12:00:00Z)."
+    )]
+    pub as_of: Option<DateTime<Utc>>,
+
+    #[arg(
+        long = "audit-log",
+        help = "Audit log of the API server to query as the audit_events table, as written by the log backend or posted to the webhook backend (repeatable)."
+    )]
+    pub audit_logs: Vec<PathBuf>,
+
+    #[arg(
+        long = "manifests",
+        value_parser = parse_manifests,
+        help = "Directory of manifests to query as the context of the name, as name=dir (e.g. 'deployments@repo' for --manifests repo=./deploy)."
+    )]
+    pub manifests: Vec<(String, PathBuf)>,
+
     #[arg(long = "context", help = "Kubernetes context.")]
     pub context: Option<String>,
 
-    /// The SQL-like query to execute against Kubernetes resources.
+    #[arg(
+        short = 'n',
+        long = "namespace",
+        help = "Namespace of tables without a namespace (e.g. pods), instead of that of the context."
+    )]
+    pub namespace: Option<String>,
+
+    #[arg(
+        short = 'A',
+        long = "all-namespaces",
+        conflicts_with = "namespace",
+        help = "List tables without a namespace (e.g. pods) from all namespaces."
+    )]
+    pub all_namespaces: bool,
+
+    #[arg(
+        long = "contexts",
+        value_delimiter = ',',
+        help = "Kubernetes contexts to list each table from, tagging rows with a `_cluster` column."
+    )]
+    pub contexts: Vec<String>,
+
+    #[arg(
+        long = "all-contexts",
+        conflicts_with = "contexts",
+        help = "List each table from every context in kubeconfig, tagging rows with a `_cluster` column."
+    )]
+    pub all_contexts: bool,
+
+    #[arg(
+        long = "explain-resolution",
+        help = "Print how each table name in the query resolves to a Kubernetes resource."
+    )]
+    pub explain_resolution: bool,
+
+    #[arg(
+        long = "dry-run",
+        conflicts_with_all = ["follow", "every", "sinks"],
+        help = "Print the logical and physical plans of the query and the requests it would make to the API servers, without listing any objects."
+    )]
+    pub dry_run: bool,
+
+    #[arg(
+        long = "follow",
+        help = "Watch the queried resources and re-evaluate the query on every change."
+    )]
+    pub follow: bool,
+
+    #[arg(
+        long = "every",
+        value_parser = humantime::parse_duration,
+        conflicts_with = "follow",
+        help = "Run the statements every interval (e.g. 10m), writing each result set to the sinks, until interrupted."
+    )]
+    pub every: Option<Duration>,
+
+    #[arg(
+        long = "sink",
+        value_parser = Sink::parse,
+        help = "Write each result set as a JSON object of its time, context, query and rows: `-` for a line to stdout (the default with --every), an http(s) URL to POST to, or a file to append a line to."
+    )]
+    pub sinks: Vec<Sink>,
+
+    #[arg(
+        short = 'o',
+        long = "output",
+        value_enum,
+        default_value_t = OutputFormat::Table,
+        help = "Output format of the query results."
+    )]
+    pub output: OutputFormat,
+
+    #[arg(
+        long = "refresh-discovery",
+        help = "Discover the API resources again instead of using the discovery cached on disk."
+    )]
+    pub refresh_discovery: bool,
+
+    #[arg(
+        long = "show-secrets",
+        help = "Show the values of Secrets (data, stringData) instead of redacting them."
+    )]
+    pub show_secrets: bool,
+
+    #[arg(
+        long = "init",
+        help = "Path to a SQL script (e.g. CREATE VIEW, SET) executed in the session before the query."
+    )]
+    pub init: Option<PathBuf>,
+
+    #[arg(
+        long = "infer-rows",
+        default_value_t = DEFAULT_INFER_ROWS,
+        help = "Number of objects per table used to infer the schema (0 for all objects)."
+    )]
+    pub infer_rows: u32,
+
+    #[arg(
+        long = "param",
+        global = true,
+        value_parser = params::parse_param,
+        help = "Value of a placeholder of the query, as name=value (e.g. --param ns=default for $ns or :ns)."
+    )]
+    pub params: Vec<Param>,
+
+    #[arg(
+        short = 'f',
+        long = "file",
+        conflicts_with = "query",
+        help = "Path to a file to read the query from."
+    )]
+    pub file: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// The SQL-like query to execute against Kubernetes resources, or `-` to read it from stdin.
+    /// Without a query, statements are read interactively.
     /// See https://datafusion.apache.org/user-guide/sql/index.html
     /// for more details on the query syntax.
-    pub query: String,
+    pub query: Option<String>,
+}
+
+#[derive(Subcommand, Clone)]
+pub enum Command {
+    /// Run a saved query, `$XDG_CONFIG_HOME/kuqu/queries/<name>.sql` (or under `~/.config`).
+    Run {
+        /// Name of the saved query.
+        name: String,
+    },
+    /// Run the query against two contexts and print the rows only in either or changed,
+    /// keyed by the namespace and name columns. Exits with 1 if any row differs.
+    Diff {
+        /// Context of the left side.
+        #[arg(long = "left")]
+        left: String,
+        /// Context of the right side.
+        #[arg(long = "right")]
+        right: String,
+        /// The query to run against both contexts.
+        query: String,
+    },
+    /// Export the objects of the resources to a Parquet file per resource under the directory,
+    /// along with `manifest.json` describing them. Exports all namespaces unless `-n` is given.
+    Snapshot {
+        /// Resources to export, e.g. `pods,deployments,services`.
+        #[arg(long = "resources", value_delimiter = ',', required = true)]
+        resources: Vec<String>,
+        /// Directory to export to.
+        #[arg(long = "out")]
+        out: PathBuf,
+    },
+    /// Take a snapshot of the resources every interval into a directory under `--out`
+    /// named after the time, when changed since the previous one, building a history
+    /// to query with `--snapshots` and `--as-of`. Runs until interrupted.
+    Archive {
+        /// Resources to archive, e.g. `pods,deployments,services`.
+        #[arg(long = "resources", value_delimiter = ',', required = true)]
+        resources: Vec<String>,
+        /// Interval between snapshots, e.g. `5m`.
+        #[arg(long = "interval", value_parser = humantime::parse_duration, default_value = "5m")]
+        interval: Duration,
+        /// Directory to archive to, appending to the snapshots already in it (local only).
+        #[arg(long = "out")]
+        out: PathBuf,
+    },
+    /// Serve the gauges configured in the file, each the result of a query run on every scrape,
+    /// at `/metrics` for Prometheus. Runs until interrupted.
+    Exporter {
+        /// TOML file of the gauges, `[[metrics]]` tables of `name`, `help`, `query`,
+        /// `labels` (the columns of the labels, all but the value by default)
+        /// and `value` (the column of the value, `value` by default).
+        #[arg(long = "config")]
+        config: PathBuf,
+        /// Address to listen on, e.g. `127.0.0.1:9123` or `:9123` for all interfaces.
+        #[arg(long = "listen", value_parser = http::parse_listen, default_value = "127.0.0.1:9123")]
+        listen: SocketAddr,
+    },
+    /// Serve read-only queries posted to `/query` as JSON, `{"query": ..., "context": ...,
+    /// "namespace": ..., "params": {...}}` (all but the query optional), returning the rows
+    /// as a JSON array or an Arrow IPC stream if accepted. Runs until interrupted.
+    Serve {
+        /// Address to listen on, e.g. `127.0.0.1:8080` or `:8080` for all interfaces.
+        /// Requests are not authenticated, so listen on a trusted address only.
+        #[arg(long = "listen", value_parser = http::parse_listen, default_value = "127.0.0.1:8080")]
+        listen: SocketAddr,
+    },
+    /// Serve read-only queries over the PostgreSQL wire protocol, for psql, Grafana
+    /// and Postgres drivers. The database names the context (`kuqu` for the current one).
+    /// Connections are neither encrypted nor authenticated. Runs until interrupted.
+    Postgres {
+        /// Address to listen on, e.g. `:5432` or `127.0.0.1:5432`.
+        #[arg(long = "listen", value_parser = http::parse_listen, default_value = "127.0.0.1:5432")]
+        listen: SocketAddr,
+    },
+    /// Check the policies of the YAML file, each a `name`, a `query` and its expectation,
+    /// `expect: empty` (the default, each row being a violation) or `nonempty`,
+    /// printing the violations. Exits with 1 if any policy of `severity: error` (the default) fails.
+    Check {
+        /// YAML file of the policies, under `policies`.
+        policies: PathBuf,
+        /// Format of the results, e.g. for CI systems and code scanning.
+        #[arg(long = "format", value_enum, default_value_t = CheckFormat::Text)]
+        format: CheckFormat,
+    },
+    /// Serve a validating admission webhook denying objects failing the policies of the YAML
+    /// file, each querying the object as the `object` table (`old_object` for the existing one)
+    /// and the cluster, restricted to `kinds` if given. Runs until interrupted.
+    Webhook {
+        /// YAML file of the policies, under `policies`.
+        #[arg(long = "policies")]
+        policies: PathBuf,
+        /// Address to listen on, e.g. `:8443` or `127.0.0.1:8443`.
+        /// All interfaces by default, as the API server calls the webhook from outside.
+        #[arg(long = "listen", value_parser = http::parse_listen, default_value = ":8443")]
+        listen: SocketAddr,
+        /// PEM file of the TLS certificate chain, to serve HTTPS as the API server requires.
+        #[arg(long = "tls-cert", requires = "tls_key")]
+        tls_cert: Option<PathBuf>,
+        /// PEM file of the TLS private key.
+        #[arg(long = "tls-key", requires = "tls_cert")]
+        tls_key: Option<PathBuf>,
+    },
+    /// Run the queries of the file, posting the rows of each query returning any to the webhook.
+    Alert {
+        /// File of the queries, separated by semicolons.
+        #[arg(long = "query-file")]
+        query_file: PathBuf,
+        /// URL of the webhook to notify, e.g. a Slack incoming webhook.
+        #[arg(long = "webhook")]
+        webhook: String,
+        /// Payload posted to the webhook.
+        #[arg(long = "webhook-format", value_enum, default_value_t = WebhookFormat::Json)]
+        webhook_format: WebhookFormat,
+    },
+    /// Serve read-only queries, the tables and their columns as tools of the
+    /// Model Context Protocol over stdio, for LLM assistants.
+    Mcp {
+        /// Namespaces the tables of namespaced resources may read, any if not given.
+        #[arg(long = "allow-namespace", value_delimiter = ',')]
+        allow_namespaces: Vec<String>,
+        /// Maximum number of rows returned by a query.
+        #[arg(long = "max-rows", default_value_t = mcp::DEFAULT_MAX_ROWS)]
+        max_rows: usize,
+    },
+}
+
+/// Parses a time given in RFC 3339.
+fn parse_as_of(as_of: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(as_of)
+        .map(|as_of| as_of.with_timezone(&Utc))
+        .map_err(|e| format!("expected a time in RFC 3339, got '{}': {}", as_of, e))
+}
+
+/// Parses a directory of manifests given as `name=dir`.
+fn parse_manifests(manifests: &str) -> Result<(String, PathBuf), String> {
+    match manifests.split_once('=') {
+        Some((name, dir)) if !name.is_empty() && !dir.is_empty() => {
+            Ok((name.to_string(), PathBuf::from(dir)))
+        }
+        _ => Err(format!("expected name=dir, got '{}'", manifests)),
+    }
+}
+
+/// Whether the query is to be read from stdin, or interactively, rather than given by the arguments.
+fn query_from_stdin(args: &Args) -> bool {
+    args.command.is_none()
+        && args.file.is_none()
+        && args.query.as_deref().is_none_or(|query| query == "-")
+}
+
+/// Returns the query to execute, if not interactive, and the values of its parameters.
+fn query(args: &Args) -> anyhow::Result<(Option<String>, Vec<Param>)> {
+    match &args.command {
+        Some(Command::Run { name }) => {
+            let path = config::saved_query_path(name)
+                .ok_or_else(|| anyhow::anyhow!("no config directory to read saved queries from"))?;
+            let query = fs::read_to_string(&path).map_err(|e| {
+                anyhow::anyhow!(
+                    "failed to read saved query '{}' ({}): {}",
+                    name,
+                    path.display(),
+                    e
+                )
+            })?;
+            Ok((Some(query), args.params.clone()))
+        }
+        Some(Command::Diff { query, .. }) => Ok((Some(query.clone()), args.params.clone())),
+        Some(
+            Command::Snapshot { .. }
+            | Command::Archive { .. }
+            | Command::Exporter { .. }
+            | Command::Serve { .. }
+            | Command::Postgres { .. }
+            | Command::Mcp { .. }
+            | Command::Alert { .. }
+            | Command::Webhook { .. }
+            | Command::Check { .. },
+        ) => Ok((None, args.params.clone())),
+        None => {
+            let query = match (&args.file, args.query.as_deref()) {
+                (Some(path), _) => fs::read_to_string(path)
+                    .map_err(|e| anyhow::anyhow!("failed to read {}: {}", path.display(), e))?,
+                (None, Some("-")) => io::read_to_string(io::stdin())
+                    .map_err(|e| anyhow::anyhow!("failed to read the query from stdin: {}", e))?,
+                (None, Some(query)) => query.to_string(),
+                (None, None) => return Ok((None, args.params.clone())),
+            };
+            Ok((Some(query), args.params.clone()))
+        }
+    }
 }
 
 /// Detects the Kubernetes context based on the provided `Args`.
 ///
 /// Context determination follows this priority:
-/// 1. Uses the context explicitly specified in the `Args` structure.
-/// 2. Retrieves the current context from the kubeconfig file.
+/// 1. Uses the path of the objects when querying files instead of a cluster.
+/// 2. Uses the API server address as the context when connecting without kubeconfig.
+/// 3. Uses the context explicitly specified in the `Args` structure.
+/// 4. Retrieves the current context from the kubeconfig file.
 ///
 /// # Errors
 /// Returns an error if the kubeconfig file cannot be read or if no current context is set in the kubeconfig.
 fn detect_context(args: &Args) -> anyhow::Result<String> {
+    if let Some(path) = &args.from_file {
+        return Ok(path.display().to_string());
+    }
+    if args.from_stdin_manifests {
+        return Ok("stdin".to_string());
+    }
+    if let Some(dir) = &args.snapshots {
+        return Ok(snapshot::find(dir, args.as_of)?.display().to_string());
+    }
+    if let Some(server) = &args.server {
+        return Ok(server.clone());
+    }
     match &args.context {
         Some(context) => Ok(context.clone()),
         _ => {
-            let kubeconfig = Kubeconfig::read()?;
+            let kubeconfig = read_kubeconfig(args.kubeconfig.as_deref())?;
             Ok(kubeconfig
                 .current_context
                 .ok_or_else(|| anyhow::anyhow!("current_context is not set"))?)
@@ -66,44 +544,640 @@ fn detect_context(args: &Args) -> anyhow::Result<String> {
     }
 }
 
+/// Returns the context requested of a server if known: a context of the kubeconfig,
+/// or the context detected when not connecting by the kubeconfig (e.g. `--from-file`).
+fn known_context(args: &Args, context: String) -> anyhow::Result<String> {
+    let known = if args.from_file.is_some()
+        || args.from_stdin_manifests
+        || args.snapshots.is_some()
+        || args.server.is_some()
+    {
+        context == detect_context(args)?
+    } else {
+        read_kubeconfig(args.kubeconfig.as_deref())?
+            .contexts
+            .iter()
+            .any(|named| named.name == context)
+    };
+    if !known {
+        anyhow::bail!("context '{}' not found", context);
+    }
+    Ok(context)
+}
+
+/// Returns the contexts to fan out to, or an empty list if not fanning out.
+fn fan_out_contexts(args: &Args) -> anyhow::Result<Vec<String>> {
+    if args.all_contexts {
+        let kubeconfig = read_kubeconfig(args.kubeconfig.as_deref())?;
+        return Ok(kubeconfig.contexts.into_iter().map(|c| c.name).collect());
+    }
+    Ok(args.contexts.clone())
+}
+
+/// Executes the statements of the SQL script in the session, discarding their results.
+async fn run_script(ctx: &SessionContext, path: &Path, views: &Views) -> anyhow::Result<()> {
+    let sql = fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("failed to read {}: {}", path.display(), e))?;
+    for statement in split_statements(ctx, &sql)? {
+        views.register(ctx, &statement).await?;
+        ctx.sql(&statement).await?.collect().await?;
+    }
+    Ok(())
+}
+
+/// Executes the statement, printing its results unless it has none (e.g. CREATE VIEW, SET).
+async fn execute(
+    ctx: &SessionContext,
+    statement: &str,
+    params: &[Param],
+    format: OutputFormat,
+    views: &Views,
+) -> anyhow::Result<()> {
+    views.register(ctx, statement).await?;
+    let df = evaluate(ctx, statement, params).await?;
+    if df.schema().fields().is_empty() {
+        return Ok(());
+    }
+    output::print(df, format).await
+}
+
+/// Prints the plans of the statement and the requests they would make to the API servers,
+/// without listing any objects.
+async fn dry_run(
+    ctx: &SessionContext,
+    statement: &str,
+    params: &[Param],
+    views: &Views,
+) -> anyhow::Result<()> {
+    views.register(ctx, statement).await?;
+    let df = evaluate(ctx, statement, params).await?;
+    // Statements such as CREATE VIEW or SET take effect when planned and have no plans to print.
+    if df.schema().fields().is_empty() {
+        return Ok(());
+    }
+    let logical_plan = df.clone().into_optimized_plan()?;
+    let physical_plan = df.create_physical_plan().await?;
+    println!("Logical plan:");
+    println!("{}", logical_plan.display_indent());
+    println!();
+    println!("Physical plan:");
+    print!("{}", displayable(physical_plan.as_ref()).indent(true));
+    println!();
+    println!("API requests:");
+    for request in provider::api_requests(physical_plan.as_ref()) {
+        println!("  {}", request);
+    }
+    Ok(())
+}
+
+/// Prints the resolution trace of each table read by the query to stderr.
+async fn explain_resolution(
+    ctx: &SessionContext,
+    query: &str,
+    views: &Views,
+    factory: &KubernetesTableProviderFactory,
+) -> anyhow::Result<()> {
+    for table in views.read_tables(ctx, query).await? {
+        let table = match table {
+            ReadTable::View(table) => {
+                eprintln!("{}", Resolution::session(table.table(), SessionTable::View));
+                continue;
+            }
+            ReadTable::Session(table) => {
+                eprintln!(
+                    "{}",
+                    Resolution::session(table.table(), SessionTable::Table)
+                );
+                continue;
+            }
+            ReadTable::Resource(table) => table,
+        };
+        let fans_out = factory.fans_out(table.table());
+        for (context, resolution) in factory.explain_resolution(table.table()).await? {
+            if fans_out {
+                eprintln!("In context '{}':", context);
+            }
+            eprintln!("{}", resolution);
+        }
+    }
+    Ok(())
+}
+
+/// Plans the query with the values of its parameters bound.
+async fn evaluate(
+    ctx: &SessionContext,
+    query: &str,
+    params: &[Param],
+) -> anyhow::Result<DataFrame> {
+    params::bind(ctx.sql(query).await?, params)
+}
+
+/// Runs the query, then re-runs it whenever a resource referenced by the query changes.
+///
+/// Each table is re-listed on every evaluation,
+/// so the watches only serve as triggers.
+/// Evaluation errors are reported and do not stop following.
+async fn follow(
+    ctx: &SessionContext,
+    query: &str,
+    params: &[Param],
+    format: OutputFormat,
+    views: &Views,
+    factory: &KubernetesTableProviderFactory,
+) -> anyhow::Result<()> {
+    let (tx, mut rx) = mpsc::channel(64);
+    for table in views.resource_tables(ctx, query).await? {
+        for api in factory.apis(table.table()).await? {
+            watch::spawn(api, tx.clone());
+        }
+    }
+    drop(tx);
+
+    loop {
+        match evaluate(ctx, query, params).await {
+            Ok(df) => {
+                if let Err(e) = output::print(df, format).await {
+                    eprintln!("Error: {}", e);
+                }
+            }
+            Err(e) => eprintln!("Error: {}", e),
+        }
+        factory.warnings().print();
+
+        match rx.recv().await {
+            Some(changed) => changed?,
+            None => return Ok(()),
+        }
+        tokio::time::sleep(FOLLOW_DEBOUNCE).await;
+        while let Ok(changed) = rx.try_recv() {
+            changed?;
+        }
+    }
+}
+
+/// Runs the query against both contexts and prints the rows that differ,
+/// exiting with 1 if any does, as `diff`.
+async fn diff(
+    args: &Args,
+    left: &str,
+    right: &str,
+    query: &str,
+    params: &[Param],
+    views: &Views,
+) -> anyhow::Result<()> {
+    let (left_batches, right_batches) = futures::try_join!(
+        collect_in(args, left, query, params, views),
+        collect_in(args, right, query, params, views),
+    )?;
+    let diff = kuqu::diff::diff(
+        Side {
+            context: left,
+            batches: &left_batches,
+        },
+        Side {
+            context: right,
+            batches: &right_batches,
+        },
+    )?;
+    let Some(diff) = diff else {
+        return Ok(());
+    };
+    let mut stdout = io::stdout().lock();
+    output::write_batches(&mut stdout, &[diff], args.output)?;
+    stdout.flush()?;
+    std::process::exit(1);
+}
+
+/// Executes the statements in the session of the context, collecting the results of the last one.
+async fn collect_in(
+    args: &Args,
+    context: &str,
+    query: &str,
+    params: &[Param],
+    views: &Views,
+) -> anyhow::Result<Vec<RecordBatch>> {
+    let factory = connect(args, context.to_string()).await?;
+    let ctx = session(args, Arc::clone(&factory))?;
+    if let Some(init) = &args.init {
+        run_script(&ctx, init, views).await?;
+    }
+    let statements = split_statements(&ctx, query)?;
+    let Some((last, preceding)) = statements.split_last() else {
+        anyhow::bail!("no statement in the query");
+    };
+    for statement in preceding {
+        views.register(&ctx, statement).await?;
+        evaluate(&ctx, statement, params).await?.collect().await?;
+    }
+    views.register(&ctx, last).await?;
+    let result = evaluate(&ctx, last, params).await?.collect().await;
+    factory.warnings().print();
+    Ok(result?)
+}
+
+/// Connects to the cluster of the context (or reads the objects of `--from-file`
+/// or `--from-stdin-manifests`), creating the factory of its tables.
+async fn connect(
+    args: &Args,
+    context: String,
+) -> anyhow::Result<Arc<KubernetesTableProviderFactory>> {
+    let connect_options = ConnectOptions {
+        kubeconfig: args.kubeconfig.clone(),
+        server: args.server.clone(),
+        token: args.token.clone(),
+        certificate_authority: args.certificate_authority.clone(),
+        insecure_skip_tls_verify: args.insecure_skip_tls_verify,
+        client_certificate: args.client_certificate.clone(),
+        client_key: args.client_key.clone(),
+        request_timeout: args.request_timeout,
+        rate_limit: args
+            .qps
+            .map(|qps| RateLimitLayer::new(qps, args.burst.unwrap_or(qps.ceil() as u32)))
+            .transpose()?,
+        retry: Retry::new(args.max_retries),
+        disable_compression: args.disable_compression,
+        refresh_discovery: args.refresh_discovery,
+        namespace: args.namespace.clone(),
+        all_namespaces: args.all_namespaces,
+    };
+    let cluster = match &args.from_file {
+        Some(path) => Cluster::from_lister(
+            Arc::new(DumpLister::load(path)?),
+            if args.all_namespaces {
+                Namespaces::All
+            } else {
+                Namespaces::One(args.namespace.clone().unwrap_or("default".to_string()))
+            },
+        ),
+        None if args.from_stdin_manifests => {
+            let mut manifests = Vec::new();
+            io::stdin()
+                .read_to_end(&mut manifests)
+                .map_err(|e| anyhow::anyhow!("failed to read the manifests from stdin: {}", e))?;
+            // Rendered manifests often leave the namespace to the deployment, so read all namespaces.
+            Cluster::from_lister(
+                Arc::new(DumpLister::from_manifests(&manifests)?),
+                match &args.namespace {
+                    Some(namespace) => Namespaces::One(namespace.clone()),
+                    None => Namespaces::All,
+                },
+            )
+        }
+        None => match &args.snapshots {
+            Some(dir) => Cluster::from_lister(
+                Arc::new(snapshot::load(&snapshot::find(dir, args.as_of)?)?),
+                if args.all_namespaces {
+                    Namespaces::All
+                } else {
+                    Namespaces::One(args.namespace.clone().unwrap_or("default".to_string()))
+                },
+            ),
+            None => Cluster::connect(&context, &connect_options).await?,
+        },
+    };
+
+    let mut factory = KubernetesTableProviderFactory::new(context, cluster);
+    for (name, dir) in &args.manifests {
+        // Manifests often leave the namespace to the deployment, so read all namespaces.
+        let cluster = Cluster::from_lister(Arc::new(DumpLister::load(dir)?), Namespaces::All);
+        factory = factory.with_cluster(name.clone(), cluster);
+    }
+    Ok(Arc::new(
+        factory
+            .with_contexts(fan_out_contexts(args)?)
+            .with_connect_options(connect_options)
+            .with_list_timeout(args.list_timeout.map(|timeout| {
+                // The API server takes whole seconds.
+                u32::try_from(timeout.as_secs_f64().ceil() as u64)
+                    .unwrap_or(u32::MAX)
+                    .max(1)
+            }))
+            .with_infer_rows(args.infer_rows)
+            .with_show_secrets(args.show_secrets)
+            .with_dry_run(args.dry_run),
+    ))
+}
+
+/// Creates the session of the tables of the factory, along with those of the arguments
+/// (e.g. `--audit-log`).
+fn session(
+    args: &Args,
+    factory: Arc<KubernetesTableProviderFactory>,
+) -> anyhow::Result<SessionContext> {
+    let ctx = session_context(factory)?;
+    if !args.audit_logs.is_empty() {
+        audit::register(&ctx, &audit::read(&args.audit_logs)?)?;
+    }
+    Ok(ctx)
+}
+
+/// Runs each query of the file, notifying the webhook of the rows of those returning any.
+/// Queries failing are reported, failing after running the others.
+async fn alert(
+    ctx: &SessionContext,
+    factory: &KubernetesTableProviderFactory,
+    views: &Views,
+    params: &[Param],
+    path: &Path,
+    webhook: &Webhook,
+) -> anyhow::Result<()> {
+    let sql = fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("failed to read {}: {}", path.display(), e))?;
+    let mut failed = 0;
+    for statement in split_statements(ctx, &sql)? {
+        views.register(ctx, &statement).await?;
+        let result = match evaluate(ctx, &statement, params).await {
+            Ok(df) => df.collect().await.map_err(anyhow::Error::from),
+            Err(e) => Err(e),
+        };
+        factory.warnings().print();
+        let notified = match result {
+            Ok(batches) if batches.iter().all(|batch| batch.num_rows() == 0) => continue,
+            Ok(batches) => {
+                webhook
+                    .notify(factory.context(), &statement, &batches)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = notified {
+            eprintln!("Error: {}: {}", statement.trim(), e);
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!("{} of the queries failed", failed);
+    }
+    Ok(())
+}
+
+/// Connects sessions to the contexts and the namespaces of the requests to the server,
+/// defaulting to those of the arguments.
+fn sessions(args: Args, views: Views) -> server::Connect {
+    let args = Arc::new(args);
+    let views = Arc::new(views);
+    Arc::new(move |context, namespace| {
+        let mut args = (*args).clone();
+        let views = Arc::clone(&views);
+        Box::pin(async move {
+            if namespace.is_some() {
+                args.namespace = namespace;
+                args.all_namespaces = false;
+            }
+            let context = match context {
+                Some(context) => known_context(&args, context)?,
+                None => detect_context(&args)?,
+            };
+            let factory = connect(&args, context).await?;
+            let ctx = session(&args, Arc::clone(&factory))?;
+            if let Some(init) = &args.init {
+                run_script(&ctx, init, &views).await?;
+            }
+            Ok(server::Session {
+                ctx,
+                warnings: factory.warnings().clone(),
+            })
+        })
+    })
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let args = Args::parse();
+    let mut command = Args::command();
+    if let Some(path) = config::config_path() {
+        command = config::with_defaults(command, &path)?;
+    }
+    let args = Args::from_arg_matches(&command.get_matches()).unwrap_or_else(|e| e.exit());
+    if args.from_stdin_manifests && query_from_stdin(&args) {
+        anyhow::bail!(
+            "--from-stdin-manifests reads stdin, so the query must be given as an argument or with -f"
+        );
+    }
+    let (query, params) = query(&args)?;
+
+    let mut views = Views::builtin();
+    if let Some(path) = views::user_views_path() {
+        views = views.with_file(&path)?;
+    }
+    if let Some(Command::Diff { left, right, .. }) = &args.command {
+        let query = query.unwrap_or_default();
+        return diff(&args, left, right, &query, &params, &views).await;
+    }
+
+    if let Some(&Command::Serve { listen }) = args.command.as_ref() {
+        eprintln!("Serving queries at http://{}/query", listen);
+        return Server::new(sessions(args, views.clone()), views)
+            .serve(listen)
+            .await;
+    }
+    if let Some(&Command::Postgres { listen }) = args.command.as_ref() {
+        eprintln!("Serving PostgreSQL connections at {}", listen);
+        return PostgresServer::new(sessions(args, views.clone()), views)
+            .serve(listen)
+            .await;
+    }
+
     let context = detect_context(&args)?;
 
-    let kubeconfig = Kubeconfig::read()?;
-    let options = KubeConfigOptions {
-        context: Some(context.clone()),
-        ..Default::default()
+    let factory = connect(&args, context).await?;
+    let ctx = session(&args, Arc::clone(&factory))?;
+    if let Some(init) = &args.init {
+        run_script(&ctx, init, &views).await?;
+    }
+    if let Some(Command::Snapshot { resources, out }) = &args.command {
+        let namespaces = match &args.namespace {
+            Some(namespace) => Namespaces::One(namespace.clone()),
+            None => Namespaces::All,
+        };
+        let manifest = snapshot::export(&ctx, &factory, resources, &namespaces, out).await?;
+        factory.warnings().print();
+        for resource in &manifest.resources {
+            println!(
+                "{}: {} rows",
+                out.join(&resource.path).display(),
+                resource.rows
+            );
+        }
+        return Ok(());
+    }
+    if let Some(Command::Archive {
+        resources,
+        interval,
+        out,
+    }) = &args.command
+    {
+        let namespaces = match &args.namespace {
+            Some(namespace) => Namespaces::One(namespace.clone()),
+            None => Namespaces::All,
+        };
+        return snapshot::archive(&ctx, &factory, resources, &namespaces, *interval, out).await;
+    }
+    if let Some(Command::Exporter { config, listen }) = &args.command {
+        let config = exporter::Config::read(config)?;
+        eprintln!("Serving metrics at http://{}/metrics", listen);
+        return Exporter::new(ctx, views, factory.warnings().clone(), config)
+            .serve(*listen)
+            .await;
+    }
+    if let Some(Command::Check {
+        policies: path,
+        format,
+    }) = &args.command
+    {
+        let policies = Policies::read(path)?;
+        let results = check::run(&ctx, &factory, &views, policies, &params).await;
+        let mut stdout = io::stdout().lock();
+        match format {
+            CheckFormat::Text => check::write_text(&mut stdout, &results, args.output)?,
+            CheckFormat::Junit => {
+                let suite = path.file_stem().unwrap_or_default().to_string_lossy();
+                check::write_junit(&mut stdout, &suite, &results)?
+            }
+            CheckFormat::Sarif => {
+                check::write_sarif(&mut stdout, path, &results)?;
+                writeln!(stdout)?;
+            }
+        }
+        stdout.flush()?;
+        if results.iter().any(|result| result.fails()) {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(Command::Webhook {
+        policies,
+        listen,
+        tls_cert,
+        tls_key,
+    }) = &args.command
+    {
+        let policies = Policies::read(policies)?;
+        let tls = match (tls_cert, tls_key) {
+            (Some(cert), Some(key)) => Some(http::tls_acceptor(cert, key)?),
+            _ => None,
+        };
+        let scheme = if tls.is_some() { "https" } else { "http" };
+        eprintln!("Serving the admission webhook at {}://{}", scheme, listen);
+        return AdmissionWebhook::new(ctx, views, factory.warnings().clone(), policies)
+            .with_params(params)
+            .serve(*listen, tls)
+            .await;
+    }
+    if let Some(Command::Alert {
+        query_file,
+        webhook,
+        webhook_format,
+    }) = &args.command
+    {
+        let webhook = Webhook::new(webhook.clone(), *webhook_format);
+        return alert(&ctx, &factory, &views, &params, query_file, &webhook).await;
+    }
+    if let Some(Command::Mcp {
+        allow_namespaces,
+        max_rows,
+    }) = &args.command
+    {
+        return McpServer::new(ctx, factory, views)
+            .with_allowed_namespaces(allow_namespaces.clone())
+            .with_max_rows(*max_rows)
+            .serve()
+            .await;
+    }
+
+    let Some(query) = query else {
+        if args.follow {
+            anyhow::bail!("--follow requires a query");
+        }
+        if args.dry_run {
+            anyhow::bail!("--dry-run requires a query");
+        }
+        if args.every.is_some() || !args.sinks.is_empty() {
+            anyhow::bail!("--every and --sink require a query");
+        }
+        let repl = repl::Repl {
+            ctx: &ctx,
+            views: &views,
+            factory: &factory,
+            params: &params,
+            format: args.output,
+            explain_resolution: args.explain_resolution,
+        };
+        return repl.run().await;
     };
-    let config = Config::from_custom_kubeconfig(kubeconfig, &options).await?;
-    let client = Client::try_from(config)?;
-
-    let discover_client = DiscoverClient::new(client.clone());
-    let api_resources = discover_client.list_api_resources().await?;
-
-    let factory = Arc::new(KubernetesTableProviderFactory::new(
-        client,
-        context,
-        api_resources,
-    ));
-    let ctx = SessionContext::new();
-    let catalog_list = Arc::new(DynamicFileCatalog::new(
-        Arc::clone(ctx.state().catalog_list()),
-        factory as Arc<dyn UrlTableFactory>,
-    ));
-    let ctx: SessionContext = ctx
-        .into_state_builder()
-        .with_config(SessionConfig::from_string_hash_map(&HashMap::from([(
-            // To avoid e.g. spec.nodeName => spec.nodename normalization in DataFusion SQL parser
-            "datafusion.sql_parser.enable_ident_normalization".to_owned(),
-            "false".to_owned(),
-        )]))?)
-        .with_catalog_list(catalog_list)
-        .build()
-        .into();
-
-    let df = ctx.sql(&args.query).await?;
-    df.show().await?;
-    Ok(())
+
+    // Statements are executed in order, following only the last one.
+    let statements = split_statements(&ctx, &query)?;
+    let Some((last, preceding)) = statements.split_last() else {
+        anyhow::bail!("no statement in the query");
+    };
+    if args.dry_run {
+        for statement in &statements {
+            if args.explain_resolution {
+                explain_resolution(&ctx, statement, &views, &factory).await?;
+            }
+            dry_run(&ctx, statement, &params, &views).await?;
+        }
+        factory.warnings().print();
+        return Ok(());
+    }
+    if args.every.is_some() || !args.sinks.is_empty() {
+        return schedule::run(
+            &ctx,
+            &factory,
+            &views,
+            &statements,
+            &params,
+            &args.sinks,
+            args.every,
+        )
+        .await;
+    }
+    for statement in preceding {
+        if args.explain_resolution {
+            explain_resolution(&ctx, statement, &views, &factory).await?;
+        }
+        let result = execute(&ctx, statement, &params, args.output, &views).await;
+        factory.warnings().print();
+        result?;
+    }
+
+    if args.explain_resolution {
+        explain_resolution(&ctx, last, &views, &factory).await?;
+    }
+    if args.follow {
+        views.register(&ctx, last).await?;
+        return follow(&ctx, last, &params, args.output, &views, &factory).await;
+    }
+    let result = execute(&ctx, last, &params, args.output, &views).await;
+    factory.warnings().print();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Args {
+        Args::try_parse_from(std::iter::once("kuqu").chain(args.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn query_from_stdin_with_stdin_manifests() {
+        assert!(query_from_stdin(&parse(&["--from-stdin-manifests"])));
+        assert!(query_from_stdin(&parse(&["--from-stdin-manifests", "-"])));
+        assert!(!query_from_stdin(&parse(&[
+            "--from-stdin-manifests",
+            "SELECT 1"
+        ])));
+        assert!(!query_from_stdin(&parse(&[
+            "--from-stdin-manifests",
+            "-f",
+            "/tmp/q.sql"
+        ])));
+        assert!(!query_from_stdin(&parse(&[
+            "--from-stdin-manifests",
+            "check",
+            "policies.yaml"
+        ])));
+    }
 }
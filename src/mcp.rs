@@ -0,0 +1,429 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use datafusion::{
+    arrow::{datatypes::DataType, json::ArrayWriter, record_batch::RecordBatch},
+    execution::context::SessionContext,
+};
+use serde_json::{Value, json};
+use tokio::io::{self, AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+use crate::{
+    provider::KubernetesTableProviderFactory,
+    server::read_only,
+    split_statements,
+    url::Namespaces,
+    views::{ReadTable, Views},
+};
+
+/// Version of the protocol answered to clients not requesting one
+const PROTOCOL_VERSION: &str = "2025-03-26";
+
+/// Maximum number of rows returned by a query, unless configured
+pub const DEFAULT_MAX_ROWS: usize = 1000;
+
+/// Serves the queries as tools of the Model Context Protocol (MCP) over stdio,
+/// for LLM assistants to read the state of the cluster.
+///
+/// Only queries are allowed, and with namespaces allowed,
+/// only tables of resources, and of namespaced ones only in those namespaces.
+pub struct McpServer {
+    ctx: SessionContext,
+    factory: Arc<KubernetesTableProviderFactory>,
+    views: Views,
+    /// Namespaces the tables may read, any if empty
+    allowed_namespaces: Vec<String>,
+    max_rows: usize,
+}
+
+impl McpServer {
+    pub fn new(
+        ctx: SessionContext,
+        factory: Arc<KubernetesTableProviderFactory>,
+        views: Views,
+    ) -> Self {
+        Self {
+            ctx,
+            factory,
+            views,
+            allowed_namespaces: Vec::new(),
+            max_rows: DEFAULT_MAX_ROWS,
+        }
+    }
+
+    /// Allow only tables of namespaced resources in the namespaces
+    pub fn with_allowed_namespaces(mut self, allowed_namespaces: Vec<String>) -> Self {
+        self.allowed_namespaces = allowed_namespaces;
+        self
+    }
+
+    /// Set the maximum number of rows returned by a query
+    pub fn with_max_rows(mut self, max_rows: usize) -> Self {
+        self.max_rows = max_rows;
+        self
+    }
+
+    /// Answer the JSON-RPC messages read from stdin, a message per line, until stdin is closed
+    pub async fn serve(self) -> anyhow::Result<()> {
+        let mut lines = BufReader::new(io::stdin()).lines();
+        let mut stdout = io::stdout();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let Some(response) = self.handle(&line).await else {
+                continue;
+            };
+            stdout
+                .write_all(format!("{}\n", response).as_bytes())
+                .await?;
+            stdout.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Response to the message, none for notifications
+    async fn handle(&self, line: &str) -> Option<Value> {
+        let message: Value = match serde_json::from_str(line) {
+            Ok(message) => message,
+            Err(e) => return Some(error(Value::Null, -32700, &format!("parse error: {}", e))),
+        };
+        let id = message.get("id")?.clone();
+        let method = message
+            .get("method")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let result = match method {
+            "initialize" => json!({
+                "protocolVersion": params
+                    .get("protocolVersion")
+                    .and_then(Value::as_str)
+                    .unwrap_or(PROTOCOL_VERSION),
+                "capabilities": { "tools": {} },
+                "serverInfo": { "name": "kuqu", "version": env!("CARGO_PKG_VERSION") },
+            }),
+            "ping" => json!({}),
+            "tools/list" => json!({ "tools": tools() }),
+            "tools/call" => {
+                let name = params
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default();
+                let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+                let (text, is_error) = match self.call(name, &arguments).await {
+                    Ok(text) => (text, false),
+                    Err(e) => (e.to_string(), true),
+                };
+                self.factory.warnings().print();
+                json!({
+                    "content": [{ "type": "text", "text": text }],
+                    "isError": is_error,
+                })
+            }
+            method => return Some(error(id, -32601, &format!("method '{}' not found", method))),
+        };
+        Some(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+    }
+
+    async fn call(&self, tool: &str, arguments: &Value) -> anyhow::Result<String> {
+        let argument = |name: &str| {
+            arguments
+                .get(name)
+                .and_then(Value::as_str)
+                .ok_or_else(|| anyhow::anyhow!("missing argument '{}'", name))
+        };
+        match tool {
+            "query" => self.query(argument("sql")?).await,
+            "list_tables" => self.list_tables().await,
+            "describe_table" => self.describe_table(argument("table")?).await,
+            tool => anyhow::bail!("tool '{}' not found", tool),
+        }
+    }
+
+    /// Rows of the query as JSON, up to the maximum number of rows
+    async fn query(&self, sql: &str) -> anyhow::Result<String> {
+        let statements = split_statements(&self.ctx, sql)?;
+        let [statement] = statements.as_slice() else {
+            anyhow::bail!(
+                "expected a single query, got {} statements",
+                statements.len()
+            );
+        };
+        self.check_namespaces(statement).await?;
+        self.views.register(&self.ctx, statement).await?;
+        let df = self
+            .ctx
+            .sql_with_options(statement, read_only())
+            .await?
+            .limit(0, Some(self.max_rows + 1))?;
+        let batches = df.collect().await?;
+        let rows: usize = batches.iter().map(|batch| batch.num_rows()).sum();
+        let mut text = json_rows(&batches, self.max_rows)?;
+        if rows > self.max_rows {
+            text.push_str(&format!(
+                "\n(truncated to the first {} rows)",
+                self.max_rows
+            ));
+        }
+        Ok(text)
+    }
+
+    /// Resources of the cluster and the views, as JSON
+    async fn list_tables(&self) -> anyhow::Result<String> {
+        let resources: Vec<Value> = self
+            .factory
+            .api_resources()
+            .await?
+            .into_iter()
+            .map(|resource| {
+                json!({
+                    "name": resource.name,
+                    "group": resource.group.filter(|group| !group.is_empty()).unwrap_or("core".to_string()),
+                    "version": resource.version,
+                    "kind": resource.kind,
+                    "namespaced": resource.namespaced,
+                    "shortNames": resource.short_names.unwrap_or_default(),
+                })
+            })
+            .collect();
+        let views: Vec<&str> = self.views.names().collect();
+        Ok(json!({
+            "resources": resources,
+            "views": views,
+            "usage": "Query resources as tables named '<resource>[/<namespace>[/<name>]]' \
+                (namespace '*' for all) and views by name; nested fields as metadata.name \
+                or spec.containers[1]['image']",
+        })
+        .to_string())
+    }
+
+    /// Columns of the table (a resource or a view) and their types
+    async fn describe_table(&self, table: &str) -> anyhow::Result<String> {
+        let query = if self.views.names().any(|name| name == table) {
+            format!("SELECT * FROM {}", table)
+        } else {
+            format!("SELECT * FROM '{}'", table.replace('\'', "''"))
+        };
+        self.check_namespaces(&query).await?;
+        self.views.register(&self.ctx, &query).await?;
+        let df = self.ctx.sql_with_options(&query, read_only()).await?;
+        Ok(df
+            .schema()
+            .fields()
+            .iter()
+            .map(|field| format!("{}: {}", field.name(), type_name(field.data_type())))
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    /// Fail unless every table of the query is a resource, and namespaced ones read only
+    /// allowed namespaces. Other tables (e.g. `owners`) and table functions (e.g. `logs`)
+    /// are denied, as they may read any namespace.
+    async fn check_namespaces(&self, query: &str) -> anyhow::Result<()> {
+        if self.allowed_namespaces.is_empty() {
+            return Ok(());
+        }
+        for table in self.views.read_tables(&self.ctx, query).await? {
+            let table = match table {
+                ReadTable::View(_) => continue,
+                ReadTable::Resource(table) => table,
+                ReadTable::Session(table) => anyhow::bail!(
+                    "table '{}' is not allowed with namespaces allowed",
+                    table.table()
+                ),
+            };
+            let resolved = self.factory.resolve_all(table.table()).await?;
+            if resolved.is_empty() {
+                anyhow::bail!(
+                    "table '{}' is not a resource, not allowed with namespaces allowed",
+                    table.table()
+                );
+            }
+            for (_, kubeurl, _) in resolved {
+                if !kubeurl.resource.namespaced {
+                    continue;
+                }
+                match &kubeurl.namespace {
+                    Namespaces::One(namespace) if self.allowed_namespaces.contains(namespace) => {}
+                    _ => anyhow::bail!(
+                        "table '{}' reads namespaces not allowed (allowed: {})",
+                        table.table(),
+                        self.allowed_namespaces.join(", ")
+                    ),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Tools served, with the JSON schemas of their arguments
+fn tools() -> Value {
+    json!([
+        {
+            "name": "query",
+            "description": "Run a read-only SQL query (DataFusion dialect) against the Kubernetes \
+                cluster, returning the rows as JSON. Tables are resources such as 'pods/default' \
+                or views; see list_tables.",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "sql": { "type": "string", "description": "A single SQL query" } },
+                "required": ["sql"],
+            },
+        },
+        {
+            "name": "list_tables",
+            "description": "List the resources of the cluster and the views queryable as tables.",
+            "inputSchema": { "type": "object", "properties": {} },
+        },
+        {
+            "name": "describe_table",
+            "description": "Describe the columns and their types of a table, \
+                e.g. 'deployments.apps/default' or a view name.",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "table": { "type": "string" } },
+                "required": ["table"],
+            },
+        },
+    ])
+}
+
+/// Compact name of the type, e.g. `struct<name: Utf8, labels: map<Utf8, Utf8>>`
+fn type_name(data_type: &DataType) -> String {
+    match data_type {
+        DataType::Struct(fields) => format!(
+            "struct<{}>",
+            fields
+                .iter()
+                .map(|field| format!("{}: {}", field.name(), type_name(field.data_type())))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        DataType::List(field) | DataType::LargeList(field) | DataType::FixedSizeList(field, _) => {
+            format!("list<{}>", type_name(field.data_type()))
+        }
+        DataType::Map(field, _) => match field.data_type() {
+            DataType::Struct(entries) if entries.len() == 2 => format!(
+                "map<{}, {}>",
+                type_name(entries[0].data_type()),
+                type_name(entries[1].data_type())
+            ),
+            data_type => type_name(data_type),
+        },
+        data_type => data_type.to_string(),
+    }
+}
+
+fn error(id: Value, code: i32, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+/// Up to the maximum number of rows of the batches as a JSON array
+fn json_rows(batches: &[RecordBatch], max_rows: usize) -> anyhow::Result<String> {
+    let mut writer = ArrayWriter::new(Vec::new());
+    let mut remaining = max_rows;
+    for batch in batches {
+        if remaining == 0 {
+            break;
+        }
+        let batch = batch.slice(0, batch.num_rows().min(remaining));
+        remaining -= batch.num_rows();
+        writer.write(&batch)?;
+    }
+    writer.finish()?;
+    let buffer = writer.into_inner();
+    if buffer.is_empty() {
+        return Ok("[]".to_string());
+    }
+    Ok(String::from_utf8(buffer)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{cluster::Cluster, lister::fake::FakeLister, session_context};
+
+    fn server() -> McpServer {
+        let lister = FakeLister::new(vec![
+            json!({"apiVersion": "v1", "kind": "Pod",
+                "metadata": {"name": "web-1", "namespace": "default"}}),
+            json!({"apiVersion": "v1", "kind": "Pod",
+                "metadata": {"name": "web-2", "namespace": "other"}}),
+        ]);
+        let cluster =
+            Cluster::from_lister(Arc::new(lister), Namespaces::One("default".to_string()));
+        let factory = Arc::new(KubernetesTableProviderFactory::new(
+            "fake".to_string(),
+            cluster,
+        ));
+        let ctx = session_context(Arc::clone(&factory)).unwrap();
+        McpServer::new(ctx, factory, Views::builtin())
+            .with_allowed_namespaces(vec!["default".to_string()])
+    }
+
+    #[tokio::test]
+    async fn allowed_namespaces() {
+        let server = server();
+        server.check_namespaces("SELECT * FROM pods").await.unwrap();
+        server
+            .check_namespaces("SELECT * FROM 'pods/default'")
+            .await
+            .unwrap();
+        assert!(
+            server
+                .check_namespaces("SELECT * FROM 'pods/other'")
+                .await
+                .is_err()
+        );
+        assert!(
+            server
+                .check_namespaces("SELECT * FROM 'pods/*'")
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn deny_other_tables() {
+        let server = server();
+        for query in [
+            "SELECT * FROM owners",
+            "SELECT * FROM node_stats",
+            "SELECT * FROM logs('pods/other/web-2')",
+            "SELECT * FROM events_for('pods/other/web-2')",
+        ] {
+            let e = server.check_namespaces(query).await.unwrap_err();
+            assert!(e.to_string().contains("not allowed"), "{}: {}", query, e);
+        }
+
+        // Through a view created in the session, too
+        server
+            .ctx
+            .sql("CREATE VIEW owned AS SELECT * FROM owners")
+            .await
+            .unwrap();
+        assert!(
+            server
+                .check_namespaces("SELECT * FROM owned")
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,234 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::HashMap, sync::Arc};
+
+use datafusion::arrow::datatypes::{DataType, Field, FieldRef, Fields, Schema};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::APIResource;
+use kube::Client;
+use serde_json::{Map, Value};
+use tokio::sync::{Mutex, OnceCell};
+
+/// Prefix of references to other schemas in an OpenAPI v3 document.
+const SCHEMA_REF_PREFIX: &str = "#/components/schemas/";
+
+/// Client for the `/openapi/v3` endpoint.
+/// Documents are cached per group version for the lifetime of the client.
+pub struct OpenApiClient {
+    client: Client,
+    index: OnceCell<Value>,
+    documents: Mutex<HashMap<String, Arc<Value>>>,
+}
+
+impl OpenApiClient {
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            index: OnceCell::new(),
+            documents: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn get(&self, path: &str) -> anyhow::Result<Value> {
+        let request = http::Request::get(path).body(Vec::new())?;
+        Ok(self.client.request(request).await?)
+    }
+
+    /// Fetch the OpenAPI v3 document of the group version the resource belongs to.
+    pub async fn document(&self, api_resource: &APIResource) -> anyhow::Result<Arc<Value>> {
+        let path = group_version_path(api_resource);
+        let mut documents = self.documents.lock().await;
+        if let Some(document) = documents.get(&path) {
+            return Ok(document.clone());
+        }
+
+        let index = self
+            .index
+            .get_or_try_init(|| self.get("/openapi/v3"))
+            .await?;
+        let url = index
+            .pointer(&format!(
+                "/paths/{}/serverRelativeURL",
+                path.replace('/', "~1")
+            ))
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow::anyhow!("OpenAPI v3 document for '{}' not found", path))?;
+
+        let document = Arc::new(self.get(url).await?);
+        documents.insert(path, document.clone());
+        Ok(document)
+    }
+}
+
+/// Returns the path of the group version under `/openapi/v3`,
+/// e.g. `api/v1` or `apis/apps/v1`.
+fn group_version_path(api_resource: &APIResource) -> String {
+    let version = api_resource.version.as_deref().unwrap_or_default();
+    match api_resource.group.as_deref() {
+        Some("core") | None => format!("api/{}", version),
+        Some(group) => format!("apis/{}/{}", group, version),
+    }
+}
+
+/// Check if the schema is the one of the resource
+/// by its `x-kubernetes-group-version-kind` extension.
+fn has_group_version_kind(schema: &Value, api_resource: &APIResource) -> bool {
+    let group = match api_resource.group.as_deref() {
+        Some("core") | None => "",
+        Some(group) => group,
+    };
+    let version = api_resource.version.as_deref().unwrap_or_default();
+    schema["x-kubernetes-group-version-kind"]
+        .as_array()
+        .is_some_and(|gvks| {
+            gvks.iter().any(|gvk| {
+                gvk["group"] == group
+                    && gvk["version"] == version
+                    && gvk["kind"] == api_resource.kind
+            })
+        })
+}
+
+/// Returns the reference if the schema only refers to another schema,
+/// either directly (`$ref`) or wrapped in `allOf` as Kubernetes does
+/// to attach a description or default to the reference.
+fn reference_of(schema: &Value) -> Option<&str> {
+    if let Some(reference) = schema["$ref"].as_str() {
+        return Some(reference);
+    }
+    match schema["allOf"].as_array()?.as_slice() {
+        [inner] => inner["$ref"].as_str(),
+        _ => None,
+    }
+}
+
+/// Converts OpenAPI schemas into Arrow types.
+struct Converter<'a> {
+    schemas: &'a Map<String, Value>,
+}
+
+impl Converter<'_> {
+    /// Convert the schema into an Arrow type.
+    /// `inferred` is the type inferred from the objects, if any,
+    /// used where OpenAPI cannot describe the structure.
+    /// `visiting` holds the references being converted to stop at recursive schemas.
+    fn data_type(
+        &self,
+        schema: &Value,
+        inferred: Option<&DataType>,
+        visiting: &mut Vec<String>,
+    ) -> Option<DataType> {
+        let Some(reference) = reference_of(schema) else {
+            return self.data_type_of(schema, inferred, visiting);
+        };
+        if visiting.iter().any(|visited| visited == reference) {
+            return inferred.cloned();
+        }
+        let target = self
+            .schemas
+            .get(reference.strip_prefix(SCHEMA_REF_PREFIX)?)?;
+        visiting.push(reference.to_string());
+        let data_type = self.data_type_of(target, inferred, visiting);
+        visiting.pop();
+        data_type
+    }
+
+    fn data_type_of(
+        &self,
+        schema: &Value,
+        inferred: Option<&DataType>,
+        visiting: &mut Vec<String>,
+    ) -> Option<DataType> {
+        if schema["x-kubernetes-int-or-string"] == true {
+            return Some(DataType::Utf8);
+        }
+        match schema["type"].as_str() {
+            Some("string") => Some(DataType::Utf8),
+            Some("integer") => Some(DataType::Int64),
+            Some("number") => Some(DataType::Float64),
+            Some("boolean") => Some(DataType::Boolean),
+            Some("array") => {
+                let inferred_item = match inferred {
+                    Some(DataType::List(item)) => Some(item.data_type()),
+                    _ => None,
+                };
+                let item = self.data_type(&schema["items"], inferred_item, visiting)?;
+                Some(DataType::List(Arc::new(Field::new_list_field(item, true))))
+            }
+            Some("object") | None if schema["properties"].is_object() => {
+                let inferred_fields = match inferred {
+                    Some(DataType::Struct(fields)) => fields.clone(),
+                    _ => Fields::empty(),
+                };
+                Some(DataType::Struct(self.struct_fields(
+                    schema,
+                    &inferred_fields,
+                    visiting,
+                )))
+            }
+            // Maps with arbitrary keys (e.g. `metadata.labels`) and free-form objects
+            // cannot be described by OpenAPI, so rely on the inferred type.
+            _ => inferred.cloned(),
+        }
+    }
+
+    /// Convert the properties of an object schema into Arrow fields.
+    /// Fields found only in the objects (e.g. not covered by a CRD schema) are kept as inferred.
+    fn struct_fields(
+        &self,
+        schema: &Value,
+        inferred: &Fields,
+        visiting: &mut Vec<String>,
+    ) -> Fields {
+        let properties = schema["properties"].as_object();
+        let mut fields: Vec<FieldRef> = properties
+            .into_iter()
+            .flatten()
+            .filter_map(|(name, property)| {
+                let inferred = inferred.find(name).map(|(_, field)| field.data_type());
+                self.data_type(property, inferred, visiting)
+                    .map(|data_type| Arc::new(Field::new(name, data_type, true)))
+            })
+            .collect();
+        fields.extend(
+            inferred
+                .iter()
+                .filter(|field| properties.is_none_or(|p| !p.contains_key(field.name())))
+                .cloned(),
+        );
+        fields.into()
+    }
+}
+
+/// Build the Arrow schema of the resource from its OpenAPI v3 document,
+/// supplemented by the schema inferred from sample objects.
+///
+/// Returns `None` if the document does not contain the schema of the resource.
+pub fn arrow_schema(
+    document: &Value,
+    api_resource: &APIResource,
+    inferred: &Schema,
+) -> Option<Schema> {
+    let schemas = document.pointer("/components/schemas")?.as_object()?;
+    let (name, root) = schemas
+        .iter()
+        .find(|(_, schema)| has_group_version_kind(schema, api_resource))?;
+    let converter = Converter { schemas };
+    let mut visiting = vec![format!("{}{}", SCHEMA_REF_PREFIX, name)];
+    Some(Schema::new(converter.struct_fields(
+        root,
+        inferred.fields(),
+        &mut visiting,
+    )))
+}
@@ -0,0 +1,295 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    io::{self, Write},
+    sync::Arc,
+};
+
+use datafusion::{
+    arrow::{
+        array::{Array, ArrayRef, StringArray},
+        csv,
+        datatypes::{DataType, Field, Schema},
+        json::{ArrayWriter, LineDelimitedWriter},
+        record_batch::RecordBatch,
+        util::display::{ArrayFormatter, FormatOptions},
+    },
+    dataframe::DataFrame,
+};
+
+use crate::alert::json_rows;
+
+/// Format used to print query results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable table
+    #[default]
+    Table,
+    /// JSON array of rows
+    Json,
+    /// One JSON object per line
+    Ndjson,
+    /// CSV with a header line, nested columns as JSON
+    Csv,
+    /// YAML sequence of rows
+    Yaml,
+    /// Graphviz DOT graph of the rows as edges: source, target and an optional label
+    Dot,
+    /// Mermaid flowchart of the rows as edges: source, target and an optional label
+    Mermaid,
+}
+
+/// Write record batches to `w` in the given format.
+pub fn write_batches<W: Write>(
+    w: W,
+    batches: &[RecordBatch],
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    match format {
+        OutputFormat::Table => {
+            let mut w = w;
+            writeln!(
+                w,
+                "{}",
+                datafusion::arrow::util::pretty::pretty_format_batches(batches)?
+            )?;
+        }
+        OutputFormat::Json => {
+            let mut writer = ArrayWriter::new(w);
+            writer.write_batches(&batches.iter().collect::<Vec<_>>())?;
+            writer.finish()?;
+            writeln!(writer.into_inner())?;
+        }
+        OutputFormat::Ndjson => {
+            let mut writer = LineDelimitedWriter::new(w);
+            writer.write_batches(&batches.iter().collect::<Vec<_>>())?;
+            writer.finish()?;
+        }
+        OutputFormat::Csv => {
+            let mut writer = csv::WriterBuilder::new().with_header(true).build(w);
+            for batch in batches {
+                writer.write(&flatten_nested(batch)?)?;
+            }
+        }
+        OutputFormat::Yaml => {
+            // Go through JSON so that nested structs and lists map naturally to YAML.
+            let mut writer = ArrayWriter::new(Vec::new());
+            writer.write_batches(&batches.iter().collect::<Vec<_>>())?;
+            writer.finish()?;
+            let rows: serde_json::Value = serde_json::from_slice(&writer.into_inner())?;
+            serde_yaml::to_writer(w, &rows)?;
+        }
+        OutputFormat::Dot => write_dot(w, &edges(batches)?)?,
+        OutputFormat::Mermaid => write_mermaid(w, &edges(batches)?)?,
+    }
+    Ok(())
+}
+
+/// Batch with the nested columns (structs, lists and maps) as JSON strings, for CSV
+fn flatten_nested(batch: &RecordBatch) -> anyhow::Result<RecordBatch> {
+    let nested = |data_type: &DataType| {
+        matches!(
+            data_type,
+            DataType::Struct(_)
+                | DataType::List(_)
+                | DataType::LargeList(_)
+                | DataType::FixedSizeList(_, _)
+                | DataType::Map(_, _)
+        )
+    };
+    let schema = batch.schema();
+    if !schema
+        .fields()
+        .iter()
+        .any(|field| nested(field.data_type()))
+    {
+        return Ok(batch.clone());
+    }
+    let mut fields = Vec::new();
+    let mut columns = Vec::new();
+    for (field, column) in schema.fields().iter().zip(batch.columns()) {
+        if nested(field.data_type()) {
+            fields.push(Arc::new(Field::new(field.name(), DataType::Utf8, true)));
+            columns.push(json_strings(column)?);
+        } else {
+            fields.push(Arc::clone(field));
+            columns.push(Arc::clone(column));
+        }
+    }
+    Ok(RecordBatch::try_new(
+        Arc::new(Schema::new(fields)),
+        columns,
+    )?)
+}
+
+/// JSON of each value of the column, null for nulls
+fn json_strings(column: &ArrayRef) -> anyhow::Result<ArrayRef> {
+    Ok(Arc::new(
+        json_values(column)?.into_iter().collect::<StringArray>(),
+    ))
+}
+
+/// JSON text of each value of the column, `None` for nulls
+pub fn json_values(column: &ArrayRef) -> anyhow::Result<Vec<Option<String>>> {
+    let batch = RecordBatch::try_from_iter([("value", Arc::clone(column))])?;
+    Ok(json_rows(&[batch])?
+        .into_iter()
+        .map(
+            |mut row| match row.get_mut("value").map(serde_json::Value::take) {
+                Some(serde_json::Value::Null) | None => None,
+                Some(value) => Some(value.to_string()),
+            },
+        )
+        .collect())
+}
+
+/// Source, target (`None` for a node without edges) and label of an edge
+type Edge = (String, Option<String>, Option<String>);
+
+/// Edges of the rows: the first column is the source, the second the target
+/// and the third, if any, the label
+fn edges(batches: &[RecordBatch]) -> anyhow::Result<Vec<Edge>> {
+    let mut edges = Vec::new();
+    for batch in batches {
+        if batch.num_columns() < 2 {
+            anyhow::bail!(
+                "graphs need the source and the target of each edge as the first columns"
+            );
+        }
+        let options = FormatOptions::default();
+        let columns = batch
+            .columns()
+            .iter()
+            .take(3)
+            .map(|column| {
+                Ok((
+                    column.logical_nulls(),
+                    ArrayFormatter::try_new(column.as_ref(), &options)?,
+                ))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let value = |index: usize, row: usize| {
+            columns
+                .get(index)
+                .filter(|(nulls, _)| nulls.as_ref().is_none_or(|nulls| nulls.is_valid(row)))
+                .map(|(_, formatter)| formatter.value(row).to_string())
+        };
+        for row in 0..batch.num_rows() {
+            let Some(source) = value(0, row) else {
+                continue;
+            };
+            edges.push((source, value(1, row), value(2, row)));
+        }
+    }
+    Ok(edges)
+}
+
+fn write_dot<W: Write>(mut w: W, edges: &[Edge]) -> io::Result<()> {
+    let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+    writeln!(w, "digraph {{")?;
+    for (source, target, label) in edges {
+        match target {
+            Some(target) => write!(w, "  {} -> {}", quote(source), quote(target))?,
+            None => write!(w, "  {}", quote(source))?,
+        }
+        match label {
+            Some(label) => writeln!(w, " [label={}];", quote(label))?,
+            None => writeln!(w, ";")?,
+        }
+    }
+    writeln!(w, "}}")
+}
+
+fn write_mermaid<W: Write>(mut w: W, edges: &[Edge]) -> io::Result<()> {
+    let escape = |s: &str| s.replace('"', "#quot;").replace('|', "#124;");
+    // Nodes are identified by their order of appearance, as names may not be valid ids.
+    let mut ids: Vec<String> = Vec::new();
+    let mut node = |name: &str| match ids.iter().position(|id| id == name) {
+        Some(id) => format!("n{}", id),
+        None => {
+            ids.push(name.to_string());
+            format!("n{}[\"{}\"]", ids.len() - 1, escape(name))
+        }
+    };
+    writeln!(w, "flowchart LR")?;
+    for (source, target, label) in edges {
+        let source = node(source);
+        match (target, label) {
+            (Some(target), Some(label)) => writeln!(
+                w,
+                "  {} -->|\"{}\"| {}",
+                source,
+                escape(label),
+                node(target)
+            )?,
+            (Some(target), None) => writeln!(w, "  {} --> {}", source, node(target))?,
+            (None, _) => writeln!(w, "  {}", source)?,
+        }
+    }
+    Ok(())
+}
+
+/// Execute the DataFrame and print its results to stdout in the given format.
+pub async fn print(df: DataFrame, format: OutputFormat) -> anyhow::Result<()> {
+    match format {
+        OutputFormat::Table => df.show().await?,
+        _ => {
+            let batches = df.collect().await?;
+            let mut stdout = io::stdout().lock();
+            write_batches(&mut stdout, &batches, format)?;
+            stdout.flush()?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::{
+        array::{Int64Array, ListArray, StructArray},
+        datatypes::Int64Type,
+    };
+
+    use super::*;
+
+    #[test]
+    fn csv_nested_columns() {
+        let names: ArrayRef = Arc::new(StringArray::from(vec!["web-1", "web-2"]));
+        let metadata = StructArray::from(vec![(
+            Arc::new(Field::new("name", DataType::Utf8, true)),
+            names,
+        )]);
+        let ports =
+            ListArray::from_iter_primitive::<Int64Type, _, _>(vec![Some(vec![Some(80)]), None]);
+        let batch = RecordBatch::try_from_iter([
+            ("metadata", Arc::new(metadata) as ArrayRef),
+            ("ports", Arc::new(ports) as ArrayRef),
+            (
+                "restarts",
+                Arc::new(Int64Array::from(vec![0, 3])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+
+        let mut out = Vec::new();
+        write_batches(&mut out, &[batch], OutputFormat::Csv).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "metadata,ports,restarts\n\
+            \"{\"\"name\"\":\"\"web-1\"\"}\",[80],0\n\
+            \"{\"\"name\"\":\"\"web-2\"\"}\",,3\n"
+        );
+    }
+}
@@ -0,0 +1,184 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{any::Any, sync::Arc};
+
+use async_trait::async_trait;
+use datafusion::{
+    arrow::{
+        array::{ArrayRef, BooleanArray, StringArray},
+        datatypes::{DataType, Field, Schema, SchemaRef},
+        record_batch::RecordBatch,
+    },
+    catalog::Session,
+    common::{DataFusionError, Result as DataFusionResult},
+    datasource::{MemTable, TableProvider, TableType},
+    logical_expr::Expr,
+    physical_plan::ExecutionPlan,
+};
+use kube::api::ListParams;
+
+use crate::provider::KubernetesTableProviderFactory;
+
+/// Table of the owner references of the objects
+pub const OWNERS_TABLE: &str = "owners";
+
+/// Resources whose owner references are read, those of the resources
+/// created by controllers; resources missing from the cluster are skipped.
+const OWNED_RESOURCES: &[&str] = &[
+    "pods",
+    "replicasets.apps",
+    "deployments.apps",
+    "statefulsets.apps",
+    "daemonsets.apps",
+    "controllerrevisions.apps",
+    "jobs.batch",
+    "cronjobs.batch",
+    "services",
+    "endpointslices.discovery.k8s.io",
+    "persistentvolumeclaims",
+];
+
+/// Edges from objects to their owners in `metadata.ownerReferences`, a row per reference,
+/// to walk owner chains with recursive queries.
+///
+/// The objects are those of the common resources created by controllers,
+/// in the namespace of tables without a namespace.
+#[derive(Debug)]
+pub struct OwnersTable {
+    factory: Arc<KubernetesTableProviderFactory>,
+    schema: SchemaRef,
+}
+
+impl OwnersTable {
+    pub fn new(factory: Arc<KubernetesTableProviderFactory>) -> Self {
+        Self {
+            factory,
+            schema: Arc::new(Schema::new(vec![
+                Field::new("child_kind", DataType::Utf8, false),
+                Field::new("child_ns", DataType::Utf8, true),
+                Field::new("child_name", DataType::Utf8, false),
+                Field::new("owner_kind", DataType::Utf8, false),
+                Field::new("owner_name", DataType::Utf8, false),
+                Field::new("controller", DataType::Boolean, false),
+                Field::new("child_uid", DataType::Utf8, true),
+                Field::new("owner_uid", DataType::Utf8, false),
+            ])),
+        }
+    }
+
+    /// Owner references of the objects of the resources
+    async fn edges(&self) -> DataFusionResult<Vec<Edge>> {
+        let mut edges = Vec::new();
+        for resource in OWNED_RESOURCES {
+            let Some((_, kubeurl, cluster)) =
+                self.factory.resolve_all(resource).await?.into_iter().next()
+            else {
+                continue;
+            };
+            let namespaces = cluster
+                .namespaces(&kubeurl.resource, &kubeurl.namespace)
+                .await
+                .map_err(|e| DataFusionError::External(e.into()))?;
+            for namespace in namespaces {
+                let objects = cluster
+                    .lister
+                    .list(
+                        &kubeurl.resource,
+                        namespace.as_deref(),
+                        &ListParams::default(),
+                        true,
+                    )
+                    .await
+                    .map_err(|e| DataFusionError::External(Box::new(e)))?;
+                for object in objects.items {
+                    let metadata = object.metadata;
+                    for owner in metadata.owner_references.into_iter().flatten() {
+                        edges.push(Edge {
+                            child_kind: kubeurl.resource.kind.clone(),
+                            child_ns: metadata.namespace.clone(),
+                            child_name: metadata.name.clone().unwrap_or_default(),
+                            owner_kind: owner.kind,
+                            owner_name: owner.name,
+                            controller: owner.controller.unwrap_or(false),
+                            child_uid: metadata.uid.clone(),
+                            owner_uid: owner.uid,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(edges)
+    }
+}
+
+/// Reference from an object to an owner
+struct Edge {
+    child_kind: String,
+    child_ns: Option<String>,
+    child_name: String,
+    owner_kind: String,
+    owner_name: String,
+    controller: bool,
+    child_uid: Option<String>,
+    owner_uid: String,
+}
+
+#[async_trait]
+impl TableProvider for OwnersTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    async fn scan(
+        &self,
+        state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        self.factory.deny_dry_run(OWNERS_TABLE)?;
+        let edges = self.edges().await?;
+        let strings = |f: fn(&Edge) -> Option<&str>| -> ArrayRef {
+            Arc::new(edges.iter().map(f).collect::<StringArray>())
+        };
+        let columns = vec![
+            strings(|edge| Some(&edge.child_kind)),
+            strings(|edge| edge.child_ns.as_deref()),
+            strings(|edge| Some(&edge.child_name)),
+            strings(|edge| Some(&edge.owner_kind)),
+            strings(|edge| Some(&edge.owner_name)),
+            Arc::new(
+                edges
+                    .iter()
+                    .map(|edge| Some(edge.controller))
+                    .collect::<BooleanArray>(),
+            ),
+            strings(|edge| edge.child_uid.as_deref()),
+            strings(|edge| Some(&edge.owner_uid)),
+        ];
+        let batch = RecordBatch::try_new(self.schema.clone(), columns)?;
+        MemTable::try_new(self.schema.clone(), vec![vec![batch]])?
+            .scan(state, projection, filters, limit)
+            .await
+    }
+}
@@ -0,0 +1,142 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{HashMap, HashSet};
+
+use datafusion::{
+    arrow::datatypes::DataType,
+    common::{ParamValues, ScalarValue, tree_node::TreeNodeRecursion},
+    dataframe::DataFrame,
+    logical_expr::{Expr, LogicalPlan},
+};
+
+/// Name and value of a parameter
+pub type Param = (String, String);
+
+/// Parses a parameter given as `name=value`.
+pub fn parse_param(param: &str) -> Result<Param, String> {
+    let (name, value) = param
+        .split_once('=')
+        .ok_or_else(|| format!("expected name=value, got '{}'", param))?;
+    let name = name.trim_start_matches(['$', ':']);
+    if name.is_empty() {
+        return Err(format!("missing parameter name in '{}'", param));
+    }
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// Binds the values of the parameters to the placeholders (`$name` or `:name`) of the query.
+///
+/// The values are bound as literals rather than interpolated into the SQL text,
+/// cast to the type each placeholder is used as (e.g. `LIMIT $n` takes an integer).
+pub fn bind(df: DataFrame, params: &[Param]) -> anyhow::Result<DataFrame> {
+    if params.is_empty() {
+        return Ok(df);
+    }
+    let types = df.logical_plan().get_parameter_types()?;
+    let limits = limit_placeholders(df.logical_plan())?;
+    let mut values = HashMap::new();
+    for (name, value) in params {
+        let mut value = ScalarValue::from(value.as_str());
+        let ids = [format!("${}", name), format!(":{}", name)];
+        let data_type = ids
+            .iter()
+            .find_map(|id| types.get(id).cloned().flatten())
+            .or_else(|| {
+                ids.iter()
+                    .any(|id| limits.contains(id))
+                    .then_some(DataType::Int64)
+            });
+        if let Some(data_type) = &data_type {
+            value = value
+                .cast_to(data_type)
+                .map_err(|e| anyhow::anyhow!("invalid value of parameter '{}': {}", name, e))?;
+        }
+        values.insert(name.clone(), value);
+    }
+    Ok(df.with_param_values(ParamValues::Map(values))?)
+}
+
+/// Placeholders of `LIMIT` and `OFFSET`, which DataFusion leaves untyped
+fn limit_placeholders(plan: &LogicalPlan) -> anyhow::Result<HashSet<String>> {
+    let mut ids = HashSet::new();
+    plan.apply_with_subqueries(|plan| {
+        if let LogicalPlan::Limit(limit) = plan {
+            for expr in [&limit.skip, &limit.fetch].into_iter().flatten() {
+                if let Expr::Placeholder(placeholder) = expr.as_ref() {
+                    ids.insert(placeholder.id.clone());
+                }
+            }
+        }
+        Ok(TreeNodeRecursion::Continue)
+    })?;
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::{arrow::util::pretty::pretty_format_batches, prelude::SessionContext};
+
+    use super::*;
+
+    async fn run(sql: &str, params: &[(&str, &str)]) -> anyhow::Result<String> {
+        let ctx = SessionContext::new();
+        let params: Vec<Param> = params
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        let df = bind(ctx.sql(sql).await?, &params)?;
+        Ok(pretty_format_batches(&df.collect().await?)?.to_string())
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            parse_param("$ns=web"),
+            Ok(("ns".to_string(), "web".to_string()))
+        );
+        assert_eq!(
+            parse_param(":q=a=b"),
+            Ok(("q".to_string(), "a=b".to_string()))
+        );
+        assert!(parse_param("ns").is_err());
+        assert!(parse_param("$=web").is_err());
+    }
+
+    #[tokio::test]
+    async fn bind_by_type() {
+        let sql = "SELECT column1 AS n FROM (VALUES (1), (2), (3)) WHERE column1 > $min";
+        assert!(run(sql, &[("min", "1")]).await.unwrap().contains("| 3 |"));
+
+        let limited = run(
+            "SELECT column1 AS n FROM (VALUES (1), (2), (3)) LIMIT $n OFFSET :skip",
+            &[("n", "1"), ("skip", "1")],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            limited
+                .lines()
+                .filter(|line| line.starts_with("| "))
+                .count(),
+            2
+        );
+        assert!(limited.contains("| 2 |"));
+
+        let e = run("SELECT 1 LIMIT $n", &[("n", "many")])
+            .await
+            .unwrap_err();
+        assert!(e.to_string().contains("parameter 'n'"));
+    }
+}
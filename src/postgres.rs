@@ -0,0 +1,975 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::HashMap,
+    hash::{BuildHasher, RandomState},
+    net::SocketAddr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicI32, Ordering},
+    },
+};
+
+use chrono::DateTime;
+use datafusion::{
+    arrow::{
+        array::{Array, ArrayRef, AsArray},
+        compute::cast,
+        datatypes::{DataType, Float64Type, Int64Type, Schema, TimeUnit, TimestampMicrosecondType},
+        record_batch::RecordBatch,
+    },
+    dataframe::DataFrame,
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufStream},
+    net::{TcpListener, TcpStream},
+    sync::Notify,
+};
+
+use crate::{
+    output,
+    params::{self, Param},
+    server::{Connect, Session, Sessions, read_only},
+    split_statements,
+    views::Views,
+};
+
+const PROTOCOL_VERSION: i32 = 196608;
+const SSL_REQUEST: i32 = 80877103;
+const GSSENC_REQUEST: i32 = 80877104;
+const CANCEL_REQUEST: i32 = 80877102;
+
+/// Largest message accepted from clients after the startup message
+const MAX_MESSAGE_LEN: i32 = 16 << 20;
+
+/// Names of the database meaning the context of the server
+const DEFAULT_DATABASES: [&str; 2] = ["kuqu", "default"];
+
+/// Microseconds from the Unix epoch to the PostgreSQL epoch, 2000-01-01
+const POSTGRES_EPOCH_MICROS: i64 = 946_684_800_000_000;
+
+/// Type of a column as sent to clients
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PgType {
+    Bool,
+    Int8,
+    Float8,
+    Text,
+    Json,
+    Timestamptz,
+}
+
+impl PgType {
+    fn of(data_type: &DataType) -> Self {
+        match data_type {
+            DataType::Boolean => Self::Bool,
+            DataType::Int8
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64
+            | DataType::UInt8
+            | DataType::UInt16
+            | DataType::UInt32 => Self::Int8,
+            DataType::Float16 | DataType::Float32 | DataType::Float64 => Self::Float8,
+            DataType::Timestamp(_, _) => Self::Timestamptz,
+            DataType::Struct(_)
+            | DataType::List(_)
+            | DataType::LargeList(_)
+            | DataType::FixedSizeList(_, _)
+            | DataType::Map(_, _) => Self::Json,
+            _ => Self::Text,
+        }
+    }
+
+    fn oid(self) -> i32 {
+        match self {
+            Self::Bool => 16,
+            Self::Int8 => 20,
+            Self::Float8 => 701,
+            Self::Text => 25,
+            Self::Json => 114,
+            Self::Timestamptz => 1184,
+        }
+    }
+
+    fn size(self) -> i16 {
+        match self {
+            Self::Bool => 1,
+            Self::Int8 | Self::Float8 | Self::Timestamptz => 8,
+            Self::Text | Self::Json => -1,
+        }
+    }
+}
+
+/// Serves read-only queries over the PostgreSQL wire protocol (v3), both the simple and
+/// the extended query protocols, without TLS or authentication.
+///
+/// The database connected to names the context to query,
+/// except `kuqu` and `default` for the context of the server.
+///
+/// Cancel requests stop the running query of the connection matching their key data.
+pub struct PostgresServer {
+    sessions: Sessions,
+    views: Views,
+    /// Last process ID given to a connection in its key data
+    process_id: AtomicI32,
+    secrets: RandomState,
+    /// Notified to cancel the query running on the connection of the key data
+    cancels: Mutex<HashMap<(i32, i32), Arc<Notify>>>,
+}
+
+impl PostgresServer {
+    pub fn new(connect: Connect, views: Views) -> Self {
+        Self {
+            sessions: Sessions::new(connect),
+            views,
+            process_id: AtomicI32::new(0),
+            secrets: RandomState::new(),
+            cancels: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Serve on the address until interrupted
+    pub async fn serve(self, addr: SocketAddr) -> anyhow::Result<()> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to listen on {}: {}", addr, e))?;
+        let server = Arc::new(self);
+        loop {
+            let (stream, remote) = listener.accept().await?;
+            let server = Arc::clone(&server);
+            tokio::spawn(async move {
+                if let Err(e) = server.handle(stream).await {
+                    eprintln!("Warning: connection from {} failed: {}", remote, e);
+                }
+            });
+        }
+    }
+
+    async fn handle(&self, stream: TcpStream) -> anyhow::Result<()> {
+        let mut stream = BufStream::new(stream);
+        let startup = match startup(&mut stream).await? {
+            Startup::Params(params) => params,
+            Startup::Cancel(key) => {
+                if let Some(cancel) = self.cancels.lock().unwrap().get(&key) {
+                    cancel.notify_waiters();
+                }
+                return Ok(());
+            }
+        };
+        let context = startup
+            .get("database")
+            .filter(|database| !DEFAULT_DATABASES.contains(&database.as_str()))
+            .cloned();
+        let session = match self.sessions.get(context, None).await {
+            Ok(session) => session,
+            Err(e) => {
+                stream
+                    .write_all(&error_response("FATAL", "08006", &e.to_string()))
+                    .await?;
+                stream.flush().await?;
+                return Ok(());
+            }
+        };
+
+        let mut out = message(b'R', &0i32.to_be_bytes());
+        for (name, value) in [
+            ("server_version", "14.0 (kuqu)"),
+            ("server_encoding", "UTF8"),
+            ("client_encoding", "UTF8"),
+            ("DateStyle", "ISO, MDY"),
+            ("TimeZone", "UTC"),
+            ("integer_datetimes", "on"),
+            ("standard_conforming_strings", "on"),
+        ] {
+            let mut body = cstring(name);
+            body.extend(cstring(value));
+            out.extend(message(b'S', &body));
+        }
+        let process_id = self
+            .process_id
+            .fetch_add(1, Ordering::Relaxed)
+            .wrapping_add(1);
+        let secret = self.secrets.hash_one(process_id) as i32;
+        let cancel = Arc::new(Notify::new());
+        self.cancels
+            .lock()
+            .unwrap()
+            .insert((process_id, secret), Arc::clone(&cancel));
+        let mut key_data = process_id.to_be_bytes().to_vec();
+        key_data.extend(secret.to_be_bytes());
+        out.extend(message(b'K', &key_data));
+        out.extend(ready_for_query());
+
+        let result = async {
+            stream.write_all(&out).await?;
+            stream.flush().await?;
+            Connection {
+                stream,
+                session,
+                views: &self.views,
+                cancel,
+                statements: HashMap::new(),
+                portals: HashMap::new(),
+            }
+            .run()
+            .await
+        }
+        .await;
+        self.cancels.lock().unwrap().remove(&(process_id, secret));
+        result
+    }
+}
+
+/// First message of a connection
+#[derive(Debug, PartialEq)]
+enum Startup {
+    /// Parameters of the startup message
+    Params(HashMap<String, String>),
+    /// Key data of the connection to cancel the query of
+    Cancel((i32, i32)),
+}
+
+/// Read the startup message, declining TLS and GSSAPI encryption.
+async fn startup<S: AsyncRead + AsyncWrite + Unpin>(stream: &mut S) -> anyhow::Result<Startup> {
+    loop {
+        let len = stream.read_i32().await?;
+        if !(8..=10_000).contains(&len) {
+            anyhow::bail!("invalid startup message length {}", len);
+        }
+        let code = stream.read_i32().await?;
+        let mut body = vec![0; len as usize - 8];
+        stream.read_exact(&mut body).await?;
+        match code {
+            SSL_REQUEST | GSSENC_REQUEST => {
+                stream.write_all(b"N").await?;
+                stream.flush().await?;
+            }
+            CANCEL_REQUEST => {
+                let mut reader = Reader::new(&body);
+                return Ok(Startup::Cancel((reader.i32()?, reader.i32()?)));
+            }
+            PROTOCOL_VERSION => {
+                let mut reader = Reader::new(&body);
+                let mut params = HashMap::new();
+                loop {
+                    let name = reader.cstring()?;
+                    if name.is_empty() {
+                        break;
+                    }
+                    params.insert(name, reader.cstring()?);
+                }
+                return Ok(Startup::Params(params));
+            }
+            code => anyhow::bail!("unsupported protocol version {}", code),
+        }
+    }
+}
+
+/// Read a message of its tag and body, none at the end of the stream.
+async fn read_message<S: AsyncRead + Unpin>(
+    stream: &mut S,
+) -> anyhow::Result<Option<(u8, Vec<u8>)>> {
+    let tag = match stream.read_u8().await {
+        Ok(tag) => tag,
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let len = stream.read_i32().await?;
+    if !(4..=MAX_MESSAGE_LEN).contains(&len) {
+        anyhow::bail!("invalid message length {}", len);
+    }
+    let mut body = vec![0; len as usize - 4];
+    stream.read_exact(&mut body).await?;
+    Ok(Some((tag, body)))
+}
+
+/// Statement parsed with the extended query protocol
+struct Prepared {
+    query: String,
+}
+
+/// Statement bound to parameters with the extended query protocol
+struct Portal {
+    /// Plan of the query, none for statements without results (e.g. `SET`)
+    df: Option<DataFrame>,
+    /// Tag of the statements without results
+    tag: String,
+    /// Formats of the result columns requested, text (0) or binary (1)
+    formats: Vec<i16>,
+}
+
+struct Connection<'a> {
+    stream: BufStream<TcpStream>,
+    session: Session,
+    views: &'a Views,
+    /// Notified by cancel requests of the connection
+    cancel: Arc<Notify>,
+    statements: HashMap<String, Prepared>,
+    portals: HashMap<String, Portal>,
+}
+
+impl Connection<'_> {
+    async fn run(mut self) -> anyhow::Result<()> {
+        // After an error of the extended protocol, messages are discarded until Sync.
+        let mut failed = false;
+        loop {
+            let Some((tag, body)) = read_message(&mut self.stream).await? else {
+                return Ok(());
+            };
+
+            let out = match tag {
+                b'X' => return Ok(()),
+                b'Q' => self.simple_query(&body).await,
+                b'S' => {
+                    failed = false;
+                    ready_for_query()
+                }
+                b'H' => Vec::new(),
+                _ if failed => continue,
+                tag => match self.extended(tag, &body).await {
+                    Ok(out) => out,
+                    Err(e) => {
+                        failed = true;
+                        error(&e)
+                    }
+                },
+            };
+            self.stream.write_all(&out).await?;
+            if matches!(tag, b'Q' | b'S' | b'H') || failed {
+                self.stream.flush().await?;
+            }
+        }
+    }
+
+    /// Run the statements of a simple query, stopping at the first error
+    async fn simple_query(&mut self, body: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        if let Err(e) = self.simple_query_into(body, &mut out).await {
+            out.extend(error(&e));
+        }
+        out.extend(ready_for_query());
+        out
+    }
+
+    async fn simple_query_into(&mut self, body: &[u8], out: &mut Vec<u8>) -> anyhow::Result<()> {
+        let query = Reader::new(body).cstring()?;
+        let statements = split_statements(&self.session.ctx, &query)?;
+        if statements.is_empty() {
+            out.extend(message(b'I', &[]));
+            return Ok(());
+        }
+        for statement in statements {
+            if let Some(tag) = no_op_tag(&statement) {
+                out.extend(command_complete(&tag));
+                continue;
+            }
+            let df = self.plan(&statement, &[]).await?;
+            let schema = df.schema().inner().clone();
+            out.extend(row_description(&schema, &[]));
+            let batches = self.collect(df).await?;
+            out.extend(data_rows(&batches, &[])?);
+            out.extend(command_complete(&select_tag(&batches)));
+        }
+        Ok(())
+    }
+
+    /// Handle a message of the extended query protocol
+    async fn extended(&mut self, tag: u8, body: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let mut reader = Reader::new(body);
+        match tag {
+            b'P' => {
+                let name = reader.cstring()?;
+                let query = reader.cstring()?;
+                self.statements.insert(name, Prepared { query });
+                Ok(message(b'1', &[]))
+            }
+            b'B' => {
+                let portal = reader.cstring()?;
+                let statement = reader.cstring()?;
+                let param_formats = reader.formats()?;
+                let count = reader.i16()?;
+                let mut params: Vec<Param> = Vec::new();
+                for i in 0..count as usize {
+                    let format = match param_formats.as_slice() {
+                        [] => 0,
+                        [format] => *format,
+                        formats => formats.get(i).copied().unwrap_or(0),
+                    };
+                    if format != 0 {
+                        anyhow::bail!("binary parameters are not supported");
+                    }
+                    let value = reader
+                        .bytes()?
+                        .ok_or_else(|| anyhow::anyhow!("NULL parameters are not supported"))?;
+                    params.push(((i + 1).to_string(), String::from_utf8(value)?));
+                }
+                let formats = reader.formats()?;
+                let query = self
+                    .statements
+                    .get(&statement)
+                    .ok_or_else(|| anyhow::anyhow!("prepared statement '{}' not found", statement))?
+                    .query
+                    .clone();
+                let portal_value = match no_op_tag(&query) {
+                    Some(tag) => Portal {
+                        df: None,
+                        tag,
+                        formats,
+                    },
+                    None => Portal {
+                        df: Some(self.plan(&query, &params).await?),
+                        tag: String::new(),
+                        formats,
+                    },
+                };
+                self.portals.insert(portal, portal_value);
+                Ok(message(b'2', &[]))
+            }
+            b'D' => {
+                let kind = reader.u8()?;
+                let name = reader.cstring()?;
+                if kind == b'S' {
+                    let query = self
+                        .statements
+                        .get(&name)
+                        .ok_or_else(|| anyhow::anyhow!("prepared statement '{}' not found", name))?
+                        .query
+                        .clone();
+                    if no_op_tag(&query).is_some() {
+                        let mut out = parameter_description(&[]);
+                        out.extend(message(b'n', &[]));
+                        return Ok(out);
+                    }
+                    let df = self.plan(&query, &[]).await?;
+                    let types = df.logical_plan().get_parameter_types()?;
+                    let mut params: Vec<(usize, Option<DataType>)> = types
+                        .into_iter()
+                        .filter_map(|(name, data_type)| {
+                            Some((name.trim_start_matches('$').parse().ok()?, data_type))
+                        })
+                        .collect();
+                    params.sort_by_key(|(index, _)| *index);
+                    let types: Vec<PgType> = params
+                        .iter()
+                        .map(|(_, data_type)| match data_type {
+                            Some(data_type) => PgType::of(data_type),
+                            None => PgType::Text,
+                        })
+                        .collect();
+                    let mut out = parameter_description(&types);
+                    out.extend(row_description(df.schema().inner(), &[]));
+                    Ok(out)
+                } else {
+                    let portal = self
+                        .portals
+                        .get(&name)
+                        .ok_or_else(|| anyhow::anyhow!("portal '{}' not found", name))?;
+                    Ok(match &portal.df {
+                        Some(df) => row_description(df.schema().inner(), &portal.formats),
+                        None => message(b'n', &[]),
+                    })
+                }
+            }
+            b'E' => {
+                let name = reader.cstring()?;
+                let portal = self
+                    .portals
+                    .remove(&name)
+                    .ok_or_else(|| anyhow::anyhow!("portal '{}' not found", name))?;
+                let Some(df) = portal.df else {
+                    return Ok(command_complete(&portal.tag));
+                };
+                let batches = self.collect(df).await?;
+                let mut out = data_rows(&batches, &portal.formats)?;
+                out.extend(command_complete(&select_tag(&batches)));
+                Ok(out)
+            }
+            b'C' => {
+                let kind = reader.u8()?;
+                let name = reader.cstring()?;
+                if kind == b'S' {
+                    self.statements.remove(&name);
+                } else {
+                    self.portals.remove(&name);
+                }
+                Ok(message(b'3', &[]))
+            }
+            tag => anyhow::bail!("unsupported message '{}'", tag as char),
+        }
+    }
+
+    /// Plan the query, with the values of the placeholders (`$1`, `$2`, ...) if any
+    /// Planning lists resources to infer schemas, hence stops on cancel requests.
+    async fn plan(&self, query: &str, params: &[Param]) -> anyhow::Result<DataFrame> {
+        self.cancelable(async {
+            self.views.register(&self.session.ctx, query).await?;
+            let df = self
+                .session
+                .ctx
+                .sql_with_options(query, read_only())
+                .await?;
+            params::bind(df, params)
+        })
+        .await
+    }
+
+    async fn collect(&self, df: DataFrame) -> anyhow::Result<Vec<RecordBatch>> {
+        let result = self.cancelable(async { Ok(df.collect().await?) }).await;
+        self.session.warnings.print();
+        result
+    }
+
+    /// Run until completing or a cancel request of the connection arrives
+    async fn cancelable<T>(
+        &self,
+        future: impl Future<Output = anyhow::Result<T>>,
+    ) -> anyhow::Result<T> {
+        tokio::select! {
+            result = future => result,
+            _ = self.cancel.notified() => Err(Canceled.into()),
+        }
+    }
+}
+
+/// Tag of the statements answered without running them, which clients send on connecting
+/// (e.g. `SET application_name`) and have no effect on read-only queries
+fn no_op_tag(statement: &str) -> Option<String> {
+    let keyword = statement
+        .split_whitespace()
+        .next()?
+        .trim_end_matches(';')
+        .to_uppercase();
+    match keyword.as_str() {
+        "SET" | "RESET" | "BEGIN" | "START" | "COMMIT" | "END" | "ROLLBACK" | "DISCARD"
+        | "DEALLOCATE" => Some(keyword),
+        _ => None,
+    }
+}
+
+fn select_tag(batches: &[RecordBatch]) -> String {
+    let rows: usize = batches.iter().map(|batch| batch.num_rows()).sum();
+    format!("SELECT {}", rows)
+}
+
+/// Reader of the fields of a message
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    fn take(&mut self, n: usize) -> anyhow::Result<&'a [u8]> {
+        if self.buf.len() < n {
+            anyhow::bail!("truncated message");
+        }
+        let (head, tail) = self.buf.split_at(n);
+        self.buf = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn i16(&mut self) -> anyhow::Result<i16> {
+        Ok(i16::from_be_bytes(self.take(2)?.try_into()?))
+    }
+
+    fn i32(&mut self) -> anyhow::Result<i32> {
+        Ok(i32::from_be_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn cstring(&mut self) -> anyhow::Result<String> {
+        let end = self
+            .buf
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or_else(|| anyhow::anyhow!("unterminated string"))?;
+        let s = String::from_utf8(self.take(end)?.to_vec())?;
+        self.take(1)?;
+        Ok(s)
+    }
+
+    /// Value prefixed with its length, none for NULL (-1)
+    fn bytes(&mut self) -> anyhow::Result<Option<Vec<u8>>> {
+        let len = self.i32()?;
+        if len < 0 {
+            return Ok(None);
+        }
+        Ok(Some(self.take(len as usize)?.to_vec()))
+    }
+
+    /// Format codes prefixed with their count
+    fn formats(&mut self) -> anyhow::Result<Vec<i16>> {
+        let count = self.i16()?;
+        (0..count).map(|_| self.i16()).collect()
+    }
+}
+
+fn message(tag: u8, body: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(body.len() + 5);
+    message.push(tag);
+    message.extend((body.len() as i32 + 4).to_be_bytes());
+    message.extend(body);
+    message
+}
+
+fn cstring(s: &str) -> Vec<u8> {
+    let mut bytes = s.as_bytes().to_vec();
+    bytes.push(0);
+    bytes
+}
+
+fn ready_for_query() -> Vec<u8> {
+    message(b'Z', b"I")
+}
+
+fn command_complete(tag: &str) -> Vec<u8> {
+    message(b'C', &cstring(tag))
+}
+
+/// Error of a query stopped by a cancel request
+#[derive(Debug)]
+struct Canceled;
+
+impl std::fmt::Display for Canceled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "canceling statement due to user request")
+    }
+}
+
+impl std::error::Error for Canceled {}
+
+/// Error response of a failed query
+fn error(e: &anyhow::Error) -> Vec<u8> {
+    let code = if e.is::<Canceled>() { "57014" } else { "XX000" };
+    error_response("ERROR", code, &e.to_string())
+}
+
+fn error_response(severity: &str, code: &str, text: &str) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (field, value) in [
+        (b'S', severity),
+        (b'V', severity),
+        (b'C', code),
+        (b'M', text),
+    ] {
+        body.push(field);
+        body.extend(cstring(value));
+    }
+    body.push(0);
+    message(b'E', &body)
+}
+
+fn parameter_description(types: &[PgType]) -> Vec<u8> {
+    let mut body = (types.len() as i16).to_be_bytes().to_vec();
+    for pg_type in types {
+        body.extend(pg_type.oid().to_be_bytes());
+    }
+    message(b't', &body)
+}
+
+/// Format of the column requested: text (0) unless binary (1) is requested for it or for all
+fn format(formats: &[i16], column: usize) -> i16 {
+    match formats {
+        [] => 0,
+        [format] => *format,
+        formats => formats.get(column).copied().unwrap_or(0),
+    }
+}
+
+fn row_description(schema: &Schema, formats: &[i16]) -> Vec<u8> {
+    let mut body = (schema.fields().len() as i16).to_be_bytes().to_vec();
+    for (i, field) in schema.fields().iter().enumerate() {
+        let pg_type = PgType::of(field.data_type());
+        body.extend(cstring(field.name()));
+        body.extend(0i32.to_be_bytes());
+        body.extend(0i16.to_be_bytes());
+        body.extend(pg_type.oid().to_be_bytes());
+        body.extend(pg_type.size().to_be_bytes());
+        body.extend((-1i32).to_be_bytes());
+        body.extend(format(formats, i).to_be_bytes());
+    }
+    message(b'T', &body)
+}
+
+fn data_rows(batches: &[RecordBatch], formats: &[i16]) -> anyhow::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    for batch in batches {
+        let columns = batch
+            .columns()
+            .iter()
+            .enumerate()
+            .map(|(i, column)| encode_column(column, format(formats, i) == 1))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        for row in 0..batch.num_rows() {
+            let mut body = (columns.len() as i16).to_be_bytes().to_vec();
+            for column in &columns {
+                match &column[row] {
+                    Some(value) => {
+                        body.extend((value.len() as i32).to_be_bytes());
+                        body.extend(value);
+                    }
+                    None => body.extend((-1i32).to_be_bytes()),
+                }
+            }
+            out.extend(message(b'D', &body));
+        }
+    }
+    Ok(out)
+}
+
+/// Values of the column in the text or the binary format of its type, none for NULL
+fn encode_column(column: &ArrayRef, binary: bool) -> anyhow::Result<Vec<Option<Vec<u8>>>> {
+    let values = match PgType::of(column.data_type()) {
+        PgType::Bool => {
+            let values = column.as_boolean();
+            (0..column.len())
+                .map(|i| {
+                    column.is_valid(i).then(|| match (binary, values.value(i)) {
+                        (true, value) => vec![value as u8],
+                        (false, true) => b"t".to_vec(),
+                        (false, false) => b"f".to_vec(),
+                    })
+                })
+                .collect()
+        }
+        PgType::Int8 => {
+            let values = cast(column, &DataType::Int64)?;
+            let values = values.as_primitive::<Int64Type>();
+            (0..column.len())
+                .map(|i| {
+                    column.is_valid(i).then(|| match binary {
+                        true => values.value(i).to_be_bytes().to_vec(),
+                        false => values.value(i).to_string().into_bytes(),
+                    })
+                })
+                .collect()
+        }
+        PgType::Float8 => {
+            let values = cast(column, &DataType::Float64)?;
+            let values = values.as_primitive::<Float64Type>();
+            (0..column.len())
+                .map(|i| {
+                    column.is_valid(i).then(|| {
+                        let value = values.value(i);
+                        match binary {
+                            true => value.to_be_bytes().to_vec(),
+                            false if value.is_nan() => b"NaN".to_vec(),
+                            false if value.is_infinite() && value > 0.0 => b"Infinity".to_vec(),
+                            false if value.is_infinite() => b"-Infinity".to_vec(),
+                            false => value.to_string().into_bytes(),
+                        }
+                    })
+                })
+                .collect()
+        }
+        PgType::Timestamptz => {
+            let values = cast(column, &DataType::Timestamp(TimeUnit::Microsecond, None))?;
+            let values = values.as_primitive::<TimestampMicrosecondType>();
+            (0..column.len())
+                .map(|i| {
+                    column.is_valid(i).then(|| {
+                        let micros = values.value(i);
+                        match binary {
+                            true => (micros - POSTGRES_EPOCH_MICROS).to_be_bytes().to_vec(),
+                            false => DateTime::from_timestamp_micros(micros)
+                                .map(|time| time.format("%Y-%m-%d %H:%M:%S%.f+00").to_string())
+                                .unwrap_or_default()
+                                .into_bytes(),
+                        }
+                    })
+                })
+                .collect()
+        }
+        // JSON is the same in the text and the binary format.
+        PgType::Json => output::json_values(column)?
+            .into_iter()
+            .map(|value| value.map(String::into_bytes))
+            .collect(),
+        // The binary format of text is its bytes.
+        PgType::Text => {
+            let values = cast(column, &DataType::Utf8)?;
+            let values = values.as_string::<i32>();
+            (0..column.len())
+                .map(|i| {
+                    column
+                        .is_valid(i)
+                        .then(|| values.value(i).as_bytes().to_vec())
+                })
+                .collect()
+        }
+    };
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::{
+        array::{BooleanArray, Int64Array, StringArray},
+        datatypes::Field,
+    };
+    use tokio::io::duplex;
+
+    use super::*;
+
+    /// Startup packet of the code and body, as clients send before any tagged message
+    fn packet(code: i32, body: &[u8]) -> Vec<u8> {
+        let mut packet = (body.len() as i32 + 8).to_be_bytes().to_vec();
+        packet.extend(code.to_be_bytes());
+        packet.extend(body);
+        packet
+    }
+
+    #[tokio::test]
+    async fn startup_after_ssl_request() {
+        let (mut client, mut server) = duplex(1024);
+        let mut params = cstring("user");
+        params.extend(cstring("alice"));
+        params.extend(cstring("database"));
+        params.extend(cstring("staging"));
+        params.push(0);
+        client.write_all(&packet(SSL_REQUEST, &[])).await.unwrap();
+        client
+            .write_all(&packet(PROTOCOL_VERSION, &params))
+            .await
+            .unwrap();
+
+        let Startup::Params(params) = startup(&mut server).await.unwrap() else {
+            panic!("expected startup parameters");
+        };
+        assert_eq!(params["user"], "alice");
+        assert_eq!(params["database"], "staging");
+        // SSL is declined with `N`.
+        assert_eq!(client.read_u8().await.unwrap(), b'N');
+    }
+
+    #[tokio::test]
+    async fn startup_cancel_request() {
+        let (mut client, mut server) = duplex(1024);
+        let mut key_data = 7i32.to_be_bytes().to_vec();
+        key_data.extend((-42i32).to_be_bytes());
+        client
+            .write_all(&packet(CANCEL_REQUEST, &key_data))
+            .await
+            .unwrap();
+        assert_eq!(
+            startup(&mut server).await.unwrap(),
+            Startup::Cancel((7, -42))
+        );
+    }
+
+    #[tokio::test]
+    async fn startup_errors() {
+        let (mut client, mut server) = duplex(1024);
+        client.write_all(&packet(1234, &[])).await.unwrap();
+        assert!(startup(&mut server).await.is_err());
+
+        let (mut client, mut server) = duplex(1024);
+        client.write_all(&4i32.to_be_bytes()).await.unwrap();
+        assert!(startup(&mut server).await.is_err());
+
+        let (mut client, mut server) = duplex(1024);
+        client.write_all(&100_000i32.to_be_bytes()).await.unwrap();
+        assert!(startup(&mut server).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn messages() {
+        let mut stream = message(b'Q', &cstring("SELECT 1"));
+        stream.extend(message(b'S', &[]));
+        let mut stream = stream.as_slice();
+
+        let (tag, body) = read_message(&mut stream).await.unwrap().unwrap();
+        assert_eq!(tag, b'Q');
+        assert_eq!(Reader::new(&body).cstring().unwrap(), "SELECT 1");
+        assert_eq!(
+            read_message(&mut stream).await.unwrap(),
+            Some((b'S', Vec::new()))
+        );
+        assert_eq!(read_message(&mut stream).await.unwrap(), None);
+
+        let mut invalid: &[u8] = &[b'Q', 0, 0, 0, 3];
+        assert!(read_message(&mut invalid).await.is_err());
+        let mut oversized = vec![b'Q'];
+        oversized.extend((MAX_MESSAGE_LEN + 1).to_be_bytes());
+        assert!(read_message(&mut oversized.as_slice()).await.is_err());
+        let mut truncated: &[u8] = &[b'Q', 0, 0, 0, 10, b'S'];
+        assert!(read_message(&mut truncated).await.is_err());
+    }
+
+    #[test]
+    fn reader() {
+        let mut body = cstring("name");
+        body.extend(2i16.to_be_bytes());
+        body.extend(3i32.to_be_bytes());
+        body.extend(b"abc");
+        body.extend((-1i32).to_be_bytes());
+        let mut reader = Reader::new(&body);
+        assert_eq!(reader.cstring().unwrap(), "name");
+        assert_eq!(reader.i16().unwrap(), 2);
+        assert_eq!(reader.bytes().unwrap(), Some(b"abc".to_vec()));
+        assert_eq!(reader.bytes().unwrap(), None);
+        assert!(reader.u8().is_err());
+        assert!(Reader::new(b"unterminated").cstring().is_err());
+    }
+
+    #[test]
+    fn rows() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("name", DataType::Utf8, true),
+            Field::new("count", DataType::Int64, true),
+            Field::new("ready", DataType::Boolean, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec![Some("web"), None])),
+                Arc::new(Int64Array::from(vec![3, 42])),
+                Arc::new(BooleanArray::from(vec![true, false])),
+            ],
+        )
+        .unwrap();
+
+        let description = row_description(&schema, &[]);
+        assert_eq!(description[0], b'T');
+        let mut reader = Reader::new(&description[5..]);
+        assert_eq!(reader.i16().unwrap(), 3);
+        assert_eq!(reader.cstring().unwrap(), "name");
+
+        let text = data_rows(std::slice::from_ref(&batch), &[]).unwrap();
+        let mut expected = 3i16.to_be_bytes().to_vec();
+        for value in [&b"web"[..], b"3", b"t"] {
+            expected.extend((value.len() as i32).to_be_bytes());
+            expected.extend(value);
+        }
+        let mut second = 3i16.to_be_bytes().to_vec();
+        second.extend((-1i32).to_be_bytes());
+        for value in [&b"42"[..], b"f"] {
+            second.extend((value.len() as i32).to_be_bytes());
+            second.extend(value);
+        }
+        expected = [message(b'D', &expected), message(b'D', &second)].concat();
+        assert_eq!(text, expected);
+
+        // Binary integers are big-endian.
+        let binary = encode_column(batch.column(1), true).unwrap();
+        assert_eq!(binary[1], Some(42i64.to_be_bytes().to_vec()));
+    }
+}
@@ -12,47 +12,93 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{any::Any, fmt::Debug, io::Cursor, sync::Arc};
+use std::{any::Any, collections::HashMap, fmt::Debug, future::Future, sync::Arc, time::Duration};
 
 use async_trait::async_trait;
 use datafusion::{
     arrow::{
-        compute::concat_batches,
-        datatypes::SchemaRef,
-        json::{ReaderBuilder, reader::infer_json_schema},
-        record_batch::RecordBatch,
+        array::{ArrayRef, StringArray},
+        datatypes::{DataType, Field, Schema, SchemaRef},
+        json::ReaderBuilder,
+        record_batch::{RecordBatch, RecordBatchOptions},
     },
     catalog::{Session, UrlTableFactory},
-    common::{DataFusionError, Result as DataFusionResult},
+    common::{DataFusionError, Result as DataFusionResult, Statistics, stats::Precision},
     datasource::{TableProvider, TableType},
     execution::context::TaskContext,
-    logical_expr::Expr,
+    logical_expr::{Expr, TableProviderFilterPushDown},
     physical_expr::EquivalenceProperties,
     physical_plan::{
         DisplayAs, DisplayFormatType, ExecutionPlan, Partitioning, PlanProperties,
         SendableRecordBatchStream,
         execution_plan::{Boundedness, EmissionType},
-        memory::MemoryStream,
+        metrics::{Count, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet, Time},
+        stream::RecordBatchStreamAdapter,
     },
 };
+use futures::{Stream, StreamExt, TryStreamExt, future, stream};
+use glob::Pattern;
 use k8s_openapi::apimachinery::pkg::apis::meta::v1::APIResource;
-use kube::{Api, Client, api::ObjectList};
+use kube::{
+    Api, Client, Resource,
+    api::{ListParams, ObjectList},
+};
+use tokio::sync::Mutex;
 
-use crate::{dynamic::DynamicObject, url::KubernetesUrl};
+use crate::{
+    cluster::{Cluster, ConnectOptions},
+    dynamic::DynamicObject,
+    helm,
+    lister::ResourceLister,
+    openapi,
+    schema::{
+        conform, infer_schema, merge_schemas, minimal_schema, prune_schema, unsampled_schema,
+    },
+    selector,
+    url::{KubernetesUrl, Namespaces, ParseError, Resolution, resource_name, split_context},
+    warnings::Warnings,
+};
 
-/// Infer schema from NDJSON
-async fn infer_schema(ndjson: &str) -> DataFusionResult<SchemaRef> {
-    // TODO: make it configurable to adjust the number of records used for schema inference
-    infer_json_schema(&mut Cursor::new(ndjson.as_bytes()), None)
-        .map(|(schema, _)| Arc::new(schema))
-        .map_err(|e| DataFusionError::External(Box::new(e)))
-}
+/// Default number of objects listed at planning time to infer the schema from.
+/// The objects to return are listed again at execution time.
+/// `0` means all objects.
+pub const DEFAULT_INFER_ROWS: u32 = 500;
+
+/// Maximum number of rows in each RecordBatch produced by the scan.
+// TODO: make it configurable?
+const BATCH_SIZE: usize = 4096;
+
+/// Number of objects listed per request at execution time, as kubectl pages lists.
+const PAGE_SIZE: u32 = 500;
+
+/// Placeholder of the values of Secrets, unless shown explicitly.
+const REDACTED: &str = "<redacted>";
+
+/// Annotation in which `kubectl apply` keeps the applied object, Secret values included.
+const LAST_APPLIED_CONFIGURATION: &str = "kubectl.kubernetes.io/last-applied-configuration";
+
+/// Column holding the context each row was listed from when fanning out to multiple contexts.
+pub const CLUSTER_COLUMN: &str = "_cluster";
 
 /// Factory for creating Kubernetes table providers
 pub struct KubernetesTableProviderFactory {
-    client: Client,
+    /// Context used for URLs without a context qualifier
     context: String,
-    api_resources: Vec<APIResource>,
+    /// Clusters connected so far, by context
+    clusters: Mutex<HashMap<String, Arc<Cluster>>>,
+    /// Contexts each table without a context qualifier is listed from, if fanning out
+    contexts: Vec<String>,
+    /// Options for connecting to clusters other than the initial one
+    connect_options: ConnectOptions,
+    infer_rows: u32,
+    /// Timeout of each list request, in seconds
+    list_timeout: Option<u32>,
+    /// Warnings about the tables created by the factory
+    warnings: Warnings,
+    /// Do not redact the values of Secrets
+    show_secrets: bool,
+    /// Plan tables without listing their objects
+    dry_run: bool,
 }
 
 impl Debug for KubernetesTableProviderFactory {
@@ -62,84 +108,873 @@ impl Debug for KubernetesTableProviderFactory {
 }
 
 impl KubernetesTableProviderFactory {
-    pub fn new(client: Client, context: String, api_resources: Vec<APIResource>) -> Self {
+    pub fn new(context: String, cluster: Cluster) -> Self {
         Self {
-            client,
+            clusters: Mutex::new(HashMap::from([(context.clone(), Arc::new(cluster))])),
             context,
-            api_resources,
+            contexts: Vec::new(),
+            connect_options: ConnectOptions::default(),
+            infer_rows: DEFAULT_INFER_ROWS,
+            list_timeout: None,
+            warnings: Warnings::default(),
+            show_secrets: false,
+            dry_run: false,
+        }
+    }
+
+    /// Name of the current context
+    pub fn context(&self) -> &str {
+        &self.context
+    }
+
+    /// Set the number of objects used to infer the schema
+    pub fn with_infer_rows(mut self, infer_rows: u32) -> Self {
+        self.infer_rows = infer_rows;
+        self
+    }
+
+    /// Set whether to show the values of Secrets instead of redacting them
+    pub fn with_show_secrets(mut self, show_secrets: bool) -> Self {
+        self.show_secrets = show_secrets;
+        self
+    }
+
+    /// Set whether to plan tables without listing their objects, for `--dry-run`.
+    /// Their schemas are resolved from OpenAPI only, and they fail if executed.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Fail planning the table in a dry run, as it is read when planned
+    pub fn deny_dry_run(&self, table: &str) -> DataFusionResult<()> {
+        if self.dry_run {
+            return Err(DataFusionError::Plan(format!(
+                "{} is read when planned, which a dry run does not",
+                table
+            )));
         }
+        Ok(())
     }
 
-    /// List API resources for a given resource type and namespace
-    async fn list_api_resources(
+    /// Warnings about the tables created so far, to print after the results
+    pub fn warnings(&self) -> &Warnings {
+        &self.warnings
+    }
+
+    /// Set the contexts to list each table from, unless the table has a context qualifier.
+    /// The rows are unioned and tagged with their context in the `_cluster` column.
+    pub fn with_contexts(mut self, contexts: Vec<String>) -> Self {
+        self.contexts = contexts;
+        self
+    }
+
+    /// Set the timeout of each list request, in seconds
+    pub fn with_list_timeout(mut self, list_timeout: Option<u32>) -> Self {
+        self.list_timeout = list_timeout;
+        self
+    }
+
+    /// Add a cluster available as the context, e.g. of objects read from files
+    pub fn with_cluster(mut self, context: String, cluster: Cluster) -> Self {
+        self.clusters.get_mut().insert(context, Arc::new(cluster));
+        self
+    }
+
+    /// Set the options for connecting to clusters
+    pub fn with_connect_options(mut self, connect_options: ConnectOptions) -> Self {
+        self.connect_options = connect_options;
+        self
+    }
+
+    /// Returns the cluster of the context, connecting to it on first use.
+    ///
+    /// The lock is not held while connecting so that multiple contexts can be connected concurrently.
+    async fn cluster(&self, context: &str) -> DataFusionResult<Arc<Cluster>> {
+        if let Some(cluster) = self.clusters.lock().await.get(context) {
+            return Ok(cluster.clone());
+        }
+        let cluster = Arc::new(
+            Cluster::connect(context, &self.connect_options)
+                .await
+                .map_err(|e| {
+                    DataFusionError::Plan(format!(
+                        "Failed to connect to context '{}': {}",
+                        context, e
+                    ))
+                })?,
+        );
+        Ok(self
+            .clusters
+            .lock()
+            .await
+            .entry(context.to_string())
+            .or_insert(cluster)
+            .clone())
+    }
+
+    /// Check if the URL is listed from all the contexts set by `with_contexts`.
+    pub fn fans_out(&self, url: &str) -> bool {
+        !self.contexts.is_empty() && split_context(url).1.is_none()
+    }
+
+    /// Returns the cluster the URL refers to, either by its context qualifier or by default.
+    async fn cluster_for_url(&self, url: &str) -> DataFusionResult<Arc<Cluster>> {
+        let (_, url_context) = split_context(url);
+        self.cluster(
+            url_context
+                .filter(|c| !c.is_empty())
+                .unwrap_or(&self.context),
+        )
+        .await
+    }
+
+    /// Parse the URL against the resources of every cluster it is listed from,
+    /// returning the context of each, or nothing if no cluster has the resource
+    /// (e.g. the name of a table to be created by CREATE TABLE).
+    ///
+    /// When fanning out, contexts without the resource (e.g. a CRD installed
+    /// in only some of the clusters) are skipped.
+    pub async fn resolve_all(
         &self,
-        api_resource: &APIResource,
-        namespace: &str,
-    ) -> DataFusionResult<ObjectList<DynamicObject>> {
-        let api: Api<DynamicObject> = if api_resource.namespaced {
-            Api::namespaced_with(self.client.clone(), namespace, api_resource)
+        url: &str,
+    ) -> DataFusionResult<Vec<(String, KubernetesUrl, Arc<Cluster>)>> {
+        if !self.fans_out(url) {
+            let cluster = self.cluster_for_url(url).await?;
+            let api_resources = api_resources_for(&cluster, url).await?;
+            return match KubernetesUrl::parse(url, &cluster.default_namespace, &api_resources) {
+                Ok(kubeurl) => {
+                    let context = kubeurl.context.clone().unwrap_or(self.context.clone());
+                    Ok(vec![(context, kubeurl, cluster)])
+                }
+                Err(ParseError::ResourceNotFound(_)) => Ok(Vec::new()),
+                Err(e) => Err(DataFusionError::Plan(format!(
+                    "Invalid Kubernetes URL '{}': {}",
+                    url, e
+                ))),
+            };
+        }
+
+        let clusters = future::try_join_all(self.contexts.iter().map(|context| async move {
+            let cluster = self.cluster(context).await?;
+            let api_resources = api_resources_for(&cluster, url).await?;
+            Ok::<_, DataFusionError>((cluster, api_resources))
+        }))
+        .await?;
+        let mut resolved = Vec::new();
+        for (context, (cluster, api_resources)) in self.contexts.iter().zip(clusters) {
+            match KubernetesUrl::parse(url, &cluster.default_namespace, &api_resources) {
+                Ok(kubeurl) => resolved.push((context.clone(), kubeurl, cluster)),
+                Err(ParseError::ResourceNotFound(_)) => continue,
+                Err(e) => {
+                    return Err(DataFusionError::Plan(format!(
+                        "Invalid Kubernetes URL '{}': {}",
+                        url, e
+                    )));
+                }
+            }
+        }
+        Ok(resolved)
+    }
+
+    /// Resources of the cluster of the current context
+    pub async fn api_resources(&self) -> DataFusionResult<Vec<APIResource>> {
+        let cluster = self.cluster(&self.context).await?;
+        Ok(all_api_resources(&cluster).await?.to_vec())
+    }
+
+    /// Client of the API server of the current context
+    pub async fn client(&self) -> DataFusionResult<Client> {
+        let cluster = self.cluster(&self.context).await?;
+        cluster.client().cloned().ok_or_else(|| {
+            DataFusionError::Plan(format!("Context '{}' is not an API server", self.context))
+        })
+    }
+
+    /// Trace how the URL resolves to a resource of each cluster it is listed from
+    pub async fn explain_resolution(
+        &self,
+        url: &str,
+    ) -> DataFusionResult<Vec<(String, Resolution)>> {
+        if !self.fans_out(url) {
+            let cluster = self.cluster_for_url(url).await?;
+            let context = split_context(url).1.unwrap_or(&self.context).to_string();
+            let api_resources = all_api_resources(&cluster).await?;
+            return Ok(vec![(context, Resolution::explain(url, api_resources))]);
+        }
+        future::try_join_all(self.contexts.iter().map(|context| async move {
+            let cluster = self.cluster(context).await?;
+            let api_resources = all_api_resources(&cluster).await?;
+            Ok((context.clone(), Resolution::explain(url, api_resources)))
+        }))
+        .await
+    }
+
+    /// Create the Apis covering the resource of each cluster the URL is listed from
+    pub async fn apis(&self, url: &str) -> DataFusionResult<Vec<Api<DynamicObject>>> {
+        let mut apis = Vec::new();
+        for (_, kubeurl, cluster) in self.resolve_all(url).await? {
+            apis.extend(cluster_apis(&cluster, &kubeurl).await?);
+        }
+        Ok(apis)
+    }
+}
+
+/// Discover the resources of the cluster needed to find the resource of the URL in
+async fn api_resources_for(cluster: &Cluster, url: &str) -> DataFusionResult<Vec<APIResource>> {
+    cluster
+        .api_resources_for(resource_name(url))
+        .await
+        .map_err(|e| DataFusionError::Plan(format!("Failed to discover API resources: {}", e)))
+}
+
+/// Discover all resources of the cluster
+async fn all_api_resources(cluster: &Cluster) -> DataFusionResult<&[APIResource]> {
+    cluster
+        .api_resources()
+        .await
+        .map_err(|e| DataFusionError::Plan(format!("Failed to discover API resources: {}", e)))
+}
+
+/// Create the Apis covering the namespaces of the URL in the cluster
+async fn cluster_apis(
+    cluster: &Cluster,
+    kubeurl: &KubernetesUrl,
+) -> DataFusionResult<Vec<Api<DynamicObject>>> {
+    cluster
+        .apis(&kubeurl.resource, &kubeurl.namespace)
+        .await
+        .map_err(|e| DataFusionError::Plan(format!("Failed to list namespaces: {}", e)))
+}
+
+/// Returns the namespaces of the URL to list in the cluster
+async fn cluster_namespaces(
+    cluster: &Cluster,
+    kubeurl: &KubernetesUrl,
+) -> DataFusionResult<Vec<Option<String>>> {
+    cluster
+        .namespaces(&kubeurl.resource, &kubeurl.namespace)
+        .await
+        .map_err(|e| DataFusionError::Plan(format!("Failed to list namespaces: {}", e)))
+}
+
+/// Check with a SelfSubjectAccessReview per source that the user may read it before listing,
+/// failing with a readable message rather than a raw API error,
+/// or skipping it with a warning if the source may be skipped.
+/// Sources whose access cannot be reviewed are kept.
+async fn check_access(
+    cluster: &Cluster,
+    api_resource: &APIResource,
+    sources: Vec<Source>,
+) -> DataFusionResult<Vec<Source>> {
+    let allowed = future::join_all(sources.iter().map(|source| {
+        let verb = if source.name.is_some() { "get" } else { "list" };
+        cluster.lister.can_i(
+            verb,
+            api_resource,
+            source.namespace.as_deref(),
+            source.name.as_deref(),
+        )
+    }))
+    .await;
+
+    let mut permitted = Vec::with_capacity(sources.len());
+    for (source, allowed) in sources.into_iter().zip(allowed) {
+        if allowed != Some(false) {
+            permitted.push(source);
+        } else if source.skip_forbidden {
+            source
+                .warnings
+                .push(format!("skipped: {}", source.denied(api_resource)));
         } else {
-            Api::all_with(self.client.clone(), api_resource)
-        };
+            return Err(DataFusionError::Plan(source.denied(api_resource)));
+        }
+    }
+    Ok(permitted)
+}
+
+/// Resolve the schema of the resource from its OpenAPI v3 document,
+/// using the schema inferred from sample objects for what OpenAPI cannot describe
+/// (e.g. keys of `metadata.labels`).
+/// Falls back to the inferred schema if the document is unavailable.
+async fn resolve_schema(
+    lister: &dyn ResourceLister,
+    api_resource: &APIResource,
+    inferred: SchemaRef,
+) -> SchemaRef {
+    match lister.openapi_document(api_resource).await {
+        Ok(document) => openapi::arrow_schema(&document, api_resource, &inferred)
+            .map(|schema| prune_schema(&schema))
+            .unwrap_or(inferred),
+        Err(_) => inferred,
+    }
+}
 
-        api.list(&Default::default())
+/// Await the list request of the source, giving up after the timeout of the list parameters.
+///
+/// The `timeout` of the list parameters is also enforced on the client,
+/// since API servers do not always honor it for lists, including retries.
+async fn with_list_timeout<T>(
+    source: &Source,
+    lp: &ListParams,
+    list: impl Future<Output = kube::Result<T>>,
+) -> DataFusionResult<T> {
+    let list = match lp.timeout {
+        Some(timeout) => tokio::time::timeout(Duration::from_secs(timeout.into()), list)
             .await
-            .map(|mut list| {
-                list.items.iter_mut().for_each(|item| {
-                    // TODO: re-consider whether to remove managedFields or not?
-                    item.metadata.managed_fields = None;
-                });
-                list
-            })
-            .map_err(|e| DataFusionError::External(Box::new(e)))
+            .map_err(|_| {
+                DataFusionError::Execution(format!(
+                    "Listing {} timed out after {}s",
+                    source.url(),
+                    timeout
+                ))
+            })?,
+        None => list.await,
+    };
+    list.map_err(|e| DataFusionError::External(Box::new(e)))
+}
+
+/// List API resources with the given list parameters
+async fn list_api_resources(
+    source: &Source,
+    lp: &ListParams,
+    metadata_only: bool,
+    metrics: &ListMetrics,
+) -> DataFusionResult<ObjectList<DynamicObject>> {
+    metrics.requests.add(1);
+    metrics.pages.add(1);
+    let _timer = metrics.api_time.timer();
+    let list = source.lister.list(
+        &source.api_resource,
+        source.namespace.as_deref(),
+        lp,
+        metadata_only,
+    );
+    with_list_timeout(source, lp, list).await
+}
+
+/// Get the named object of the source, `None` if missing.
+async fn get_api_resource(
+    source: &Source,
+    name: &str,
+    metadata_only: bool,
+    metrics: &ListMetrics,
+) -> DataFusionResult<Option<DynamicObject>> {
+    metrics.requests.add(1);
+    let _timer = metrics.api_time.timer();
+    source
+        .lister
+        .get(
+            &source.api_resource,
+            source.namespace.as_deref(),
+            name,
+            metadata_only,
+        )
+        .await
+        .map_err(|e| DataFusionError::External(Box::new(e)))
+}
+
+/// Fetch the objects of the source:
+/// the named object with a GET, which needs only get permission, or the list otherwise.
+/// A missing named object results in no objects.
+///
+/// With `metadata_only`, only the metadata of the objects is requested
+/// (as `PartialObjectMetadata`), leaving every other field empty.
+async fn fetch_objects(
+    source: &Source,
+    lp: &ListParams,
+    metadata_only: bool,
+    metrics: &ListMetrics,
+) -> DataFusionResult<Vec<DynamicObject>> {
+    let mut objects = match &source.name {
+        None => {
+            list_api_resources(source, lp, metadata_only, metrics)
+                .await?
+                .items
+        }
+        Some(name) => get_api_resource(source, name, metadata_only, metrics)
+            .await?
+            .into_iter()
+            .collect(),
+    };
+    prepare_objects(source, &mut objects, metrics);
+    Ok(objects)
+}
+
+/// Fetch the objects of the source as [`fetch_objects`] does,
+/// but listing them page by page so that only one page is held at a time.
+fn fetch_pages(
+    source: Source,
+    lp: ListParams,
+    metadata_only: bool,
+    metrics: ListMetrics,
+) -> impl Stream<Item = DataFusionResult<Vec<DynamicObject>>> + Send {
+    /// Next page to list, with the number of objects listed so far
+    enum Page {
+        Next(Option<String>, u32),
+        Done,
+    }
+
+    stream::try_unfold(Page::Next(None, 0), move |page| {
+        let (source, lp, metrics) = (source.clone(), lp.clone(), metrics.clone());
+        async move {
+            let Page::Next(continue_token, listed) = page else {
+                return Ok(None);
+            };
+            if source.name.is_some() {
+                let objects = fetch_objects(&source, &lp, metadata_only, &metrics).await?;
+                return Ok(Some((objects, Page::Done)));
+            }
+            let page_lp = ListParams {
+                limit: min_limit(
+                    lp.limit.map(|limit| limit.saturating_sub(listed)),
+                    Some(PAGE_SIZE),
+                ),
+                continue_token,
+                ..lp.clone()
+            };
+            let list = list_api_resources(&source, &page_lp, metadata_only, &metrics).await?;
+            let mut objects = list.items;
+            prepare_objects(&source, &mut objects, &metrics);
+            let listed = listed.saturating_add(u32::try_from(objects.len()).unwrap_or(u32::MAX));
+            let next = match list.metadata.continue_.filter(|token| !token.is_empty()) {
+                Some(_) if lp.limit.is_some_and(|limit| listed >= limit) => Page::Done,
+                Some(token) => Page::Next(Some(token), listed),
+                None => Page::Done,
+            };
+            Ok(Some((objects, next)))
+        }
+    })
+}
+
+/// Record the objects fetched, dropping their managed fields and redacting Secrets.
+fn prepare_objects(source: &Source, objects: &mut [DynamicObject], metrics: &ListMetrics) {
+    metrics.record_objects(objects);
+    objects.iter_mut().for_each(|object| {
+        // TODO: re-consider whether to remove managedFields or not?
+        object.metadata.managed_fields = None;
+    });
+    if source.redact_secrets {
+        objects.iter_mut().for_each(redact_secret);
+    }
+}
+
+/// Replace the values of the Secret, including those kept by `kubectl apply`
+/// in the last applied configuration, with a placeholder.
+///
+/// The release of a Helm release Secret is stripped of its values and manifests instead,
+/// so that `helm_releases` can be queried without `--show-secrets`.
+fn redact_secret(object: &mut DynamicObject) {
+    let helm_release = (object.data.get("type").and_then(serde_json::Value::as_str)
+        == Some(helm::RELEASE_SECRET_TYPE))
+    .then(|| object.data.pointer("/data/release")?.as_str())
+    .flatten()
+    .and_then(helm::strip_release);
+    for field in ["data", "stringData"] {
+        if let Some(values) = object
+            .data
+            .get_mut(field)
+            .and_then(serde_json::Value::as_object_mut)
+        {
+            values
+                .values_mut()
+                .for_each(|value| *value = REDACTED.into());
+        }
+    }
+    if let (Some(release), Some(data)) = (helm_release, object.data.get_mut("data")) {
+        data["release"] = release.into();
+    }
+    if let Some(configuration) = object
+        .metadata
+        .annotations
+        .as_mut()
+        .and_then(|annotations| annotations.get_mut(LAST_APPLIED_CONFIGURATION))
+    {
+        *configuration = REDACTED.to_string();
+    }
+}
+
+/// Fetch the objects of each source to infer the schema from.
+async fn sample_objects(
+    sources: &[Source],
+    lp: &ListParams,
+) -> DataFusionResult<Vec<Vec<DynamicObject>>> {
+    future::try_join_all(sources.iter().map(|source| async move {
+        let metrics = ListMetrics::default();
+        skip_forbidden(source, fetch_objects(source, lp, false, &metrics).await)
+    }))
+    .await
+}
+
+/// Check if the error is a 403 Forbidden response from the API server.
+fn is_forbidden(e: &DataFusionError) -> bool {
+    matches!(
+        e,
+        DataFusionError::External(e)
+            if matches!(e.downcast_ref::<kube::Error>(), Some(kube::Error::Api(response)) if response.code == 403)
+    )
+}
+
+/// Treat a forbidden request of a source that may be skipped as having no objects,
+/// adding a warning about the partial results.
+fn skip_forbidden<T: Default>(source: &Source, result: DataFusionResult<T>) -> DataFusionResult<T> {
+    match result {
+        Err(e) if source.skip_forbidden && is_forbidden(&e) => {
+            let url = source.url();
+            source.warnings.push(match &source.cluster {
+                Some(cluster) => format!("skipped {} in context '{}': {}", url, cluster, e),
+                None => format!("skipped {}: {}", url, e),
+            });
+            Ok(T::default())
+        }
+        result => result,
     }
 }
 
+/// Count the objects of the source without decoding them,
+/// listing their metadata page by page so that only one page is held at a time.
+async fn count_objects(
+    source: &Source,
+    lp: &ListParams,
+    metrics: &ListMetrics,
+) -> DataFusionResult<usize> {
+    if let Some(name) = &source.name {
+        let object = get_api_resource(source, name, true, metrics).await?;
+        metrics.record_objects(object.as_slice());
+        return Ok(object.map_or(0, |_| 1));
+    }
+
+    let mut count = 0;
+    let mut continue_token = None;
+    loop {
+        let page_lp = ListParams {
+            limit: min_limit(
+                lp.limit.map(|limit| limit.saturating_sub(count)),
+                Some(PAGE_SIZE),
+            ),
+            continue_token: continue_token.take(),
+            ..lp.clone()
+        };
+        let list = list_api_resources(source, &page_lp, true, metrics).await?;
+        metrics.record_objects(&list.items);
+        count = count.saturating_add(u32::try_from(list.items.len()).unwrap_or(u32::MAX));
+        continue_token = list.metadata.continue_.filter(|token| !token.is_empty());
+        if continue_token.is_none() || lp.limit.is_some_and(|limit| count >= limit) {
+            return Ok(count as usize);
+        }
+    }
+}
+
+/// Requests the plan would make to the API servers to list the objects of its tables,
+/// as `LIST <url>` or `GET <url>` with the list options and the context if fanning out.
+pub fn api_requests(plan: &dyn ExecutionPlan) -> Vec<String> {
+    let mut requests = match plan.as_any().downcast_ref::<KubernetesExec>() {
+        Some(exec) => exec.requests(),
+        None => Vec::new(),
+    };
+    for child in plan.children() {
+        requests.extend(api_requests(child.as_ref()));
+    }
+    requests
+}
+
+/// Whether no columns other than `_cluster` are read, as for `count(*)`,
+/// so that only the number of objects is needed
+fn count_only(schema: &Schema) -> bool {
+    schema
+        .fields()
+        .iter()
+        .all(|field| field.name() == CLUSTER_COLUMN)
+}
+
+/// Whether the columns can all be read from metadata-only responses
+fn metadata_only(schema: &Schema) -> bool {
+    schema.fields().iter().all(|field| {
+        matches!(
+            field.name().as_str(),
+            "metadata" | "apiVersion" | "kind" | CLUSTER_COLUMN
+        )
+    })
+}
+
 #[async_trait]
 impl UrlTableFactory for KubernetesTableProviderFactory {
     /// Try to create a table provider from a Kubernetes URL
     async fn try_new(&self, url: &str) -> DataFusionResult<Option<Arc<dyn TableProvider>>> {
-        let kubeurl =
-            KubernetesUrl::parse(url, &self.context, &self.api_resources).map_err(|e| {
-                DataFusionError::Plan(format!("Invalid Kubernetes URL '{}': {}", url, e))
-            })?;
+        let fans_out = self.fans_out(url);
+        let resolved = self.resolve_all(url).await?;
+        // Leave names of no resource to the other tables of the session.
+        if resolved.is_empty() {
+            return Ok(None);
+        }
+        let api_resource = resolved[0].1.resource.clone();
+        let list_params = ListParams {
+            timeout: self.list_timeout,
+            ..resolved[0].1.list_params.clone()
+        };
+
+        // Sample the objects the table is limited to by the URL options.
+        let lp = match self.infer_rows {
+            0 => list_params.clone(),
+            infer_rows => ListParams {
+                limit: min_limit(list_params.limit, Some(infer_rows)),
+                ..list_params.clone()
+            },
+        };
+
+        let clusters =
+            future::try_join_all(resolved.into_iter().map(|(context, kubeurl, cluster)| {
+                let lp = &lp;
+                let list_params = &list_params;
+                async move {
+                    let redact_secrets = !self.show_secrets
+                        && kubeurl.resource.group.as_deref() == Some("core")
+                        && kubeurl.resource.name == "secrets";
+                    let to_sources = |namespaces: Vec<Option<String>>, skip_forbidden: bool| {
+                        namespaces
+                            .into_iter()
+                            .map(|namespace| Source {
+                                cluster: fans_out.then(|| context.clone()),
+                                lister: cluster.lister.clone(),
+                                api_resource: kubeurl.resource.clone(),
+                                namespace,
+                                name: kubeurl.name.clone(),
+                                skip_forbidden,
+                                warnings: self.warnings.clone(),
+                                redact_secrets,
+                            })
+                            .collect::<Vec<_>>()
+                    };
+                    // A namespace of a pattern that cannot be listed is skipped with a warning.
+                    let skip_forbidden = matches!(kubeurl.namespace, Namespaces::Glob(_));
+                    let namespaces = cluster_namespaces(&cluster, &kubeurl).await?;
+                    // Users with only namespace-scoped RBAC cannot list across namespaces,
+                    // so list each namespace instead, skipping those they cannot list either.
+                    // Those who cannot list the namespaces either list the namespace of the context.
+                    let cluster_wide =
+                        matches!(kubeurl.namespace, Namespaces::All) && kubeurl.resource.namespaced;
+                    let all = Namespaces::Glob(Pattern::new("*").expect("valid pattern"));
+                    let each_namespace = || async {
+                        match cluster.namespaces(&kubeurl.resource, &all).await {
+                            Ok(namespaces) => Some(to_sources(namespaces, true)),
+                            Err(_) => {
+                                let namespace = cluster.context_namespace.clone()?;
+                                self.warnings.push(format!(
+                                    "listed {} in context '{}' in namespace '{}' only, \
+                                     as neither they nor namespaces can be listed",
+                                    kubeurl.resource.name, context, namespace
+                                ));
+                                Some(to_sources(vec![Some(namespace)], false))
+                            }
+                        }
+                    };
+
+                    let sources = to_sources(namespaces, skip_forbidden);
+                    let mut sources = match check_access(&cluster, &kubeurl.resource, sources).await
+                    {
+                        Err(e) if cluster_wide => {
+                            let Some(sources) = each_namespace().await else {
+                                return Err(e);
+                            };
+                            check_access(&cluster, &kubeurl.resource, sources).await?
+                        }
+                        result => result?,
+                    };
+                    // Access reviews may be unavailable, so the list can still be forbidden.
+                    // Objects read from files are sampled, as that does not hit any cluster.
+                    let dry_run = self.dry_run && cluster.client().is_some();
+                    let sampled = match dry_run {
+                        true => Ok(vec![Vec::new(); sources.len()]),
+                        false => sample_objects(&sources, lp).await,
+                    };
+                    let objects_by_source = match sampled {
+                        Err(e) if is_forbidden(&e) && cluster_wide => {
+                            let Some(namespace_sources) = each_namespace().await else {
+                                return Err(e);
+                            };
+                            sources = namespace_sources;
+                            sample_objects(&sources, lp).await?
+                        }
+                        result => result?,
+                    };
+                    // The sample has all objects of the table unless a source was cut off by the sample size.
+                    let complete = lp.limit == list_params.limit
+                        || objects_by_source.iter().all(|objects| {
+                            lp.limit.is_none_or(|limit| objects.len() < limit as usize)
+                        });
+                    let num_rows = (complete && !dry_run)
+                        .then(|| objects_by_source.iter().map(Vec::len).sum::<usize>());
+                    let objects: Vec<DynamicObject> =
+                        objects_by_source.into_iter().flatten().collect();
 
-        let object_list = self
-            .list_api_resources(&kubeurl.resource, &kubeurl.namespace)
+                    // An empty list is a legitimate result (e.g. counting Pods in an empty namespace),
+                    // so fall back to a minimal schema, completed by OpenAPI where available.
+                    let inferred = if dry_run {
+                        unsampled_schema()
+                    } else if objects.is_empty() {
+                        minimal_schema()
+                    } else {
+                        infer_schema(&objects)?
+                    };
+                    let schema =
+                        resolve_schema(cluster.lister.as_ref(), &kubeurl.resource, inferred).await;
+                    Ok::<_, DataFusionError>((schema, sources, num_rows))
+                }
+            }))
             .await?;
 
-        if object_list.items.is_empty() {
-            return Err(DataFusionError::Plan(format!(
-                "No items found for resource '{}' in namespace '{}'",
-                kubeurl.resource.kind, kubeurl.namespace
-            )));
+        let schemas: Vec<SchemaRef> = clusters
+            .iter()
+            .map(|(schema, _, _)| schema.clone())
+            .collect();
+        let num_rows = clusters
+            .iter()
+            .map(|(_, _, num_rows)| *num_rows)
+            .sum::<Option<usize>>();
+        let mut schema = merge_schemas(&schemas);
+        if fans_out {
+            let fields =
+                std::iter::once(Arc::new(Field::new(CLUSTER_COLUMN, DataType::Utf8, false)))
+                    .chain(schema.fields().iter().cloned())
+                    .collect::<Vec<_>>();
+            schema = Arc::new(Schema::new(fields));
         }
+        let sources = clusters
+            .into_iter()
+            .flat_map(|(_, sources, _)| sources)
+            .collect();
 
-        let ndjson = object_list
-            .items
-            .iter()
-            .map(|item| serde_json::json!(item).to_string())
-            .collect::<Vec<_>>()
-            .join("\n");
+        Ok(Some(Arc::new(
+            KubernetesTableProvider::new(schema, api_resource, sources)
+                .with_list_params(list_params)
+                .with_num_rows(num_rows)
+                .with_dry_run(self.dry_run),
+        )))
+    }
+}
 
-        let schema = infer_schema(&ndjson).await?;
+/// Resource of a cluster to list the objects from, along with the context
+/// to fill the `_cluster` column with when fanning out
+#[derive(Clone)]
+pub struct Source {
+    cluster: Option<String>,
+    lister: Arc<dyn ResourceLister>,
+    api_resource: APIResource,
+    /// Namespace to list, `None` for all namespaces or a cluster-scoped resource
+    namespace: Option<String>,
+    /// Name of the single object to fetch, if any
+    name: Option<String>,
+    /// Treat the source as empty with a warning if listing it is forbidden
+    skip_forbidden: bool,
+    warnings: Warnings,
+    /// Redact the values of the objects, which are Secrets
+    redact_secrets: bool,
+}
 
-        Ok(Some(Arc::new(KubernetesTableProvider::new(
-            schema,
-            Arc::new(ndjson),
-        ))))
+impl Source {
+    /// URL of the objects of the source, as requested from an API server
+    fn url(&self) -> String {
+        let namespace = self
+            .namespace
+            .as_deref()
+            .filter(|_| self.api_resource.namespaced);
+        DynamicObject::url_path(&self.api_resource, namespace)
+    }
+
+    /// Message telling that the user may not read the source, as in `kubectl auth can-i`
+    fn denied(&self, api_resource: &APIResource) -> String {
+        let mut message = match &self.name {
+            Some(name) => format!("you cannot get {} '{}'", api_resource.name, name),
+            None => format!("you cannot list {}", api_resource.name),
+        };
+        match &self.namespace {
+            Some(namespace) => message.push_str(&format!(" in namespace '{}'", namespace)),
+            None if api_resource.namespaced => message.push_str(" across all namespaces"),
+            None => {}
+        }
+        if let Some(cluster) = &self.cluster {
+            message.push_str(&format!(" in context '{}'", cluster));
+        }
+        message
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct KubernetesTableProvider {
     schema: SchemaRef,
-    ndjson: Arc<String>,
+    api_resource: APIResource,
+    sources: Vec<Source>,
+    /// List parameters given in the URL, combined with the pushed down ones
+    list_params: ListParams,
+    /// Number of objects seen at planning time, if all of them were listed
+    num_rows: Option<usize>,
+    /// Fail instead of listing the objects when executed
+    dry_run: bool,
+}
+
+impl Debug for KubernetesTableProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KubernetesTableProvider")
+            .field("schema", &self.schema)
+            .field("api_resource", &self.api_resource.name)
+            .finish()
+    }
 }
 
 impl KubernetesTableProvider {
-    pub fn new(schema: SchemaRef, ndjson: Arc<String>) -> Self {
-        Self { schema, ndjson }
+    pub fn new(schema: SchemaRef, api_resource: APIResource, sources: Vec<Source>) -> Self {
+        Self {
+            schema,
+            api_resource,
+            sources,
+            list_params: ListParams::default(),
+            num_rows: None,
+            dry_run: false,
+        }
+    }
+
+    /// Set the list parameters given in the URL
+    pub fn with_list_params(mut self, list_params: ListParams) -> Self {
+        self.list_params = list_params;
+        self
+    }
+
+    /// Set the number of objects seen at planning time, if all of them were listed
+    pub fn with_num_rows(mut self, num_rows: Option<usize>) -> Self {
+        self.num_rows = num_rows;
+        self
+    }
+
+    /// Set whether to fail instead of listing the objects when executed
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Statistics of the table, with the number of rows estimated from planning time
+    /// since objects may be created or deleted before execution
+    fn table_statistics(&self, schema: &Schema) -> Statistics {
+        let num_rows = match self.num_rows {
+            Some(num_rows) => Precision::Inexact(num_rows),
+            None => Precision::Absent,
+        };
+        Statistics::new_unknown(schema).with_num_rows(num_rows)
+    }
+}
+
+/// Combine two selectors so that objects must match both
+fn join_selectors(a: Option<String>, b: Option<String>) -> Option<String> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(format!("{},{}", a, b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Returns the smaller of two optional limits
+fn min_limit(a: Option<u32>, b: Option<u32>) -> Option<u32> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
     }
 }
 
@@ -157,12 +992,35 @@ impl TableProvider for KubernetesTableProvider {
         TableType::Base
     }
 
+    fn statistics(&self) -> Option<Statistics> {
+        Some(self.table_statistics(&self.schema))
+    }
+
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> DataFusionResult<Vec<TableProviderFilterPushDown>> {
+        // Selectors only narrow down the listed objects, so DataFusion still applies the filters.
+        Ok(filters
+            .iter()
+            .map(|filter| {
+                if selector::label_requirement(filter).is_some()
+                    || selector::field_requirement(filter, &self.api_resource).is_some()
+                {
+                    TableProviderFilterPushDown::Inexact
+                } else {
+                    TableProviderFilterPushDown::Unsupported
+                }
+            })
+            .collect())
+    }
+
     async fn scan(
         &self,
         _state: &dyn Session,
         projection: Option<&Vec<usize>>,
-        _filters: &[Expr],
-        _limit: Option<usize>,
+        filters: &[Expr],
+        limit: Option<usize>,
     ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
         let projected_schema = if let Some(proj) = projection {
             match self.schema.project(proj) {
@@ -173,72 +1031,349 @@ impl TableProvider for KubernetesTableProvider {
             self.schema.clone()
         };
 
-        Ok(Arc::new(KubernetesExec::new(
-            projected_schema,
-            self.ndjson.clone(),
-        )))
+        let label_selector = selector::label_selector(filters);
+        let field_selector = selector::field_selector(filters, &self.api_resource);
+        // DataFusion passes `limit` only when no other operator (e.g. filter) must
+        // see the remaining rows first, so the API server can stop after `limit` objects.
+        let lp = ListParams {
+            label_selector: join_selectors(self.list_params.label_selector.clone(), label_selector),
+            field_selector: join_selectors(self.list_params.field_selector.clone(), field_selector),
+            limit: min_limit(
+                self.list_params.limit,
+                limit.and_then(|limit| u32::try_from(limit).ok()),
+            ),
+            ..self.list_params.clone()
+        };
+
+        // Only metadata is transferred when no other field is referenced,
+        // such as for names, labels or `count(*)`.
+        let metadata_only = metadata_only(&projected_schema);
+
+        // The limit applies to each source.
+        let statistics = self.table_statistics(&projected_schema).with_fetch(
+            projected_schema.clone(),
+            limit,
+            0,
+            self.sources.len().max(1),
+        )?;
+
+        Ok(Arc::new(
+            KubernetesExec::new(projected_schema, self.sources.clone(), lp)
+                .with_metadata_only(metadata_only)
+                .with_statistics(statistics)
+                .with_dry_run(self.dry_run),
+        ))
     }
 }
 
-/// Convert NDJSON to a DataFusion RecordBatch
-fn record_batch_from_ndjson(ndjson: &str, schema: SchemaRef) -> DataFusionResult<RecordBatch> {
-    let reader = ReaderBuilder::new(schema.clone())
-        // TODO: make it configurable?
-        .with_batch_size(4096)
+/// Decode objects into DataFusion RecordBatches of up to `BATCH_SIZE` rows,
+/// lazily as the iterator is consumed.
+///
+/// The objects are serialized straight into the decoder
+/// without going through JSON text, but for the fields typed as strings
+/// because of conflicting types, which are [`conform`]ed first.
+fn record_batches_from_objects(
+    mut objects: Vec<DynamicObject>,
+    schema: SchemaRef,
+    decode_time: Time,
+) -> DataFusionResult<impl Iterator<Item = DataFusionResult<RecordBatch>>> {
+    let mut decoder = ReaderBuilder::new(schema.clone())
+        .with_batch_size(BATCH_SIZE)
         .with_coerce_primitive(true)
-        .build(Cursor::new(ndjson.as_bytes()))?;
-
-    let mut batches: Vec<RecordBatch> = Vec::new();
+        .build_decoder()?;
+    Ok((0..objects.len()).step_by(BATCH_SIZE).map(move |start| {
+        let _timer = decode_time.timer();
+        let end = usize::min(start + BATCH_SIZE, objects.len());
+        for object in &mut objects[start..end] {
+            conform(&mut object.data, schema.fields());
+        }
+        decoder.serialize(&objects[start..end])?;
+        Ok(decoder
+            .flush()?
+            .unwrap_or_else(|| RecordBatch::new_empty(schema.clone())))
+    }))
+}
 
-    for batch_result in reader {
-        match batch_result {
-            Ok(batch) => {
-                batches.push(batch);
+/// Add the `_cluster` column to the batch decoded from the objects of the cluster,
+/// placing the columns as in `schema`.
+fn with_cluster_column(
+    batch: RecordBatch,
+    schema: &SchemaRef,
+    cluster: &str,
+) -> DataFusionResult<RecordBatch> {
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            if field.name() == CLUSTER_COLUMN {
+                Ok(Arc::new(StringArray::from(vec![cluster; batch.num_rows()])) as ArrayRef)
+            } else {
+                batch.column_by_name(field.name()).cloned().ok_or_else(|| {
+                    DataFusionError::Internal(format!("Column '{}' not decoded", field.name()))
+                })
             }
-            Err(e) => {
-                if !batches.is_empty() {
-                    break;
-                } else {
-                    return Err(DataFusionError::External(Box::new(e)));
-                }
+        })
+        .collect::<DataFusionResult<Vec<_>>>()?;
+    let options = RecordBatchOptions::new().with_row_count(Some(batch.num_rows()));
+    Ok(RecordBatch::try_new_with_options(
+        schema.clone(),
+        columns,
+        &options,
+    )?)
+}
+
+/// List the objects of the source and decode them into RecordBatches of `schema`,
+/// a page at a time. Without any columns to decode, the objects are only counted.
+fn list_source(
+    source: Source,
+    schema: SchemaRef,
+    lp: ListParams,
+    metadata_only: bool,
+    metrics: ListMetrics,
+) -> impl Stream<Item = DataFusionResult<RecordBatch>> + Send {
+    if count_only(&schema) {
+        return stream::once(async move {
+            let count = skip_forbidden(&source, count_objects(&source, &lp, &metrics).await)?;
+            let options = RecordBatchOptions::new().with_row_count(Some(count));
+            let batch =
+                RecordBatch::try_new_with_options(Arc::new(Schema::empty()), Vec::new(), &options)?;
+            match &source.cluster {
+                Some(cluster) => with_cluster_column(batch, &schema, cluster),
+                None => Ok(batch.with_schema(schema)?),
             }
+        })
+        .boxed();
+    }
+
+    let decode_schema = match &source.cluster {
+        Some(_) => {
+            let fields: Vec<_> = schema
+                .fields()
+                .iter()
+                .filter(|field| field.name() != CLUSTER_COLUMN)
+                .cloned()
+                .collect();
+            Arc::new(Schema::new(fields))
         }
+        None => schema.clone(),
+    };
+    let decode_time = metrics.decode_time.clone();
+    fetch_pages(source.clone(), lp, metadata_only, metrics)
+        .map(move |objects| {
+            // Forbidden sources that may be skipped fail at the first page, then empty.
+            let objects = skip_forbidden(&source, objects)?;
+            record_batches_from_objects(objects, decode_schema.clone(), decode_time.clone())?
+                .map(|batch| match &source.cluster {
+                    Some(cluster) => with_cluster_column(batch?, &schema, cluster),
+                    None => batch,
+                })
+                .collect::<DataFusionResult<Vec<_>>>()
+        })
+        .map_ok(|batches| stream::iter(batches.into_iter().map(Ok)))
+        .try_flatten()
+        .boxed()
+}
+
+/// Metrics of listing a source, shown by `EXPLAIN ANALYZE`
+#[derive(Clone, Default)]
+struct ListMetrics {
+    /// Requests made to the API server, lists and gets
+    requests: Count,
+    /// Pages of the lists
+    pages: Count,
+    objects: Count,
+    /// Size of the objects as JSON, approximating the bytes transferred
+    bytes: Count,
+    /// Time waiting for the API server
+    api_time: Time,
+    /// Time decoding the objects into RecordBatches
+    decode_time: Time,
+    output_rows: Count,
+}
+
+impl ListMetrics {
+    fn new(metrics: &ExecutionPlanMetricsSet, partition: usize) -> Self {
+        Self {
+            requests: MetricBuilder::new(metrics).counter("requests", partition),
+            pages: MetricBuilder::new(metrics).counter("pages", partition),
+            objects: MetricBuilder::new(metrics).counter("objects", partition),
+            bytes: MetricBuilder::new(metrics).counter("bytes", partition),
+            api_time: MetricBuilder::new(metrics).subset_time("api_time", partition),
+            decode_time: MetricBuilder::new(metrics).subset_time("decode_time", partition),
+            output_rows: MetricBuilder::new(metrics).output_rows(partition),
+        }
+    }
+
+    /// Count the objects received and their size
+    fn record_objects(&self, objects: &[DynamicObject]) {
+        self.objects.add(objects.len());
+        let mut size = JsonSize(0);
+        for object in objects {
+            // Serializing a DynamicObject does not fail.
+            let _ = serde_json::to_writer(&mut size, object);
+        }
+        self.bytes.add(size.0);
+    }
+}
+
+/// Writer counting the bytes written to it
+struct JsonSize(usize);
+
+impl std::io::Write for JsonSize {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
     }
 
-    if batches.is_empty() {
-        Ok(RecordBatch::new_empty(schema))
-    } else {
-        concat_batches(&schema, &batches).map_err(DataFusionError::from)
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
     }
 }
 
-#[derive(Debug)]
+/// Execution plan that lists the objects when executed
 struct KubernetesExec {
     properties: PlanProperties,
     schema: SchemaRef,
-    ndjson: Arc<String>,
+    sources: Vec<Source>,
+    lp: ListParams,
+    /// Request only the metadata of the objects
+    metadata_only: bool,
+    statistics: Statistics,
+    metrics: ExecutionPlanMetricsSet,
+    /// Fail instead of listing the objects
+    dry_run: bool,
+}
+
+impl Debug for KubernetesExec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KubernetesExec")
+            .field("schema", &self.schema)
+            .field("lp", &self.lp)
+            .field("metadata_only", &self.metadata_only)
+            .finish()
+    }
 }
 
 impl KubernetesExec {
-    fn new(schema: SchemaRef, ndjson: Arc<String>) -> Self {
-        // TODO: properties set here are not refined. There is room for optimization.
+    fn new(schema: SchemaRef, sources: Vec<Source>, lp: ListParams) -> Self {
+        // Each source (a namespace or a cluster) is a partition,
+        // so that DataFusion lists and decodes them in parallel.
         let properties = PlanProperties::new(
             EquivalenceProperties::new(schema.clone()),
-            Partitioning::UnknownPartitioning(1),
-            EmissionType::Final,
+            Partitioning::UnknownPartitioning(sources.len().max(1)),
+            EmissionType::Incremental,
             Boundedness::Bounded,
         );
         Self {
             properties,
+            statistics: Statistics::new_unknown(&schema),
             schema,
-            ndjson,
+            sources,
+            lp,
+            metadata_only: false,
+            metrics: ExecutionPlanMetricsSet::new(),
+            dry_run: false,
         }
     }
+
+    /// Set whether to request only the metadata of the objects
+    fn with_metadata_only(mut self, metadata_only: bool) -> Self {
+        self.metadata_only = metadata_only;
+        self
+    }
+
+    /// Set the statistics estimated at planning time
+    fn with_statistics(mut self, statistics: Statistics) -> Self {
+        self.statistics = statistics;
+        self
+    }
+
+    /// Set whether to fail instead of listing the objects
+    fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Requests the plan makes to the API servers, one per source
+    fn requests(&self) -> Vec<String> {
+        let count_only = count_only(&self.schema);
+        self.sources
+            .iter()
+            .map(|source| {
+                let mut request = match &source.name {
+                    Some(name) => format!("GET {}/{}", source.url(), name),
+                    None => {
+                        let mut query = form_urlencoded::Serializer::new(String::new());
+                        if let Some(label_selector) = &self.lp.label_selector {
+                            query.append_pair("labelSelector", label_selector);
+                        }
+                        if let Some(field_selector) = &self.lp.field_selector {
+                            query.append_pair("fieldSelector", field_selector);
+                        }
+                        if let Some(limit) = min_limit(self.lp.limit, Some(PAGE_SIZE)) {
+                            query.append_pair("limit", &limit.to_string());
+                        }
+                        if let Some(timeout) = self.lp.timeout {
+                            query.append_pair("timeoutSeconds", &timeout.to_string());
+                        }
+                        match query.finish() {
+                            query if query.is_empty() => format!("LIST {}", source.url()),
+                            query => format!("LIST {}?{}", source.url(), query),
+                        }
+                    }
+                };
+                let metadata_only = count_only || self.metadata_only;
+                match (metadata_only, source.name.is_none()) {
+                    (true, true) => request.push_str(" (metadata only, paged)"),
+                    (true, false) => request.push_str(" (metadata only)"),
+                    (false, true) => request.push_str(" (paged)"),
+                    (false, false) => {}
+                }
+                if let Some(cluster) = &source.cluster {
+                    request.push_str(&format!(" in context '{}'", cluster));
+                }
+                request
+            })
+            .collect()
+    }
 }
 
 impl DisplayAs for KubernetesExec {
     fn fmt_as(&self, _t: DisplayFormatType, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "KubernetesExec")
+        // Sources differ only in their namespace or cluster, so show the first one.
+        match self.sources.first() {
+            Some(source) => write!(f, "KubernetesExec: url={}", source.url())?,
+            None => write!(f, "KubernetesExec: no namespaces")?,
+        }
+        if let Some(name) = self.sources.first().and_then(|source| source.name.as_ref()) {
+            write!(f, ", name={}", name)?;
+        }
+        if self.sources.len() > 1 {
+            write!(f, ", sources={}", self.sources.len())?;
+        }
+        let clusters: Vec<&str> = self
+            .sources
+            .iter()
+            .filter_map(|source| source.cluster.as_deref())
+            .collect();
+        if !clusters.is_empty() {
+            write!(f, ", clusters={}", clusters.join(","))?;
+        }
+        if let Some(label_selector) = &self.lp.label_selector {
+            write!(f, ", labelSelector={}", label_selector)?;
+        }
+        if let Some(field_selector) = &self.lp.field_selector {
+            write!(f, ", fieldSelector={}", field_selector)?;
+        }
+        if let Some(limit) = self.lp.limit {
+            write!(f, ", limit={}", limit)?;
+        }
+        if count_only(&self.schema) {
+            write!(f, ", count_only")?;
+        } else if self.metadata_only {
+            write!(f, ", metadata_only")?;
+        }
+        Ok(())
     }
 }
 
@@ -259,6 +1394,14 @@ impl ExecutionPlan for KubernetesExec {
         &self.properties
     }
 
+    fn partition_statistics(&self, partition: Option<usize>) -> DataFusionResult<Statistics> {
+        // Objects are not counted per source at planning time.
+        match partition {
+            Some(_) => Ok(Statistics::new_unknown(&self.schema)),
+            None => Ok(self.statistics.clone()),
+        }
+    }
+
     fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
         Vec::new()
     }
@@ -270,15 +1413,266 @@ impl ExecutionPlan for KubernetesExec {
         Ok(self)
     }
 
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
     fn execute(
         &self,
-        _partition: usize,
+        partition: usize,
         _context: Arc<TaskContext>,
     ) -> DataFusionResult<SendableRecordBatchStream> {
-        Ok(Box::pin(MemoryStream::try_new(
-            vec![record_batch_from_ndjson(&self.ndjson, self.schema.clone())?],
+        if self.dry_run {
+            return Err(DataFusionError::Execution(format!(
+                "Not listing {} in a dry run",
+                self.sources.first().map_or_else(String::new, Source::url)
+            )));
+        }
+        // Without any source (e.g. no namespace matched), the only partition is empty.
+        let source = self.sources.get(partition).cloned();
+        let schema = self.schema.clone();
+        let lp = self.lp.clone();
+        let metadata_only = self.metadata_only;
+        let metrics = ListMetrics::new(&self.metrics, partition);
+        let output_rows = metrics.output_rows.clone();
+        let batches = match source {
+            Some(source) => list_source(source, schema, lp, metadata_only, metrics).boxed(),
+            None => stream::empty().boxed(),
+        }
+        .inspect_ok(move |batch| output_rows.add(batch.num_rows()));
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
             self.schema.clone(),
-            None,
-        )?))
+            batches,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::{
+        arrow::array::Array,
+        common::cast::{as_int64_array, as_string_array},
+        prelude::SessionContext,
+    };
+    use serde_json::json;
+
+    use super::*;
+    use crate::{lister::fake::FakeLister, session_context};
+
+    fn objects() -> Vec<serde_json::Value> {
+        vec![
+            json!({"apiVersion": "v1", "kind": "Pod",
+                "metadata": {"name": "web-1", "namespace": "default", "labels": {"app": "web"}},
+                "spec": {"nodeName": "n1"}}),
+            json!({"apiVersion": "v1", "kind": "Pod",
+                "metadata": {"name": "db-1", "namespace": "default", "labels": {"app": "db"}},
+                "spec": {"nodeName": "n2"}}),
+            json!({"apiVersion": "v1", "kind": "Pod",
+                "metadata": {"name": "web-2", "namespace": "other", "labels": {"app": "web"}},
+                "spec": {"nodeName": "n1"}}),
+            json!({"apiVersion": "v1", "kind": "Secret",
+                "metadata": {"name": "token", "namespace": "default"},
+                "type": "Opaque", "data": {"token": "c2VjcmV0"}}),
+        ]
+    }
+
+    fn session(lister: Arc<FakeLister>, show_secrets: bool) -> SessionContext {
+        let cluster = Cluster::from_lister(lister, Namespaces::One("default".to_string()));
+        let factory = KubernetesTableProviderFactory::new("fake".to_string(), cluster)
+            .with_show_secrets(show_secrets);
+        session_context(Arc::new(factory)).unwrap()
+    }
+
+    /// Values of the first column of the results, in order
+    async fn strings(ctx: &SessionContext, sql: &str) -> Vec<Option<String>> {
+        let batches = ctx.sql(sql).await.unwrap().collect().await.unwrap();
+        let mut values = Vec::new();
+        for batch in batches {
+            let column = as_string_array(batch.column(0)).unwrap();
+            values.extend(
+                (0..column.len()).map(|i| column.is_valid(i).then(|| column.value(i).to_string())),
+            );
+        }
+        values
+    }
+
+    #[tokio::test]
+    async fn list_each_namespace_if_forbidden() {
+        let mut objects = objects();
+        for namespace in ["default", "other"] {
+            objects.push(json!({"apiVersion": "v1", "kind": "Namespace",
+                "metadata": {"name": namespace}}));
+        }
+        let lister = Arc::new(FakeLister::new(objects.clone()).with_forbidden("pods", None));
+        let ctx = session(Arc::clone(&lister), false);
+        let names = strings(&ctx, "SELECT metadata.name FROM 'pods/*' ORDER BY 1").await;
+        assert_eq!(
+            names,
+            [
+                Some("db-1".into()),
+                Some("web-1".into()),
+                Some("web-2".into())
+            ]
+        );
+
+        // The namespace of the context is listed if the namespaces cannot be listed either.
+        let lister = Arc::new(
+            FakeLister::new(objects)
+                .with_forbidden("pods", None)
+                .with_forbidden("namespaces", None),
+        );
+        let cluster = Cluster::from_lister(lister, Namespaces::One("default".to_string()));
+        let factory = Arc::new(KubernetesTableProviderFactory::new(
+            "fake".to_string(),
+            cluster,
+        ));
+        let ctx = session_context(Arc::clone(&factory)).unwrap();
+        let names = strings(&ctx, "SELECT metadata.name FROM 'pods/*' ORDER BY 1").await;
+        assert_eq!(names, [Some("db-1".into()), Some("web-1".into())]);
+        assert_eq!(
+            factory.warnings().take(),
+            [
+                "listed pods in context 'fake' in namespace 'default' only, \
+              as neither they nor namespaces can be listed"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn list_default_namespace() {
+        let lister = Arc::new(FakeLister::new(objects()));
+        let ctx = session(Arc::clone(&lister), false);
+        let names = strings(&ctx, "SELECT metadata.name FROM pods ORDER BY 1").await;
+        assert_eq!(names, [Some("db-1".into()), Some("web-1".into())]);
+        let names = strings(&ctx, "SELECT metadata.name FROM 'pods/*' ORDER BY 1").await;
+        assert_eq!(
+            names,
+            [
+                Some("db-1".into()),
+                Some("web-1".into()),
+                Some("web-2".into())
+            ]
+        );
+        let requests = lister.requests();
+        assert!(requests.iter().all(|request| request.resource == "pods"));
+    }
+
+    #[tokio::test]
+    async fn push_down_selectors() {
+        let lister = Arc::new(FakeLister::new(objects()));
+        let ctx = session(Arc::clone(&lister), false);
+        let sql = "SELECT metadata.name FROM pods \
+                   WHERE metadata.labels['app'] = 'web' AND spec.nodeName = 'n1'";
+        assert_eq!(strings(&ctx, sql).await, [Some("web-1".into())]);
+
+        let request = lister.requests().pop().unwrap();
+        assert_eq!(request.namespace.as_deref(), Some("default"));
+        assert_eq!(request.lp.label_selector.as_deref(), Some("app=web"));
+        assert_eq!(
+            request.lp.field_selector.as_deref(),
+            Some("spec.nodeName=n1")
+        );
+        assert!(!request.metadata_only);
+    }
+
+    #[tokio::test]
+    async fn list_metadata_only() {
+        let lister = Arc::new(FakeLister::new(objects()));
+        let ctx = session(Arc::clone(&lister), false);
+        strings(&ctx, "SELECT metadata.name FROM pods").await;
+        assert!(lister.requests().pop().unwrap().metadata_only);
+
+        let batches = ctx
+            .sql("SELECT count(*) FROM pods")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        assert_eq!(as_int64_array(batches[0].column(0)).unwrap().value(0), 2);
+        let request = lister.requests().pop().unwrap();
+        assert!(request.metadata_only);
+        assert_eq!(request.lp.limit, Some(PAGE_SIZE));
+    }
+
+    #[tokio::test]
+    async fn list_pages() {
+        let pods = (0..PAGE_SIZE * 2 + 100)
+            .map(|i| {
+                json!({"apiVersion": "v1", "kind": "Pod",
+                    "metadata": {"name": format!("pod-{}", i), "namespace": "default"},
+                    "spec": {"nodeName": "n1"}})
+            })
+            .collect();
+        let lister = Arc::new(FakeLister::new(pods));
+        let ctx = session(Arc::clone(&lister), false);
+
+        let df = ctx.sql("SELECT spec.nodeName FROM pods").await.unwrap();
+        let planned = lister.requests().len();
+        let batches = df.collect().await.unwrap();
+        let rows: usize = batches.iter().map(RecordBatch::num_rows).sum();
+        assert_eq!(rows, PAGE_SIZE as usize * 2 + 100);
+        // A batch per page, decoded as listed
+        assert_eq!(batches.len(), 3);
+        let continue_tokens: Vec<_> = lister.requests()[planned..]
+            .iter()
+            .map(|request| {
+                assert_eq!(request.lp.limit, Some(PAGE_SIZE));
+                request.lp.continue_token.clone()
+            })
+            .collect();
+        assert_eq!(
+            continue_tokens,
+            [None, Some("500".to_string()), Some("1000".to_string())]
+        );
+
+        let df = ctx
+            .sql("SELECT spec.nodeName FROM pods LIMIT 600")
+            .await
+            .unwrap();
+        let planned = lister.requests().len();
+        let batches = df.collect().await.unwrap();
+        assert_eq!(
+            batches.iter().map(RecordBatch::num_rows).sum::<usize>(),
+            600
+        );
+        let limits: Vec<_> = lister.requests()[planned..]
+            .iter()
+            .map(|request| request.lp.limit)
+            .collect();
+        assert_eq!(limits, [Some(PAGE_SIZE), Some(100)]);
+    }
+
+    #[tokio::test]
+    async fn decode_conflicting_shapes() {
+        let pods = vec![
+            json!({"apiVersion": "v1", "kind": "Pod",
+                "metadata": {"name": "a", "namespace": "default"},
+                "spec": {"nodeName": "n1", "extra": {"x": 1}}}),
+            json!({"apiVersion": "v1", "kind": "Pod",
+                "metadata": {"name": "b", "namespace": "default"},
+                "spec": {"nodeName": "n2", "extra": "plain"}}),
+            json!({"apiVersion": "v1", "kind": "Pod",
+                "metadata": {"name": "c", "namespace": "default"},
+                "spec": {"nodeName": "n3", "extra": [1, 2]}}),
+        ];
+        let ctx = session(Arc::new(FakeLister::new(pods)), false);
+        assert_eq!(
+            strings(&ctx, "SELECT spec.extra FROM pods ORDER BY metadata.name").await,
+            [
+                Some(r#"{"x":1}"#.into()),
+                Some("plain".into()),
+                Some("[1,2]".into())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn redact_secrets() {
+        let sql = "SELECT data['token'] FROM secrets";
+        let ctx = session(Arc::new(FakeLister::new(objects())), false);
+        assert_eq!(strings(&ctx, sql).await, [Some(REDACTED.into())]);
+        let ctx = session(Arc::new(FakeLister::new(objects())), true);
+        assert_eq!(strings(&ctx, sql).await, [Some("c2VjcmV0".into())]);
     }
 }
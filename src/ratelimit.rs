@@ -0,0 +1,169 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, ready},
+    time::Duration,
+};
+
+use tokio::time::{Instant, Sleep};
+use tower::{Layer, Service};
+
+/// Layer limiting the rate of requests with a token bucket,
+/// as the `--qps` and `--burst` options of client-go:
+/// up to `burst` requests are sent at once, refilled at `qps` requests per second.
+#[derive(Debug, Clone)]
+pub struct RateLimitLayer {
+    qps: f64,
+    burst: u32,
+}
+
+impl RateLimitLayer {
+    /// Fails unless `qps` is a positive number.
+    pub fn new(qps: f64, burst: u32) -> anyhow::Result<Self> {
+        if !(qps.is_finite() && qps > 0.0) {
+            anyhow::bail!("qps must be a positive number, got {}", qps);
+        }
+        Ok(Self {
+            qps,
+            burst: burst.max(1),
+        })
+    }
+}
+
+/// Parses a positive number of requests per second.
+pub fn parse_qps(qps: &str) -> Result<f64, String> {
+    match qps.parse::<f64>() {
+        Ok(parsed) if parsed.is_finite() && parsed > 0.0 => Ok(parsed),
+        _ => Err(format!("expected a positive number, got '{}'", qps)),
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit {
+            inner,
+            qps: self.qps,
+            burst: self.burst,
+            tokens: self.burst.into(),
+            refilled_at: Instant::now(),
+            sleep: None,
+        }
+    }
+}
+
+/// Service that is not ready until a token is available.
+pub struct RateLimit<S> {
+    inner: S,
+    qps: f64,
+    burst: u32,
+    tokens: f64,
+    refilled_at: Instant,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl<S> RateLimit<S> {
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.qps).min(self.burst.into());
+        self.refilled_at = now;
+    }
+}
+
+impl<S, R> Service<R> for RateLimit<S>
+where
+    S: Service<R>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        loop {
+            if let Some(sleep) = self.sleep.as_mut() {
+                ready!(sleep.as_mut().poll(cx));
+                self.sleep = None;
+            }
+            self.refill();
+            if self.tokens >= 1.0 {
+                break;
+            }
+            let wait = Duration::from_secs_f64((1.0 - self.tokens) / self.qps);
+            self.sleep = Some(Box::pin(tokio::time::sleep(wait)));
+        }
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        self.tokens -= 1.0;
+        self.inner.call(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::poll_fn;
+
+    use super::*;
+
+    /// Service sending nothing
+    struct Noop;
+
+    impl Service<()> for Noop {
+        type Response = ();
+        type Error = ();
+        type Future = std::future::Ready<Result<(), ()>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), ()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _: ()) -> Self::Future {
+            std::future::ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn invalid_qps() {
+        for qps in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(RateLimitLayer::new(qps, 1).is_err(), "{}", qps);
+        }
+        for qps in ["0", "-1", "NaN", "inf", "x"] {
+            assert!(parse_qps(qps).is_err(), "{}", qps);
+        }
+        assert_eq!(parse_qps("0.5"), Ok(0.5));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn token_bucket() {
+        let mut service = RateLimitLayer::new(2.0, 3).unwrap().layer(Noop);
+        let start = Instant::now();
+        let mut sent = Vec::new();
+        for _ in 0..5 {
+            poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+            service.call(()).await.unwrap();
+            sent.push(start.elapsed());
+        }
+        // The burst is sent at once, then a request every 1/qps seconds.
+        assert_eq!(
+            sent,
+            [0, 0, 0, 500, 1000].map(Duration::from_millis).to_vec()
+        );
+    }
+}
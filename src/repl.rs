@@ -0,0 +1,166 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    io::{self, BufRead, Write},
+    sync::Arc,
+};
+
+use datafusion::{
+    arrow::{
+        array::StringArray,
+        datatypes::{DataType, Field, Schema},
+        record_batch::RecordBatch,
+    },
+    execution::context::SessionContext,
+    logical_expr::TableType,
+};
+
+use kuqu::{
+    output::{self, OutputFormat},
+    params::Param,
+    provider::KubernetesTableProviderFactory,
+    split_statements,
+    views::Views,
+};
+
+use crate::{execute, explain_resolution};
+
+const PROMPT: &str = "kuqu> ";
+const CONTINUATION_PROMPT: &str = "   -> ";
+const HELP: &str = "\
+Statements end with `;`. Views and tables created by CREATE VIEW and CREATE TABLE AS
+remain queryable until the session ends.
+
+\\dv  List the views and tables of the session
+\\?   Show this help
+\\q   Quit";
+
+/// Interactive session executing the statements read from stdin,
+/// all in the same session so that the views and tables created by a statement
+/// (e.g. `CREATE TABLE cached_pods AS SELECT * FROM pods`) are queryable by the following ones.
+pub struct Repl<'a> {
+    pub ctx: &'a SessionContext,
+    pub views: &'a Views,
+    pub factory: &'a KubernetesTableProviderFactory,
+    pub params: &'a [Param],
+    pub format: OutputFormat,
+    pub explain_resolution: bool,
+}
+
+impl Repl<'_> {
+    /// Read and execute statements until `\q` or the end of stdin.
+    /// Errors are reported and do not end the session.
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let mut stdin = io::stdin().lock();
+        let mut buffer = String::new();
+        loop {
+            eprint!(
+                "{}",
+                if buffer.is_empty() {
+                    PROMPT
+                } else {
+                    CONTINUATION_PROMPT
+                }
+            );
+            io::stderr().flush()?;
+
+            let mut line = String::new();
+            if stdin.read_line(&mut line)? == 0 {
+                eprintln!();
+                return Ok(());
+            }
+            let command = line.trim();
+            if buffer.is_empty() && command.starts_with('\\') {
+                match command {
+                    "\\q" => return Ok(()),
+                    "\\dv" => self.list_tables().await.unwrap_or_else(|e| {
+                        eprintln!("Error: {}", e);
+                    }),
+                    "\\?" => eprintln!("{}", HELP),
+                    _ => eprintln!("Unknown command: {} (\\? for help)", command),
+                }
+                continue;
+            }
+
+            buffer.push_str(&line);
+            if !command.ends_with(';') {
+                continue;
+            }
+            if let Err(e) = self.execute(&buffer).await {
+                eprintln!("Error: {}", e);
+            }
+            self.factory.warnings().print();
+            buffer.clear();
+        }
+    }
+
+    async fn execute(&self, sql: &str) -> anyhow::Result<()> {
+        for statement in split_statements(self.ctx, sql)? {
+            if self.explain_resolution {
+                explain_resolution(self.ctx, &statement, self.views, self.factory).await?;
+            }
+            execute(self.ctx, &statement, self.params, self.format, self.views).await?;
+        }
+        Ok(())
+    }
+
+    /// Print the views and tables created in the session,
+    /// along with the views available to create on reference.
+    async fn list_tables(&self) -> anyhow::Result<()> {
+        let state = self.ctx.state();
+        let options = &state.config().options().catalog;
+        let schema = self
+            .ctx
+            .catalog(&options.default_catalog)
+            .and_then(|catalog| catalog.schema(&options.default_schema))
+            .ok_or_else(|| anyhow::anyhow!("no default schema"))?;
+
+        let mut tables = Vec::new();
+        for name in schema.table_names() {
+            let Some(table) = schema.table(&name).await? else {
+                continue;
+            };
+            let kind = match table.table_type() {
+                TableType::View => "view",
+                TableType::Base => "table",
+                TableType::Temporary => "temporary table",
+            };
+            tables.push((name, kind));
+        }
+        for name in self.views.names() {
+            if !tables.iter().any(|(table, _)| table == name) {
+                tables.push((name.to_string(), "view (not created yet)"));
+            }
+        }
+        tables.sort();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("name", DataType::Utf8, false),
+            Field::new("type", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from_iter_values(
+                    tables.iter().map(|(name, _)| name.as_str()),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    tables.iter().map(|(_, kind)| *kind),
+                )),
+            ],
+        )?;
+        output::write_batches(io::stdout().lock(), &[batch], self.format)
+    }
+}
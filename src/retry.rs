@@ -0,0 +1,68 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{future::Future, time::Duration};
+
+/// Default number of retries of a request failing transiently.
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// Delay before the first retry, doubled on each further retry.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(200);
+
+/// Upper bound of the delay between retries.
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Retry policy with exponential backoff for transient API server errors,
+/// i.e. throttling (429) and server errors (5xx).
+#[derive(Debug, Clone, Copy)]
+pub struct Retry {
+    max_retries: u32,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_RETRIES)
+    }
+}
+
+impl Retry {
+    pub fn new(max_retries: u32) -> Self {
+        Self { max_retries }
+    }
+
+    /// Run the request, retrying it while it fails transiently up to `max_retries` times.
+    pub async fn run<T, F, Fut>(&self, mut request: F) -> kube::Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = kube::Result<T>>,
+    {
+        let mut backoff = INITIAL_BACKOFF;
+        let mut retries = 0;
+        loop {
+            match request().await {
+                Err(e) if retries < self.max_retries && is_transient(&e) => {
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    retries += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Check if the error is worth retrying.
+fn is_transient(e: &kube::Error) -> bool {
+    matches!(e, kube::Error::Api(response) if response.code == 429 || response.code >= 500)
+}
@@ -0,0 +1,167 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{path::PathBuf, time::Duration};
+
+use chrono::{SecondsFormat, Utc};
+use datafusion::{arrow::record_batch::RecordBatch, execution::context::SessionContext};
+use serde_json::{Value, json};
+use tokio::{
+    fs::OpenOptions,
+    io::{self, AsyncWriteExt},
+    time::MissedTickBehavior,
+};
+
+use crate::{
+    alert::json_rows,
+    http::post_json,
+    params::{self, Param},
+    provider::KubernetesTableProviderFactory,
+    views::Views,
+};
+
+/// Destination of result sets, each written as a JSON object of its time, context, query and rows
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sink {
+    /// A line per result set to stdout
+    Stdout,
+    /// A line per result set appended to the file
+    File(PathBuf),
+    /// POSTed to the URL
+    Webhook(String),
+}
+
+impl Sink {
+    /// Parse a sink: `-` for stdout, an `http(s)://` URL, or a file path.
+    pub fn parse(sink: &str) -> Result<Self, String> {
+        match sink {
+            "" => Err("empty sink".to_string()),
+            "-" => Ok(Self::Stdout),
+            sink if sink.starts_with("http://") || sink.starts_with("https://") => {
+                Ok(Self::Webhook(sink.to_string()))
+            }
+            sink => Ok(Self::File(PathBuf::from(sink))),
+        }
+    }
+
+    /// Write the result set
+    pub async fn write(&self, record: &Value) -> anyhow::Result<()> {
+        match self {
+            Self::Stdout => {
+                let mut stdout = io::stdout();
+                stdout.write_all(format!("{}\n", record).as_bytes()).await?;
+                stdout.flush().await?;
+            }
+            Self::File(path) => {
+                let mut file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .await
+                    .map_err(|e| anyhow::anyhow!("failed to open {}: {}", path.display(), e))?;
+                file.write_all(format!("{}\n", record).as_bytes()).await?;
+            }
+            Self::Webhook(url) => post_json(url, record).await?,
+        }
+        Ok(())
+    }
+}
+
+/// Result set of the query as written to sinks
+pub fn record(context: &str, query: &str, batches: &[RecordBatch]) -> anyhow::Result<Value> {
+    Ok(json!({
+        "time": Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        "context": context,
+        "query": query,
+        "count": batches.iter().map(|batch| batch.num_rows()).sum::<usize>(),
+        "rows": json_rows(batches)?,
+    }))
+}
+
+/// Run the statements, writing the result set of each to the sinks (stdout if none),
+/// then again every interval if given, until interrupted.
+///
+/// Failures are reported and, when running every interval, retried at the next one.
+pub async fn run(
+    ctx: &SessionContext,
+    factory: &KubernetesTableProviderFactory,
+    views: &Views,
+    statements: &[String],
+    params: &[Param],
+    sinks: &[Sink],
+    every: Option<Duration>,
+) -> anyhow::Result<()> {
+    let stdout = [Sink::Stdout];
+    let sinks = if sinks.is_empty() { &stdout[..] } else { sinks };
+    let mut ticker = every.map(|every| {
+        let mut ticker = tokio::time::interval(every);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        ticker
+    });
+    loop {
+        if let Some(ticker) = &mut ticker {
+            ticker.tick().await;
+        }
+        let mut failed = 0;
+        for statement in statements {
+            let result = run_statement(ctx, views, statement, params).await;
+            factory.warnings().print();
+            let written = match result {
+                Ok(batches) => write(sinks, factory.context(), statement, &batches).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = written {
+                eprintln!("Error: {}: {}", statement.trim(), e);
+                failed += 1;
+            }
+        }
+        if ticker.is_none() {
+            if failed > 0 {
+                anyhow::bail!("{} of the queries failed", failed);
+            }
+            return Ok(());
+        }
+    }
+}
+
+async fn run_statement(
+    ctx: &SessionContext,
+    views: &Views,
+    statement: &str,
+    params: &[Param],
+) -> anyhow::Result<Vec<RecordBatch>> {
+    views.register(ctx, statement).await?;
+    let df = params::bind(ctx.sql(statement).await?, params)?;
+    Ok(df.collect().await?)
+}
+
+/// Write the result set to every sink, failing if any failed
+async fn write(
+    sinks: &[Sink],
+    context: &str,
+    statement: &str,
+    batches: &[RecordBatch],
+) -> anyhow::Result<()> {
+    let record = record(context, statement, batches)?;
+    let mut errors = Vec::new();
+    for sink in sinks {
+        if let Err(e) = sink.write(&record).await {
+            errors.push(e.to_string());
+        }
+    }
+    if !errors.is_empty() {
+        anyhow::bail!("{}", errors.join("; "));
+    }
+    Ok(())
+}
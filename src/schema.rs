@@ -0,0 +1,341 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{iter, sync::Arc};
+
+use datafusion::{
+    arrow::{
+        datatypes::{DataType, Field, FieldRef, Fields, Schema, SchemaRef},
+        error::ArrowError,
+        json::reader::infer_json_schema_from_iterator,
+    },
+    common::{DataFusionError, Result as DataFusionResult},
+};
+
+use serde_json::Value;
+
+use crate::dynamic::DynamicObject;
+
+/// Remove the field if it can never hold data, i.e. it is an empty struct
+/// (e.g. `securityContext: {}`) or a list of those.
+/// Such fields cannot be written to Parquet by `COPY ... TO`.
+fn prune_empty_struct(field: &FieldRef) -> Option<FieldRef> {
+    let data_type = match field.data_type() {
+        DataType::Struct(fields) => {
+            let fields: Fields = fields.iter().filter_map(prune_empty_struct).collect();
+            if fields.is_empty() {
+                return None;
+            }
+            DataType::Struct(fields)
+        }
+        DataType::List(item) => DataType::List(prune_empty_struct(item)?),
+        _ => return Some(field.clone()),
+    };
+    Some(Arc::new(Field::clone(field).with_data_type(data_type)))
+}
+
+/// Remove fields that can never hold data from the schema.
+/// Fields missing from the schema are skipped by the JSON reader.
+pub fn prune_schema(schema: &Schema) -> SchemaRef {
+    let fields: Fields = schema
+        .fields()
+        .iter()
+        .filter_map(prune_empty_struct)
+        .collect();
+    Arc::new(Schema::new_with_metadata(fields, schema.metadata.clone()))
+}
+
+/// Merge two types into one that can hold values of both.
+///
+/// - `Null` is absorbed by the other type
+/// - `Int64` and `Float64` widen to `Float64`
+/// - Structs are merged field by field, and lists item by item
+/// - Any other types widen to `Utf8`, into which the JSON reader coerces primitives
+///   and [`conform`] writes objects and arrays as JSON text
+fn merge_data_type(a: &DataType, b: &DataType) -> DataType {
+    match (a, b) {
+        (a, b) if a == b => a.clone(),
+        (DataType::Null, other) | (other, DataType::Null) => other.clone(),
+        (DataType::Int64, DataType::Float64) | (DataType::Float64, DataType::Int64) => {
+            DataType::Float64
+        }
+        (DataType::Struct(a), DataType::Struct(b)) => DataType::Struct(merge_fields(a, b)),
+        (DataType::List(a), DataType::List(b)) => DataType::List(merge_field(a, b.data_type())),
+        _ => DataType::Utf8,
+    }
+}
+
+fn merge_field(field: &FieldRef, data_type: &DataType) -> FieldRef {
+    Arc::new(
+        Field::clone(field)
+            .with_data_type(merge_data_type(field.data_type(), data_type))
+            .with_nullable(true),
+    )
+}
+
+/// Union of the fields, in order of first appearance,
+/// with the types of fields present in both merged.
+fn merge_fields(a: &Fields, b: &Fields) -> Fields {
+    let mut fields: Vec<FieldRef> = a
+        .iter()
+        .map(|field| match b.find(field.name()) {
+            Some((_, other)) => merge_field(field, other.data_type()),
+            None => field.clone(),
+        })
+        .collect();
+    fields.extend(
+        b.iter()
+            .filter(|field| a.find(field.name()).is_none())
+            .cloned(),
+    );
+    fields.into()
+}
+
+/// Write the objects and arrays of the value that the fields type as strings
+/// (having been merged with scalars) as JSON text, so that the JSON reader can decode them.
+pub fn conform(value: &mut Value, fields: &Fields) {
+    let Value::Object(object) = value else {
+        return;
+    };
+    for field in fields {
+        if let Some(value) = object.get_mut(field.name()) {
+            conform_value(value, field.data_type());
+        }
+    }
+}
+
+fn conform_value(value: &mut Value, data_type: &DataType) {
+    match (data_type, &mut *value) {
+        (DataType::Utf8, Value::Object(_) | Value::Array(_)) => {
+            *value = Value::String(value.to_string());
+        }
+        (DataType::Struct(fields), Value::Object(_)) => conform(value, fields),
+        (DataType::List(item), Value::Array(items)) => {
+            for value in items {
+                conform_value(value, item.data_type());
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Merge the schemas of the same resource (e.g. from different clusters) into one
+/// that can hold the objects of all of them.
+pub fn merge_schemas(schemas: &[SchemaRef]) -> SchemaRef {
+    let fields = schemas.iter().fold(Fields::empty(), |fields, schema| {
+        merge_fields(&fields, schema.fields())
+    });
+    Arc::new(Schema::new(fields))
+}
+
+/// Minimal schema of any Kubernetes object,
+/// used when there are no objects to infer the schema from.
+pub fn minimal_schema() -> SchemaRef {
+    object_schema(minimal_metadata_fields())
+}
+
+/// Schema of any Kubernetes object, for tables planned without objects (e.g. in a dry run):
+/// the minimal schema with the labels and annotations as maps, since their keys are unknown.
+pub fn unsampled_schema() -> SchemaRef {
+    let map = |name: &str| {
+        Field::new_map(
+            name,
+            "entries",
+            Field::new("keys", DataType::Utf8, false),
+            Field::new("values", DataType::Utf8, true),
+            false,
+            true,
+        )
+    };
+    let mut metadata = minimal_metadata_fields();
+    metadata.extend([map("labels"), map("annotations")]);
+    object_schema(metadata)
+}
+
+fn minimal_metadata_fields() -> Vec<Field> {
+    vec![
+        Field::new("name", DataType::Utf8, true),
+        Field::new("namespace", DataType::Utf8, true),
+        Field::new("uid", DataType::Utf8, true),
+        Field::new("resourceVersion", DataType::Utf8, true),
+        Field::new("creationTimestamp", DataType::Utf8, true),
+    ]
+}
+
+fn object_schema(metadata: Vec<Field>) -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("apiVersion", DataType::Utf8, true),
+        Field::new("kind", DataType::Utf8, true),
+        Field::new("metadata", DataType::Struct(metadata.into()), true),
+    ]))
+}
+
+/// Infer schema from objects
+///
+/// The schema of each object is inferred separately and then merged,
+/// so that objects with disjoint fields (e.g. Pods with different volume types)
+/// or conflicting types contribute all of their fields instead of failing inference.
+pub fn infer_schema(objects: &[DynamicObject]) -> DataFusionResult<SchemaRef> {
+    let values = objects
+        .iter()
+        .map(serde_json::to_value)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| DataFusionError::External(Box::new(e)))?;
+    infer_json_schema(values).map_err(|e| DataFusionError::External(Box::new(e)))
+}
+
+/// Infer schema from JSON values, each separately and then merged as [`infer_schema`] does.
+/// Values are to be decoded after [`conform`]ing them to the schema.
+pub fn infer_json_schema(values: impl IntoIterator<Item = Value>) -> Result<SchemaRef, ArrowError> {
+    let mut fields = Fields::empty();
+    for value in values {
+        let schema = infer_json_schema_from_iterator(iter::once(Ok(value)))?;
+        fields = merge_fields(&fields, schema.fields());
+    }
+    Ok(prune_schema(&Schema::new(fields)))
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::json::ReaderBuilder;
+    use serde_json::json;
+
+    use super::*;
+
+    fn list(data_type: DataType) -> DataType {
+        DataType::List(Arc::new(Field::new("item", data_type, true)))
+    }
+
+    fn object(value: Value) -> DynamicObject {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn merge_types() {
+        let a = DataType::Struct(vec![Field::new("x", DataType::Int64, true)].into());
+        let b = DataType::Struct(vec![Field::new("y", DataType::Boolean, true)].into());
+        for (a, b, merged) in [
+            (DataType::Int64, DataType::Int64, DataType::Int64),
+            (DataType::Null, DataType::Boolean, DataType::Boolean),
+            (DataType::Int64, DataType::Float64, DataType::Float64),
+            (DataType::Int64, DataType::Utf8, DataType::Utf8),
+            (DataType::Boolean, DataType::Int64, DataType::Utf8),
+            (
+                a.clone(),
+                b,
+                DataType::Struct(
+                    vec![
+                        Field::new("x", DataType::Int64, true),
+                        Field::new("y", DataType::Boolean, true),
+                    ]
+                    .into(),
+                ),
+            ),
+            (
+                list(DataType::Int64),
+                list(DataType::Float64),
+                list(DataType::Float64),
+            ),
+            (list(DataType::Int64), DataType::Int64, DataType::Utf8),
+            (a.clone(), DataType::Utf8, DataType::Utf8),
+            (a, list(DataType::Int64), DataType::Utf8),
+        ] {
+            assert_eq!(merge_data_type(&a, &b), merged, "{} and {}", a, b);
+            // Fields of structs are in order of first appearance.
+            if !matches!(merged, DataType::Struct(_)) {
+                assert_eq!(merge_data_type(&b, &a), merged, "{} and {}", b, a);
+            }
+        }
+    }
+
+    #[test]
+    fn infer_disjoint_and_conflicting_objects() {
+        let objects = [
+            object(
+                json!({"metadata": {"name": "a"}, "spec": {"volume": {"emptyDir": {}}, "x": 1}}),
+            ),
+            object(
+                json!({"metadata": {"name": "b"}, "spec": {"volume": {"hostPath": {"path": "/"}}, "x": "1"}}),
+            ),
+            object(json!({"metadata": {"name": "c"}, "status": "Success"})),
+            object(json!({"metadata": {"name": "d"}, "status": {"phase": "Running"}})),
+        ];
+        let schema = infer_schema(&objects).unwrap();
+        let spec = schema.field_with_name("spec").unwrap();
+        let DataType::Struct(spec) = spec.data_type() else {
+            panic!("spec is {}", spec.data_type());
+        };
+        assert_eq!(spec.find("x").unwrap().1.data_type(), &DataType::Utf8);
+        let DataType::Struct(volume) = spec.find("volume").unwrap().1.data_type() else {
+            panic!("volume is not a struct");
+        };
+        // The empty `emptyDir` is pruned.
+        let volume: Vec<_> = volume.iter().map(|field| field.name().as_str()).collect();
+        assert_eq!(volume, ["hostPath"]);
+        assert_eq!(
+            schema.field_with_name("status").unwrap().data_type(),
+            &DataType::Utf8
+        );
+    }
+
+    #[test]
+    fn merge_schemas_of_clusters() {
+        let a = Arc::new(Schema::new(vec![
+            Field::new("kind", DataType::Utf8, true),
+            Field::new("count", DataType::Int64, true),
+        ]));
+        let b = Arc::new(Schema::new(vec![
+            Field::new("count", DataType::Float64, true),
+            Field::new("extra", DataType::Boolean, true),
+        ]));
+        let merged = merge_schemas(&[a, b]);
+        let fields: Vec<_> = merged
+            .fields()
+            .iter()
+            .map(|field| (field.name().as_str(), field.data_type().clone()))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("kind", DataType::Utf8),
+                ("count", DataType::Float64),
+                ("extra", DataType::Boolean),
+            ]
+        );
+        assert!(merge_schemas(&[]).fields().is_empty());
+    }
+
+    #[test]
+    fn decode_conformed_values() {
+        let mut values = vec![
+            json!({"status": "Success", "items": [1, 2]}),
+            json!({"status": {"code": 200}, "items": 3}),
+            json!({"status": ["a"], "items": [{"a": 1}]}),
+        ];
+        let schema = infer_json_schema(values.clone()).unwrap();
+        for value in &mut values {
+            conform(value, schema.fields());
+        }
+        assert_eq!(values[1]["status"], json!(r#"{"code":200}"#));
+        assert_eq!(values[2]["items"], json!(r#"[{"a":1}]"#));
+
+        let mut decoder = ReaderBuilder::new(Arc::clone(&schema))
+            .with_coerce_primitive(true)
+            .build_decoder()
+            .unwrap();
+        decoder.serialize(&values).unwrap();
+        let batch = decoder.flush().unwrap().unwrap();
+        assert_eq!(batch.num_rows(), 3);
+    }
+}
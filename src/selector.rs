@@ -0,0 +1,312 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use datafusion::{
+    common::ScalarValue,
+    logical_expr::{BinaryExpr, Expr, Operator, expr::InList},
+};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::APIResource;
+
+/// Fields supported in field selectors by every resource.
+const COMMON_FIELDS: [&str; 2] = ["metadata.name", "metadata.namespace"];
+
+/// Fields supported in field selectors by Pods, in addition to `COMMON_FIELDS`.
+const POD_FIELDS: [&str; 2] = ["spec.nodeName", "status.phase"];
+
+/// Returns the path of nested field accesses,
+/// e.g. `metadata.labels.app` => `["metadata", "labels", "app"]`.
+fn field_path(expr: &Expr) -> Option<Vec<String>> {
+    match expr {
+        Expr::Column(column) => Some(vec![column.name.clone()]),
+        Expr::ScalarFunction(func) if func.name() == "get_field" => match func.args.as_slice() {
+            [base, key] => {
+                let mut path = field_path(base)?;
+                path.push(string_literal(key)?);
+                Some(path)
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns the value of a string literal.
+fn string_literal(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Literal(
+            ScalarValue::Utf8(Some(value))
+            | ScalarValue::LargeUtf8(Some(value))
+            | ScalarValue::Utf8View(Some(value)),
+            _,
+        ) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+/// Returns the label key if the expression refers to `metadata.labels.<key>`.
+fn label_key(expr: &Expr) -> Option<String> {
+    match field_path(expr)?.as_slice() {
+        [metadata, labels, key] if metadata == "metadata" && labels == "labels" => {
+            is_selector_token(key).then(|| key.clone())
+        }
+        _ => None,
+    }
+}
+
+/// Check that the value can be embedded in a selector as is.
+/// Label keys and values are restricted to alphanumerics, `-`, `_`, `.` and `/`,
+/// so anything else could never match and would break the selector syntax.
+fn is_selector_token(value: &str) -> bool {
+    value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'))
+}
+
+/// Returns the value if the expression is a string literal usable in a selector.
+fn selector_value(expr: &Expr) -> Option<String> {
+    string_literal(expr).filter(|value| is_selector_token(value) && !value.contains('/'))
+}
+
+/// Translate a filter into a label selector requirement.
+///
+/// Supported predicates:
+/// - `metadata.labels.<key> = 'value'` => `key=value`
+/// - `metadata.labels.<key> != 'value'` => `key!=value`
+/// - `metadata.labels.<key> IN ('a', 'b')` => `key in (a,b)`
+/// - `metadata.labels.<key> NOT IN ('a', 'b')` => `key notin (a,b)`
+/// - `metadata.labels.<key> IS NOT NULL` => `key`
+///
+/// Each requirement selects a superset of the rows matched by the predicate
+/// (e.g. `key!=value` also selects objects without the label),
+/// so the predicate must still be evaluated on the returned objects.
+pub fn label_requirement(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::BinaryExpr(BinaryExpr { left, op, right }) => {
+            let op = match op {
+                Operator::Eq => "=",
+                Operator::NotEq => "!=",
+                _ => return None,
+            };
+            let (key, value) = match (label_key(left), label_key(right)) {
+                (Some(key), None) => (key, selector_value(right)?),
+                (None, Some(key)) => (key, selector_value(left)?),
+                _ => return None,
+            };
+            Some(format!("{}{}{}", key, op, value))
+        }
+        Expr::InList(InList {
+            expr,
+            list,
+            negated,
+        }) => {
+            let key = label_key(expr)?;
+            let values = list
+                .iter()
+                .map(selector_value)
+                .collect::<Option<Vec<_>>>()?;
+            let op = if *negated { "notin" } else { "in" };
+            Some(format!("{} {} ({})", key, op, values.join(",")))
+        }
+        Expr::IsNotNull(expr) => label_key(expr),
+        _ => None,
+    }
+}
+
+/// Returns the field name if the expression refers to a field
+/// that the resource supports in field selectors.
+fn field_name(expr: &Expr, api_resource: &APIResource) -> Option<String> {
+    let field = field_path(expr)?.join(".");
+    let is_pod = api_resource.group.as_deref() == Some("core") && api_resource.name == "pods";
+    let supported = match field.as_str() {
+        "metadata.namespace" => api_resource.namespaced,
+        field if COMMON_FIELDS.contains(&field) => true,
+        field if POD_FIELDS.contains(&field) => is_pod,
+        _ => false,
+    };
+    supported.then_some(field)
+}
+
+/// Translate a filter into a field selector requirement.
+///
+/// Supported predicates:
+/// - `metadata.name` and `metadata.namespace` for every resource
+/// - `spec.nodeName` and `status.phase` for Pods
+///
+/// compared with `=` or `!=` against a string literal.
+pub fn field_requirement(expr: &Expr, api_resource: &APIResource) -> Option<String> {
+    let Expr::BinaryExpr(BinaryExpr { left, op, right }) = expr else {
+        return None;
+    };
+    let op = match op {
+        Operator::Eq => "=",
+        Operator::NotEq => "!=",
+        _ => return None,
+    };
+    let (field, value) = match (
+        field_name(left, api_resource),
+        field_name(right, api_resource),
+    ) {
+        (Some(field), None) => (field, selector_value(right)?),
+        (None, Some(field)) => (field, selector_value(left)?),
+        _ => return None,
+    };
+    Some(format!("{}{}{}", field, op, value))
+}
+
+/// Build a field selector from the filters that can be translated.
+/// Returns `None` if none of them can be.
+pub fn field_selector(filters: &[Expr], api_resource: &APIResource) -> Option<String> {
+    let requirements = filters
+        .iter()
+        .filter_map(|filter| field_requirement(filter, api_resource))
+        .collect::<Vec<_>>();
+    (!requirements.is_empty()).then(|| requirements.join(","))
+}
+
+/// Build a label selector from the filters that can be translated.
+/// Returns `None` if none of them can be.
+pub fn label_selector(filters: &[Expr]) -> Option<String> {
+    let requirements = filters
+        .iter()
+        .filter_map(label_requirement)
+        .collect::<Vec<_>>();
+    (!requirements.is_empty()).then(|| requirements.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::{
+        functions::core::expr_fn::get_field,
+        logical_expr::{col, lit},
+    };
+
+    use super::*;
+
+    fn label(key: &str) -> Expr {
+        get_field(get_field(col("metadata"), "labels"), key)
+    }
+
+    #[test]
+    fn label_equality() {
+        assert_eq!(
+            label_requirement(&label("app").eq(lit("web"))).as_deref(),
+            Some("app=web")
+        );
+        assert_eq!(
+            label_requirement(&lit("web").not_eq(label("app"))).as_deref(),
+            Some("app!=web")
+        );
+        assert_eq!(
+            label_requirement(&label("app.kubernetes.io/name").eq(lit("web"))).as_deref(),
+            Some("app.kubernetes.io/name=web")
+        );
+    }
+
+    #[test]
+    fn label_set() {
+        let values = vec![lit("web"), lit("db")];
+        assert_eq!(
+            label_requirement(&label("app").in_list(values.clone(), false)).as_deref(),
+            Some("app in (web,db)")
+        );
+        assert_eq!(
+            label_requirement(&label("app").in_list(values, true)).as_deref(),
+            Some("app notin (web,db)")
+        );
+        assert_eq!(
+            label_requirement(&label("app").is_not_null()).as_deref(),
+            Some("app")
+        );
+    }
+
+    #[test]
+    fn label_unsupported() {
+        // Values that cannot be written in a selector are left to DataFusion.
+        assert_eq!(label_requirement(&label("app").eq(lit("a b"))), None);
+        assert_eq!(label_requirement(&label("app").eq(lit("a/b"))), None);
+        assert_eq!(label_requirement(&label("app").gt(lit("web"))), None);
+        assert_eq!(label_requirement(&label("app").is_null()), None);
+        assert_eq!(label_requirement(&label("app").eq(label("tier"))), None);
+        let annotation = get_field(get_field(col("metadata"), "annotations"), "app");
+        assert_eq!(label_requirement(&annotation.eq(lit("web"))), None);
+    }
+
+    #[test]
+    fn label_selector_of_filters() {
+        let filters = [
+            label("app").eq(lit("web")),
+            label("tier").not_eq(lit("cache")),
+            col("kind").eq(lit("Pod")),
+        ];
+        assert_eq!(
+            label_selector(&filters).as_deref(),
+            Some("app=web,tier!=cache")
+        );
+        assert_eq!(label_selector(&filters[2..]), None);
+    }
+
+    fn api_resource(group: &str, name: &str, namespaced: bool) -> APIResource {
+        APIResource {
+            group: Some(group.to_string()),
+            name: name.to_string(),
+            namespaced,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn common_fields() {
+        let deployments = api_resource("apps", "deployments", true);
+        let name = get_field(col("metadata"), "name");
+        let namespace = get_field(col("metadata"), "namespace");
+        assert_eq!(
+            field_requirement(&name.clone().eq(lit("web")), &deployments).as_deref(),
+            Some("metadata.name=web")
+        );
+        assert_eq!(
+            field_requirement(&lit("default").not_eq(namespace.clone()), &deployments).as_deref(),
+            Some("metadata.namespace!=default")
+        );
+        // Cluster-scoped resources have no namespace to select.
+        let nodes = api_resource("core", "nodes", false);
+        assert_eq!(field_requirement(&namespace.eq(lit("a")), &nodes), None);
+        assert_eq!(field_requirement(&name.gt(lit("a")), &nodes), None);
+    }
+
+    #[test]
+    fn pod_fields() {
+        let pods = api_resource("core", "pods", true);
+        let node_name = get_field(col("spec"), "nodeName");
+        let phase = get_field(col("status"), "phase");
+        assert_eq!(
+            field_requirement(&node_name.clone().eq(lit("n1")), &pods).as_deref(),
+            Some("spec.nodeName=n1")
+        );
+        assert_eq!(
+            field_selector(
+                &[
+                    phase.clone().not_eq(lit("Running")),
+                    node_name.clone().eq(lit("n1"))
+                ],
+                &pods
+            )
+            .as_deref(),
+            Some("status.phase!=Running,spec.nodeName=n1")
+        );
+        // Only Pods support these fields.
+        let jobs = api_resource("batch", "jobs", true);
+        assert_eq!(field_requirement(&phase.eq(lit("Running")), &jobs), None);
+        assert_eq!(field_selector(&[node_name.eq(lit("n1"))], &jobs), None);
+    }
+}
@@ -0,0 +1,358 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::HashMap, net::SocketAddr, sync::Arc};
+
+use chrono::{SecondsFormat, Utc};
+use datafusion::{
+    arrow::{
+        datatypes::SchemaRef, ipc::writer::StreamWriter, json::ArrayWriter,
+        record_batch::RecordBatch,
+    },
+    execution::context::{SQLOptions, SessionContext},
+};
+use futures::future::BoxFuture;
+use http::{Method, Request, StatusCode, header::ACCEPT};
+use http_body_util::{BodyExt, Limited};
+use hyper::body::{Bytes, Incoming};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::sync::{Mutex, OnceCell};
+
+use crate::{
+    http::{response, serve},
+    params::{self, Param},
+    views::Views,
+    warnings::Warnings,
+};
+
+/// Media type of Arrow IPC streams, returned when accepted by the client
+const ARROW_STREAM: &str = "application/vnd.apache.arrow.stream";
+
+/// Maximum size of request bodies
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// Maximum number of sessions, each of a context and a namespace requested
+const MAX_SESSIONS: usize = 256;
+
+/// Session of a context with a default namespace, shared by the requests for them
+#[derive(Clone)]
+pub struct Session {
+    pub ctx: SessionContext,
+    pub warnings: Warnings,
+}
+
+/// Connects a session to the context and the default namespace of a request,
+/// those of the server if not given.
+pub type Connect = Arc<
+    dyn Fn(Option<String>, Option<String>) -> BoxFuture<'static, anyhow::Result<Session>>
+        + Send
+        + Sync,
+>;
+
+/// Context and default namespace of a session, those of the server if not given
+type SessionKey = (Option<String>, Option<String>);
+
+/// Sessions of the contexts and the namespaces requested, connected at the first request
+pub struct Sessions {
+    connect: Connect,
+    sessions: Mutex<HashMap<SessionKey, Arc<OnceCell<Session>>>>,
+}
+
+impl Sessions {
+    pub fn new(connect: Connect) -> Self {
+        Self {
+            connect,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the session of the context and the namespace, connecting at the first request
+    pub async fn get(
+        &self,
+        context: Option<String>,
+        namespace: Option<String>,
+    ) -> anyhow::Result<Session> {
+        let key = (context, namespace);
+        let cell = {
+            let mut sessions = self.sessions.lock().await;
+            if !sessions.contains_key(&key) && sessions.len() >= MAX_SESSIONS {
+                anyhow::bail!("too many sessions (at most {})", MAX_SESSIONS);
+            }
+            Arc::clone(sessions.entry(key.clone()).or_default())
+        };
+        // Connect without the lock, so that other sessions are not waiting for this one.
+        match cell
+            .get_or_try_init(|| (self.connect)(key.0.clone(), key.1.clone()))
+            .await
+        {
+            Ok(session) => Ok(session.clone()),
+            Err(e) => {
+                let mut sessions = self.sessions.lock().await;
+                if sessions.get(&key).is_some_and(|cell| cell.get().is_none()) {
+                    sessions.remove(&key);
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Options of the statements allowed from clients: queries only
+pub fn read_only() -> SQLOptions {
+    SQLOptions::new()
+        .with_allow_ddl(false)
+        .with_allow_dml(false)
+        .with_allow_statements(false)
+}
+
+/// Body of `POST /query`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QueryRequest {
+    query: String,
+    /// Context to query instead of that of the server
+    #[serde(default)]
+    context: Option<String>,
+    /// Namespace to read tables without a namespace from, instead of that of the server
+    #[serde(default)]
+    namespace: Option<String>,
+    /// Values of the placeholders, e.g. `{"ns": "default"}` for `$ns`
+    #[serde(default)]
+    params: HashMap<String, Value>,
+}
+
+/// Runs read-only queries posted to `/query`, returning the rows as a JSON array,
+/// or as an Arrow IPC stream if accepted. Each request is logged to stderr.
+pub struct Server {
+    sessions: Sessions,
+    views: Views,
+}
+
+impl Server {
+    pub fn new(connect: Connect, views: Views) -> Self {
+        Self {
+            sessions: Sessions::new(connect),
+            views,
+        }
+    }
+
+    /// Serve on the address until interrupted
+    pub async fn serve(self, addr: SocketAddr) -> anyhow::Result<()> {
+        let server = Arc::new(self);
+        serve(addr, move |request, remote| {
+            let server = Arc::clone(&server);
+            async move { server.handle(request, remote).await }
+        })
+        .await
+    }
+
+    async fn handle(
+        &self,
+        request: Request<Incoming>,
+        remote: SocketAddr,
+    ) -> http::Response<http_body_util::Full<Bytes>> {
+        if request.uri().path() != "/query" {
+            return error(StatusCode::NOT_FOUND, "not found");
+        }
+        if request.method() != Method::POST {
+            return error(StatusCode::METHOD_NOT_ALLOWED, "use POST");
+        }
+        let arrow = request
+            .headers()
+            .get(ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .is_some_and(|accept| accept.contains(ARROW_STREAM));
+        let body = match Limited::new(request.into_body(), MAX_BODY_SIZE)
+            .collect()
+            .await
+        {
+            Ok(body) => body.to_bytes(),
+            Err(e) => {
+                return error(
+                    StatusCode::BAD_REQUEST,
+                    &format!("failed to read the body: {}", e),
+                );
+            }
+        };
+        let request: QueryRequest = match serde_json::from_slice(&body) {
+            Ok(request) => request,
+            Err(e) => return error(StatusCode::BAD_REQUEST, &format!("invalid request: {}", e)),
+        };
+
+        let result = self.query(&request).await;
+        let (status, rows) = match &result {
+            Ok((_, batches)) => (
+                StatusCode::OK,
+                batches
+                    .iter()
+                    .map(|batch| batch.num_rows())
+                    .sum::<usize>()
+                    .to_string(),
+            ),
+            Err(_) => (StatusCode::BAD_REQUEST, "-".to_string()),
+        };
+        eprintln!(
+            "{} {} context={} namespace={} status={} rows={} query={:?}",
+            Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            remote,
+            request.context.as_deref().unwrap_or("-"),
+            request.namespace.as_deref().unwrap_or("-"),
+            status.as_u16(),
+            rows,
+            request.query,
+        );
+        let (schema, batches) = match result {
+            Ok(result) => result,
+            Err(e) => return error(status, &e.to_string()),
+        };
+        let body = if arrow {
+            arrow_stream(schema, &batches).map(|body| (ARROW_STREAM, body))
+        } else {
+            json_rows(&batches).map(|body| ("application/json", body))
+        };
+        match body {
+            Ok((content_type, body)) => response(StatusCode::OK, content_type, body),
+            Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+        }
+    }
+
+    /// Run the query of the request, rejecting statements other than queries.
+    async fn query(&self, request: &QueryRequest) -> anyhow::Result<(SchemaRef, Vec<RecordBatch>)> {
+        let session = self
+            .sessions
+            .get(request.context.clone(), request.namespace.clone())
+            .await?;
+        let params: Vec<Param> = request
+            .params
+            .iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                };
+                (name.clone(), value)
+            })
+            .collect();
+        self.views.register(&session.ctx, &request.query).await?;
+        let df = session
+            .ctx
+            .sql_with_options(&request.query, read_only())
+            .await?;
+        let df = params::bind(df, &params)?;
+        let schema = df.schema().inner().clone();
+        let result = df.collect().await;
+        session.warnings.print();
+        Ok((schema, result?))
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> http::Response<http_body_util::Full<Bytes>> {
+    response(
+        status,
+        "application/json",
+        json!({ "error": message }).to_string(),
+    )
+}
+
+/// Rows of the batches as a JSON array
+fn json_rows(batches: &[RecordBatch]) -> anyhow::Result<Vec<u8>> {
+    let mut writer = ArrayWriter::new(Vec::new());
+    writer.write_batches(&batches.iter().collect::<Vec<_>>())?;
+    writer.finish()?;
+    let buffer = writer.into_inner();
+    if buffer.is_empty() {
+        return Ok(b"[]".to_vec());
+    }
+    Ok(buffer)
+}
+
+/// Batches as an Arrow IPC stream
+fn arrow_stream(schema: SchemaRef, batches: &[RecordBatch]) -> anyhow::Result<Vec<u8>> {
+    let mut writer = StreamWriter::try_new(Vec::new(), &schema)?;
+    for batch in batches {
+        writer.write(batch)?;
+    }
+    writer.finish()?;
+    Ok(writer.into_inner()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use super::*;
+
+    /// Sessions connecting to any context but `slow`, which never connects, and `bad`
+    fn sessions(connects: Arc<AtomicUsize>) -> Sessions {
+        Sessions::new(Arc::new(move |context, _| {
+            let connects = Arc::clone(&connects);
+            Box::pin(async move {
+                connects.fetch_add(1, Ordering::Relaxed);
+                match context.as_deref() {
+                    Some("slow") => std::future::pending().await,
+                    Some("bad") => anyhow::bail!("context 'bad' not found"),
+                    _ => Ok(Session {
+                        ctx: SessionContext::new(),
+                        warnings: Warnings::default(),
+                    }),
+                }
+            })
+        }))
+    }
+
+    #[tokio::test]
+    async fn connect_once() {
+        let connects = Arc::new(AtomicUsize::new(0));
+        let sessions = sessions(Arc::clone(&connects));
+        sessions.get(None, None).await.unwrap();
+        sessions.get(None, None).await.unwrap();
+        sessions.get(None, Some("web".to_string())).await.unwrap();
+        assert_eq!(connects.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn connect_without_waiting_for_others() {
+        let sessions = Arc::new(sessions(Arc::new(AtomicUsize::new(0))));
+        let slow = tokio::spawn({
+            let sessions = Arc::clone(&sessions);
+            async move { sessions.get(Some("slow".to_string()), None).await }
+        });
+        tokio::task::yield_now().await;
+        tokio::time::timeout(Duration::from_secs(5), sessions.get(None, None))
+            .await
+            .expect("blocked by the slow session")
+            .unwrap();
+        slow.abort();
+    }
+
+    #[tokio::test]
+    async fn forget_failed_sessions() {
+        let connects = Arc::new(AtomicUsize::new(0));
+        let sessions = sessions(Arc::clone(&connects));
+        for _ in 0..2 {
+            assert!(sessions.get(Some("bad".to_string()), None).await.is_err());
+        }
+        assert_eq!(connects.load(Ordering::Relaxed), 2);
+        assert!(sessions.sessions.lock().await.is_empty());
+
+        for i in 0..MAX_SESSIONS {
+            sessions.get(None, Some(i.to_string())).await.unwrap();
+        }
+        assert!(sessions.get(None, None).await.is_err());
+    }
+}
@@ -0,0 +1,167 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use datafusion::{
+    arrow::{array::RecordBatch, datatypes::DataType},
+    common::ParamValues,
+    dataframe::DataFrame,
+    execution::context::SessionContext,
+};
+
+use crate::{
+    params::{self, Param},
+    views::Views,
+};
+
+/// Session executing many queries against the same clusters.
+///
+/// The resources of each cluster are discovered once per session,
+/// not once per query. The views a query references are created before planning it.
+#[derive(Clone)]
+pub struct KuquSession {
+    ctx: SessionContext,
+    views: Views,
+}
+
+impl From<SessionContext> for KuquSession {
+    fn from(ctx: SessionContext) -> Self {
+        Self {
+            ctx,
+            views: Views::builtin(),
+        }
+    }
+}
+
+impl KuquSession {
+    /// Set the views queryable by name, the built-in views by default
+    pub fn with_views(mut self, views: Views) -> Self {
+        self.views = views;
+        self
+    }
+
+    /// Returns the underlying DataFusion session
+    pub fn context(&self) -> &SessionContext {
+        &self.ctx
+    }
+
+    /// Creates the views the query references, and plans it.
+    async fn plan(&self, sql: &str) -> anyhow::Result<DataFrame> {
+        self.views.register(&self.ctx, sql).await?;
+        Ok(self.ctx.sql(sql).await?)
+    }
+
+    /// Plans the query, binding the parameters to its placeholders (`$name` or `:name`).
+    pub async fn query(&self, sql: &str, params: &[Param]) -> anyhow::Result<DataFrame> {
+        params::bind(self.plan(sql).await?, params)
+    }
+
+    /// Executes the query, collecting the results.
+    pub async fn collect(&self, sql: &str, params: &[Param]) -> anyhow::Result<Vec<RecordBatch>> {
+        Ok(self.query(sql, params).await?.collect().await?)
+    }
+
+    /// Plans the query once, to be executed with different parameters.
+    ///
+    /// The tables are resolved and their schemas inferred at preparation;
+    /// the objects are listed again at each execution.
+    pub async fn prepare(&self, sql: &str) -> anyhow::Result<PreparedQuery> {
+        Ok(PreparedQuery {
+            df: self.plan(sql).await?,
+        })
+    }
+}
+
+/// Query planned once by [`KuquSession::prepare`].
+#[derive(Clone)]
+pub struct PreparedQuery {
+    df: DataFrame,
+}
+
+impl PreparedQuery {
+    /// Returns the types of the placeholders, `None` if unknown from the query
+    pub fn parameter_types(&self) -> anyhow::Result<HashMap<String, Option<DataType>>> {
+        Ok(self.df.logical_plan().get_parameter_types()?)
+    }
+
+    /// Binds the parameters, cast to the type of each placeholder, to the query.
+    pub fn bind(&self, params: &[Param]) -> anyhow::Result<DataFrame> {
+        params::bind(self.df.clone(), params)
+    }
+
+    /// Binds the values as they are to the placeholders of the query.
+    pub fn bind_values(&self, values: impl Into<ParamValues>) -> anyhow::Result<DataFrame> {
+        Ok(self.df.clone().with_param_values(values)?)
+    }
+
+    /// Executes the query with the parameters, collecting the results.
+    pub async fn execute(&self, params: &[Param]) -> anyhow::Result<Vec<RecordBatch>> {
+        Ok(self.bind(params)?.collect().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use datafusion::arrow::util::pretty::pretty_format_batches;
+    use serde_json::json;
+
+    use crate::{KuquContext, lister::fake::FakeLister};
+
+    #[tokio::test]
+    async fn query_views() {
+        let lister = Arc::new(FakeLister::new(vec![json!({
+            "apiVersion": "v1", "kind": "Pod",
+            "metadata": {"name": "web-1", "namespace": "default"},
+            "spec": {"nodeName": "n1", "containers": [{"name": "app", "image": "web:1"}]},
+            "status": {"phase": "Running"},
+        })]));
+        let session = KuquContext::from_lister("fake", lister, "default")
+            .session()
+            .unwrap();
+        let batches = session
+            .collect("SELECT pod, container, image FROM pod_containers", &[])
+            .await
+            .unwrap();
+        assert_eq!(
+            pretty_format_batches(&batches).unwrap().to_string(),
+            "\
++-------+-----------+-------+
+| pod   | container | image |
++-------+-----------+-------+
+| web-1 | app       | web:1 |
++-------+-----------+-------+"
+        );
+
+        let query = session
+            .prepare("SELECT count(*) AS pods FROM pod_containers WHERE phase = $phase")
+            .await
+            .unwrap();
+        let batches = query
+            .execute(&[("phase".to_string(), "Running".to_string())])
+            .await
+            .unwrap();
+        assert_eq!(
+            pretty_format_batches(&batches).unwrap().to_string(),
+            "\
++------+
+| pods |
++------+
+| 1    |
++------+"
+        );
+    }
+}
@@ -0,0 +1,545 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use datafusion::{
+    arrow::{datatypes::SchemaRef, record_batch::RecordBatch},
+    execution::context::SessionContext,
+    parquet::arrow::{ArrowWriter, arrow_reader::ParquetRecordBatchReaderBuilder},
+};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::APIResource;
+use kube::api::TypeMeta;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::time::MissedTickBehavior;
+
+use crate::{
+    alert::json_rows, dump::DumpLister, dynamic::DynamicObject,
+    provider::KubernetesTableProviderFactory, url::Namespaces,
+};
+
+/// Name of the manifest file of a snapshot
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Description of a snapshot, stored as `manifest.json` next to the Parquet files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Context the snapshot was taken from
+    pub context: String,
+    /// When the snapshot was taken, in RFC 3339
+    pub created: String,
+    pub resources: Vec<ManifestResource>,
+}
+
+/// Resource exported to a snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestResource {
+    /// Plural name, e.g. `deployments`
+    pub name: String,
+    /// API group, `core` for the core group
+    pub group: String,
+    pub version: String,
+    pub kind: String,
+    pub namespaced: bool,
+    /// Short names, e.g. `deploy`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub short_names: Vec<String>,
+    /// Directory of the Parquet files of the objects, relative to the snapshot
+    pub path: PathBuf,
+    /// Number of objects exported
+    pub rows: usize,
+}
+
+impl ManifestResource {
+    /// `apiVersion` of the objects
+    pub fn api_version(&self) -> String {
+        match self.group.as_str() {
+            "core" => self.version.clone(),
+            group => format!("{}/{}", group, self.version),
+        }
+    }
+
+    /// The resource as discovered from an API server
+    pub fn api_resource(&self) -> APIResource {
+        APIResource {
+            name: self.name.clone(),
+            singular_name: self.kind.to_lowercase(),
+            group: Some(self.group.clone()),
+            version: Some(self.version.clone()),
+            kind: self.kind.clone(),
+            namespaced: self.namespaced,
+            short_names: Some(self.short_names.clone()),
+            verbs: vec!["get".to_string(), "list".to_string()],
+            ..Default::default()
+        }
+    }
+}
+
+impl Manifest {
+    /// Read the manifest of the snapshot directory
+    pub fn read(dir: &Path) -> anyhow::Result<Self> {
+        let path = dir.join(MANIFEST_FILE);
+        let content = fs::read(&path)
+            .map_err(|e| anyhow::anyhow!("failed to read {}: {}", path.display(), e))?;
+        serde_json::from_slice(&content)
+            .map_err(|e| anyhow::anyhow!("invalid snapshot manifest {}: {}", path.display(), e))
+    }
+
+    /// When the snapshot was taken
+    pub fn created(&self) -> anyhow::Result<DateTime<Utc>> {
+        DateTime::parse_from_rfc3339(&self.created)
+            .map(|created| created.with_timezone(&Utc))
+            .map_err(|e| anyhow::anyhow!("invalid creation time '{}': {}", self.created, e))
+    }
+}
+
+/// Find the latest snapshot taken at or before the time (or the latest of all),
+/// either the directory itself or one of the snapshots directly under it.
+pub fn find(dir: &Path, as_of: Option<DateTime<Utc>>) -> anyhow::Result<PathBuf> {
+    let dirs = if dir.join(MANIFEST_FILE).is_file() {
+        vec![dir.to_path_buf()]
+    } else {
+        snapshots(dir)?
+    };
+
+    let mut latest: Option<(DateTime<Utc>, PathBuf)> = None;
+    for dir in dirs {
+        let created = Manifest::read(&dir)?.created()?;
+        if as_of.is_some_and(|as_of| created > as_of) {
+            continue;
+        }
+        if latest.as_ref().is_none_or(|(latest, _)| created > *latest) {
+            latest = Some((created, dir));
+        }
+    }
+    match (latest, as_of) {
+        (Some((_, dir)), _) => Ok(dir),
+        (None, Some(as_of)) => anyhow::bail!(
+            "no snapshot in {} taken at or before {}",
+            dir.display(),
+            as_of.to_rfc3339_opts(SecondsFormat::Secs, true)
+        ),
+        (None, None) => anyhow::bail!("no snapshot in {}", dir.display()),
+    }
+}
+
+/// Snapshots directly under the directory
+fn snapshots(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| anyhow::anyhow!("failed to read {}: {}", dir.display(), e))?;
+    let mut dirs = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.join(MANIFEST_FILE).is_file() {
+            dirs.push(path);
+        }
+    }
+    Ok(dirs)
+}
+
+/// Read the objects of the snapshot directory, to query them as a cluster.
+pub fn load(dir: &Path) -> anyhow::Result<DumpLister> {
+    let manifest = Manifest::read(dir)?;
+    let mut resources = Vec::new();
+    for resource in &manifest.resources {
+        let types = TypeMeta {
+            api_version: resource.api_version(),
+            kind: resource.kind.clone(),
+        };
+        let objects = read_rows(&dir.join(&resource.path))?
+            .into_iter()
+            .map(|row| {
+                let mut object: DynamicObject = serde_json::from_value(row)?;
+                object.types.get_or_insert(types.clone());
+                Ok(object)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        resources.push((resource.api_resource(), objects));
+    }
+    Ok(DumpLister::from_resources(resources))
+}
+
+/// Rows of the Parquet files under the directory as JSON objects
+fn read_rows(dir: &Path) -> anyhow::Result<Vec<Value>> {
+    let mut files = fs::read_dir(dir)
+        .map_err(|e| anyhow::anyhow!("failed to read {}: {}", dir.display(), e))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    files.retain(|path| path.extension().is_some_and(|ext| ext == "parquet"));
+    files.sort();
+
+    let mut rows = Vec::new();
+    for file in files {
+        let reader = ParquetRecordBatchReaderBuilder::try_new(fs::File::open(&file)?)?.build()?;
+        let batches = reader.collect::<Result<Vec<_>, _>>()?;
+        rows.extend(json_rows(&batches)?);
+    }
+    Ok(rows)
+}
+
+/// Objects of a resource to export
+pub struct Table {
+    pub resource: ManifestResource,
+    schema: SchemaRef,
+    pub batches: Vec<RecordBatch>,
+}
+
+/// Export the objects of the resources (e.g. `pods`, `deployments.apps`) in the namespaces
+/// to a snapshot directory, a Parquet file per resource under a directory named after it,
+/// along with `manifest.json`.
+pub async fn export(
+    ctx: &SessionContext,
+    factory: &KubernetesTableProviderFactory,
+    resources: &[String],
+    namespaces: &Namespaces,
+    out: &Path,
+) -> anyhow::Result<Manifest> {
+    let tables = collect(ctx, factory, resources, namespaces).await?;
+    write(factory.context(), &tables, out)
+}
+
+/// Query the objects of the resources in the namespaces.
+pub async fn collect(
+    ctx: &SessionContext,
+    factory: &KubernetesTableProviderFactory,
+    resources: &[String],
+    namespaces: &Namespaces,
+) -> anyhow::Result<Vec<Table>> {
+    let mut tables = Vec::new();
+    for resource in resources {
+        let url = match namespaces {
+            Namespaces::One(namespace) => format!("{}/{}", resource, namespace),
+            _ => format!("{}/*", resource),
+        };
+        let Some((_, kubeurl, _)) = factory.resolve_all(&url).await?.into_iter().next() else {
+            anyhow::bail!("resource '{}' not found", resource);
+        };
+        let api_resource = kubeurl.resource;
+        let group = api_resource.group.clone().unwrap_or("core".to_string());
+        let path = PathBuf::from(match group.as_str() {
+            "core" => api_resource.name.clone(),
+            group => format!("{}.{}", api_resource.name, group),
+        });
+
+        let df = ctx.sql(&format!("SELECT * FROM '{}'", url)).await?;
+        let schema = df.schema().inner().clone();
+        let batches = df.collect().await?;
+        tables.push(Table {
+            resource: ManifestResource {
+                name: api_resource.name.clone(),
+                group,
+                version: api_resource.version.clone().unwrap_or_default(),
+                kind: api_resource.kind.clone(),
+                namespaced: api_resource.namespaced,
+                short_names: api_resource.short_names.clone().unwrap_or_default(),
+                path,
+                rows: batches.iter().map(|batch| batch.num_rows()).sum(),
+            },
+            schema,
+            batches,
+        });
+    }
+    Ok(tables)
+}
+
+/// Write the objects to a snapshot directory, as taken now from the context.
+///
+/// Resources without objects are written with their schema only.
+pub fn write(context: &str, tables: &[Table], out: &Path) -> anyhow::Result<Manifest> {
+    for table in tables {
+        let dir = out.join(&table.resource.path);
+        fs::create_dir_all(&dir)
+            .map_err(|e| anyhow::anyhow!("failed to create {}: {}", dir.display(), e))?;
+        let file = fs::File::create(dir.join("part-0.parquet"))?;
+        let mut writer = ArrowWriter::try_new(file, Arc::clone(&table.schema), None)?;
+        for batch in &table.batches {
+            writer.write(batch)?;
+        }
+        writer.close()?;
+    }
+
+    let manifest = Manifest {
+        context: context.to_string(),
+        created: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        resources: tables.iter().map(|table| table.resource.clone()).collect(),
+    };
+    fs::create_dir_all(out)
+        .map_err(|e| anyhow::anyhow!("failed to create {}: {}", out.display(), e))?;
+    fs::write(
+        out.join(MANIFEST_FILE),
+        serde_json::to_vec_pretty(&manifest)?,
+    )?;
+    Ok(manifest)
+}
+
+/// Objects of each resource of a snapshot, by the directory of its Parquet files
+type Contents = Vec<(PathBuf, Vec<Value>)>;
+
+/// Objects of the tables, to compare snapshots by
+fn contents(tables: &[Table]) -> anyhow::Result<Contents> {
+    tables
+        .iter()
+        .map(|table| Ok((table.resource.path.clone(), json_rows(&table.batches)?)))
+        .collect()
+}
+
+/// Objects of the latest snapshot under the directory, if any, to append to.
+fn latest(out: &Path) -> anyhow::Result<Option<Contents>> {
+    if !out.exists() {
+        return Ok(None);
+    }
+    if out.join(MANIFEST_FILE).is_file() {
+        anyhow::bail!(
+            "cannot archive to '{}': it is a snapshot, not a directory of snapshots",
+            out.display()
+        );
+    }
+    if snapshots(out)?.is_empty() {
+        return Ok(None);
+    }
+    let dir = find(out, None)?;
+    let manifest = Manifest::read(&dir)?;
+    manifest
+        .resources
+        .into_iter()
+        .map(|resource| {
+            let rows = read_rows(&dir.join(&resource.path))?;
+            Ok((resource.path, rows))
+        })
+        .collect::<anyhow::Result<_>>()
+        .map(Some)
+}
+
+/// Take a snapshot of the resources into a directory under `out` named after the time,
+/// every interval until interrupted, skipping those identical to the previous one.
+///
+/// Snapshots already under `out` are kept and appended to, the latest of them
+/// being the previous one. Failures to take a snapshot are reported and retried
+/// at the next interval.
+pub async fn archive(
+    ctx: &SessionContext,
+    factory: &KubernetesTableProviderFactory,
+    resources: &[String],
+    namespaces: &Namespaces,
+    interval: Duration,
+    out: &Path,
+) -> anyhow::Result<()> {
+    if let Some((scheme, _)) = out.to_string_lossy().split_once("://") {
+        anyhow::bail!(
+            "cannot archive to '{}': only local directories are supported, not {}://",
+            out.display(),
+            scheme
+        );
+    }
+    let mut previous = latest(out)?;
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    loop {
+        ticker.tick().await;
+        let result = collect(ctx, factory, resources, namespaces).await;
+        factory.warnings().print();
+        let (tables, current) = match result.and_then(|tables| {
+            let current = contents(&tables)?;
+            Ok((tables, current))
+        }) {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                eprintln!("Warning: failed to take a snapshot: {}", e);
+                continue;
+            }
+        };
+        if previous.as_ref() == Some(&current) {
+            continue;
+        }
+        let dir = out.join(Utc::now().format("%Y%m%dT%H%M%SZ").to_string());
+        match write(factory.context(), &tables, &dir) {
+            Ok(_) => println!("{}", dir.display()),
+            Err(e) => eprintln!("Warning: failed to write {}: {}", dir.display(), e),
+        }
+        previous = Some(current);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::util::pretty::pretty_format_batches;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        cluster::Cluster, lister::ResourceLister, lister::fake::FakeLister, session_context,
+    };
+
+    fn session(
+        lister: Arc<dyn ResourceLister>,
+    ) -> (SessionContext, Arc<KubernetesTableProviderFactory>) {
+        let cluster = Cluster::from_lister(lister, Namespaces::One("default".to_string()));
+        let factory = Arc::new(KubernetesTableProviderFactory::new(
+            "fake".to_string(),
+            cluster,
+        ));
+        (session_context(factory.clone()).unwrap(), factory)
+    }
+
+    fn fake_session() -> (SessionContext, Arc<KubernetesTableProviderFactory>) {
+        session(Arc::new(FakeLister::new(vec![
+            json!({"apiVersion": "v1", "kind": "Pod",
+                "metadata": {"name": "web-1", "namespace": "default", "labels": {"app": "web"}},
+                "spec": {"nodeName": "n1"}}),
+            json!({"apiVersion": "v1", "kind": "Secret",
+                "metadata": {"name": "token", "namespace": "default"},
+                "type": "Opaque", "data": {"token": "c2VjcmV0"}}),
+        ])))
+    }
+
+    /// Empty temporary directory of the test
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("kuqu-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[tokio::test]
+    async fn export_and_load() {
+        let (ctx, factory) = fake_session();
+        let resources = ["pods".to_string(), "secrets".to_string()];
+        let out = temp_dir("export");
+        let manifest = export(&ctx, &factory, &resources, &Namespaces::All, &out)
+            .await
+            .unwrap();
+        let exported: Vec<_> = manifest
+            .resources
+            .iter()
+            .map(|resource| {
+                (
+                    resource.api_version(),
+                    resource.kind.as_str(),
+                    resource.rows,
+                )
+            })
+            .collect();
+        assert_eq!(
+            exported,
+            [
+                ("v1".to_string(), "Pod", 1),
+                ("v1".to_string(), "Secret", 1)
+            ]
+        );
+        assert_eq!(find(&out, None).unwrap(), out);
+
+        let (ctx, _) = session(Arc::new(load(&out).unwrap()));
+        let batches = ctx
+            .sql("SELECT kind, metadata.name, metadata.labels['app'], spec.nodeName FROM pods")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        assert_eq!(
+            pretty_format_batches(&batches).unwrap().to_string(),
+            "\
++------+---------------------+----------------------------+---------------------+
+| kind | pods.metadata[name] | pods.metadata[labels][app] | pods.spec[nodeName] |
++------+---------------------+----------------------------+---------------------+
+| Pod  | web-1               | web                        | n1                  |
++------+---------------------+----------------------------+---------------------+"
+        );
+        fs::remove_dir_all(&out).unwrap();
+    }
+
+    #[test]
+    fn find_as_of() {
+        let dir = temp_dir("find");
+        for (name, created) in [
+            ("a", "2025-01-01T00:00:00Z"),
+            ("b", "2025-01-02T00:00:00Z"),
+            ("c", "2025-01-03T00:00:00Z"),
+        ] {
+            fs::create_dir_all(dir.join(name)).unwrap();
+            let manifest = Manifest {
+                context: "fake".to_string(),
+                created: created.to_string(),
+                resources: Vec::new(),
+            };
+            fs::write(
+                dir.join(name).join(MANIFEST_FILE),
+                serde_json::to_vec(&manifest).unwrap(),
+            )
+            .unwrap();
+        }
+        fs::create_dir_all(dir.join("not-a-snapshot")).unwrap();
+        let time = |time: &str| Some(DateTime::parse_from_rfc3339(time).unwrap().to_utc());
+
+        assert_eq!(find(&dir, None).unwrap(), dir.join("c"));
+        assert_eq!(
+            find(&dir, time("2025-01-02T12:00:00Z")).unwrap(),
+            dir.join("b")
+        );
+        assert_eq!(
+            find(&dir, time("2025-01-02T00:00:00Z")).unwrap(),
+            dir.join("b")
+        );
+        let error = find(&dir, time("2024-12-31T00:00:00Z")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "no snapshot in {} taken at or before 2024-12-31T00:00:00Z",
+                dir.display()
+            )
+        );
+
+        // A snapshot directory is the only snapshot to read.
+        let snapshot = dir.join("a");
+        assert_eq!(find(&snapshot, None).unwrap(), snapshot);
+        assert_eq!(
+            find(&snapshot, time("2025-06-01T00:00:00Z")).unwrap(),
+            snapshot
+        );
+        assert!(find(&snapshot, time("2024-12-31T00:00:00Z")).is_err());
+
+        let empty = dir.join("not-a-snapshot");
+        assert_eq!(
+            find(&empty, None).unwrap_err().to_string(),
+            format!("no snapshot in {}", empty.display())
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn append_to_latest() {
+        let (ctx, factory) = fake_session();
+        let resources = ["pods".to_string(), "secrets".to_string()];
+        let tables = collect(&ctx, &factory, &resources, &Namespaces::All)
+            .await
+            .unwrap();
+
+        let out = temp_dir("archive");
+        assert_eq!(latest(&out).unwrap(), None);
+        write("fake", &tables, &out.join("20250101T000000Z")).unwrap();
+        let previous = latest(&out).unwrap();
+        let current = contents(&tables).unwrap();
+        assert_eq!(previous, Some(current.clone()));
+        assert_eq!(current.len(), 2);
+        assert_eq!(current[0].1.len(), 1);
+        assert!(latest(&out.join("20250101T000000Z")).is_err());
+        fs::remove_dir_all(&out).unwrap();
+    }
+}
@@ -0,0 +1,219 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{any::Any, sync::Arc};
+
+use async_trait::async_trait;
+use datafusion::{
+    arrow::{
+        array::{ArrayRef, Float64Array, Int64Array, StringArray},
+        datatypes::{DataType, Field, Schema, SchemaRef},
+        record_batch::RecordBatch,
+    },
+    catalog::Session,
+    common::{DataFusionError, Result as DataFusionResult},
+    datasource::{MemTable, TableProvider, TableType},
+    logical_expr::Expr,
+    physical_plan::ExecutionPlan,
+};
+use futures::{StreamExt, stream};
+use k8s_openapi::api::core::v1::Node;
+use kube::{Api, Client, api::ListParams};
+use serde_json::Value;
+
+use crate::provider::KubernetesTableProviderFactory;
+
+/// Table of the resource usage of the nodes and their pods
+pub const NODE_STATS_TABLE: &str = "node_stats";
+
+/// Number of nodes whose summaries are requested at once
+const CONCURRENCY: usize = 8;
+
+/// Resource usage of each node and of each pod on it, as summarized by the kubelets
+/// at `/stats/summary` through the API server proxy, so without metrics-server.
+///
+/// A row per node (with a NULL `pod`) and per pod. Nodes whose summary cannot be read
+/// are skipped with a warning.
+#[derive(Debug)]
+pub struct NodeStatsTable {
+    factory: Arc<KubernetesTableProviderFactory>,
+    schema: SchemaRef,
+}
+
+impl NodeStatsTable {
+    pub fn new(factory: Arc<KubernetesTableProviderFactory>) -> Self {
+        Self {
+            factory,
+            schema: Arc::new(Schema::new(vec![
+                Field::new("node", DataType::Utf8, false),
+                Field::new("namespace", DataType::Utf8, true),
+                Field::new("pod", DataType::Utf8, true),
+                Field::new("cpu_usage_cores", DataType::Float64, true),
+                Field::new("memory_working_set_bytes", DataType::Int64, true),
+                Field::new("memory_usage_bytes", DataType::Int64, true),
+                Field::new("memory_available_bytes", DataType::Int64, true),
+                Field::new("ephemeral_storage_used_bytes", DataType::Int64, true),
+                Field::new("ephemeral_storage_capacity_bytes", DataType::Int64, true),
+                Field::new("time", DataType::Utf8, true),
+            ])),
+        }
+    }
+
+    /// Usage of the nodes and their pods
+    async fn rows(&self) -> DataFusionResult<Vec<Row>> {
+        let client = self.factory.client().await?;
+        let nodes = Api::<Node>::all(client.clone())
+            .list_metadata(&ListParams::default())
+            .await
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        let summaries = stream::iter(
+            nodes
+                .items
+                .into_iter()
+                .filter_map(|node| node.metadata.name),
+        )
+        .map(|node| {
+            let client = client.clone();
+            async move {
+                let summary = summary(&client, &node).await;
+                (node, summary)
+            }
+        })
+        .buffered(CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+
+        let mut rows = Vec::new();
+        for (node, summary) in summaries {
+            match summary {
+                Ok(summary) => rows.extend(summary_rows(&node, &summary)),
+                Err(e) => self
+                    .factory
+                    .warnings()
+                    .push(format!("skipped the stats of node {}: {}", node, e)),
+            }
+        }
+        Ok(rows)
+    }
+}
+
+#[async_trait]
+impl TableProvider for NodeStatsTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    async fn scan(
+        &self,
+        state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        self.factory.deny_dry_run(NODE_STATS_TABLE)?;
+        let batch = batch(&self.schema, self.rows().await?)?;
+        MemTable::try_new(self.schema.clone(), vec![vec![batch]])?
+            .scan(state, projection, filters, limit)
+            .await
+    }
+}
+
+/// Summary of the node from its kubelet
+async fn summary(client: &Client, node: &str) -> anyhow::Result<Value> {
+    let request = http::Request::get(format!("/api/v1/nodes/{}/proxy/stats/summary", node))
+        .body(Vec::new())?;
+    Ok(serde_json::from_str(&client.request_text(request).await?)?)
+}
+
+/// Usage of a node or of a pod on it
+struct Row {
+    node: String,
+    /// Namespace and name of the pod, `None` for the node itself
+    pod: Option<(String, String)>,
+    cpu_usage_cores: Option<f64>,
+    memory_working_set_bytes: Option<i64>,
+    memory_usage_bytes: Option<i64>,
+    memory_available_bytes: Option<i64>,
+    ephemeral_storage_used_bytes: Option<i64>,
+    ephemeral_storage_capacity_bytes: Option<i64>,
+    time: Option<String>,
+}
+
+/// Rows of the node and its pods in the summary
+fn summary_rows(node: &str, summary: &Value) -> Vec<Row> {
+    let row = |stats: &Value, pod: Option<(String, String)>, fs: &Value| Row {
+        node: node.to_string(),
+        pod,
+        cpu_usage_cores: stats["cpu"]["usageNanoCores"]
+            .as_f64()
+            .map(|nano_cores| nano_cores / 1e9),
+        memory_working_set_bytes: stats["memory"]["workingSetBytes"].as_i64(),
+        memory_usage_bytes: stats["memory"]["usageBytes"].as_i64(),
+        memory_available_bytes: stats["memory"]["availableBytes"].as_i64(),
+        ephemeral_storage_used_bytes: fs["usedBytes"].as_i64(),
+        ephemeral_storage_capacity_bytes: fs["capacityBytes"].as_i64(),
+        time: stats["cpu"]["time"]
+            .as_str()
+            .or(stats["memory"]["time"].as_str())
+            .map(String::from),
+    };
+    let stats = &summary["node"];
+    let mut rows = vec![row(stats, None, &stats["fs"])];
+    for pod in summary["pods"].as_array().into_iter().flatten() {
+        let pod_ref = &pod["podRef"];
+        let name = (
+            pod_ref["namespace"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            pod_ref["name"].as_str().unwrap_or_default().to_string(),
+        );
+        rows.push(row(pod, Some(name), &pod["ephemeral-storage"]));
+    }
+    rows
+}
+
+fn batch(schema: &SchemaRef, rows: Vec<Row>) -> DataFusionResult<RecordBatch> {
+    let strings = |f: fn(&Row) -> Option<&str>| -> ArrayRef {
+        Arc::new(rows.iter().map(f).collect::<StringArray>())
+    };
+    let ints = |f: fn(&Row) -> Option<i64>| -> ArrayRef {
+        Arc::new(rows.iter().map(f).collect::<Int64Array>())
+    };
+    let columns = vec![
+        strings(|row| Some(&row.node)),
+        strings(|row| row.pod.as_ref().map(|(namespace, _)| namespace.as_str())),
+        strings(|row| row.pod.as_ref().map(|(_, name)| name.as_str())),
+        Arc::new(
+            rows.iter()
+                .map(|row| row.cpu_usage_cores)
+                .collect::<Float64Array>(),
+        ) as ArrayRef,
+        ints(|row| row.memory_working_set_bytes),
+        ints(|row| row.memory_usage_bytes),
+        ints(|row| row.memory_available_bytes),
+        ints(|row| row.ephemeral_storage_used_bytes),
+        ints(|row| row.ephemeral_storage_capacity_bytes),
+        strings(|row| row.time.as_deref()),
+    ];
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
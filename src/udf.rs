@@ -0,0 +1,110 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use datafusion::{
+    arrow::array::{Array, ArrayRef},
+    common::{Result as DataFusionResult, cast::as_string_array},
+    execution::context::SessionContext,
+    logical_expr::ColumnarValue,
+};
+
+mod base64;
+mod condition;
+mod duration;
+mod field;
+mod helm;
+mod image;
+mod ip;
+mod json;
+mod label;
+mod quantity;
+mod selector;
+
+/// Register the scalar functions for Kubernetes objects to the session.
+pub fn register(ctx: &SessionContext) {
+    ctx.register_udf(base64::base64_decode());
+    ctx.register_udf(base64::base64_encode());
+    ctx.register_udf(quantity::parse_quantity_udf());
+    ctx.register_udf(quantity::parse_quantity_bytes_udf());
+    ctx.register_udf(duration::age_udf());
+    ctx.register_udf(duration::age_seconds_udf());
+    ctx.register_udf(duration::parse_k8s_duration_udf());
+    ctx.register_udf(label::label_udf());
+    ctx.register_udf(label::annotation_udf());
+    ctx.register_udf(condition::condition_udf());
+    ctx.register_udf(image::image_registry_udf());
+    ctx.register_udf(image::image_repository_udf());
+    ctx.register_udf(image::image_tag_udf());
+    ctx.register_udf(image::image_digest_udf());
+    ctx.register_udf(selector::matches_selector_udf());
+    ctx.register_udf(ip::ip_in_cidr_udf());
+    ctx.register_udf(ip::cidr_overlaps_udf());
+    ctx.register_udf(ip::ip_family_udf());
+    ctx.register_udf(json::json_get_udf());
+    ctx.register_udf(field::try_get_field_udf());
+    ctx.register_udf(helm::helm_release_udf());
+}
+
+/// Evaluate the SQL expression with the functions registered,
+/// formatted as a string or `None` if NULL.
+#[cfg(test)]
+async fn eval(expr: &str) -> Option<String> {
+    use datafusion::arrow::util::display::array_value_to_string;
+
+    let ctx = SessionContext::new();
+    register(&ctx);
+    let batches = ctx
+        .sql(&format!("SELECT {}", expr))
+        .await
+        .unwrap()
+        .collect()
+        .await
+        .unwrap();
+    let column = batches[0].column(0);
+    // Arrays of the Null type have no null buffer, but are all logically NULL.
+    let null = column.logical_nulls().is_some_and(|nulls| nulls.is_null(0));
+    (!null).then(|| array_value_to_string(column, 0).unwrap())
+}
+
+/// Apply `f` to each string of the single argument of a function,
+/// resulting in NULL for NULL strings and where `f` returns `None`.
+fn map_strings<T, A>(
+    args: &[ColumnarValue],
+    f: impl Fn(&str) -> Option<T>,
+) -> DataFusionResult<ColumnarValue>
+where
+    A: Array + FromIterator<Option<T>> + 'static,
+{
+    let arrays = ColumnarValue::values_to_arrays(args)?;
+    let strings = as_string_array(&arrays[0])?;
+    let array: A = strings.iter().map(|s| s.and_then(&f)).collect();
+    Ok(ColumnarValue::Array(Arc::new(array) as ArrayRef))
+}
+
+/// Apply `f` to each pair of strings of the two arguments of a function,
+/// resulting in NULL where either string is NULL or `f` returns `None`.
+fn map_string_pairs<T, A>(
+    args: &[ColumnarValue],
+    f: impl Fn(&str, &str) -> Option<T>,
+) -> DataFusionResult<ColumnarValue>
+where
+    A: Array + FromIterator<Option<T>> + 'static,
+{
+    let arrays = ColumnarValue::values_to_arrays(args)?;
+    let (a, b) = (as_string_array(&arrays[0])?, as_string_array(&arrays[1])?);
+    let array: A = a.iter().zip(b.iter()).map(|(a, b)| f(a?, b?)).collect();
+    Ok(ColumnarValue::Array(Arc::new(array) as ArrayRef))
+}
@@ -0,0 +1,78 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use datafusion::{
+    arrow::{array::StringArray, datatypes::DataType},
+    logical_expr::{ScalarUDF, Volatility, create_udf},
+};
+
+use super::map_strings;
+
+/// `base64_decode(string)`: decode base64 (e.g. values of Secrets or binaryData of ConfigMaps).
+/// NULL if the string is not base64 or does not decode to UTF-8 text.
+pub fn base64_decode() -> ScalarUDF {
+    create_udf(
+        "base64_decode",
+        vec![DataType::Utf8],
+        DataType::Utf8,
+        Volatility::Immutable,
+        Arc::new(|args| {
+            map_strings::<_, StringArray>(args, |s| {
+                String::from_utf8(STANDARD.decode(s).ok()?).ok()
+            })
+        }),
+    )
+}
+
+/// `base64_encode(string)`: encode the string as base64.
+pub fn base64_encode() -> ScalarUDF {
+    create_udf(
+        "base64_encode",
+        vec![DataType::Utf8],
+        DataType::Utf8,
+        Volatility::Immutable,
+        Arc::new(|args| map_strings::<_, StringArray>(args, |s| Some(STANDARD.encode(s)))),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::udf::eval;
+
+    #[tokio::test]
+    async fn base64_functions() {
+        assert_eq!(
+            eval("base64_decode('c2VjcmV0')").await.as_deref(),
+            Some("secret")
+        );
+        assert_eq!(
+            eval("base64_encode('secret')").await.as_deref(),
+            Some("c2VjcmV0")
+        );
+        assert_eq!(
+            eval("base64_decode(base64_encode('ünïcode'))")
+                .await
+                .as_deref(),
+            Some("ünïcode")
+        );
+        assert_eq!(eval("base64_decode('not base64!')").await, None);
+        // 0xff 0xfe is not UTF-8.
+        assert_eq!(eval("base64_decode('//4=')").await, None);
+        assert_eq!(eval("base64_decode(NULL)").await, None);
+        assert_eq!(eval("base64_encode(NULL)").await, None);
+    }
+}
@@ -0,0 +1,225 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{any::Any, sync::Arc};
+
+use datafusion::{
+    arrow::{
+        array::{Array, ArrayRef, AsArray, StringArray, StructArray},
+        buffer::NullBuffer,
+        compute::cast,
+        datatypes::{DataType, Field, Fields},
+    },
+    common::{Result as DataFusionResult, cast::as_string_array, plan_err},
+    logical_expr::{
+        ColumnarValue, ScalarFunctionArgs, ScalarUDF, ScalarUDFImpl, Signature, Volatility,
+    },
+};
+
+/// Fields of the conditions returned, as in `status.conditions`.
+const CONDITION_FIELDS: [&str; 4] = ["status", "reason", "message", "lastTransitionTime"];
+
+/// `condition(status.conditions, type)`: the condition of the type
+/// as a struct of its status, reason, message and lastTransitionTime, NULL if absent.
+pub fn condition_udf() -> ScalarUDF {
+    ScalarUDF::new_from_impl(Condition {
+        signature: Signature::any(2, Volatility::Immutable),
+    })
+}
+
+#[derive(Debug)]
+struct Condition {
+    signature: Signature,
+}
+
+fn condition_fields() -> Fields {
+    CONDITION_FIELDS
+        .iter()
+        .map(|name| Field::new(*name, DataType::Utf8, true))
+        .collect()
+}
+
+impl ScalarUDFImpl for Condition {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "condition"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> DataFusionResult<DataType> {
+        match &arg_types[0] {
+            DataType::List(field)
+                if matches!(field.data_type(), DataType::Struct(_) | DataType::Null) => {}
+            DataType::Null => {}
+            other => {
+                return plan_err!("condition expects a list of conditions, got {}", other);
+            }
+        }
+        Ok(DataType::Struct(condition_fields()))
+    }
+
+    fn invoke_with_args(&self, args: ScalarFunctionArgs) -> DataFusionResult<ColumnarValue> {
+        let arrays = ColumnarValue::values_to_arrays(&args.args)?;
+        let types = cast(&arrays[1], &DataType::Utf8)?;
+        let types = as_string_array(&types)?;
+
+        // Index of the matching condition among all conditions of all rows.
+        let (found, columns): (Vec<Option<usize>>, Vec<Option<ArrayRef>>) =
+            // Lists of nulls are inferred from objects whose conditions are all empty.
+            match arrays[0]
+                .as_list_opt::<i32>()
+                .filter(|lists| matches!(lists.value_type(), DataType::Struct(_)))
+            {
+                Some(lists) => {
+                    let conditions = lists.values().as_struct();
+                    let column = |name: &str| {
+                        conditions
+                            .column_by_name(name)
+                            .and_then(|column| cast(column, &DataType::Utf8).ok())
+                    };
+                    let condition_types = column("type");
+                    let found = (0..types.len())
+                        .map(|i| {
+                            let condition_types = condition_types.as_ref()?.as_string::<i32>();
+                            if lists.is_null(i) || types.is_null(i) {
+                                return None;
+                            }
+                            let offsets = lists.value_offsets();
+                            (offsets[i] as usize..offsets[i + 1] as usize).find(|&j| {
+                                condition_types.is_valid(j)
+                                    && condition_types.value(j) == types.value(i)
+                            })
+                        })
+                        .collect();
+                    (
+                        found,
+                        CONDITION_FIELDS.iter().map(|name| column(name)).collect(),
+                    )
+                }
+                None => (vec![None; types.len()], vec![None; CONDITION_FIELDS.len()]),
+            };
+
+        let columns = columns
+            .iter()
+            .map(|column| {
+                let column = column.as_ref().map(|column| column.as_string::<i32>());
+                let values: StringArray = found
+                    .iter()
+                    .map(|j| {
+                        let (column, j) = (column?, (*j)?);
+                        column.is_valid(j).then(|| column.value(j))
+                    })
+                    .collect();
+                Arc::new(values) as ArrayRef
+            })
+            .collect();
+        let nulls = NullBuffer::from(found.iter().map(Option::is_some).collect::<Vec<_>>());
+        Ok(ColumnarValue::Array(Arc::new(StructArray::try_new(
+            condition_fields(),
+            columns,
+            Some(nulls),
+        )?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::{arrow::util::display::array_value_to_string, prelude::SessionContext};
+
+    use crate::udf::eval;
+
+    /// Values of the column of the results, in order
+    async fn strings(sql: &str) -> Vec<Option<String>> {
+        let ctx = SessionContext::new();
+        crate::udf::register(&ctx);
+        let batches = ctx.sql(sql).await.unwrap().collect().await.unwrap();
+        let mut values = Vec::new();
+        for batch in batches {
+            let column = batch.column(0);
+            values.extend((0..column.len()).map(|i| {
+                column
+                    .is_valid(i)
+                    .then(|| array_value_to_string(column, i).unwrap())
+            }));
+        }
+        values
+    }
+
+    #[tokio::test]
+    async fn conditions_of_rows() {
+        let condition = |kind: &str, status: &str| {
+            format!(
+                "named_struct('type', '{}', 'status', '{}', 'reason', 'R{}')",
+                kind, status, kind
+            )
+        };
+        // Rows with conditions of other rows before them, to walk the offsets of each row.
+        let sql = format!(
+            "SELECT condition(c, t)['status'] FROM (VALUES
+                (make_array({}, {}), 'Ready'),
+                (make_array({}, {}, {}), 'Ready'),
+                (make_array({}), 'Ready'),
+                (CASE WHEN false THEN make_array({}) END, 'Ready'),
+                (make_array({}), NULL)
+            ) AS v(c, t)",
+            condition("Scheduled", "True"),
+            condition("Ready", "False"),
+            condition("Scheduled", "True"),
+            condition("Initialized", "True"),
+            condition("Ready", "True"),
+            condition("Scheduled", "True"),
+            condition("Ready", "True"),
+            condition("Ready", "True"),
+        );
+        assert_eq!(
+            strings(&sql).await,
+            [Some("False".into()), Some("True".into()), None, None, None]
+        );
+    }
+
+    #[tokio::test]
+    async fn condition_values() {
+        let conditions = "make_array(named_struct('type', 'Ready', 'status', 'True', \
+            'lastTransitionTime', to_timestamp('2025-01-01T00:00:00Z')))";
+        assert_eq!(
+            eval(&format!(
+                "condition({}, 'Ready')['lastTransitionTime']",
+                conditions
+            ))
+            .await,
+            Some("2025-01-01T00:00:00".into())
+        );
+        // Fields missing from the conditions are NULL.
+        assert_eq!(
+            eval(&format!("condition({}, 'Ready')['reason']", conditions)).await,
+            None
+        );
+        assert_eq!(
+            eval(&format!("condition({}, 'Synced')", conditions)).await,
+            None
+        );
+        // Objects whose conditions are all empty
+        assert_eq!(
+            eval("condition(make_array(NULL, NULL), 'Ready')").await,
+            None
+        );
+        assert_eq!(eval("condition(NULL, 'Ready')").await, None);
+    }
+}
@@ -0,0 +1,218 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use datafusion::{
+    arrow::{
+        array::{Float64Array, Int64Array, StringArray},
+        datatypes::DataType,
+    },
+    logical_expr::{ScalarUDF, Volatility, create_udf},
+};
+
+use super::map_strings;
+
+/// `age(timestamp)`: time elapsed since the RFC 3339 timestamp (e.g. `metadata.creationTimestamp`),
+/// formatted as the AGE column of kubectl (e.g. `5d3h`), NULL if invalid.
+pub fn age_udf() -> ScalarUDF {
+    create_udf(
+        "age",
+        vec![DataType::Utf8],
+        DataType::Utf8,
+        // The current time is the same throughout the query.
+        Volatility::Stable,
+        Arc::new(|args| {
+            let now = Utc::now();
+            map_strings::<_, StringArray>(args, |s| Some(human_duration(age(s, now)?)))
+        }),
+    )
+}
+
+/// `age_seconds(timestamp)`: seconds elapsed since the RFC 3339 timestamp, NULL if invalid.
+pub fn age_seconds_udf() -> ScalarUDF {
+    create_udf(
+        "age_seconds",
+        vec![DataType::Utf8],
+        DataType::Int64,
+        Volatility::Stable,
+        Arc::new(|args| {
+            let now = Utc::now();
+            map_strings::<_, Int64Array>(args, |s| age(s, now))
+        }),
+    )
+}
+
+/// `parse_k8s_duration(string)`: seconds of a duration as found in CRDs (e.g. `1h30m`, `500ms`),
+/// NULL if invalid.
+pub fn parse_k8s_duration_udf() -> ScalarUDF {
+    create_udf(
+        "parse_k8s_duration",
+        vec![DataType::Utf8],
+        DataType::Float64,
+        Volatility::Immutable,
+        Arc::new(|args| map_strings::<_, Float64Array>(args, parse_duration)),
+    )
+}
+
+/// Seconds elapsed from the RFC 3339 timestamp until now.
+fn age(timestamp: &str, now: DateTime<Utc>) -> Option<i64> {
+    let timestamp = DateTime::parse_from_rfc3339(timestamp).ok()?;
+    Some((now - timestamp.with_timezone(&Utc)).num_seconds())
+}
+
+/// Format seconds as kubectl does, with a precision decreasing as the duration grows.
+///
+/// See `HumanDuration` in k8s.io/apimachinery/pkg/util/duration.
+fn human_duration(seconds: i64) -> String {
+    if seconds < -1 {
+        return "<invalid>".to_string();
+    } else if seconds < 0 {
+        return "0s".to_string();
+    } else if seconds < 60 * 2 {
+        return format!("{}s", seconds);
+    }
+    let minutes = seconds / 60;
+    if minutes < 10 {
+        return match seconds % 60 {
+            0 => format!("{}m", minutes),
+            s => format!("{}m{}s", minutes, s),
+        };
+    } else if minutes < 60 * 3 {
+        return format!("{}m", minutes);
+    }
+    let hours = minutes / 60;
+    if hours < 8 {
+        match minutes % 60 {
+            0 => format!("{}h", hours),
+            m => format!("{}h{}m", hours, m),
+        }
+    } else if hours < 48 {
+        format!("{}h", hours)
+    } else if hours < 24 * 8 {
+        match hours % 24 {
+            0 => format!("{}d", hours / 24),
+            h => format!("{}d{}h", hours / 24, h),
+        }
+    } else if hours < 24 * 365 * 2 {
+        format!("{}d", hours / 24)
+    } else if hours < 24 * 365 * 8 {
+        match (hours / 24) % 365 {
+            0 => format!("{}y", hours / 24 / 365),
+            d => format!("{}y{}d", hours / 24 / 365, d),
+        }
+    } else {
+        format!("{}y", hours / 24 / 365)
+    }
+}
+
+/// Parse a duration in the format of Go's `time.ParseDuration` into seconds:
+/// a signed sequence of decimal numbers with units `ns`, `us` (or `µs`), `ms`, `s`, `m` and `h`.
+fn parse_duration(duration: &str) -> Option<f64> {
+    let (sign, mut rest) = match duration.trim() {
+        s if s.starts_with('-') => (-1.0, &s[1..]),
+        s => (1.0, s.strip_prefix('+').unwrap_or(s)),
+    };
+    if rest == "0" {
+        return Some(0.0);
+    }
+    if rest.is_empty() {
+        return None;
+    }
+
+    let mut seconds = 0.0;
+    while !rest.is_empty() {
+        let unit_start = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let (number, tail) = rest.split_at(unit_start);
+        let number: f64 = number.parse().ok()?;
+        let unit_end = tail
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_end);
+        let unit_seconds = match unit {
+            "ns" => 1e-9,
+            "us" | "µs" | "μs" => 1e-6,
+            "ms" => 1e-3,
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            _ => return None,
+        };
+        seconds += number * unit_seconds;
+        rest = tail;
+    }
+    Some(sign * seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::udf::eval;
+
+    #[test]
+    fn ages() {
+        let now = DateTime::parse_from_rfc3339("2025-01-02T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(age("2025-01-01T23:59:30Z", now), Some(30));
+        assert_eq!(age("2025-01-01T00:00:00+09:00", now), Some(33 * 3600));
+        assert_eq!(age("yesterday", now), None);
+    }
+
+    #[test]
+    fn human_durations() {
+        assert_eq!(human_duration(-5), "<invalid>");
+        assert_eq!(human_duration(-1), "0s");
+        assert_eq!(human_duration(90), "90s");
+        assert_eq!(human_duration(150), "2m30s");
+        assert_eq!(human_duration(600), "10m");
+        assert_eq!(human_duration(3 * 3600 + 1800), "3h30m");
+        assert_eq!(human_duration(10 * 3600), "10h");
+        assert_eq!(human_duration(5 * 86400 + 3 * 3600), "5d3h");
+        assert_eq!(human_duration(30 * 86400), "30d");
+        assert_eq!(human_duration(3 * 365 * 86400 + 10 * 86400), "3y10d");
+        assert_eq!(human_duration(10 * 365 * 86400), "10y");
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("1h30m"), Some(5400.0));
+        assert_eq!(parse_duration("500ms"), Some(0.5));
+        assert_eq!(parse_duration("1.5s"), Some(1.5));
+        assert_eq!(parse_duration("-2m"), Some(-120.0));
+        assert_eq!(parse_duration("0"), Some(0.0));
+        assert!((parse_duration("10µs").unwrap() - 1e-5).abs() < 1e-12);
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("10"), None);
+        assert_eq!(parse_duration("1d"), None);
+    }
+
+    #[tokio::test]
+    async fn duration_functions() {
+        assert_eq!(
+            eval("parse_k8s_duration('2h')").await.as_deref(),
+            Some("7200.0")
+        );
+        assert_eq!(eval("age('invalid')").await, None);
+        assert_eq!(
+            eval("age_seconds('2000-01-01T00:00:00Z') > 0")
+                .await
+                .as_deref(),
+            Some("true")
+        );
+    }
+}
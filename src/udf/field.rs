@@ -0,0 +1,170 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{any::Any, sync::Arc};
+
+use datafusion::{
+    arrow::{
+        array::{Array, AsArray, make_array, new_null_array},
+        buffer::NullBuffer,
+        datatypes::{DataType, Field, FieldRef},
+    },
+    common::{Result as DataFusionResult, ScalarValue, exec_err, plan_err},
+    logical_expr::{
+        ColumnarValue, ReturnFieldArgs, ScalarFunctionArgs, ScalarUDF, ScalarUDFImpl, Signature,
+        Volatility,
+    },
+};
+
+/// `try_get_field(struct, name)`: the field of the struct, NULL if the struct has no such field.
+///
+/// Fields are in the schema only if OpenAPI describes them or some object has them
+/// (e.g. `resources.requests` of containers), so queries meant for any cluster use this
+/// where a field may be missing.
+pub fn try_get_field_udf() -> ScalarUDF {
+    ScalarUDF::new_from_impl(TryGetField {
+        signature: Signature::any(2, Volatility::Immutable),
+    })
+}
+
+#[derive(Debug)]
+struct TryGetField {
+    signature: Signature,
+}
+
+/// The name of the field, which must be a string literal.
+fn field_name(name: Option<&ScalarValue>) -> DataFusionResult<&str> {
+    match name {
+        Some(ScalarValue::Utf8(Some(name))) => Ok(name),
+        _ => plan_err!("try_get_field expects the name of the field as a string literal"),
+    }
+}
+
+impl ScalarUDFImpl for TryGetField {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "try_get_field"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> DataFusionResult<DataType> {
+        exec_err!("return_field_from_args should be called instead")
+    }
+
+    fn return_field_from_args(&self, args: ReturnFieldArgs) -> DataFusionResult<FieldRef> {
+        let name = field_name(args.scalar_arguments[1])?;
+        let data_type = match args.arg_fields[0].data_type() {
+            DataType::Struct(fields) => fields
+                .find(name)
+                .map(|(_, field)| field.data_type().clone())
+                .unwrap_or(DataType::Null),
+            _ => DataType::Null,
+        };
+        Ok(Arc::new(Field::new(self.name(), data_type, true)))
+    }
+
+    fn invoke_with_args(&self, args: ScalarFunctionArgs) -> DataFusionResult<ColumnarValue> {
+        let name = match &args.args[1] {
+            ColumnarValue::Scalar(name) => field_name(Some(name))?,
+            ColumnarValue::Array(_) => field_name(None)?,
+        };
+        let array = args.args[0].to_array(args.number_rows)?;
+        let column = array
+            .as_struct_opt()
+            .and_then(|structs| {
+                let column = structs.column_by_name(name)?;
+                // Rows where the struct itself is NULL are NULL too.
+                Some(match structs.nulls() {
+                    Some(nulls) => {
+                        let nulls = NullBuffer::union(Some(nulls), column.nulls());
+                        make_array(column.to_data().into_builder().nulls(nulls).build().ok()?)
+                    }
+                    None => column.clone(),
+                })
+            })
+            .unwrap_or_else(|| new_null_array(args.return_field.data_type(), args.number_rows));
+        Ok(ColumnarValue::Array(column))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::{arrow::util::display::array_value_to_string, prelude::SessionContext};
+
+    use crate::udf::eval;
+
+    #[tokio::test]
+    async fn present_and_missing_fields() {
+        let spec = "named_struct('replicas', 3, 'paused', false)";
+        assert_eq!(
+            eval(&format!("try_get_field({}, 'replicas')", spec)).await,
+            Some("3".into())
+        );
+        assert_eq!(
+            eval(&format!("try_get_field({}, 'selector')", spec)).await,
+            None
+        );
+        assert_eq!(eval("try_get_field(NULL, 'replicas')").await, None);
+        assert_eq!(eval("try_get_field('spec', 'replicas')").await, None);
+    }
+
+    #[tokio::test]
+    async fn null_structs() {
+        let ctx = SessionContext::new();
+        crate::udf::register(&ctx);
+        // The field of a NULL struct is NULL even where the field itself is not.
+        let batches = ctx
+            .sql(
+                "SELECT try_get_field(CASE WHEN n > 1 THEN named_struct('n', n) END, 'n')
+                FROM (VALUES (1), (2), (3)) AS v(n) ORDER BY n",
+            )
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let column = batches[0].column(0);
+        let values: Vec<_> = (0..column.len())
+            .map(|i| {
+                column
+                    .is_valid(i)
+                    .then(|| array_value_to_string(column, i).unwrap())
+            })
+            .collect();
+        assert_eq!(values, [None, Some("2".into()), Some("3".into())]);
+    }
+
+    #[tokio::test]
+    async fn non_literal_name() {
+        let ctx = SessionContext::new();
+        crate::udf::register(&ctx);
+        let error = ctx
+            .sql("SELECT try_get_field(named_struct('a', 1), name) FROM (VALUES ('a')) AS v(name)")
+            .await
+            .unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("try_get_field expects the name of the field as a string literal"),
+            "{}",
+            error
+        );
+    }
+}
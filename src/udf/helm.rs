@@ -0,0 +1,38 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use datafusion::{
+    arrow::{array::StringArray, datatypes::DataType},
+    logical_expr::{ScalarUDF, Volatility, create_udf},
+};
+
+use super::map_strings;
+use crate::helm::decode_release;
+
+/// `helm_release(string)`: the Helm v3 release stored in `data.release`
+/// of a Secret or ConfigMap, as JSON text (e.g. for `json_get`).
+/// NULL if not a release.
+pub fn helm_release_udf() -> ScalarUDF {
+    create_udf(
+        "helm_release",
+        vec![DataType::Utf8],
+        DataType::Utf8,
+        Volatility::Immutable,
+        Arc::new(|args| {
+            map_strings::<_, StringArray>(args, |s| Some(decode_release(s)?.to_string()))
+        }),
+    )
+}
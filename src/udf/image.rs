@@ -0,0 +1,207 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use datafusion::{
+    arrow::{array::StringArray, datatypes::DataType},
+    logical_expr::{ScalarUDF, Volatility, create_udf},
+};
+
+use super::map_strings;
+
+/// Registry of images without one, as container runtimes default to.
+const DEFAULT_REGISTRY: &str = "docker.io";
+
+/// Parts of an image reference, `[registry/]repository[:tag][@digest]`,
+/// normalized as container runtimes do (e.g. `nginx` is `docker.io/library/nginx:latest`).
+struct ImageReference {
+    registry: String,
+    repository: String,
+    tag: Option<String>,
+    digest: Option<String>,
+}
+
+impl ImageReference {
+    fn parse(image: &str) -> Option<Self> {
+        let image = image.trim();
+        let (name, digest) = match image.split_once('@') {
+            Some((name, digest)) => (name, Some(digest.to_string())),
+            None => (image, None),
+        };
+        // A colon after the last slash separates the tag, other colons are registry ports.
+        let (name, tag) = match name.rsplit_once(':') {
+            Some((repository, tag)) if !tag.contains('/') => (repository, Some(tag.to_string())),
+            _ => (name, None),
+        };
+        if name.is_empty() {
+            return None;
+        }
+        // The first component is a registry only if it looks like a host.
+        let (registry, repository) = match name.split_once('/') {
+            Some((host, repository)) if host.contains(['.', ':']) || host == "localhost" => {
+                (host.to_string(), repository.to_string())
+            }
+            _ => (DEFAULT_REGISTRY.to_string(), name.to_string()),
+        };
+        let repository = if registry == DEFAULT_REGISTRY && !repository.contains('/') {
+            format!("library/{}", repository)
+        } else {
+            repository
+        };
+        // Images are pulled with the `latest` tag unless tagged or pinned by digest.
+        let tag = tag.or_else(|| digest.is_none().then(|| "latest".to_string()));
+        Some(Self {
+            registry,
+            repository,
+            tag,
+            digest,
+        })
+    }
+}
+
+/// Create a function returning a part of an image reference, NULL if absent or invalid.
+fn image_part_udf(name: &str, part: fn(ImageReference) -> Option<String>) -> ScalarUDF {
+    create_udf(
+        name,
+        vec![DataType::Utf8],
+        DataType::Utf8,
+        Volatility::Immutable,
+        Arc::new(move |args| {
+            map_strings::<_, StringArray>(args, |image| part(ImageReference::parse(image)?))
+        }),
+    )
+}
+
+/// `image_registry(image)`: e.g. `gcr.io` for `gcr.io/proj/app:v1`, `docker.io` for `nginx`.
+pub fn image_registry_udf() -> ScalarUDF {
+    image_part_udf("image_registry", |image| Some(image.registry))
+}
+
+/// `image_repository(image)`: e.g. `proj/app` for `gcr.io/proj/app:v1`, `library/nginx` for `nginx`.
+pub fn image_repository_udf() -> ScalarUDF {
+    image_part_udf("image_repository", |image| Some(image.repository))
+}
+
+/// `image_tag(image)`: e.g. `v1` for `gcr.io/proj/app:v1`, `latest` for `nginx`,
+/// NULL for an untagged image pinned by digest.
+pub fn image_tag_udf() -> ScalarUDF {
+    image_part_udf("image_tag", |image| image.tag)
+}
+
+/// `image_digest(image)`: e.g. `sha256:...` for `app@sha256:...`, NULL if not pinned by digest.
+pub fn image_digest_udf() -> ScalarUDF {
+    image_part_udf("image_digest", |image| image.digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::udf::eval;
+
+    fn parts(image: &str) -> (String, String, Option<String>, Option<String>) {
+        let image = ImageReference::parse(image).unwrap();
+        (image.registry, image.repository, image.tag, image.digest)
+    }
+
+    #[test]
+    fn docker_hub_images() {
+        assert_eq!(
+            parts("nginx"),
+            (
+                "docker.io".into(),
+                "library/nginx".into(),
+                Some("latest".into()),
+                None
+            )
+        );
+        assert_eq!(
+            parts("bitnami/redis:7.2"),
+            (
+                "docker.io".into(),
+                "bitnami/redis".into(),
+                Some("7.2".into()),
+                None
+            )
+        );
+    }
+
+    #[test]
+    fn registry_images() {
+        assert_eq!(
+            parts("gcr.io/proj/app:v1"),
+            ("gcr.io".into(), "proj/app".into(), Some("v1".into()), None)
+        );
+        // A port of the registry is not a tag.
+        assert_eq!(
+            parts("localhost:5000/app"),
+            (
+                "localhost:5000".into(),
+                "app".into(),
+                Some("latest".into()),
+                None
+            )
+        );
+        assert_eq!(
+            parts("registry.local:5000/team/app:1.0"),
+            (
+                "registry.local:5000".into(),
+                "team/app".into(),
+                Some("1.0".into()),
+                None
+            )
+        );
+    }
+
+    #[test]
+    fn digest_images() {
+        assert_eq!(
+            parts("ghcr.io/org/app@sha256:abc"),
+            (
+                "ghcr.io".into(),
+                "org/app".into(),
+                None,
+                Some("sha256:abc".into())
+            )
+        );
+        assert_eq!(
+            parts("app:v2@sha256:abc"),
+            (
+                "docker.io".into(),
+                "library/app".into(),
+                Some("v2".into()),
+                Some("sha256:abc".into())
+            )
+        );
+        assert!(ImageReference::parse("").is_none());
+        assert!(ImageReference::parse(":v1").is_none());
+    }
+
+    #[tokio::test]
+    async fn image_functions() {
+        assert_eq!(
+            eval("image_registry('nginx:1.25')").await.as_deref(),
+            Some("docker.io")
+        );
+        assert_eq!(
+            eval("image_repository('nginx:1.25')").await.as_deref(),
+            Some("library/nginx")
+        );
+        assert_eq!(
+            eval("image_tag('nginx:1.25')").await.as_deref(),
+            Some("1.25")
+        );
+        assert_eq!(eval("image_digest('nginx:1.25')").await, None);
+    }
+}
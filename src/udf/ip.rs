@@ -0,0 +1,180 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{net::IpAddr, sync::Arc};
+
+use datafusion::{
+    arrow::{
+        array::{BooleanArray, Int64Array},
+        datatypes::DataType,
+    },
+    logical_expr::{ScalarUDF, Volatility, create_udf},
+};
+
+use super::{map_string_pairs, map_strings};
+
+/// `ip_in_cidr(ip, cidr)`: whether the IP address is in the CIDR block
+/// (e.g. `ip_in_cidr(status.podIP, '10.0.0.0/16')`), NULL if either is invalid.
+pub fn ip_in_cidr_udf() -> ScalarUDF {
+    create_udf(
+        "ip_in_cidr",
+        vec![DataType::Utf8, DataType::Utf8],
+        DataType::Boolean,
+        Volatility::Immutable,
+        Arc::new(|args| {
+            map_string_pairs::<_, BooleanArray>(args, |ip, cidr| {
+                let ip: IpAddr = ip.trim().parse().ok()?;
+                Some(Cidr::parse(cidr)?.contains(ip))
+            })
+        }),
+    )
+}
+
+/// `cidr_overlaps(cidr, cidr)`: whether the CIDR blocks share any address
+/// (e.g. NetworkPolicy ipBlocks against the Service CIDR), NULL if either is invalid.
+pub fn cidr_overlaps_udf() -> ScalarUDF {
+    create_udf(
+        "cidr_overlaps",
+        vec![DataType::Utf8, DataType::Utf8],
+        DataType::Boolean,
+        Volatility::Immutable,
+        Arc::new(|args| {
+            map_string_pairs::<_, BooleanArray>(args, |a, b| {
+                Some(Cidr::parse(a)?.overlaps(&Cidr::parse(b)?))
+            })
+        }),
+    )
+}
+
+/// `ip_family(ip)`: 4 or 6 for an IPv4 or IPv6 address or CIDR block, NULL if invalid.
+pub fn ip_family_udf() -> ScalarUDF {
+    create_udf(
+        "ip_family",
+        vec![DataType::Utf8],
+        DataType::Int64,
+        Volatility::Immutable,
+        Arc::new(|args| {
+            map_strings::<_, Int64Array>(args, |ip| {
+                let ip = match ip.trim().parse::<IpAddr>() {
+                    Ok(ip) => ip,
+                    Err(_) => Cidr::parse(ip)?.address,
+                };
+                Some(if ip.is_ipv4() { 4 } else { 6 })
+            })
+        }),
+    )
+}
+
+/// CIDR block, e.g. `10.0.0.0/16` or `fd00::/8`
+struct Cidr {
+    address: IpAddr,
+    prefix_len: u32,
+}
+
+impl Cidr {
+    fn parse(cidr: &str) -> Option<Self> {
+        let (address, prefix_len) = cidr.trim().split_once('/')?;
+        let address: IpAddr = address.parse().ok()?;
+        let prefix_len: u32 = prefix_len.parse().ok()?;
+        let max_prefix_len = if address.is_ipv4() { 32 } else { 128 };
+        (prefix_len <= max_prefix_len).then_some(Self {
+            address,
+            prefix_len,
+        })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        same_prefix(self.address, ip, self.prefix_len)
+    }
+
+    fn overlaps(&self, other: &Cidr) -> bool {
+        same_prefix(
+            self.address,
+            other.address,
+            self.prefix_len.min(other.prefix_len),
+        )
+    }
+}
+
+/// Whether the addresses of the same family share the first `prefix_len` bits.
+fn same_prefix(a: IpAddr, b: IpAddr, prefix_len: u32) -> bool {
+    match (a, b) {
+        (IpAddr::V4(a), IpAddr::V4(b)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix_len).unwrap_or(0);
+            u32::from(a) & mask == u32::from(b) & mask
+        }
+        (IpAddr::V6(a), IpAddr::V6(b)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix_len).unwrap_or(0);
+            u128::from(a) & mask == u128::from(b) & mask
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::udf::eval;
+
+    fn cidr(cidr: &str) -> Cidr {
+        Cidr::parse(cidr).unwrap()
+    }
+
+    #[test]
+    fn contains() {
+        assert!(cidr("10.0.0.0/16").contains("10.0.255.1".parse().unwrap()));
+        assert!(!cidr("10.0.0.0/16").contains("10.1.0.1".parse().unwrap()));
+        assert!(cidr("0.0.0.0/0").contains("192.168.1.1".parse().unwrap()));
+        assert!(cidr("10.0.0.1/32").contains("10.0.0.1".parse().unwrap()));
+        assert!(cidr("fd00::/8").contains("fd12::1".parse().unwrap()));
+        // Addresses of another family are never contained.
+        assert!(!cidr("0.0.0.0/0").contains("::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn overlaps() {
+        assert!(cidr("10.0.0.0/8").overlaps(&cidr("10.96.0.0/12")));
+        assert!(cidr("10.96.0.0/12").overlaps(&cidr("10.0.0.0/8")));
+        assert!(!cidr("10.0.0.0/16").overlaps(&cidr("10.1.0.0/16")));
+        assert!(!cidr("fd00::/8").overlaps(&cidr("10.0.0.0/8")));
+    }
+
+    #[test]
+    fn invalid_cidrs() {
+        assert!(Cidr::parse("10.0.0.0").is_none());
+        assert!(Cidr::parse("10.0.0.0/33").is_none());
+        assert!(Cidr::parse("fd00::/129").is_none());
+        assert!(Cidr::parse("10.0.0/8").is_none());
+    }
+
+    #[tokio::test]
+    async fn ip_functions() {
+        assert_eq!(
+            eval("ip_in_cidr('10.0.1.2', '10.0.0.0/16')")
+                .await
+                .as_deref(),
+            Some("true")
+        );
+        assert_eq!(eval("ip_in_cidr('x', '10.0.0.0/16')").await, None);
+        assert_eq!(
+            eval("cidr_overlaps('10.0.0.0/8', '192.168.0.0/16')")
+                .await
+                .as_deref(),
+            Some("false")
+        );
+        assert_eq!(eval("ip_family('10.0.0.1')").await.as_deref(), Some("4"));
+        assert_eq!(eval("ip_family('fd00::/8')").await.as_deref(), Some("6"));
+        assert_eq!(eval("ip_family('host')").await, None);
+    }
+}
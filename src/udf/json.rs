@@ -0,0 +1,98 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use datafusion::{
+    arrow::{array::StringArray, datatypes::DataType},
+    logical_expr::{ScalarUDF, Volatility, create_udf},
+};
+use jsonpath_rust::JsonPath;
+use serde_json::Value;
+
+use super::map_string_pairs;
+
+/// `json_get(json, path)`: the value at the JSONPath (e.g. `$.spec.replicas`) in the JSON text,
+/// such as the last applied configuration annotation.
+/// Strings are returned as is and other values as JSON text, multiple matches as a JSON array.
+/// NULL if nothing matches, or the JSON or the path is invalid.
+pub fn json_get_udf() -> ScalarUDF {
+    create_udf(
+        "json_get",
+        vec![DataType::Utf8, DataType::Utf8],
+        DataType::Utf8,
+        Volatility::Immutable,
+        Arc::new(|args| map_string_pairs::<_, StringArray>(args, json_get)),
+    )
+}
+
+fn json_get(json: &str, path: &str) -> Option<String> {
+    let json: Value = serde_json::from_str(json).ok()?;
+    let path = JsonPath::<Value>::try_from(path).ok()?;
+    let mut values: Vec<Value> = path
+        .find_slice_ptr(&json)
+        .into_iter()
+        .map(|value| (*value).clone())
+        .collect();
+    let value = match values.len() {
+        0 => return None,
+        1 => values.remove(0),
+        _ => Value::Array(values),
+    };
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s),
+        value => Some(value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::udf::eval;
+
+    #[test]
+    fn json_paths() {
+        let json = r#"{"spec": {"replicas": 3, "template": {"spec": {"containers": [
+            {"name": "web", "image": "nginx"}, {"name": "sidecar", "image": "envoy"}]}}}}"#;
+        assert_eq!(json_get(json, "$.spec.replicas").as_deref(), Some("3"));
+        assert_eq!(
+            json_get(json, "$.spec.template.spec.containers[0].image").as_deref(),
+            Some("nginx")
+        );
+        assert_eq!(
+            json_get(json, "$.spec.template.spec.containers[*].name").as_deref(),
+            Some(r#"["web","sidecar"]"#)
+        );
+        assert_eq!(
+            json_get(json, "$.spec.template.spec.containers[1]").as_deref(),
+            Some(r#"{"image":"envoy","name":"sidecar"}"#)
+        );
+        assert_eq!(json_get(json, "$.spec.paused"), None);
+        assert_eq!(json_get(r#"{"a": null}"#, "$.a"), None);
+        assert_eq!(json_get("{not json", "$.a"), None);
+        assert_eq!(json_get(json, "not a path["), None);
+    }
+
+    #[tokio::test]
+    async fn json_get_function() {
+        assert_eq!(
+            eval(r#"json_get('{"a": {"b": "c"}}', '$.a.b')"#).await,
+            Some("c".into())
+        );
+        assert_eq!(eval(r#"json_get('{"a": 1}', '$.b')"#).await, None);
+        assert_eq!(eval("json_get('[', '$.a')").await, None);
+        assert_eq!(eval("json_get(NULL, '$.a')").await, None);
+    }
+}
@@ -0,0 +1,207 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{any::Any, sync::Arc};
+
+use datafusion::{
+    arrow::{
+        array::{Array, ArrayRef, AsArray, StringArray},
+        compute::cast,
+        datatypes::DataType,
+    },
+    common::{Result as DataFusionResult, cast::as_string_array, plan_err},
+    logical_expr::{
+        ColumnarValue, ScalarFunctionArgs, ScalarUDF, ScalarUDFImpl, Signature, Volatility,
+    },
+};
+
+/// `label(metadata.labels, key)`: the value of the label, NULL if absent.
+pub fn label_udf() -> ScalarUDF {
+    ScalarUDF::new_from_impl(MapValue::new("label"))
+}
+
+/// `annotation(metadata.annotations, key)`: the value of the annotation, NULL if absent.
+pub fn annotation_udf() -> ScalarUDF {
+    ScalarUDF::new_from_impl(MapValue::new("annotation"))
+}
+
+/// Value of a key of a string map such as labels or annotations.
+///
+/// The maps are inferred as structs with a field per key seen,
+/// so referencing a key as a field fails if no object has it,
+/// whereas this results in NULL. Maps planned without objects (e.g. in a dry run)
+/// are Arrow maps, read entry by entry.
+#[derive(Debug)]
+struct MapValue {
+    name: &'static str,
+    signature: Signature,
+}
+
+impl MapValue {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            signature: Signature::any(2, Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for MapValue {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> DataFusionResult<DataType> {
+        // Maps without any key at all are inferred as NULL.
+        if !matches!(
+            arg_types[0],
+            DataType::Struct(_) | DataType::Map(_, _) | DataType::Null
+        ) {
+            return plan_err!(
+                "{} expects a map such as metadata.{}s, got {}",
+                self.name,
+                self.name,
+                arg_types[0]
+            );
+        }
+        Ok(DataType::Utf8)
+    }
+
+    fn invoke_with_args(&self, args: ScalarFunctionArgs) -> DataFusionResult<ColumnarValue> {
+        let arrays = ColumnarValue::values_to_arrays(&args.args)?;
+        let (maps, keys) = (&arrays[0], cast(&arrays[1], &DataType::Utf8)?);
+        let keys = as_string_array(&keys)?;
+        if let Some(maps) = maps.as_map_opt() {
+            let (map_keys, map_values) = (
+                cast(maps.keys(), &DataType::Utf8)?,
+                cast(maps.values(), &DataType::Utf8)?,
+            );
+            let (map_keys, map_values) =
+                (map_keys.as_string::<i32>(), map_values.as_string::<i32>());
+            let offsets = maps.value_offsets();
+            let values: StringArray = (0..keys.len())
+                .map(|i| {
+                    if maps.is_null(i) || keys.is_null(i) {
+                        return None;
+                    }
+                    let entry = (offsets[i] as usize..offsets[i + 1] as usize)
+                        .find(|&entry| map_keys.value(entry) == keys.value(i))?;
+                    map_values.is_valid(entry).then(|| map_values.value(entry))
+                })
+                .collect();
+            return Ok(ColumnarValue::Array(Arc::new(values)));
+        }
+        // Values that are not strings (e.g. nested objects) are treated as absent.
+        let entries: Vec<(&str, ArrayRef)> = match maps.as_struct_opt() {
+            Some(maps) => maps
+                .fields()
+                .iter()
+                .zip(maps.columns())
+                .filter_map(|(field, column)| {
+                    Some((field.name().as_str(), cast(column, &DataType::Utf8).ok()?))
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let values: StringArray = (0..keys.len())
+            .map(|i| {
+                if maps.is_null(i) || keys.is_null(i) {
+                    return None;
+                }
+                let (_, column) = entries.iter().find(|(key, _)| *key == keys.value(i))?;
+                let column = column.as_string::<i32>();
+                column.is_valid(i).then(|| column.value(i))
+            })
+            .collect();
+        Ok(ColumnarValue::Array(Arc::new(values)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::{datasource::MemTable, prelude::SessionContext};
+
+    use crate::{schema::unsampled_schema, udf::eval};
+
+    #[tokio::test]
+    async fn label_values() {
+        let labels = "named_struct('app', 'web', 'replicas', 3)";
+        assert_eq!(
+            eval(&format!("label({}, 'app')", labels)).await,
+            Some("web".into())
+        );
+        // Values are read as strings.
+        assert_eq!(
+            eval(&format!("annotation({}, 'replicas')", labels)).await,
+            Some("3".into())
+        );
+        assert_eq!(eval(&format!("label({}, 'tier')", labels)).await, None);
+        assert_eq!(eval(&format!("label({}, NULL)", labels)).await, None);
+        assert_eq!(eval("label(NULL, 'app')").await, None);
+        assert_eq!(
+            eval("label(CASE WHEN false THEN named_struct('app', 'web') END, 'app')").await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn non_map_argument() {
+        let ctx = SessionContext::new();
+        crate::udf::register(&ctx);
+        let error = ctx.sql("SELECT label('app', 'app')").await.unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("label expects a map such as metadata.labels, got Utf8"),
+            "{}",
+            error
+        );
+    }
+
+    #[tokio::test]
+    async fn map_values() {
+        assert_eq!(
+            eval("label(map(['app', 'tier'], ['web', 'front']), 'tier')").await,
+            Some("front".into())
+        );
+        assert_eq!(eval("label(map(['app'], ['web']), 'tier')").await, None);
+    }
+
+    #[tokio::test]
+    async fn plan_unsampled_schema() {
+        // Tables are planned with the unsampled schema in a dry run.
+        let ctx = SessionContext::new();
+        crate::udf::register(&ctx);
+        let table = MemTable::try_new(unsampled_schema(), vec![vec![]]).unwrap();
+        ctx.register_table("pods", std::sync::Arc::new(table))
+            .unwrap();
+        let batches = ctx
+            .sql("SELECT label(metadata.labels, 'app'), annotation(metadata.annotations, 'a') FROM pods")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        assert!(batches.iter().all(|batch| batch.num_rows() == 0));
+    }
+}
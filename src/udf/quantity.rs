@@ -0,0 +1,140 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use datafusion::{
+    arrow::{
+        array::{Float64Array, Int64Array},
+        datatypes::DataType,
+    },
+    logical_expr::{ScalarUDF, Volatility, create_udf},
+};
+
+use super::map_strings;
+
+/// `parse_quantity(string)`: the value of a quantity (e.g. CPU `250m` is 0.25), NULL if invalid.
+pub fn parse_quantity_udf() -> ScalarUDF {
+    create_udf(
+        "parse_quantity",
+        vec![DataType::Utf8],
+        DataType::Float64,
+        Volatility::Immutable,
+        Arc::new(|args| map_strings::<_, Float64Array>(args, parse_quantity)),
+    )
+}
+
+/// `parse_quantity_bytes(string)`: the value of a quantity rounded up to an integer,
+/// as for memory and storage (e.g. `1Gi` is 1073741824), NULL if invalid.
+pub fn parse_quantity_bytes_udf() -> ScalarUDF {
+    create_udf(
+        "parse_quantity_bytes",
+        vec![DataType::Utf8],
+        DataType::Int64,
+        Volatility::Immutable,
+        Arc::new(|args| {
+            map_strings::<_, Int64Array>(args, |s| {
+                parse_quantity(s).map(|value| value.ceil() as i64)
+            })
+        }),
+    )
+}
+
+/// Parse a quantity: a decimal number followed by a binary SI suffix (`Ki`, `Mi`, ...),
+/// a decimal SI suffix (`m`, `k`, `M`, ...) or a decimal exponent (`e3`).
+///
+/// See https://kubernetes.io/docs/reference/kubernetes-api/common-definitions/quantity/
+pub fn parse_quantity(quantity: &str) -> Option<f64> {
+    let quantity = quantity.trim();
+    let suffix_start = quantity
+        .find(|c: char| !(c.is_ascii_digit() || matches!(c, '+' | '-' | '.')))
+        .unwrap_or(quantity.len());
+    let (number, suffix) = quantity.split_at(suffix_start);
+    let number: f64 = number.parse().ok()?;
+    // Dividing rather than multiplying by fractions keeps e.g. `3800m` exactly 3.8.
+    let value = match suffix {
+        "" => number,
+        "n" => number / 1e9,
+        "u" => number / 1e6,
+        "m" => number / 1e3,
+        "k" => number * 1e3,
+        "M" => number * 1e6,
+        "G" => number * 1e9,
+        "T" => number * 1e12,
+        "P" => number * 1e15,
+        "E" => number * 1e18,
+        "Ki" => number * 2f64.powi(10),
+        "Mi" => number * 2f64.powi(20),
+        "Gi" => number * 2f64.powi(30),
+        "Ti" => number * 2f64.powi(40),
+        "Pi" => number * 2f64.powi(50),
+        "Ei" => number * 2f64.powi(60),
+        _ => {
+            let exponent: i32 = suffix.strip_prefix(['e', 'E'])?.parse().ok()?;
+            if exponent < 0 {
+                number / 10f64.powi(-exponent)
+            } else {
+                number * 10f64.powi(exponent)
+            }
+        }
+    };
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::udf::eval;
+
+    #[test]
+    fn quantities() {
+        assert_eq!(parse_quantity("250m"), Some(0.25));
+        assert_eq!(parse_quantity("3800m"), Some(3.8));
+        assert_eq!(parse_quantity("2"), Some(2.0));
+        assert_eq!(parse_quantity("1.5"), Some(1.5));
+        assert_eq!(parse_quantity("100n"), Some(1e-7));
+        assert_eq!(parse_quantity("1k"), Some(1000.0));
+        assert_eq!(parse_quantity("1G"), Some(1e9));
+        assert_eq!(parse_quantity("1Ki"), Some(1024.0));
+        assert_eq!(parse_quantity("1Gi"), Some(1073741824.0));
+        assert_eq!(parse_quantity("1e3"), Some(1000.0));
+        assert_eq!(parse_quantity("5E-1"), Some(0.5));
+        assert_eq!(parse_quantity(" 128Mi "), Some(134217728.0));
+    }
+
+    #[test]
+    fn invalid_quantities() {
+        assert_eq!(parse_quantity(""), None);
+        assert_eq!(parse_quantity("Gi"), None);
+        assert_eq!(parse_quantity("1GB"), None);
+        assert_eq!(parse_quantity("1ki"), None);
+        assert_eq!(parse_quantity("abc"), None);
+    }
+
+    #[tokio::test]
+    async fn quantity_functions() {
+        assert_eq!(eval("parse_quantity('500m')").await.as_deref(), Some("0.5"));
+        assert_eq!(
+            eval("parse_quantity_bytes('1Gi')").await.as_deref(),
+            Some("1073741824")
+        );
+        // Fractional bytes are rounded up.
+        assert_eq!(
+            eval("parse_quantity_bytes('1.5')").await.as_deref(),
+            Some("2")
+        );
+        assert_eq!(eval("parse_quantity('x')").await, None);
+        assert_eq!(eval("parse_quantity(NULL)").await, None);
+    }
+}
@@ -0,0 +1,285 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{any::Any, collections::BTreeMap, sync::Arc};
+
+use datafusion::{
+    arrow::{
+        array::{Array, ArrayRef, AsArray, BooleanArray, StructArray},
+        compute::cast,
+        datatypes::DataType,
+    },
+    common::{Result as DataFusionResult, plan_err},
+    logical_expr::{
+        ColumnarValue, ScalarFunctionArgs, ScalarUDF, ScalarUDFImpl, Signature, Volatility,
+    },
+};
+
+/// `matches_selector(labels, selector)`: whether the labels satisfy the selector,
+/// either a map of labels (e.g. `spec.selector` of Services)
+/// or a label selector with `matchLabels` and `matchExpressions` (e.g. of Deployments).
+/// NULL if the selector is NULL.
+pub fn matches_selector_udf() -> ScalarUDF {
+    ScalarUDF::new_from_impl(MatchesSelector {
+        signature: Signature::any(2, Volatility::Immutable),
+    })
+}
+
+#[derive(Debug)]
+struct MatchesSelector {
+    signature: Signature,
+}
+
+/// Requirement of `matchExpressions`
+struct Requirement {
+    key: String,
+    operator: String,
+    values: Vec<String>,
+}
+
+impl Requirement {
+    fn matches(&self, labels: &BTreeMap<String, String>) -> bool {
+        let value = labels.get(&self.key);
+        match self.operator.as_str() {
+            "In" => value.is_some_and(|value| self.values.contains(value)),
+            "NotIn" => value.is_none_or(|value| !self.values.contains(value)),
+            "Exists" => value.is_some(),
+            "DoesNotExist" => value.is_none(),
+            _ => false,
+        }
+    }
+}
+
+/// Whether the struct is a label selector rather than a map of labels
+fn is_label_selector(selectors: &StructArray) -> bool {
+    !selectors.fields().is_empty()
+        && selectors
+            .fields()
+            .iter()
+            .all(|field| matches!(field.name().as_str(), "matchLabels" | "matchExpressions"))
+}
+
+/// String maps of each row of a struct array (e.g. labels), NULL rows as `None`.
+/// Keys with values that are NULL or not strings are left out.
+fn string_maps(array: &dyn Array) -> Vec<Option<BTreeMap<String, String>>> {
+    let Some(maps) = array.as_struct_opt() else {
+        return vec![None; array.len()];
+    };
+    let columns: Vec<(&str, ArrayRef)> = maps
+        .fields()
+        .iter()
+        .zip(maps.columns())
+        .filter_map(|(field, column)| {
+            Some((field.name().as_str(), cast(column, &DataType::Utf8).ok()?))
+        })
+        .collect();
+    (0..maps.len())
+        .map(|i| {
+            maps.is_valid(i).then(|| {
+                columns
+                    .iter()
+                    .filter_map(|(key, column)| {
+                        let column = column.as_string::<i32>();
+                        column
+                            .is_valid(i)
+                            .then(|| (key.to_string(), column.value(i).to_string()))
+                    })
+                    .collect()
+            })
+        })
+        .collect()
+}
+
+/// Requirements of `matchExpressions` of each row, NULL rows as empty.
+fn requirements(array: Option<&ArrayRef>, len: usize) -> Vec<Vec<Requirement>> {
+    let Some(lists) = array.and_then(|array| array.as_list_opt::<i32>()) else {
+        return (0..len).map(|_| Vec::new()).collect();
+    };
+    let Some(expressions) = lists.values().as_struct_opt() else {
+        return (0..len).map(|_| Vec::new()).collect();
+    };
+    let column = |name: &str| {
+        expressions
+            .column_by_name(name)
+            .and_then(|column| cast(column, &DataType::Utf8).ok())
+    };
+    let (keys, operators) = (column("key"), column("operator"));
+    let values = expressions
+        .column_by_name("values")
+        .and_then(|values| cast(values, &DataType::new_list(DataType::Utf8, true)).ok());
+
+    let requirement = |j: usize| {
+        let string = |column: &Option<ArrayRef>| {
+            let column = column.as_ref()?.as_string::<i32>();
+            column.is_valid(j).then(|| column.value(j).to_string())
+        };
+        let values = values
+            .as_ref()
+            .map(|values| values.as_list::<i32>())
+            .filter(|values| values.is_valid(j))
+            .map(|values| {
+                let values = values.value(j);
+                let values = values.as_string::<i32>();
+                values.iter().flatten().map(str::to_string).collect()
+            })
+            .unwrap_or_default();
+        Requirement {
+            key: string(&keys).unwrap_or_default(),
+            operator: string(&operators).unwrap_or_default(),
+            values,
+        }
+    };
+    (0..len)
+        .map(|i| {
+            if lists.is_null(i) {
+                return Vec::new();
+            }
+            let offsets = lists.value_offsets();
+            (offsets[i] as usize..offsets[i + 1] as usize)
+                .map(requirement)
+                .collect()
+        })
+        .collect()
+}
+
+impl ScalarUDFImpl for MatchesSelector {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "matches_selector"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> DataFusionResult<DataType> {
+        for arg_type in arg_types {
+            if !matches!(arg_type, DataType::Struct(_) | DataType::Null) {
+                return plan_err!(
+                    "matches_selector expects labels and a selector, got {}",
+                    arg_type
+                );
+            }
+        }
+        Ok(DataType::Boolean)
+    }
+
+    fn invoke_with_args(&self, args: ScalarFunctionArgs) -> DataFusionResult<ColumnarValue> {
+        let arrays = ColumnarValue::values_to_arrays(&args.args)?;
+        let (labels, selectors) = (string_maps(&arrays[0]), &arrays[1]);
+        // Objects without any labels match selectors without requirements.
+        let no_labels = BTreeMap::new();
+        let labels = |i: usize| labels[i].as_ref().unwrap_or(&no_labels);
+
+        let matches: BooleanArray = match selectors.as_struct_opt() {
+            Some(selectors) if is_label_selector(selectors) => {
+                let match_labels = selectors
+                    .column_by_name("matchLabels")
+                    .map(|match_labels| string_maps(match_labels));
+                let match_expressions = requirements(
+                    selectors.column_by_name("matchExpressions"),
+                    selectors.len(),
+                );
+                (0..selectors.len())
+                    .map(|i| {
+                        selectors.is_valid(i).then(|| {
+                            let labels = labels(i);
+                            let match_labels = match_labels
+                                .as_ref()
+                                .and_then(|match_labels| match_labels[i].as_ref());
+                            match_labels.is_none_or(|match_labels| {
+                                match_labels
+                                    .iter()
+                                    .all(|(key, value)| labels.get(key) == Some(value))
+                            }) && match_expressions[i]
+                                .iter()
+                                .all(|requirement| requirement.matches(labels))
+                        })
+                    })
+                    .collect()
+            }
+            _ => string_maps(selectors)
+                .iter()
+                .enumerate()
+                .map(|(i, selector)| {
+                    let labels = labels(i);
+                    selector.as_ref().map(|selector| {
+                        selector
+                            .iter()
+                            .all(|(key, value)| labels.get(key) == Some(value))
+                    })
+                })
+                .collect(),
+        };
+        Ok(ColumnarValue::Array(Arc::new(matches)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::udf::eval;
+
+    const LABELS: &str = "named_struct('app', 'web', 'tier', 'db')";
+
+    fn requirement(key: &str, operator: &str, values: &[&str]) -> Requirement {
+        Requirement {
+            key: key.to_string(),
+            operator: operator.to_string(),
+            values: values.iter().map(|value| value.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn requirements() {
+        let labels = BTreeMap::from([("app".to_string(), "web".to_string())]);
+        assert!(requirement("app", "In", &["web", "api"]).matches(&labels));
+        assert!(!requirement("app", "NotIn", &["web"]).matches(&labels));
+        assert!(requirement("tier", "NotIn", &["db"]).matches(&labels));
+        assert!(requirement("app", "Exists", &[]).matches(&labels));
+        assert!(requirement("tier", "DoesNotExist", &[]).matches(&labels));
+        assert!(!requirement("app", "Gt", &["1"]).matches(&labels));
+    }
+
+    #[tokio::test]
+    async fn map_selector() {
+        let matches =
+            |selector: &str| eval_bool(format!("matches_selector({}, {})", LABELS, selector));
+        assert_eq!(matches("named_struct('app', 'web')").await, Some(true));
+        assert_eq!(matches("named_struct('app', 'api')").await, Some(false));
+        assert_eq!(matches("named_struct('env', 'prod')").await, Some(false));
+        assert_eq!(matches("NULL").await, None);
+    }
+
+    #[tokio::test]
+    async fn label_selector() {
+        let matches =
+            |selector: &str| eval_bool(format!("matches_selector({}, {})", LABELS, selector));
+        let selector = "named_struct('matchLabels', named_struct('app', 'web'), \
+             'matchExpressions', [named_struct('key', 'tier', 'operator', 'In', 'values', ['db', 'cache'])])";
+        assert_eq!(matches(selector).await, Some(true));
+        let selector = "named_struct('matchExpressions', \
+             [named_struct('key', 'tier', 'operator', 'NotIn', 'values', ['db'])])";
+        assert_eq!(matches(selector).await, Some(false));
+        let selector = "named_struct('matchLabels', named_struct('app', 'api'))";
+        assert_eq!(matches(selector).await, Some(false));
+    }
+
+    async fn eval_bool(expr: String) -> Option<bool> {
+        eval(&expr).await.map(|value| value == "true")
+    }
+}
@@ -0,0 +1,90 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{future::Future, sync::Arc, thread};
+
+use datafusion::{
+    common::{Result as DataFusionResult, ScalarValue, plan_err},
+    error::DataFusionError,
+    execution::context::SessionContext,
+    logical_expr::Expr,
+};
+
+use crate::provider::KubernetesTableProviderFactory;
+
+mod events;
+mod logs;
+mod promql;
+
+/// Register the table functions to the session, reading the clusters of the factory.
+pub fn register(ctx: &SessionContext, factory: &Arc<KubernetesTableProviderFactory>) {
+    ctx.register_udtf(
+        "events_for",
+        Arc::new(events::EventsFor {
+            factory: Arc::clone(factory),
+        }),
+    );
+    ctx.register_udtf(
+        "logs",
+        Arc::new(logs::Logs {
+            factory: Arc::clone(factory),
+        }),
+    );
+    ctx.register_udtf(
+        "promql",
+        Arc::new(promql::PromQl {
+            factory: Arc::clone(factory),
+        }),
+    );
+}
+
+/// String of a literal argument of the table function, `None` if NULL
+fn string_arg(function: &str, args: &[Expr], index: usize) -> DataFusionResult<Option<String>> {
+    match args.get(index) {
+        Some(Expr::Literal(
+            ScalarValue::Utf8(value) | ScalarValue::Utf8View(value) | ScalarValue::LargeUtf8(value),
+            _,
+        )) => Ok(value.clone()),
+        Some(Expr::Literal(ScalarValue::Null, _)) => Ok(None),
+        Some(arg) => plan_err!(
+            "{} expects a string literal as argument {}, got {}",
+            function,
+            index + 1,
+            arg
+        ),
+        None => Ok(None),
+    }
+}
+
+/// Run the future to completion at planning, which is not async, blocking until it is done.
+/// It runs on a runtime of its own on another thread, as a runtime cannot be nested
+/// in the one the query is planned on.
+fn block_on<F>(future: F) -> DataFusionResult<F::Output>
+where
+    F: Future + Send,
+    F::Output: Send,
+{
+    thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .map(|runtime| runtime.block_on(future))
+            })
+            .join()
+    })
+    .map_err(|_| DataFusionError::Internal("table function panicked".to_string()))?
+    .map_err(|e| DataFusionError::External(Box::new(e)))
+}
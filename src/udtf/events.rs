@@ -0,0 +1,333 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use datafusion::{
+    arrow::{
+        array::{ArrayRef, Int64Array, StringArray},
+        datatypes::{DataType, Field, Schema, SchemaRef},
+        record_batch::RecordBatch,
+    },
+    catalog::{Session, TableFunctionImpl, TableProvider},
+    common::{DataFusionError, Result as DataFusionResult, plan_err},
+    datasource::{MemTable, TableType},
+    logical_expr::Expr,
+    physical_plan::ExecutionPlan,
+};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::APIResource;
+use kube::api::ListParams;
+use serde_json::Value;
+
+use super::string_arg;
+use crate::{cluster::Cluster, dynamic::DynamicObject, provider::KubernetesTableProviderFactory};
+
+/// `events_for(kind, namespace, name)`: the Events of the object and of the objects it owns,
+/// directly or not (e.g. the ReplicaSets and Pods of a Deployment).
+#[derive(Debug)]
+pub struct EventsFor {
+    pub factory: Arc<KubernetesTableProviderFactory>,
+}
+
+impl TableFunctionImpl for EventsFor {
+    fn call(&self, args: &[Expr]) -> DataFusionResult<Arc<dyn TableProvider>> {
+        let (Some(kind), Some(namespace), Some(name)) = (
+            string_arg("events_for", args, 0)?,
+            string_arg("events_for", args, 1)?,
+            string_arg("events_for", args, 2)?,
+        ) else {
+            return plan_err!(
+                "events_for expects the kind, the namespace and the name of an object"
+            );
+        };
+        Ok(Arc::new(EventsForTable {
+            factory: Arc::clone(&self.factory),
+            kind,
+            namespace,
+            name,
+            schema: Arc::new(Schema::new(vec![
+                Field::new("kind", DataType::Utf8, true),
+                Field::new("name", DataType::Utf8, true),
+                Field::new("type", DataType::Utf8, true),
+                Field::new("reason", DataType::Utf8, true),
+                Field::new("message", DataType::Utf8, true),
+                Field::new("count", DataType::Int64, true),
+                Field::new("first_timestamp", DataType::Utf8, true),
+                Field::new("last_timestamp", DataType::Utf8, true),
+                Field::new("source", DataType::Utf8, true),
+            ])),
+        }))
+    }
+}
+
+/// Events of an object and of those it owns, listed at each scan
+#[derive(Debug)]
+struct EventsForTable {
+    factory: Arc<KubernetesTableProviderFactory>,
+    /// Kind or resource name of the object, e.g. `Deployment` or `deployments.apps`
+    kind: String,
+    namespace: String,
+    name: String,
+    schema: SchemaRef,
+}
+
+impl EventsForTable {
+    /// Events involving the object or an object it owns
+    async fn events(&self) -> DataFusionResult<Vec<DynamicObject>> {
+        let url = format!("{}/{}", self.kind.to_lowercase(), self.namespace);
+        let Some((_, kubeurl, cluster)) = self.factory.resolve_all(&url).await?.into_iter().next()
+        else {
+            return plan_err!("events_for: resource '{}' not found", self.kind);
+        };
+        let namespace = kubeurl
+            .resource
+            .namespaced
+            .then_some(self.namespace.as_str());
+        let object = cluster
+            .lister
+            .get(&kubeurl.resource, namespace, &self.name, true)
+            .await
+            .map_err(|e| DataFusionError::External(Box::new(e)))?
+            .ok_or_else(|| {
+                DataFusionError::Execution(format!(
+                    "events_for: {} {}/{} not found",
+                    kubeurl.resource.kind, self.namespace, self.name
+                ))
+            })?;
+        let uid = object.metadata.uid.clone().unwrap_or_default();
+
+        let Some((_, events_url, _)) = self
+            .factory
+            .resolve_all(&format!("events/{}", self.namespace))
+            .await?
+            .into_iter()
+            .next()
+        else {
+            return plan_err!("events_for: no events in the cluster");
+        };
+        let events = cluster
+            .lister
+            .list(
+                &events_url.resource,
+                Some(&self.namespace),
+                &ListParams::default(),
+                false,
+            )
+            .await
+            .map_err(|e| DataFusionError::External(Box::new(e)))?
+            .items;
+
+        let owners = Owners::list(&cluster, &self.namespace, &events).await?;
+        Ok(events
+            .into_iter()
+            .filter(|event| {
+                let involved = involved_object(event);
+                match involved["uid"].as_str().filter(|uid| !uid.is_empty()) {
+                    Some(involved_uid) => owners.is_owned_by(involved_uid, &uid),
+                    // Events may not record the uid of the object.
+                    None => {
+                        involved["kind"].as_str() == Some(kubeurl.resource.kind.as_str())
+                            && involved["name"].as_str() == Some(self.name.as_str())
+                    }
+                }
+            })
+            .collect())
+    }
+}
+
+/// Object an Event is about, of either `v1` or `events.k8s.io/v1`
+fn involved_object(event: &DynamicObject) -> &Value {
+    match &event.data["involvedObject"] {
+        Value::Null => &event.data["regarding"],
+        involved => involved,
+    }
+}
+
+/// Owners of the objects of the kinds involved in the events, and of the kinds of their owners
+struct Owners {
+    /// Uids of the owners of each object
+    owners: HashMap<String, Vec<String>>,
+}
+
+impl Owners {
+    async fn list(
+        cluster: &Cluster,
+        namespace: &str,
+        events: &[DynamicObject],
+    ) -> DataFusionResult<Self> {
+        let api_resources = cluster.api_resources().await.map_err(|e| {
+            DataFusionError::Plan(format!("Failed to discover API resources: {}", e))
+        })?;
+        let mut pending: Vec<(String, String)> = events
+            .iter()
+            .map(|event| {
+                let involved = involved_object(event);
+                (
+                    involved["apiVersion"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                    involved["kind"].as_str().unwrap_or_default().to_string(),
+                )
+            })
+            .collect();
+        let mut listed = HashSet::new();
+        let mut owners = HashMap::new();
+        while let Some((api_version, kind)) = pending.pop() {
+            if !listed.insert((api_version.clone(), kind.clone())) {
+                continue;
+            }
+            let Some(api_resource) = find_kind(api_resources, &api_version, &kind) else {
+                continue;
+            };
+            let objects = cluster
+                .lister
+                .list(
+                    api_resource,
+                    api_resource.namespaced.then_some(namespace),
+                    &ListParams::default(),
+                    true,
+                )
+                .await
+                .map_err(|e| DataFusionError::External(Box::new(e)))?;
+            for object in objects.items {
+                let references = object.metadata.owner_references.unwrap_or_default();
+                pending.extend(
+                    references
+                        .iter()
+                        .map(|owner| (owner.api_version.clone(), owner.kind.clone())),
+                );
+                owners.insert(
+                    object.metadata.uid.unwrap_or_default(),
+                    references.into_iter().map(|owner| owner.uid).collect(),
+                );
+            }
+        }
+        Ok(Self { owners })
+    }
+
+    /// Whether the object is the owner, or owned by it directly or not
+    fn is_owned_by(&self, uid: &str, owner: &str) -> bool {
+        let mut visited = HashSet::new();
+        let mut pending = vec![uid];
+        while let Some(uid) = pending.pop() {
+            if uid == owner {
+                return true;
+            }
+            if visited.insert(uid) {
+                pending.extend(
+                    self.owners
+                        .get(uid)
+                        .into_iter()
+                        .flatten()
+                        .map(String::as_str),
+                );
+            }
+        }
+        false
+    }
+}
+
+/// Resource of the kind in the group of the API version
+fn find_kind<'a>(
+    api_resources: &'a [APIResource],
+    api_version: &str,
+    kind: &str,
+) -> Option<&'a APIResource> {
+    let group = match api_version.split_once('/') {
+        Some((group, _)) => group,
+        None => "core",
+    };
+    api_resources.iter().find(|api_resource| {
+        api_resource.kind == kind
+            && api_resource.group.as_deref().unwrap_or("core") == group
+            // Subresources such as `pods/status` share the kind of the resource.
+            && !api_resource.name.contains('/')
+    })
+}
+
+#[async_trait]
+impl TableProvider for EventsForTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    async fn scan(
+        &self,
+        state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        self.factory.deny_dry_run("events_for")?;
+        let events = self.events().await?;
+        let strings = |f: &dyn Fn(&DynamicObject) -> Option<&str>| -> ArrayRef {
+            Arc::new(events.iter().map(f).collect::<StringArray>())
+        };
+        let columns = vec![
+            strings(&|event| involved_object(event)["kind"].as_str()),
+            strings(&|event| involved_object(event)["name"].as_str()),
+            strings(&|event| event.data["type"].as_str()),
+            strings(&|event| event.data["reason"].as_str()),
+            strings(&|event| {
+                event.data["message"]
+                    .as_str()
+                    .or(event.data["note"].as_str())
+            }),
+            Arc::new(
+                events
+                    .iter()
+                    .map(|event| {
+                        event.data["count"]
+                            .as_i64()
+                            .or(event.data["deprecatedCount"].as_i64())
+                    })
+                    .collect::<Int64Array>(),
+            ),
+            strings(&|event| {
+                event.data["firstTimestamp"]
+                    .as_str()
+                    .or(event.data["eventTime"].as_str())
+            }),
+            strings(&|event| {
+                event.data["lastTimestamp"]
+                    .as_str()
+                    .or(event.data["series"]["lastObservedTime"].as_str())
+                    .or(event.data["eventTime"].as_str())
+            }),
+            strings(&|event| {
+                event.data["source"]["component"]
+                    .as_str()
+                    .or(event.data["reportingComponent"].as_str())
+            }),
+        ];
+        let batch = RecordBatch::try_new(self.schema.clone(), columns)?;
+        MemTable::try_new(self.schema.clone(), vec![vec![batch]])?
+            .scan(state, projection, filters, limit)
+            .await
+    }
+}
@@ -0,0 +1,241 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{any::Any, sync::Arc};
+
+use async_trait::async_trait;
+use chrono::DateTime;
+use datafusion::{
+    arrow::{
+        array::{ArrayRef, StringArray, TimestampNanosecondArray},
+        datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit},
+        record_batch::RecordBatch,
+    },
+    catalog::{Session, TableFunctionImpl, TableProvider},
+    common::{DataFusionError, Result as DataFusionResult, plan_err},
+    datasource::{MemTable, TableType},
+    logical_expr::Expr,
+    physical_plan::ExecutionPlan,
+};
+use k8s_openapi::api::core::v1::Pod;
+use kube::{Api, api::LogParams};
+
+use super::string_arg;
+use crate::provider::KubernetesTableProviderFactory;
+
+/// Bytes of the log of a container read at most
+pub const LIMIT_BYTES: i64 = 16 * 1024 * 1024;
+
+/// `logs(namespace, pod[, container[, since]])`: the log lines of the containers of the pod
+/// (or of the container only), since the duration ago (e.g. `10m`) if given.
+///
+/// At most [`LIMIT_BYTES`] of the log of each container are read, and only the last lines
+/// of the `LIMIT` of a query without filters.
+///
+/// Arguments are positional only: the SQL planner drops named arguments such as
+/// `container => 'app'` before calling table functions, so pass `NULL` to skip one.
+#[derive(Debug)]
+pub struct Logs {
+    pub factory: Arc<KubernetesTableProviderFactory>,
+}
+
+impl TableFunctionImpl for Logs {
+    fn call(&self, args: &[Expr]) -> DataFusionResult<Arc<dyn TableProvider>> {
+        let (Some(namespace), Some(pod)) =
+            (string_arg("logs", args, 0)?, string_arg("logs", args, 1)?)
+        else {
+            return plan_err!("logs expects the namespace and the name of a pod");
+        };
+        let since = match string_arg("logs", args, 3)? {
+            Some(since) => match humantime::parse_duration(&since) {
+                Ok(since) => Some(since.as_secs().max(1) as i64),
+                Err(e) => {
+                    return plan_err!(
+                        "logs expects a duration such as 10m, got '{}': {}",
+                        since,
+                        e
+                    );
+                }
+            },
+            None => None,
+        };
+        Ok(Arc::new(LogsTable {
+            factory: Arc::clone(&self.factory),
+            namespace,
+            pod,
+            container: string_arg("logs", args, 2)?,
+            since,
+            schema: Arc::new(Schema::new(vec![
+                Field::new("namespace", DataType::Utf8, false),
+                Field::new("pod", DataType::Utf8, false),
+                Field::new("container", DataType::Utf8, false),
+                Field::new(
+                    "timestamp",
+                    DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into())),
+                    true,
+                ),
+                Field::new("line", DataType::Utf8, false),
+            ])),
+        }))
+    }
+}
+
+/// Log lines of the containers of a pod, read at each scan
+#[derive(Debug)]
+struct LogsTable {
+    factory: Arc<KubernetesTableProviderFactory>,
+    namespace: String,
+    pod: String,
+    container: Option<String>,
+    /// Seconds of the logs to read, all if `None`
+    since: Option<i64>,
+    schema: SchemaRef,
+}
+
+impl LogsTable {
+    /// Parameters to read the log of the container, the last `limit` lines if given
+    fn log_params(&self, container: &str, limit: Option<usize>) -> LogParams {
+        LogParams {
+            container: Some(container.to_string()),
+            since_seconds: self.since,
+            tail_lines: limit.map(|limit| limit as i64),
+            limit_bytes: Some(LIMIT_BYTES),
+            timestamps: true,
+            ..Default::default()
+        }
+    }
+
+    /// Container and log lines of each container read
+    async fn read(&self, limit: Option<usize>) -> DataFusionResult<Vec<(String, String)>> {
+        let api = Api::<Pod>::namespaced(self.factory.client().await?, &self.namespace);
+        let containers = match &self.container {
+            Some(container) => vec![container.clone()],
+            None => {
+                let pod = api
+                    .get(&self.pod)
+                    .await
+                    .map_err(|e| DataFusionError::External(Box::new(e)))?;
+                pod.spec
+                    .map(|spec| spec.containers.into_iter().map(|c| c.name).collect())
+                    .unwrap_or_default()
+            }
+        };
+        let mut logs = Vec::new();
+        for container in containers {
+            let params = self.log_params(&container, limit);
+            let mut log = api.logs(&self.pod, &params).await.map_err(|e| {
+                DataFusionError::Execution(format!(
+                    "failed to read the logs of {}/{} ({}): {}",
+                    self.namespace, self.pod, container, e
+                ))
+            })?;
+            // A log cut at the limit ends in part of a line.
+            if log.len() as i64 >= LIMIT_BYTES && !log.ends_with('\n') {
+                log.truncate(log.rfind('\n').map_or(0, |i| i + 1));
+            }
+            logs.push((container, log));
+        }
+        Ok(logs)
+    }
+}
+
+#[async_trait]
+impl TableProvider for LogsTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    async fn scan(
+        &self,
+        state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        self.factory.deny_dry_run("logs")?;
+        let mut containers = Vec::new();
+        let mut timestamps = Vec::new();
+        let mut lines = Vec::new();
+        // The limit is pushed down only when there are no filters to apply to the lines.
+        for (container, log) in self.read(limit).await? {
+            for line in log.lines() {
+                // Each line is prefixed with its RFC 3339 timestamp.
+                let (timestamp, line) = match line.split_once(' ') {
+                    Some((timestamp, rest)) => match DateTime::parse_from_rfc3339(timestamp) {
+                        Ok(timestamp) => (timestamp.timestamp_nanos_opt(), rest),
+                        Err(_) => (None, line),
+                    },
+                    None => (None, line),
+                };
+                containers.push(container.clone());
+                timestamps.push(timestamp);
+                lines.push(line.to_string());
+            }
+        }
+        let rows = lines.len();
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(StringArray::from(vec![self.namespace.as_str(); rows])),
+            Arc::new(StringArray::from(vec![self.pod.as_str(); rows])),
+            Arc::new(StringArray::from(containers)),
+            Arc::new(TimestampNanosecondArray::from(timestamps).with_timezone("UTC")),
+            Arc::new(StringArray::from(lines)),
+        ];
+        let batch = RecordBatch::try_new(self.schema.clone(), columns)?;
+        MemTable::try_new(self.schema.clone(), vec![vec![batch]])?
+            .scan(state, projection, filters, limit)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cluster::Cluster, lister::fake::FakeLister, url::Namespaces};
+
+    #[test]
+    fn log_params() {
+        let cluster = Cluster::from_lister(
+            Arc::new(FakeLister::new(Vec::new())),
+            Namespaces::One("default".to_string()),
+        );
+        let factory = Arc::new(KubernetesTableProviderFactory::new(
+            "fake".to_string(),
+            cluster,
+        ));
+        let table = Logs { factory }
+            .call(&[
+                Expr::Literal("default".into(), None),
+                Expr::Literal("api".into(), None),
+                Expr::Literal(datafusion::common::ScalarValue::Utf8(None), None),
+                Expr::Literal("10m".into(), None),
+            ])
+            .unwrap();
+        let table = table.as_any().downcast_ref::<LogsTable>().unwrap();
+
+        let params = table.log_params("app", Some(5));
+        assert_eq!(params.container.as_deref(), Some("app"));
+        assert_eq!(params.since_seconds, Some(600));
+        assert_eq!(params.tail_lines, Some(5));
+        assert_eq!(params.limit_bytes, Some(LIMIT_BYTES));
+        assert_eq!(table.log_params("app", None).tail_lines, None);
+    }
+}
@@ -0,0 +1,226 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env,
+    sync::Arc,
+    time::Duration,
+};
+
+use datafusion::{
+    arrow::{
+        array::{ArrayRef, Float64Array, StringArray, TimestampMillisecondArray},
+        datatypes::{DataType, Field, Schema, TimeUnit},
+        record_batch::RecordBatch,
+    },
+    catalog::{TableFunctionImpl, TableProvider},
+    common::{Result as DataFusionResult, plan_err},
+    datasource::MemTable,
+    error::DataFusionError,
+    logical_expr::Expr,
+};
+use serde_json::Value;
+
+use super::{block_on, string_arg};
+use crate::{http, provider::KubernetesTableProviderFactory};
+
+/// Environment variable of the Prometheus URL, unless given to the function
+const PROMETHEUS_URL_ENV: &str = "PROMETHEUS_URL";
+
+/// Time to wait for Prometheus to answer a query
+const QUERY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// `promql(query[, url])`: the samples of the PromQL query evaluated now by the Prometheus at the URL
+/// (`$PROMETHEUS_URL` if not given), a row per sample with a column per label, `value` and `timestamp`.
+///
+/// The query is run at planning, as the labels make up the columns.
+#[derive(Debug)]
+pub struct PromQl {
+    pub factory: Arc<KubernetesTableProviderFactory>,
+}
+
+impl TableFunctionImpl for PromQl {
+    fn call(&self, args: &[Expr]) -> DataFusionResult<Arc<dyn TableProvider>> {
+        let Some(query) = string_arg("promql", args, 0)? else {
+            return plan_err!("promql expects a PromQL query");
+        };
+        self.factory.deny_dry_run("promql")?;
+        let url = match string_arg("promql", args, 1)? {
+            Some(url) => url,
+            None => match env::var(PROMETHEUS_URL_ENV) {
+                Ok(url) => url,
+                Err(_) => {
+                    return plan_err!(
+                        "promql expects the URL of Prometheus, or {} to be set",
+                        PROMETHEUS_URL_ENV
+                    );
+                }
+            },
+        };
+        let url = format!(
+            "{}/api/v1/query?{}",
+            url.trim_end_matches('/'),
+            form_urlencoded::Serializer::new(String::new())
+                .append_pair("query", &query)
+                .finish()
+        );
+        let response = block_on(async {
+            match tokio::time::timeout(QUERY_TIMEOUT, http::get_json(&url)).await {
+                Ok(response) => response,
+                Err(_) => Err(anyhow::anyhow!("timed out querying {}", url)),
+            }
+        })?
+        .map_err(|e| DataFusionError::External(e.into()))?;
+        let samples = samples(&response)
+            .map_err(|e| DataFusionError::Execution(format!("promql '{}' failed: {}", query, e)))?;
+        Ok(Arc::new(table(samples)?))
+    }
+}
+
+/// Labels, value and timestamp (in seconds) of a sample
+type Sample = (BTreeMap<String, String>, f64, f64);
+
+/// Samples of the response of the query API, for instant vectors, range vectors and scalars
+fn samples(response: &Value) -> anyhow::Result<Vec<Sample>> {
+    if response["status"] != "success" {
+        anyhow::bail!(
+            "{}",
+            response["error"].as_str().unwrap_or("unexpected response")
+        );
+    }
+    let data = &response["data"];
+    let result = &data["result"];
+    let labels = |series: &Value| -> BTreeMap<String, String> {
+        series["metric"]
+            .as_object()
+            .map(|metric| {
+                metric
+                    .iter()
+                    .map(|(name, value)| (name.clone(), value.as_str().unwrap_or_default().into()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let empty = Vec::new();
+    let series = result.as_array().unwrap_or(&empty);
+    let mut samples = Vec::new();
+    match data["resultType"].as_str() {
+        Some("vector") => {
+            for series in series {
+                let (value, timestamp) = point(&series["value"])?;
+                samples.push((labels(series), value, timestamp));
+            }
+        }
+        Some("matrix") => {
+            for series in series {
+                for value in series["values"].as_array().unwrap_or(&empty) {
+                    let (value, timestamp) = point(value)?;
+                    samples.push((labels(series), value, timestamp));
+                }
+            }
+        }
+        Some("scalar") => {
+            let (value, timestamp) = point(result)?;
+            samples.push((BTreeMap::new(), value, timestamp));
+        }
+        result_type => anyhow::bail!("unsupported result type {}", result_type.unwrap_or("none")),
+    }
+    Ok(samples)
+}
+
+/// Value and timestamp of a `[timestamp, "value"]` pair
+fn point(point: &Value) -> anyhow::Result<(f64, f64)> {
+    match (point[0].as_f64(), point[1].as_str()) {
+        (Some(timestamp), Some(value)) => value
+            .parse()
+            .map(|value| (value, timestamp))
+            .map_err(|_| anyhow::anyhow!("invalid sample value '{}'", value)),
+        _ => anyhow::bail!("invalid sample {}", point),
+    }
+}
+
+/// Table of the samples, with the labels as columns in order of name
+fn table(samples: Vec<Sample>) -> DataFusionResult<MemTable> {
+    let names: BTreeSet<&String> = samples
+        .iter()
+        .flat_map(|(labels, _, _)| labels.keys())
+        .filter(|name| !["value", "timestamp"].contains(&name.as_str()))
+        .collect();
+    let mut fields: Vec<Field> = names
+        .iter()
+        .map(|name| Field::new(*name, DataType::Utf8, true))
+        .collect();
+    fields.push(Field::new("value", DataType::Float64, false));
+    fields.push(Field::new(
+        "timestamp",
+        DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+        false,
+    ));
+    let schema = Arc::new(Schema::new(fields));
+
+    let mut columns: Vec<ArrayRef> = names
+        .iter()
+        .map(|name| {
+            Arc::new(
+                samples
+                    .iter()
+                    .map(|(labels, _, _)| labels.get(*name).cloned())
+                    .collect::<StringArray>(),
+            ) as ArrayRef
+        })
+        .collect();
+    columns.push(Arc::new(
+        samples
+            .iter()
+            .map(|(_, value, _)| *value)
+            .collect::<Float64Array>(),
+    ));
+    columns.push(Arc::new(
+        TimestampMillisecondArray::from_iter_values(
+            samples
+                .iter()
+                .map(|(_, _, timestamp)| (timestamp * 1000.0).round() as i64),
+        )
+        .with_timezone("UTC"),
+    ));
+    let batch = RecordBatch::try_new(Arc::clone(&schema), columns)?;
+    MemTable::try_new(schema, vec![vec![batch]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cluster::Cluster, lister::fake::FakeLister, session_context, url::Namespaces};
+
+    #[tokio::test]
+    async fn deny_dry_run() {
+        let cluster = Cluster::from_lister(
+            Arc::new(FakeLister::new(Vec::new())),
+            Namespaces::One("default".to_string()),
+        );
+        let factory =
+            KubernetesTableProviderFactory::new("fake".to_string(), cluster).with_dry_run(true);
+        let ctx = session_context(Arc::new(factory)).unwrap();
+        // Nothing listens on the port, so planning would fail to connect if it queried.
+        let error = ctx
+            .sql("SELECT * FROM promql('up', 'http://127.0.0.1:1')")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.strip_backtrace(),
+            "Error during planning: promql is read when planned, which a dry run does not"
+        );
+    }
+}
@@ -14,105 +14,356 @@
 
 use std::fmt;
 
+use glob::Pattern;
 use k8s_openapi::apimachinery::pkg::apis::meta::v1::APIResource;
-use kube::config::Kubeconfig;
+use kube::api::ListParams;
 
-/// Determines the Kubernetes namespace based on the provided `Args`.
-///
-/// Namespace determination follows this priority:
-/// 1. Uses the namespace explicitly specified in the `Args` structure.
-/// 2. Retrieves the default namespace associated with the current context from kubeconfig.
-/// 3. Uses "default".
-fn determine_namespace(namespace: Option<String>, context: &str) -> String {
-    if let Some(ns) = namespace {
-        return ns;
-    }
-
-    let default_namespace = match Kubeconfig::read() {
-        Ok(kubeconfig) => kubeconfig
-            .contexts
-            .iter()
-            .find(|c| Some(c.name.as_str()) == Some(context))
-            .and_then(|context| {
-                context
-                    .context
-                    .as_ref()
-                    .and_then(|ctx| ctx.namespace.clone())
-            })
-            .unwrap_or_else(|| String::from("default")),
-        Err(_) => String::from("default"),
-    };
+/// Criteria used to match a resource name against an APIResource,
+/// in the order they are checked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchCriterion {
+    /// `name` (e.g. `pods`)
+    Name,
+    /// `singularName` (e.g. `pod`)
+    SingularName,
+    /// `shortNames` (e.g. `po`)
+    ShortName,
+    /// `name` qualified with `group` (e.g. `deployments.apps`)
+    GroupQualified,
+    /// `name` qualified with `version` and `group` as in kubectl,
+    /// pinning the exact version (e.g. `deployments.v1.apps`, or `pods.v1` for the core group)
+    VersionQualified,
+}
+
+impl MatchCriterion {
+    pub const ALL: [MatchCriterion; 5] = [
+        MatchCriterion::Name,
+        MatchCriterion::SingularName,
+        MatchCriterion::ShortName,
+        MatchCriterion::GroupQualified,
+        MatchCriterion::VersionQualified,
+    ];
 
-    default_namespace
+    fn matches(&self, resource: &str, api_resource: &APIResource) -> bool {
+        match self {
+            MatchCriterion::Name => api_resource.name == resource,
+            MatchCriterion::SingularName => api_resource.singular_name == resource,
+            MatchCriterion::ShortName => api_resource
+                .short_names
+                .as_ref()
+                .is_some_and(|short_names| short_names.contains(&resource.to_string())),
+            MatchCriterion::GroupQualified => api_resource
+                .group
+                .as_ref()
+                .is_some_and(|group| format!("{}.{}", api_resource.name, group) == resource),
+            MatchCriterion::VersionQualified => {
+                let version = api_resource.version.as_deref().unwrap_or_default();
+                match api_resource.group.as_deref() {
+                    Some("core") | None => format!("{}.{}", api_resource.name, version) == resource,
+                    Some(group) => {
+                        format!("{}.{}.{}", api_resource.name, version, group) == resource
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for MatchCriterion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatchCriterion::Name => write!(f, "name"),
+            MatchCriterion::SingularName => write!(f, "singularName"),
+            MatchCriterion::ShortName => write!(f, "shortNames"),
+            MatchCriterion::GroupQualified => write!(f, "group-qualified name"),
+            MatchCriterion::VersionQualified => write!(f, "version-qualified name"),
+        }
+    }
 }
 
 /// Check if the resource name matches the APIResource
-/// Search targeting by:
-/// - `name`
-/// - `singularName`
-/// - `shortNames`
-/// - `group``
-fn match_resource(resource: &str, api_resource: &APIResource) -> bool {
-    api_resource.name == resource
-        || api_resource.singular_name == resource
-        || api_resource
-            .short_names
-            .as_ref()
-            .is_some_and(|short_names| short_names.contains(&resource.to_string()))
-        || api_resource
-            .group
-            .as_ref()
-            .is_some_and(|group| format!("{}.{}", api_resource.name, group) == resource)
-}
-
-/// Find the specified resource in the APIResources
+/// and return the first criterion that matched.
+fn match_resource(resource: &str, api_resource: &APIResource) -> Option<MatchCriterion> {
+    MatchCriterion::ALL
+        .into_iter()
+        .find(|criterion| criterion.matches(resource, api_resource))
+}
+
+/// Find the specified resource in the APIResources.
+///
+/// The first match wins, which is the preferred version of the group
+/// as discovery lists it first.
 pub fn find_resource(resource: &str, api_resources: &[APIResource]) -> Option<APIResource> {
     for api_resource in api_resources {
-        if match_resource(resource, api_resource) {
+        if match_resource(resource, api_resource).is_some() {
             return Some(api_resource.clone());
         }
     }
     None
 }
 
-/// Structure representing a Kubernetes resource URL
+/// Format an APIResource as `name (group/version)` for diagnostics.
+fn describe_resource(api_resource: &APIResource) -> String {
+    format!(
+        "{} ({}/{})",
+        api_resource.name,
+        api_resource.group.as_deref().unwrap_or_default(),
+        api_resource.version.as_deref().unwrap_or_default()
+    )
+}
+
+/// Table of the session a table name resolves to before any APIResource
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SessionTable {
+    /// A view, built-in or defined by the user
+    View,
+    /// Any other table of the session (e.g. `owners`)
+    Table,
+}
+
+impl fmt::Display for SessionTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionTable::View => write!(f, "view"),
+            SessionTable::Table => write!(f, "session table"),
+        }
+    }
+}
+
+/// Trace of how a table name is resolved to an APIResource.
+#[derive(Debug, Clone)]
+pub struct Resolution {
+    /// Table name as written in the query
+    pub table: String,
+    /// Resource part of the table name (without namespace)
+    pub resource: String,
+    /// Table of the session the name resolved to, before trying APIResources
+    pub session_table: Option<SessionTable>,
+    /// APIResources matched by each criterion
+    pub steps: Vec<(MatchCriterion, Vec<APIResource>)>,
+    /// APIResource chosen by `find_resource` and the criterion it matched by
+    pub resolved: Option<(APIResource, MatchCriterion)>,
+}
+
+impl Resolution {
+    /// Trace the resolution of the given table name.
+    ///
+    /// Note that `find_resource` picks the first APIResource matching
+    /// any criterion, so the resolved one is not necessarily the first
+    /// candidate listed under the first matching criterion.
+    pub fn explain(table: &str, api_resources: &[APIResource]) -> Self {
+        let resource = resource_name(table).to_string();
+
+        let steps = MatchCriterion::ALL
+            .into_iter()
+            .map(|criterion| {
+                let candidates = api_resources
+                    .iter()
+                    .filter(|api_resource| criterion.matches(&resource, api_resource))
+                    .cloned()
+                    .collect();
+                (criterion, candidates)
+            })
+            .collect();
+
+        let resolved = api_resources.iter().find_map(|api_resource| {
+            match_resource(&resource, api_resource)
+                .map(|criterion| (api_resource.clone(), criterion))
+        });
+
+        Self {
+            table: table.to_string(),
+            resource,
+            session_table: None,
+            steps,
+            resolved,
+        }
+    }
+
+    /// Trace of a table name resolved to a table of the session, e.g. a view.
+    pub fn session(table: &str, session_table: SessionTable) -> Self {
+        Self {
+            table: table.to_string(),
+            resource: resource_name(table).to_string(),
+            session_table: Some(session_table),
+            steps: Vec::new(),
+            resolved: None,
+        }
+    }
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Resolving table '{}':", self.table)?;
+        if let Some(session_table) = self.session_table {
+            writeln!(f, "  view or session table: {}", session_table)?;
+            return write!(f, "  => {} '{}'", session_table, self.table);
+        }
+        writeln!(f, "  view or session table: no match")?;
+        for (criterion, candidates) in &self.steps {
+            if candidates.is_empty() {
+                writeln!(f, "  {}: no match", criterion)?;
+            } else {
+                let candidates = candidates
+                    .iter()
+                    .map(describe_resource)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                writeln!(f, "  {}: {}", criterion, candidates)?;
+            }
+        }
+        match &self.resolved {
+            Some((api_resource, criterion)) => write!(
+                f,
+                "  => {} (matched by {})",
+                describe_resource(api_resource),
+                criterion
+            ),
+            None => write!(f, "  => Resource '{}' not found", self.resource),
+        }
+    }
+}
+
+/// Namespaces a table URL refers to
 #[derive(Debug, Clone, PartialEq)]
+pub enum Namespaces {
+    /// A single namespace
+    One(String),
+    /// All namespaces, listed through the cluster-scoped endpoint
+    All,
+    /// Namespaces matching the glob pattern (e.g. `prod-*`)
+    Glob(Pattern),
+}
+
+impl Namespaces {
+    fn parse(namespace: &str) -> Option<Self> {
+        match namespace {
+            "*" | "-" => Some(Namespaces::All),
+            namespace if namespace.contains(['*', '?', '[']) => {
+                Pattern::new(namespace).ok().map(Namespaces::Glob)
+            }
+            namespace => Some(Namespaces::One(namespace.to_string())),
+        }
+    }
+}
+
+/// Structure representing a Kubernetes resource URL
+#[derive(Debug, Clone)]
 pub struct KubernetesUrl {
     /// Resource type (e.g., "pod", "node", "service")
     pub resource: APIResource,
     /// Namespace (if specified)
-    pub namespace: String,
+    pub namespace: Namespaces,
+    /// Object name, fetched with a GET instead of a list (if specified)
+    pub name: Option<String>,
+    /// List parameters given in the query string
+    pub list_params: ListParams,
+    /// Kubeconfig context (if specified)
+    pub context: Option<String>,
+}
+
+/// Split the context qualifier off the URL,
+/// e.g. `pod/default@staging` => (`pod/default`, Some(`staging`))
+pub fn split_context(url: &str) -> (&str, Option<&str>) {
+    match url.rsplit_once('@') {
+        Some((path, context)) => (path, Some(context)),
+        None => (url, None),
+    }
+}
+
+/// Returns the resource part of the URL,
+/// e.g. `pods/default?limit=10@staging` => `pods`
+pub fn resource_name(url: &str) -> &str {
+    let (path, _) = split_context(url);
+    let (path, _) = split_query(path);
+    path.split('/').next().unwrap_or_default()
+}
+
+/// Split the query string off the path,
+/// e.g. `pod/default?limit=10` => (`pod/default`, Some(`limit=10`))
+fn split_query(path: &str) -> (&str, Option<&str>) {
+    match path.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (path, None),
+    }
+}
+
+/// Parse the query string into list parameters,
+/// e.g. `labelSelector=app=web&fieldSelector=status.phase=Running&limit=500`.
+/// Keys and values are percent-decoded, e.g. `labelSelector=tier%20in%20(web%2Capi)`.
+/// Returns `None` for unknown options or invalid values.
+fn parse_list_params(query: &str) -> Option<ListParams> {
+    let mut lp = ListParams::default();
+    for option in query.split('&').filter(|option| !option.is_empty()) {
+        if !option.contains('=') {
+            return None;
+        }
+        let (key, value) = form_urlencoded::parse(option.as_bytes()).next()?;
+        match key.as_ref() {
+            "labelSelector" => lp.label_selector = Some(value.into_owned()),
+            "fieldSelector" => lp.field_selector = Some(value.into_owned()),
+            "limit" => lp.limit = Some(value.parse().ok()?),
+            _ => return None,
+        }
+    }
+    Some(lp)
 }
 
 impl KubernetesUrl {
     /// Parse URL string to create KubernetesUrl
     ///
     /// Supported formats:
-    /// - `pod` => pod in default namespace
+    /// - `pod` => pod in the default namespace (or all namespaces if that is `Namespaces::All`)
     /// - `pod/something` => Pod in "something" namespace
+    /// - `pod/*` or `pod/-` => Pod in all namespaces
+    /// - `pod/prod-*` => Pod in namespaces matching the glob pattern
+    /// - `pod/something/name` => Pod named "name" in "something" namespace
+    /// - `pod/something?labelSelector=app=web&limit=10` => Pod listed with the given options
     /// - `node/something` => For non-namespaced resources, namespace is ignored
+    /// - `pod/something@staging` => Pod in "something" namespace of "staging" context
+    ///
+    /// `default_namespace` and `api_resources` must be those of the context the URL refers to.
     pub fn parse(
         url: &str,
-        context: &str,
+        default_namespace: &Namespaces,
         api_resources: &[APIResource],
     ) -> Result<Self, ParseError> {
         if url.is_empty() {
             return Err(ParseError::EmptyUrl);
         }
 
-        let parts: Vec<&str> = url.split('/').collect();
+        let (path, url_context) = split_context(url);
+        if url_context.is_some_and(str::is_empty) {
+            return Err(ParseError::InvalidFormat(url.to_string()));
+        }
+
+        let (path, query) = split_query(path);
+        let list_params = match query {
+            Some(query) => parse_list_params(query)
+                .ok_or_else(|| ParseError::InvalidFormat(url.to_string()))?,
+            None => ListParams::default(),
+        };
+
+        let parts: Vec<&str> = path.split('/').collect();
 
-        let (resource, namespace) = match parts.len() {
+        let (resource, namespace, name) = match parts.len() {
             1 => {
                 let resource = parts[0].to_string();
-                (resource, determine_namespace(None, context))
+                (resource, default_namespace.clone(), None)
             }
-            2 => {
-                // Format like "pod/something"
+            2 | 3 => {
+                // Format like "pod/something" or "pod/something/name"
                 let resource = parts[0].to_string();
-                let namespace = parts[1].to_string();
+                let namespace = Namespaces::parse(parts[1])
+                    .ok_or_else(|| ParseError::InvalidFormat(url.to_string()))?;
+                let name = match parts.get(2) {
+                    Some(&"") => {
+                        return Err(ParseError::InvalidFormat(url.to_string()));
+                    }
+                    name => name.map(|name| name.to_string()),
+                };
 
-                (resource, namespace)
+                (resource, namespace, name)
             }
             _ => return Err(ParseError::InvalidFormat(url.to_string())),
         };
@@ -123,9 +374,21 @@ impl KubernetesUrl {
             None => return Err(ParseError::ResourceNotFound(resource)),
         };
 
+        // Objects of namespaced resources can only be fetched from a namespace,
+        // so look the name up in every namespace.
+        let namespace = match namespace {
+            Namespaces::All if name.is_some() && api_resource.namespaced => {
+                Namespaces::Glob(Pattern::new("*").expect("valid pattern"))
+            }
+            namespace => namespace,
+        };
+
         Ok(KubernetesUrl {
             resource: api_resource,
             namespace,
+            name,
+            list_params,
+            context: url_context.map(str::to_string),
         })
     }
 }
@@ -140,7 +403,12 @@ pub enum ParseError {
 const SUPPORTED_FORMATS: &str = "Supported formats:
 - `pod` => pod in default namespace
 - `pod/namespace` => Pod in `something` namespace
-- `node/something` => For non-namespaced resources, namespace is ignored";
+- `pod/*` or `pod/-` => Pod in all namespaces
+- `pod/prod-*` => Pod in namespaces matching the glob pattern
+- `pod/namespace/name` => Pod named `name` in `namespace` namespace
+- `pod/namespace?labelSelector=..&fieldSelector=..&limit=..` => Pod listed with the given options
+- `node/something` => For non-namespaced resources, namespace is ignored
+- `pod/namespace@context` => Pod in `namespace` namespace of `context` context";
 
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -155,3 +423,126 @@ impl fmt::Display for ParseError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api_resources() -> Vec<APIResource> {
+        let resource = |group: &str, name: &str, kind: &str, namespaced: bool| APIResource {
+            group: Some(group.to_string()),
+            version: Some("v1".to_string()),
+            name: name.to_string(),
+            singular_name: kind.to_lowercase(),
+            kind: kind.to_string(),
+            namespaced,
+            short_names: Some(vec![]),
+            ..Default::default()
+        };
+        vec![
+            resource("core", "pods", "Pod", true),
+            resource("core", "nodes", "Node", false),
+            resource("apps", "deployments", "Deployment", true),
+        ]
+    }
+
+    fn parse(url: &str) -> Result<KubernetesUrl, ParseError> {
+        KubernetesUrl::parse(
+            url,
+            &Namespaces::One("default".to_string()),
+            &api_resources(),
+        )
+    }
+
+    #[test]
+    fn explain_resolution() {
+        let trace = Resolution::explain("deployments.apps/web", &api_resources()).to_string();
+        assert!(trace.starts_with(
+            "Resolving table 'deployments.apps/web':\n  view or session table: no match\n"
+        ));
+        assert!(trace.ends_with("=> deployments (apps/v1) (matched by group-qualified name)"));
+
+        let trace = Resolution::session("pod_containers", SessionTable::View).to_string();
+        assert_eq!(
+            trace,
+            "Resolving table 'pod_containers':\n  view or session table: view\n  => view 'pod_containers'"
+        );
+    }
+
+    #[test]
+    fn context_qualifier() {
+        assert_eq!(
+            split_context("pods/default@staging"),
+            ("pods/default", Some("staging"))
+        );
+        assert_eq!(split_context("pods"), ("pods", None));
+        assert_eq!(resource_name("pods/default?limit=10@staging"), "pods");
+
+        let url = parse("pods/kube-system@staging").unwrap();
+        assert_eq!(url.context.as_deref(), Some("staging"));
+        assert_eq!(url.namespace, Namespaces::One("kube-system".to_string()));
+        let url = parse("deployments@prod").unwrap();
+        assert_eq!(url.context.as_deref(), Some("prod"));
+        assert_eq!(url.namespace, Namespaces::One("default".to_string()));
+        assert_eq!(parse("pods").unwrap().context, None);
+        assert!(matches!(parse("pods@"), Err(ParseError::InvalidFormat(_))));
+    }
+
+    #[test]
+    fn namespace_patterns() {
+        assert_eq!(parse("pods/*").unwrap().namespace, Namespaces::All);
+        assert_eq!(parse("pods/-").unwrap().namespace, Namespaces::All);
+        let Namespaces::Glob(pattern) = parse("pods/prod-*").unwrap().namespace else {
+            panic!("expected a glob");
+        };
+        assert!(pattern.matches("prod-eu"));
+        assert!(!pattern.matches("staging"));
+        assert!(matches!(
+            parse("pods/team-[ab]").unwrap().namespace,
+            Namespaces::Glob(_)
+        ));
+        assert!(matches!(
+            parse("pods/[").unwrap_err(),
+            ParseError::InvalidFormat(_)
+        ));
+        // A named object is looked up in every namespace rather than across them.
+        let url = parse("pods/*/web-1").unwrap();
+        assert_eq!(url.name.as_deref(), Some("web-1"));
+        assert!(matches!(url.namespace, Namespaces::Glob(_)));
+        assert_eq!(parse("nodes/*/n1").unwrap().namespace, Namespaces::All);
+    }
+
+    #[test]
+    fn list_options() {
+        let lp = parse_list_params(
+            "labelSelector=app=web,tier!=cache&fieldSelector=status.phase=Running&limit=500",
+        )
+        .unwrap();
+        assert_eq!(lp.label_selector.as_deref(), Some("app=web,tier!=cache"));
+        assert_eq!(lp.field_selector.as_deref(), Some("status.phase=Running"));
+        assert_eq!(lp.limit, Some(500));
+        assert_eq!(parse_list_params("").unwrap().limit, None);
+
+        assert!(parse_list_params("limit=ten").is_none());
+        assert!(parse_list_params("limit").is_none());
+        assert!(parse_list_params("watch=true").is_none());
+
+        let url = parse("pods/default?limit=10@staging").unwrap();
+        assert_eq!(url.list_params.limit, Some(10));
+        assert_eq!(url.context.as_deref(), Some("staging"));
+        assert!(matches!(
+            parse("pods?limit=-1"),
+            Err(ParseError::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn percent_decoded_list_options() {
+        let lp = parse_list_params("labelSelector=app%3Dweb").unwrap();
+        assert_eq!(lp.label_selector.as_deref(), Some("app=web"));
+        let lp = parse_list_params("labelSelector=tier%20in%20(web%2Capi)%2Capp").unwrap();
+        assert_eq!(lp.label_selector.as_deref(), Some("tier in (web,api),app"));
+        let lp = parse_list_params("field%53elector=metadata.name%3Dweb%261").unwrap();
+        assert_eq!(lp.field_selector.as_deref(), Some("metadata.name=web&1"));
+    }
+}
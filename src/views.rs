@@ -0,0 +1,661 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use datafusion::{
+    catalog::view::ViewTable,
+    common::{TableReference, tree_node::TreeNodeRecursion},
+    execution::context::SessionContext,
+    logical_expr::LogicalPlan,
+    sql::{
+        parser::{DFParser, Statement},
+        sqlparser::ast::{self, ObjectNamePart},
+    },
+};
+
+use crate::{config::config_dir, table_references};
+
+/// Containers of all Pods, one row per container.
+const POD_CONTAINERS: &str = "
+SELECT
+    metadata.namespace AS namespace,
+    metadata.name AS pod,
+    try_get_field(spec, 'nodeName') AS node,
+    try_get_field(status, 'phase') AS phase,
+    container.name AS container,
+    container.image AS image,
+    label(try_get_field(try_get_field(container, 'resources'), 'requests'), 'cpu') AS cpu_request,
+    label(try_get_field(try_get_field(container, 'resources'), 'requests'), 'memory') AS memory_request,
+    label(try_get_field(try_get_field(container, 'resources'), 'limits'), 'cpu') AS cpu_limit,
+    label(try_get_field(try_get_field(container, 'resources'), 'limits'), 'memory') AS memory_limit
+FROM (SELECT metadata, spec, status, unnest(spec.containers) AS container FROM 'pods/*')";
+
+/// Capacity and allocatable resources of Nodes against the requests of the Pods on them,
+/// leaving out finished Pods as `kubectl describe node` does.
+const NODE_CAPACITY: &str = "
+SELECT
+    n.metadata.name AS node,
+    parse_quantity(label(try_get_field(n.status, 'capacity'), 'cpu')) AS cpu_capacity,
+    parse_quantity(label(try_get_field(n.status, 'allocatable'), 'cpu')) AS cpu_allocatable,
+    coalesce(r.cpu_requested, 0) AS cpu_requested,
+    coalesce(r.cpu_requested, 0) * 100
+        / parse_quantity(label(try_get_field(n.status, 'allocatable'), 'cpu')) AS cpu_requested_pct,
+    parse_quantity_bytes(label(try_get_field(n.status, 'capacity'), 'memory')) AS memory_capacity,
+    parse_quantity_bytes(label(try_get_field(n.status, 'allocatable'), 'memory')) AS memory_allocatable,
+    coalesce(r.memory_requested, 0) AS memory_requested,
+    coalesce(r.memory_requested, 0) * 100.0
+        / parse_quantity_bytes(label(try_get_field(n.status, 'allocatable'), 'memory')) AS memory_requested_pct,
+    parse_quantity(label(try_get_field(n.status, 'allocatable'), 'pods')) AS pods_allocatable,
+    coalesce(r.pods, 0) AS pods
+FROM nodes n
+LEFT JOIN (
+    SELECT
+        node,
+        sum(parse_quantity(cpu_request)) AS cpu_requested,
+        sum(parse_quantity_bytes(memory_request)) AS memory_requested,
+        count(DISTINCT namespace || '/' || pod) AS pods
+    FROM pod_containers
+    WHERE node IS NOT NULL AND phase NOT IN ('Succeeded', 'Failed')
+    GROUP BY node
+) r ON n.metadata.name = r.node";
+
+/// Permissions granted by Role and ClusterRoleBindings,
+/// one row per subject, verb, API group and resource of the rules of the bound role.
+/// `namespace` is NULL for permissions granted cluster-wide.
+const RBAC_BINDINGS: &str = "
+SELECT
+    b.subject.kind AS subject_kind,
+    b.subject.name AS subject_name,
+    try_get_field(b.subject, 'namespace') AS subject_namespace,
+    b.namespace AS namespace,
+    b.binding AS binding,
+    b.role_kind AS role_kind,
+    b.role AS role,
+    r.verb AS verb,
+    r.api_group AS api_group,
+    r.resource AS resource
+FROM (
+    SELECT metadata.namespace AS namespace, metadata.name AS binding,
+        roleRef.kind AS role_kind, roleRef.name AS role, unnest(subjects) AS subject
+    FROM 'rolebindings/*'
+    UNION ALL
+    SELECT NULL, metadata.name, roleRef.kind, roleRef.name, unnest(subjects)
+    FROM clusterrolebindings
+) b
+JOIN (
+    SELECT namespace, kind, name, verb, api_group, unnest(rule.resources) AS resource
+    FROM (
+        SELECT namespace, kind, name, verb, unnest(rule.apiGroups) AS api_group, rule
+        FROM (
+            SELECT namespace, kind, name, unnest(rule.verbs) AS verb, rule
+            FROM (
+                SELECT metadata.namespace AS namespace, 'Role' AS kind, metadata.name AS name,
+                    unnest(rules) AS rule
+                FROM 'roles/*'
+                UNION ALL
+                SELECT NULL, 'ClusterRole', metadata.name, unnest(rules)
+                FROM clusterroles
+            )
+        )
+    )
+) r ON b.role_kind = r.kind AND b.role = r.name
+    AND (r.kind = 'ClusterRole' OR r.namespace = b.namespace)";
+
+/// Paths of Ingress rules, one row per host and path,
+/// with the TLS Secret of the host and the class of the Ingress.
+const INGRESS_ROUTES: &str = "
+SELECT
+    p.namespace AS namespace,
+    p.ingress AS ingress,
+    p.ingress_class AS ingress_class,
+    p.host AS host,
+    p.path.path AS path,
+    p.path.pathType AS path_type,
+    try_get_field(try_get_field(p.path.backend, 'service'), 'name') AS service,
+    coalesce(
+        CAST(try_get_field(try_get_field(try_get_field(p.path.backend, 'service'), 'port'), 'number') AS VARCHAR),
+        try_get_field(try_get_field(try_get_field(p.path.backend, 'service'), 'port'), 'name')
+    ) AS port,
+    t.secret AS tls_secret
+FROM (
+    SELECT namespace, ingress, ingress_class, try_get_field(rule, 'host') AS host,
+        unnest(rule.http.paths) AS path
+    FROM (
+        SELECT
+            metadata.namespace AS namespace,
+            metadata.name AS ingress,
+            coalesce(
+                try_get_field(spec, 'ingressClassName'),
+                annotation(try_get_field(metadata, 'annotations'), 'kubernetes.io/ingress.class')
+            ) AS ingress_class,
+            unnest(spec.rules) AS rule
+        FROM 'ingresses/*'
+    )
+) p
+LEFT JOIN (
+    SELECT namespace, ingress, unnest(tls.hosts) AS host, try_get_field(tls, 'secretName') AS secret
+    FROM (
+        SELECT metadata.namespace AS namespace, metadata.name AS ingress, unnest(spec.tls) AS tls
+        FROM 'ingresses/*'
+    )
+) t ON p.namespace = t.namespace AND p.ingress = t.ingress AND p.host = t.host";
+
+/// Endpoints of Services from their EndpointSlices, one row per endpoint,
+/// with the numbers of ready and not ready endpoints of the Service.
+/// Services without any endpoint have a single row with NULL `address`.
+const SERVICE_ENDPOINTS: &str = "
+SELECT
+    s.metadata.namespace AS namespace,
+    s.metadata.name AS service,
+    sum(CASE WHEN e.ready THEN 1 ELSE 0 END)
+        OVER (PARTITION BY s.metadata.namespace, s.metadata.name) AS ready_addresses,
+    sum(CASE WHEN NOT e.ready THEN 1 ELSE 0 END)
+        OVER (PARTITION BY s.metadata.namespace, s.metadata.name) AS not_ready,
+    e.address AS address,
+    e.ready AS ready,
+    e.target_pod AS target_pod,
+    e.node AS node
+FROM 'services/*' s
+LEFT JOIN (
+    SELECT
+        namespace,
+        service,
+        endpoint.addresses[1] AS address,
+        coalesce(try_get_field(try_get_field(endpoint, 'conditions'), 'ready'), true) AS ready,
+        CASE WHEN try_get_field(try_get_field(endpoint, 'targetRef'), 'kind') = 'Pod'
+            THEN try_get_field(try_get_field(endpoint, 'targetRef'), 'name') END AS target_pod,
+        try_get_field(endpoint, 'nodeName') AS node
+    FROM (
+        SELECT
+            metadata.namespace AS namespace,
+            label(try_get_field(metadata, 'labels'), 'kubernetes.io/service-name') AS service,
+            unnest(endpoints) AS endpoint
+        FROM 'endpointslices/*'
+    )
+) e ON s.metadata.namespace = e.namespace AND s.metadata.name = e.service";
+
+/// PersistentVolumeClaims with their bound PersistentVolumes and StorageClasses.
+const PVC_BINDINGS: &str = "
+SELECT
+    c.metadata.namespace AS namespace,
+    c.metadata.name AS claim,
+    try_get_field(c.status, 'phase') AS phase,
+    try_get_field(c.spec, 'volumeName') AS volume,
+    coalesce(try_get_field(c.spec, 'storageClassName'), try_get_field(v.spec, 'storageClassName'))
+        AS storage_class,
+    label(try_get_field(try_get_field(c.spec, 'resources'), 'requests'), 'storage') AS requested,
+    coalesce(
+        label(try_get_field(v.spec, 'capacity'), 'storage'),
+        label(try_get_field(c.status, 'capacity'), 'storage')
+    ) AS capacity,
+    array_to_string(coalesce(c.status.accessModes, c.spec.accessModes), ',') AS access_modes,
+    try_get_field(c.spec, 'volumeMode') AS volume_mode,
+    try_get_field(v.spec, 'persistentVolumeReclaimPolicy') AS reclaim_policy,
+    try_get_field(v.status, 'phase') AS volume_phase,
+    s.provisioner AS provisioner,
+    s.volumeBindingMode AS volume_binding_mode,
+    s.allowVolumeExpansion AS allow_volume_expansion
+FROM 'persistentvolumeclaims/*' c
+LEFT JOIN persistentvolumes v ON try_get_field(c.spec, 'volumeName') = v.metadata.name
+LEFT JOIN storageclasses s
+    ON coalesce(try_get_field(c.spec, 'storageClassName'), try_get_field(v.spec, 'storageClassName'))
+        = s.metadata.name";
+
+/// Events with the count and times of both the legacy (`count`, `lastTimestamp`)
+/// and the series (`series.count`, `series.lastObservedTime`) forms, most recent first.
+const EVENTS_NORMALIZED: &str = "
+SELECT
+    involvedObject.kind AS kind,
+    try_get_field(involvedObject, 'namespace') AS namespace,
+    involvedObject.name AS name,
+    reason,
+    message,
+    type,
+    coalesce(try_get_field(series, 'count'), count, 1) AS count,
+    to_timestamp(coalesce(firstTimestamp, eventTime, metadata.creationTimestamp)) AS first_seen,
+    to_timestamp(coalesce(
+        try_get_field(series, 'lastObservedTime'),
+        lastTimestamp,
+        eventTime,
+        firstTimestamp,
+        metadata.creationTimestamp
+    )) AS last_seen
+FROM 'events/*'
+ORDER BY last_seen DESC";
+
+/// HorizontalPodAutoscalers, one row per metric with its target and current value
+/// (utilizations as percentages, e.g. `80%`), along with replicas and conditions.
+/// HPAs without metrics have a single row with NULL metric.
+const HPA_STATUS: &str = "
+SELECT
+    namespace,
+    hpa,
+    target_kind,
+    target,
+    min_replicas,
+    max_replicas,
+    current_replicas,
+    desired_replicas,
+    metric_type,
+    metric,
+    coalesce(
+        CAST(try_get_field(metric_target, 'averageUtilization') AS VARCHAR) || '%',
+        try_get_field(metric_target, 'averageValue'),
+        try_get_field(metric_target, 'value')
+    ) AS target_value,
+    coalesce(
+        CAST(try_get_field(metric_current, 'averageUtilization') AS VARCHAR) || '%',
+        try_get_field(metric_current, 'averageValue'),
+        try_get_field(metric_current, 'value')
+    ) AS current_value,
+    able_to_scale,
+    scaling_active,
+    scaling_limited
+FROM (
+    SELECT
+        namespace,
+        hpa,
+        target_kind,
+        target,
+        min_replicas,
+        max_replicas,
+        current_replicas,
+        desired_replicas,
+        able_to_scale,
+        scaling_active,
+        scaling_limited,
+        try_get_field(metric_spec, 'type') AS metric_type,
+        coalesce(
+            try_get_field(try_get_field(metric_spec, 'resource'), 'name'),
+            try_get_field(try_get_field(metric_spec, 'containerResource'), 'name'),
+            try_get_field(try_get_field(try_get_field(metric_spec, 'pods'), 'metric'), 'name'),
+            try_get_field(try_get_field(try_get_field(metric_spec, 'object'), 'metric'), 'name'),
+            try_get_field(try_get_field(try_get_field(metric_spec, 'external'), 'metric'), 'name')
+        ) AS metric,
+        coalesce(
+            try_get_field(try_get_field(metric_spec, 'resource'), 'target'),
+            try_get_field(try_get_field(metric_spec, 'containerResource'), 'target'),
+            try_get_field(try_get_field(metric_spec, 'pods'), 'target'),
+            try_get_field(try_get_field(metric_spec, 'object'), 'target'),
+            try_get_field(try_get_field(metric_spec, 'external'), 'target')
+        ) AS metric_target,
+        coalesce(
+            try_get_field(try_get_field(metric_status, 'resource'), 'current'),
+            try_get_field(try_get_field(metric_status, 'containerResource'), 'current'),
+            try_get_field(try_get_field(metric_status, 'pods'), 'current'),
+            try_get_field(try_get_field(metric_status, 'object'), 'current'),
+            try_get_field(try_get_field(metric_status, 'external'), 'current')
+        ) AS metric_current
+    FROM (
+        SELECT
+            metadata.namespace AS namespace,
+            metadata.name AS hpa,
+            spec.scaleTargetRef.kind AS target_kind,
+            spec.scaleTargetRef.name AS target,
+            coalesce(try_get_field(spec, 'minReplicas'), 1) AS min_replicas,
+            spec.maxReplicas AS max_replicas,
+            try_get_field(status, 'currentReplicas') AS current_replicas,
+            status.desiredReplicas AS desired_replicas,
+            spec.metrics[i] AS metric_spec,
+            status.currentMetrics[i] AS metric_status,
+            condition(status.conditions, 'AbleToScale')['status'] AS able_to_scale,
+            condition(status.conditions, 'ScalingActive')['status'] AS scaling_active,
+            condition(status.conditions, 'ScalingLimited')['status'] AS scaling_limited
+        FROM (
+            SELECT
+                metadata,
+                spec,
+                status,
+                unnest(range(
+                    1,
+                    greatest(coalesce(CAST(array_length(spec.metrics) AS BIGINT), 0), 1) + 1
+                )) AS i
+            FROM 'horizontalpodautoscalers/*'
+        )
+    )
+)";
+
+/// Images of the containers of running Pods, one row per image.
+const CLUSTER_IMAGES: &str = "
+SELECT
+    image,
+    image_registry(image) AS registry,
+    image_repository(image) AS repository,
+    image_tag(image) AS tag,
+    image_digest(image) AS digest,
+    count(DISTINCT namespace || '/' || pod) AS pods,
+    count(DISTINCT namespace) AS namespaces,
+    count(*) AS containers
+FROM pod_containers
+WHERE phase = 'Running'
+GROUP BY image";
+
+/// Helm v3 releases, one row per revision kept in the history (`status` of the latest is `deployed`),
+/// decoded from the Secrets Helm stores them in.
+const HELM_RELEASES: &str = "
+SELECT
+    json_get(release, '$.name') AS release,
+    namespace,
+    json_get(release, '$.chart.metadata.name') AS chart,
+    json_get(release, '$.chart.metadata.version') AS chart_version,
+    json_get(release, '$.chart.metadata.appVersion') AS app_version,
+    json_get(release, '$.info.status') AS status,
+    CAST(json_get(release, '$.version') AS BIGINT) AS revision,
+    to_timestamp(json_get(release, '$.info.last_deployed')) AS updated
+FROM (
+    SELECT metadata.namespace AS namespace, helm_release(label(data, 'release')) AS release
+    FROM 'secrets/*?labelSelector=owner=helm'
+    WHERE type = 'helm.sh/release.v1'
+)";
+
+/// Returns the path of the views defined by the user, `<config dir>/views.sql`.
+pub fn user_views_path() -> Option<PathBuf> {
+    Some(config_dir()?.join("views.sql"))
+}
+
+/// Views queryable like resources, by name.
+///
+/// A view is created in the session only when a query references it,
+/// since creating it lists the resources it reads.
+///
+/// The built-in views read nested fields that may be missing from the schema
+/// with `try_get_field`, so that they are NULL. Top-level fields (e.g. `status`)
+/// and lists they unnest must be known from OpenAPI or from the objects sampled,
+/// which offline objects (e.g. rendered manifests) may lack.
+#[derive(Clone)]
+pub struct Views {
+    views: BTreeMap<String, String>,
+}
+
+impl Views {
+    /// The views built into kuqu
+    pub fn builtin() -> Self {
+        Self {
+            views: [
+                ("pod_containers", POD_CONTAINERS),
+                ("node_capacity", NODE_CAPACITY),
+                ("rbac_bindings", RBAC_BINDINGS),
+                ("ingress_routes", INGRESS_ROUTES),
+                ("service_endpoints", SERVICE_ENDPOINTS),
+                ("pvc_bindings", PVC_BINDINGS),
+                ("events_normalized", EVENTS_NORMALIZED),
+                ("hpa_status", HPA_STATUS),
+                ("cluster_images", CLUSTER_IMAGES),
+                ("helm_releases", HELM_RELEASES),
+            ]
+            .into_iter()
+            .map(|(name, sql)| (name.to_string(), sql.to_string()))
+            .collect(),
+        }
+    }
+
+    /// Add the views defined by the `CREATE VIEW` statements in the file,
+    /// replacing the views of the same names. A missing file defines no views.
+    pub fn with_file(mut self, path: &Path) -> anyhow::Result<Self> {
+        let sql = match fs::read_to_string(path) {
+            Ok(sql) => sql,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(self),
+            Err(e) => anyhow::bail!("failed to read views from {}: {}", path.display(), e),
+        };
+        let statements = DFParser::parse_sql(&sql)
+            .map_err(|e| anyhow::anyhow!("failed to parse views in {}: {}", path.display(), e))?;
+        for statement in statements {
+            let Statement::Statement(statement) = statement else {
+                anyhow::bail!(
+                    "{} must only contain CREATE VIEW statements",
+                    path.display()
+                );
+            };
+            let ast::Statement::CreateView { name, query, .. } = *statement else {
+                anyhow::bail!(
+                    "{} must only contain CREATE VIEW statements",
+                    path.display()
+                );
+            };
+            let [ObjectNamePart::Identifier(name)] = name.0.as_slice() else {
+                anyhow::bail!(
+                    "view name '{}' in {} must not be qualified",
+                    name,
+                    path.display()
+                );
+            };
+            self.views.insert(name.value.clone(), query.to_string());
+        }
+        Ok(self)
+    }
+
+    /// Returns the names of the views
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.views.keys().map(String::as_str)
+    }
+
+    /// Returns the SQL of the view the table refers to, if any
+    fn get(&self, table: &TableReference) -> Option<&str> {
+        match table {
+            TableReference::Bare { table } => self.views.get(table.as_ref()).map(String::as_str),
+            _ => None,
+        }
+    }
+
+    /// Create the views referenced by the query, and the views they reference, in the session.
+    pub async fn register(&self, ctx: &SessionContext, query: &str) -> anyhow::Result<()> {
+        for table in table_references(ctx, query)? {
+            let Some(sql) = self.get(&table) else {
+                continue;
+            };
+            if ctx.table_exist(table.clone())? {
+                continue;
+            }
+            Box::pin(self.register(ctx, sql)).await?;
+            ctx.sql(&format!("CREATE VIEW \"{}\" AS {}", table.table(), sql))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Returns the tables of resources the query reads,
+    /// directly or through views, including views already created in the session.
+    pub async fn resource_tables(
+        &self,
+        ctx: &SessionContext,
+        query: &str,
+    ) -> anyhow::Result<Vec<TableReference>> {
+        Ok(self
+            .read_tables(ctx, query)
+            .await?
+            .into_iter()
+            .filter_map(|table| match table {
+                ReadTable::Resource(table) => Some(table),
+                ReadTable::View(_) | ReadTable::Session(_) => None,
+            })
+            .collect())
+    }
+
+    /// Returns the tables the query reads, directly or through views,
+    /// including views already created in the session.
+    /// Each view comes before the tables it reads.
+    pub async fn read_tables(
+        &self,
+        ctx: &SessionContext,
+        query: &str,
+    ) -> anyhow::Result<Vec<ReadTable>> {
+        let mut tables = Vec::new();
+        for table in table_references(ctx, query)? {
+            if ctx.table_exist(table.clone())? {
+                tables.extend(session_read_tables(ctx, table).await?);
+            } else if let Some(sql) = self.get(&table) {
+                tables.push(ReadTable::View(table));
+                tables.extend(Box::pin(self.read_tables(ctx, sql)).await?);
+            } else {
+                tables.push(ReadTable::Resource(table));
+            }
+        }
+        Ok(tables)
+    }
+}
+
+/// Table read by a query
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReadTable {
+    /// View, built-in or of the session, reading the tables that follow it
+    View(TableReference),
+    /// Table not of the session, a resource unless it resolves to none
+    /// (e.g. a table function)
+    Resource(TableReference),
+    /// Table of the session other than a view, e.g. `owners`
+    Session(TableReference),
+}
+
+/// Returns the tables read by the table of the session, itself unless it is a view.
+async fn session_read_tables(
+    ctx: &SessionContext,
+    table: TableReference,
+) -> anyhow::Result<Vec<ReadTable>> {
+    let provider = ctx.table_provider(table.clone()).await?;
+    let Some(view) = provider.as_any().downcast_ref::<ViewTable>() else {
+        return Ok(vec![ReadTable::Session(table)]);
+    };
+    let mut scanned = Vec::new();
+    view.logical_plan().apply_with_subqueries(|plan| {
+        if let LogicalPlan::TableScan(scan) = plan {
+            scanned.push(scan.table_name.clone());
+        }
+        Ok(TreeNodeRecursion::Continue)
+    })?;
+
+    let mut tables = vec![ReadTable::View(table)];
+    for table in scanned {
+        if ctx.table_exist(table.clone())? {
+            tables.extend(Box::pin(session_read_tables(ctx, table)).await?);
+        } else {
+            tables.push(ReadTable::Resource(table));
+        }
+    }
+    Ok(tables)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use datafusion::arrow::util::pretty::pretty_format_batches;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        cluster::Cluster, lister::fake::FakeLister, provider::KubernetesTableProviderFactory,
+        session_context, url::Namespaces,
+    };
+
+    /// Objects with their top-level fields, but without most optional nested ones
+    fn sparse_objects() -> Vec<serde_json::Value> {
+        vec![
+            json!({"apiVersion": "v1", "kind": "Pod",
+                "metadata": {"name": "web-1", "namespace": "default"},
+                "spec": {"containers": [{"name": "web", "image": "nginx:1.25"}]},
+                "status": {"hostIP": "10.0.0.1"}}),
+            json!({"apiVersion": "networking.k8s.io/v1", "kind": "Ingress",
+                "metadata": {"name": "web", "namespace": "default"},
+                "spec": {"rules": [{"http": {"paths": [{"path": "/", "pathType": "Prefix",
+                    "backend": {"resource": {"kind": "Bucket", "name": "assets"}}}]}}],
+                    "tls": [{"hosts": ["web.example.com"]}]}}),
+            json!({"apiVersion": "v1", "kind": "Service",
+                "metadata": {"name": "web", "namespace": "default"},
+                "spec": {"clusterIP": "10.96.0.1"}}),
+            json!({"apiVersion": "discovery.k8s.io/v1", "kind": "EndpointSlice",
+                "metadata": {"name": "web-abc", "namespace": "default",
+                    "labels": {"kubernetes.io/service-name": "web"}},
+                "addressType": "IPv4",
+                "endpoints": [{"addresses": ["10.0.0.5"]}]}),
+            json!({"apiVersion": "v1", "kind": "Event",
+                "metadata": {"name": "n1.reboot", "namespace": "default",
+                    "creationTimestamp": "2025-01-01T00:00:00Z"},
+                "involvedObject": {"kind": "Node", "name": "n1"},
+                "reason": "Rebooted", "message": "rebooted", "type": "Warning", "count": 2,
+                "firstTimestamp": "2025-01-01T00:00:00Z", "lastTimestamp": "2025-01-01T01:00:00Z",
+                "eventTime": null, "series": null}),
+            json!({"apiVersion": "autoscaling/v2", "kind": "HorizontalPodAutoscaler",
+                "metadata": {"name": "web", "namespace": "default"},
+                "spec": {"scaleTargetRef": {"kind": "Deployment", "name": "web"}, "maxReplicas": 3,
+                    "metrics": [{"type": "Resource", "resource": {"name": "cpu",
+                        "target": {"type": "Utilization", "averageUtilization": 80}}}]},
+                "status": {"desiredReplicas": 1, "currentMetrics": [], "conditions": []}}),
+        ]
+    }
+
+    #[tokio::test]
+    async fn read_tables_through_views() {
+        let lister = Arc::new(FakeLister::new(sparse_objects()));
+        let cluster = Cluster::from_lister(lister, Namespaces::One("default".to_string()));
+        let factory = KubernetesTableProviderFactory::new("fake".to_string(), cluster);
+        let ctx = session_context(Arc::new(factory)).unwrap();
+        ctx.sql("CREATE VIEW owned AS SELECT * FROM owners")
+            .await
+            .unwrap();
+        let tables = Views::builtin()
+            .read_tables(&ctx, "SELECT * FROM cluster_images, owned")
+            .await
+            .unwrap();
+        assert_eq!(
+            tables,
+            [
+                ReadTable::View("cluster_images".into()),
+                ReadTable::View("pod_containers".into()),
+                ReadTable::Resource("pods/*".into()),
+                ReadTable::View("owned".into()),
+                ReadTable::Session("owners".into()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn builtin_views_with_missing_fields() {
+        let lister = Arc::new(FakeLister::new(sparse_objects()));
+        let cluster = Cluster::from_lister(lister, Namespaces::One("default".to_string()));
+        let factory = KubernetesTableProviderFactory::new("fake".to_string(), cluster);
+        let ctx = session_context(Arc::new(factory)).unwrap();
+        let views = Views::builtin();
+        for (view, row) in [
+            (
+                "pod_containers",
+                "| default   | web-1 |      |       | web       | nginx:1.25 |",
+            ),
+            (
+                "ingress_routes",
+                "| default   | web     |               |      | /    | Prefix    |",
+            ),
+            (
+                "service_endpoints",
+                "| default   | web     | 1               | 0         | 10.0.0.5 | true  |",
+            ),
+            ("events_normalized", "| Node | "),
+            (
+                "hpa_status",
+                "| default   | web | Deployment  | web    | 1            | 3            |",
+            ),
+        ] {
+            let query = format!("SELECT * FROM {}", view);
+            views.register(&ctx, &query).await.unwrap();
+            let batches = ctx.sql(&query).await.unwrap().collect().await.unwrap();
+            let table = pretty_format_batches(&batches).unwrap().to_string();
+            assert!(table.contains(row), "{}:\n{}", view, table);
+        }
+    }
+}
@@ -0,0 +1,42 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::{Arc, Mutex};
+
+/// Warnings about partial results of a query (e.g. namespaces skipped because listing them is forbidden),
+/// collected while planning and executing to be printed after the results.
+#[derive(Debug, Clone, Default)]
+pub struct Warnings(Arc<Mutex<Vec<String>>>);
+
+impl Warnings {
+    /// Add the warning, unless already added (e.g. at planning time for the same source).
+    pub fn push(&self, warning: String) {
+        let mut warnings = self.0.lock().unwrap();
+        if !warnings.contains(&warning) {
+            warnings.push(warning);
+        }
+    }
+
+    /// Returns the warnings added so far, and clears them.
+    pub fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+
+    /// Print the warnings added so far to stderr, and clear them.
+    pub fn print(&self) {
+        for warning in self.take() {
+            eprintln!("Warning: {}", warning);
+        }
+    }
+}
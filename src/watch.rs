@@ -0,0 +1,89 @@
+// Copyright 2025 kuqu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use futures::StreamExt;
+use kube::{
+    Api,
+    api::{ListParams, WatchEvent, WatchParams},
+};
+use tokio::{sync::mpsc, task::JoinHandle};
+
+use crate::dynamic::DynamicObject;
+
+/// HTTP status returned by the API server when the requested
+/// resourceVersion is too old to resume the watch from.
+const GONE: u16 = 410;
+
+/// Fetch the current resourceVersion of the collection without listing its items.
+async fn current_resource_version(api: &Api<DynamicObject>) -> anyhow::Result<String> {
+    Ok(api
+        .list_metadata(&ListParams::default().limit(1))
+        .await?
+        .metadata
+        .resource_version
+        .unwrap_or_default())
+}
+
+/// Watch the collection and notify `tx` on every change.
+///
+/// Only metadata is watched because the query re-lists the resources anyway;
+/// the watch merely signals when re-evaluation is needed.
+/// The watch is resumed from the last seen resourceVersion whenever the server closes it.
+async fn watch_changes(
+    api: Api<DynamicObject>,
+    tx: mpsc::Sender<anyhow::Result<()>>,
+) -> anyhow::Result<()> {
+    let wp = WatchParams::default();
+    let mut resource_version = current_resource_version(&api).await?;
+
+    loop {
+        let mut events = api.watch_metadata(&wp, &resource_version).await?.boxed();
+
+        while let Some(event) = events.next().await {
+            match event? {
+                WatchEvent::Added(obj) | WatchEvent::Modified(obj) | WatchEvent::Deleted(obj) => {
+                    if let Some(rv) = obj.metadata.resource_version {
+                        resource_version = rv;
+                    }
+                    if tx.send(Ok(())).await.is_err() {
+                        return Ok(());
+                    }
+                }
+                WatchEvent::Bookmark(bookmark) => {
+                    resource_version = bookmark.metadata.resource_version;
+                }
+                WatchEvent::Error(e) if e.code == GONE => {
+                    // Changes may have been missed, so re-evaluate from the latest state.
+                    resource_version = current_resource_version(&api).await?;
+                    if tx.send(Ok(())).await.is_err() {
+                        return Ok(());
+                    }
+                    break;
+                }
+                WatchEvent::Error(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+/// Spawn a task that watches the collection behind `api`
+/// and sends a notification to `tx` on every change.
+/// Errors are forwarded to `tx` and stop the watch.
+pub fn spawn(api: Api<DynamicObject>, tx: mpsc::Sender<anyhow::Result<()>>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        if let Err(e) = watch_changes(api, tx.clone()).await {
+            let _ = tx.send(Err(e)).await;
+        }
+    })
+}